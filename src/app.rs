@@ -1,19 +1,55 @@
 use crate::{
-    command_runner::{run_command, Terminal},
-    file_item::{FileItem, MAX_FILES},
-    file_tree::{build_file_tree, generate_file_tree_string, show_file_tree, sort_file_tree},
-    prompt_builder::extract_text,
-    remote::{Remote, RemoteUpdate, RemoteUrl},
+    code_import::CodeImport,
+    code_state::CodeState,
+    command_runner::{run_command, CommandOutput, HistoryEntry, RunView, StdinMode, Terminal},
+    config::ProjectConfig,
+    conversation::{content_hash, Conversation, PromptKind, PromptMode},
+    diff_view::Layout,
+    error::PromptError,
+    escaping::Escaping,
+    eval::Evaluator,
+    file_item::{FileItem, ScanOptions, ScanStats, ScannedFile, MAX_FILES},
+    file_order::FileOrder,
+    file_tree::{
+        add_deferred_dirs, build_file_tree, generate_file_tree_string, show_tree_row,
+        sort_file_tree, tree_row_height, visible_rows, FileTree, TreeAction, TreeSort, TreeView,
+    },
+    folding::{Fold, Preview},
+    ignore_editor::IgnoreEditor,
+    instructions::InstructionLibrary,
+    intern::RelPath,
+    jobs::{JobId, JobKind, JobOutput, Jobs},
+    json_prompt::{
+        JsonFile, JsonPrompt, JsonRemote, JsonRun, JsonRunStatus, JsonSnippet, OutputFormat,
+    },
+    labels::{PromptLabels, LANGUAGES},
+    language_packs::LanguagePack,
+    notes::{NoteEditor, Notes, ProjectNotes},
+    notifications::{Level, NotificationLog},
+    providers::{Provider, ProviderUpdate, Providers},
+    redactions::Redactions,
+    remote::{Remote, RemoteUpdate, SOURCE_TYPES},
+    responses::ResponsePane,
+    scaffold::ProjectKind,
+    sections::Section,
+    symbols::SymbolPicker,
+    system_prompts::SystemProfiles,
+    text_hygiene::{self, Bom, TextIssues},
+    text_search::TextSearch,
+    workspace::WorkspaceCrate,
 };
 use clipboard::ClipboardProvider;
 use core::f32;
 use eframe::egui;
+use encoding_rs::Encoding;
 use globset::GlobSet;
 use shell_words;
 use std::{
+    collections::{BTreeMap, BTreeSet},
     env,
     path::PathBuf,
     process::Command as SysCommand,
+    sync::atomic::AtomicBool,
     time::{Duration, Instant},
 };
 
@@ -21,27 +57,115 @@ pub struct MyApp {
     pub files: Vec<FileItem>,
     pub extra_text: String,
     pub ignore_set: GlobSet,
+    /// Rule lines behind `ignore_set`, for the scan stats.
+    pub ignore_rules: usize,
     pub generated_prompt: String,
     pub token_count: usize,
+    /// Byte offsets in the last built prompt that end a cacheable prefix; empty unless the
+    /// cache-friendly layout was used.
+    pub cache_breakpoints: Vec<usize>,
+    /// Estimated (cached, fresh) input tokens of the last built prompt, against the one
+    /// before it.
+    pub cache_split: Option<(usize, usize)>,
     pub current_folder: Option<PathBuf>,
     pub include_file_tree: bool,
     pub notification: Option<(String, Instant)>,
+    pub notifications: NotificationLog,
 
     pub remote: Remote,
+    pub lint: crate::lint::Lint,
+    pub commit_message: crate::commit_message::CommitMessage,
+    pub pr_description: crate::pr_description::PrDescription,
+    pub shell: crate::pty_session::Shell,
+    /// Set when started with `--mcp`; see mcp.rs.
+    pub mcp: Option<crate::mcp::McpServer>,
+    /// Set when started with `--serve ADDR`; see http_api.rs.
+    pub http_api: Option<crate::http_api::ApiServer>,
+    pub attachments: crate::attachments::Attachments,
+    pub jobs: Jobs,
     pub terminal: Terminal,
-    pub scanned_files: usize,
-    pub ignored_files: usize,
-    pub ignored_dirs: usize,
-    pub symlinks_skipped: usize,
-}
-
-fn cdata_wrap(s: &str) -> String {
-    let safe = s.replace("]]>", "]]]]><![CDATA[>");
-    format!("<![CDATA[{}]]>", safe)
+    pub scan_stats: ScanStats,
+    /// The background rescan started by `start_scan`, while it runs.
+    pub scan_job: Option<JobId>,
+    /// Folders the scan left unwalked in a project over `MAX_FILES`; see file_item.rs.
+    pub deferred_dirs: BTreeSet<String>,
+    /// Deferred folders being walked, by job; they stay deferred until the walk lands.
+    pub loading_dirs: BTreeMap<String, JobId>,
+    /// Archives whose entries are listed in the tree; see archives.rs.
+    pub open_archives: BTreeSet<String>,
+    pub show_ignored_paths: bool,
+    pub ignore_editor: IgnoreEditor,
+    pub config: ProjectConfig,
+    /// The folder's config.toml exists but didn't parse; saves are refused until it does.
+    pub config_load_failed: bool,
+    pub tree_view: TreeView,
+    /// `files` as folders, rebuilt by `refresh_files`.
+    pub file_tree: FileTree,
+    pub hide_token_threshold: usize,
+    pub symbol_picker: SymbolPicker,
+    pub workspace: Vec<WorkspaceCrate>,
+    pub preview: Option<Preview>,
+    pub notes: Notes,
+    pub token_calibration: crate::token_calibration::TokenCalibration,
+    pub project_notes: ProjectNotes,
+    pub conversation: Conversation,
+    pub evaluator: Evaluator,
+    pub response: ResponsePane,
+    pub providers: Providers,
+    pub code_import: CodeImport,
+    pub show_histogram: bool,
+    pub histogram_view: crate::token_histogram::HistogramView,
+    /// Treemap scope: every file rather than the selection.
+    pub treemap_whole_project: bool,
+    /// Directory the treemap is zoomed into; empty = project root.
+    pub treemap_dir: String,
+    pub show_layout_editor: bool,
+    pub show_file_order: bool,
+    /// The File order window's list: (order, selection by path, arranged), recomputed
+    /// when either input changes since dependency order reads the files.
+    pub arranged_files: Option<(FileOrder, Vec<usize>, Vec<usize>)>,
+    pub show_prompt_viewer: bool,
+    /// "Select files from errors" picks enclosing definitions instead of whole files
+    pub errors_as_snippets: bool,
+    /// The running terminal command is a "Fix failing tests" run; focus when it finishes
+    pub focus_tests_pending: bool,
+    /// Detected for the open folder (empty when disabled in config).
+    pub language_packs: Vec<&'static LanguagePack>,
+    /// Code state of the prompt most recently built; recorded with its turn.
+    pub last_snapshot: Option<CodeState>,
+    /// Selected files repeating another, found in the last built prompt.
+    pub duplicates: Vec<crate::duplicates::Duplicate>,
+    pub prompt_search: TextSearch,
+    /// Folded sections in the prompt viewer, by `prompt_outline::Region::key`
+    pub prompt_folds: std::collections::HashSet<String>,
+    pub preview_search: TextSearch,
+    /// Syntax colours for the previewed file.
+    pub preview_highlight: Option<crate::highlight::Highlighted>,
+    /// First and last line for the preview's Select range.
+    pub preview_range: (usize, usize),
+    pub instructions: InstructionLibrary,
+    pub system_profiles: SystemProfiles,
+    /// Set when an opened folder has no `.prompt` yet; drives the setup offer.
+    pub scaffold_offer: Option<ProjectKind>,
+    pub note_editor: Option<NoteEditor>,
+    /// Regions collapsed in the preview, left out of the prompt
+    pub folds: std::collections::HashMap<RelPath, Vec<Fold>>,
+    /// From `.prompt/redactions.toml`, reloaded with the file list
+    pub redactions: Redactions,
+    /// From `.prompt/env.toml`, reloaded with the file list
+    pub env_presets: crate::env_presets::EnvPresets,
+    /// Tokens comment stripping saves per file, with the mtime it was measured at
+    pub comment_savings: std::collections::HashMap<RelPath, (Option<std::time::SystemTime>, usize)>,
+    /// Tokens of the <errors> summary by output hash (None: no diagnostics)
+    pub error_summary_tokens: std::collections::HashMap<u64, Option<usize>>,
+    // Kept alive for the whole session: on X11 the contents vanish when the owner drops
+    pub clipboard: Option<clipboard::ClipboardContext>,
+    // Prompt whose clipboard copy could not be verified, offered for saving instead
+    pub unsaved_prompt: Option<String>,
 }
 
 // 🤖 Escape rules for XML ATTRIBUTE values (quotes must be escaped)
-fn escape_xml_attr(s: &str) -> String {
+pub fn escape_xml_attr(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
         .replace('>', "&gt;")
@@ -54,7 +178,7 @@ const DEFAULT_SYSTEM_PROMPT_ABS: &str =
 
 fn find_system_prompt_path(
     current_folder: Option<&std::path::Path>,
-) -> Result<std::path::PathBuf, String> {
+) -> Result<std::path::PathBuf, PromptError> {
     use std::path::PathBuf;
 
     let mut tried: Vec<PathBuf> = Vec::new();
@@ -96,51 +220,154 @@ fn find_system_prompt_path(
         .map(|p| p.display().to_string())
         .collect::<Vec<_>>()
         .join(", ");
-    Err(format!("System prompt not found. Tried: {}", tried_list))
+    Err(PromptError::SystemPromptNotFound { tried: tried_list })
+}
+// Cap per-file bytes to keep prompts manageable
+const MAX_PER_FILE_BYTES: usize = 512 * 1024; // 🤖 512 KiB kept per file
+
+// 🤖 read text safely with a size cap; avoids loading huge/binary blobs fully
+fn read_text_capped(
+    path: &std::path::Path,
+    entry: Option<&str>,
+    max_bytes: usize,
+    labels: &PromptLabels,
+    normalize: bool,
+    line_numbers: bool,
+    keywords: &[String],
+) -> Result<(String, TextIssues), PromptError> {
+    let (text, bom, encoding) = match entry {
+        Some(entry) => read_archive_text(path, entry, max_bytes, labels)?,
+        None => read_text_raw(path, max_bytes, labels)?,
+    };
+    let issues = TextIssues::scan(&text, bom, encoding);
+    let text = if normalize {
+        text_hygiene::normalize(text)
+    } else {
+        text
+    };
+    let text = if line_numbers {
+        crate::line_numbers::number(&text, &labels.truncated)
+    } else {
+        text
+    };
+    // Over the cap: keep the blocks that matter most (imports, types, instruction keywords)
+    let text = if text.len() > max_bytes {
+        crate::truncation::truncate(&text, max_bytes, keywords, &labels.truncated)
+    } else {
+        text
+    };
+    Ok((text, issues))
 }
-// 🤖 read text safely with head+tail cap; avoids loading huge/binary blobs fully
-fn read_text_capped(path: &std::path::Path, max_bytes: usize) -> Option<String> {
+
+/// Files up to this many times the cap are read whole (and truncated by structure later);
+/// larger ones only have their byte head and tail read.
+const STRUCTURED_TRUNCATION_FACTOR: usize = 8;
+
+/// An archive entry's text, read whole up to the structured-truncation size.
+fn read_archive_text(
+    path: &std::path::Path,
+    entry: &str,
+    max_bytes: usize,
+    labels: &PromptLabels,
+) -> Result<(String, Option<Bom>, Option<&'static Encoding>), PromptError> {
+    let (bytes, complete) =
+        crate::archives::read_entry(path, entry, max_bytes * STRUCTURED_TRUNCATION_FACTOR)?;
+    let bom = Bom::detect(&bytes);
+    let wide = matches!(bom, Some(Bom::Utf16Le | Bom::Utf16Be));
+    if !wide && bytes[..bytes.len().min(1024)].contains(&0) {
+        return Ok((format!("{}\n", labels.binary_omitted), None, None));
+    }
+    let encoding = text_hygiene::detect_encoding(&bytes, bom);
+    let skip = if wide { 2 } else { 0 };
+    let mut text = text_hygiene::decode(&bytes[skip..], encoding);
+    if !complete {
+        text.push('\n');
+        text.push_str(&labels.truncated);
+        text.push('\n');
+    }
+    Ok((
+        text,
+        bom,
+        (encoding != encoding_rs::UTF_8).then_some(encoding),
+    ))
+}
+
+fn read_text_raw(
+    path: &std::path::Path,
+    max_bytes: usize,
+    labels: &PromptLabels,
+) -> Result<(String, Option<Bom>, Option<&'static Encoding>), PromptError> {
     use std::fs::File; // 🤖 localize imports to avoid changing top-of-file
     use std::io::{Read, Seek, SeekFrom};
 
-    let mut f = File::open(path).ok()?;
-    let len = f.metadata().ok()?.len() as usize;
+    let read_err = |e| PromptError::io("read", path, e);
+    let mut f = File::open(path).map_err(read_err)?;
+    let len = f.metadata().map_err(read_err)?.len() as usize;
 
     // Quick binary sniff: read a small prefix and look for NUL
     let mut sniff = [0u8; 1024];
-    let n = f.read(&mut sniff).ok().unwrap_or(0);
+    let n = f.read(&mut sniff).map_err(read_err)?;
+    let bom = Bom::detect(&sniff[..n]);
+
+    // UTF-16 is full of NULs, so decode it before the binary check (head only if huge)
+    if let Some(wide @ (Bom::Utf16Le | Bom::Utf16Be)) = bom {
+        let mut buf = sniff[..n].to_vec();
+        f.take(max_bytes.saturating_sub(n) as u64)
+            .read_to_end(&mut buf)
+            .map_err(read_err)?;
+        let encoding = text_hygiene::detect_encoding(&buf, bom);
+        let mut text = text_hygiene::decode(&buf[wide.len()..], encoding);
+        if len > max_bytes {
+            text.push('\n');
+            text.push_str(&labels.truncated);
+            text.push('\n');
+        }
+        return Ok((text, bom, Some(encoding)));
+    }
     if sniff[..n].contains(&0) {
-        return Some(String::from("[binary file omitted]\n")); // 🤖 safe marker
+        return Ok((format!("{}\n", labels.binary_omitted), None, None)); // 🤖 safe marker
     }
+    // Anything but UTF-8 is transcoded (lossy -> valid UTF-8) and its encoding reported
+    let transcoded =
+        |encoding: &'static Encoding| (encoding != encoding_rs::UTF_8).then_some(encoding);
 
-    // Small file: read all (lossy -> valid UTF-8)
-    if len <= max_bytes {
+    // Small or moderately large file: read all
+    if len <= max_bytes * STRUCTURED_TRUNCATION_FACTOR {
         let mut buf = Vec::with_capacity(len);
         if n > 0 {
             buf.extend_from_slice(&sniff[..n]);
         }
-        f.read_to_end(&mut buf).ok()?;
-        return Some(String::from_utf8_lossy(&buf).into_owned());
+        f.read_to_end(&mut buf).map_err(read_err)?;
+        let encoding = text_hygiene::detect_encoding(&buf, bom);
+        return Ok((
+            text_hygiene::decode(&buf, encoding),
+            bom,
+            transcoded(encoding),
+        ));
     }
 
-    // Large file: read head and tail halves
+    // Huge file: read head and tail halves
     let half = max_bytes / 2;
     let mut head = vec![0u8; half.saturating_sub(n)];
-    f.read_exact(&mut head).ok()?;
+    f.read_exact(&mut head).map_err(read_err)?;
 
     // Seek for tail
     let tail_len = half;
     let tail_start = (len.saturating_sub(tail_len)) as u64;
-    f.seek(SeekFrom::Start(tail_start)).ok()?;
+    f.seek(SeekFrom::Start(tail_start)).map_err(read_err)?;
     let mut tail = vec![0u8; tail_len];
-    f.read_exact(&mut tail).ok()?;
+    f.read_exact(&mut tail).map_err(read_err)?;
 
-    let mut out = String::new();
-    out.push_str(&String::from_utf8_lossy(&sniff[..n]));
-    out.push_str(&String::from_utf8_lossy(&head));
-    out.push_str("\n[... truncated ...]\n"); // 🤖 explicit truncation marker
-    out.push_str(&String::from_utf8_lossy(&tail));
-    Some(out)
+    // The tail is decoded with the encoding detected from the head
+    head.splice(0..0, sniff[..n].iter().copied());
+    let encoding = text_hygiene::detect_encoding(&head, bom);
+    let mut out = text_hygiene::decode(&head, encoding);
+    // 🤖 explicit truncation marker
+    out.push('\n');
+    out.push_str(&labels.truncated);
+    out.push('\n');
+    out.push_str(&text_hygiene::decode(&tail, encoding));
+    Ok((out, bom, transcoded(encoding)))
 }
 
 impl MyApp {
@@ -153,67 +380,127 @@ impl MyApp {
         Self::project_config_dir(base).join("terminal_history.json")
     }
 
+    /// Show a transient status message and record it in the notification log.
+    pub fn notify(&mut self, message: String) {
+        self.notifications.push(Level::Info, message.clone());
+        self.notification = Some((message, Instant::now()));
+    }
+
+    /// Log an error and show it in the notification area.
+    pub fn report_error(&mut self, err: PromptError) {
+        eprintln!("[prompt] ERROR: {}", err);
+        let message = err.to_string();
+        self.notifications.push(Level::Error, message.clone());
+        self.notification = Some((message, Instant::now()));
+    }
+
     fn load_history(&mut self) {
         let Some(ref base) = self.current_folder else {
             return;
         };
         let path = Self::history_file_path(base);
-        if let Ok(data) = std::fs::read_to_string(&path) {
-            if let Ok(v) = serde_json::from_str::<serde_json::Value>(&data) {
+        let Ok(data) = std::fs::read_to_string(&path) else {
+            return; // no history yet
+        };
+        match serde_json::from_str::<serde_json::Value>(&data) {
+            Ok(v) => {
                 if let Some(arr) = v.get("commands").and_then(|v| v.as_array()) {
+                    // Entries from before working directories were recorded are plain strings
                     self.terminal.history = arr
                         .iter()
-                        .filter_map(|x| x.as_str().map(|s| s.to_string()))
+                        .filter_map(|x| match x.as_str() {
+                            Some(command) => {
+                                Some(HistoryEntry::new(command.to_string(), String::new()))
+                            }
+                            None => serde_json::from_value(x.clone()).ok(),
+                        })
                         .collect();
                 }
                 if let Some(max) = v.get("max").and_then(|v| v.as_u64()) {
                     self.terminal.max_history = max as usize;
                 }
             }
+            Err(e) => self.report_error(PromptError::Config {
+                path,
+                message: e.to_string(),
+            }),
         }
     }
 
-    fn save_history(&self) -> std::io::Result<()> {
+    fn save_history(&self) -> Result<(), PromptError> {
         let Some(ref base) = self.current_folder else {
             return Ok(());
         };
         let dir = Self::project_config_dir(base);
-        std::fs::create_dir_all(&dir)?;
+        std::fs::create_dir_all(&dir).map_err(|e| PromptError::io("create", &dir, e))?;
         let path = Self::history_file_path(base);
         let json = serde_json::json!({
             "commands": self.terminal.history,
             "max": self.terminal.max_history,
         });
-        std::fs::write(path, serde_json::to_string_pretty(&json).unwrap())
+        std::fs::write(&path, serde_json::to_string_pretty(&json).unwrap())
+            .map_err(|e| PromptError::io("write", &path, e))
     }
 
-    fn save_history_silent(&self) {
-        let _ = self.save_history();
+    fn persist_history(&mut self) {
+        if let Err(e) = self.save_history() {
+            self.report_error(e);
+        }
     }
     fn add_to_history(&mut self, cmd: &str) {
         let cmd = cmd.trim();
         if cmd.is_empty() {
             return;
         }
-        if let Some(pos) = self.terminal.history.iter().position(|c| c == cmd) {
-            self.terminal.history.remove(pos);
+        let mut entry = HistoryEntry::new(
+            cmd.to_string(),
+            self.terminal.working_dir.trim().to_string(),
+        );
+        // Re-running keeps the entry's schedule
+        if let Some(pos) = self
+            .terminal
+            .history
+            .iter()
+            .position(|e| e.same_command(&entry))
+        {
+            entry = self.terminal.history.remove(pos);
         }
-        self.terminal.history.insert(0, cmd.to_string());
+        entry.last_run = Some(chrono::Local::now());
+        self.terminal.history.insert(0, entry);
         if self.terminal.history.len() > self.terminal.max_history {
             self.terminal.history.pop();
         }
-        self.save_history_silent();
+        self.persist_history();
     }
 
-    fn run_terminal_command(&mut self, command: String) {
+    fn run_terminal_command(&mut self, command: String, working_dir: String) {
+        self.run_terminal_command_for(command, working_dir, None);
+    }
+
+    /// `run_terminal_command`, also answering an MCP client's call when `reply` is given.
+    /// Client commands get no stdin.
+    fn run_terminal_command_for(
+        &mut self,
+        command: String,
+        working_dir: String,
+        reply: Option<crate::mcp::Reply>,
+    ) {
         let tokens: Vec<String> = match shell_words::split(&command) {
             Ok(t) => t,
             Err(err) => {
-                self.terminal.terminal_output = format!("Error parsing command: {}", err);
+                let err = PromptError::CommandParse(err.to_string());
+                self.terminal.terminal_output = err.to_string();
+                if let Some(reply) = reply {
+                    let _ = reply.send(Err(err.to_string()));
+                }
+                self.report_error(err);
                 return;
             }
         };
         if tokens.is_empty() {
+            if let Some(reply) = reply {
+                let _ = reply.send(Err("empty command".into()));
+            }
             return;
         }
 
@@ -247,35 +534,542 @@ impl MyApp {
         if idx >= tokens.len() {
             self.terminal.terminal_output =
                 "Expected a command after environment assignments.".to_string();
+            if let Some(reply) = reply {
+                let _ = reply.send(Err(self.terminal.terminal_output.clone()));
+            }
             return;
         }
 
+        // The chosen preset goes first so inline assignments override it
+        let preset = self.env_presets.get(&self.config.env_preset);
+        let secrets = preset.map(|p| p.secret_values()).unwrap_or_default();
+        if let Some(preset) = preset {
+            let inline = std::mem::take(&mut env_overrides);
+            env_overrides = preset
+                .vars
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .chain(inline)
+                .collect();
+        }
+
         let cmd = tokens[idx].clone();
         let args: Vec<String> = tokens[idx + 1..].to_vec();
         let head = self.terminal.head_lines;
         let tail = self.terminal.tail_lines;
         let timeout = self.terminal.timeout_secs;
-        let tx = self.terminal.terminal_update_tx.clone();
-        let working_dir = self
+        let marker = PromptLabels::resolve(&self.config.labels).output_truncated;
+        let base = self
             .current_folder
             .clone()
-            .unwrap_or_else(|| std::env::current_dir().unwrap());
-
-        std::thread::spawn(move || {
-            let args_ref: Vec<&str> = args.iter().map(String::as_str).collect();
-            let do_timeout = timeout > 0;
-            let output = run_command(
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_else(|| PathBuf::from("."));
+        let dir_label = working_dir.trim().to_string();
+        let working_dir = crate::command_runner::resolve_dir(&base, &working_dir);
+        if !working_dir.is_dir() {
+            let err = PromptError::io(
+                "use working directory",
                 &working_dir,
-                &cmd,
-                &args_ref,
-                head,
-                tail,
-                do_timeout,
-                Duration::from_secs(timeout),
-                &env_overrides,
+                std::io::Error::from(std::io::ErrorKind::NotFound),
             );
-            let _ = tx.send(output);
+            self.terminal.terminal_output = err.to_string();
+            if let Some(reply) = reply {
+                let _ = reply.send(Err(err.to_string()));
+            }
+            self.report_error(err);
+            return;
+        }
+
+        let stdin = match self.terminal.stdin_mode {
+            _ if reply.is_some() => None,
+            StdinMode::None => None,
+            StdinMode::Text => Some(self.terminal.stdin_text.clone()),
+            StdinMode::Prompt => Some(build_prompt_as(self, PromptKind::Full)),
+        };
+
+        let label = command.trim().to_string();
+        let job = self
+            .jobs
+            .spawn(JobKind::Command, command.trim(), move |ctx| {
+                let args_ref: Vec<&str> = args.iter().map(String::as_str).collect();
+                let do_timeout = timeout > 0;
+                let result = run_command(
+                    &working_dir,
+                    &cmd,
+                    &args_ref,
+                    head,
+                    tail,
+                    do_timeout,
+                    Duration::from_secs(timeout),
+                    &env_overrides,
+                    &marker,
+                    stdin,
+                    Some(ctx.cancel_flag()),
+                );
+                let result = result.map(|mut output| {
+                    output.text = crate::env_presets::mask(output.text, &secrets);
+                    output
+                });
+                if let Some(reply) = reply {
+                    let _ = reply.send(match &result {
+                        Ok(output) => Ok(match output.exit_code {
+                            Some(code) => format!("exit code {}\n{}", code, output.text),
+                            None => format!("killed (timed out or signalled)\n{}", output.text),
+                        }),
+                        Err(e) => Err(e.to_string()),
+                    });
+                }
+                JobOutput::Terminal {
+                    command: label,
+                    working_dir: dir_label,
+                    result,
+                }
+            });
+        self.terminal.job = Some(job);
+    }
+
+    /// Answers the MCP client's pending tool calls.
+    fn serve_mcp(&mut self) {
+        let Some(server) = &self.mcp else {
+            return;
+        };
+        use crate::mcp::{McpCall, McpRequest};
+        let requests: Vec<McpRequest> = std::iter::from_fn(|| server.next()).collect();
+        for McpRequest { call, reply } in requests {
+            let answer = match call {
+                McpCall::ListFiles => Ok(self
+                    .files
+                    .iter()
+                    .filter(|f| !f.tree_only)
+                    .map(|f| {
+                        let mark = if f.selected { " (selected)" } else { "" };
+                        format!(
+                            "{}\t{} tokens{}\n",
+                            f.rel_path.replace('\\', "/"),
+                            f.token_count,
+                            mark
+                        )
+                    })
+                    .collect()),
+                McpCall::ReadFile(rel) => self.mcp_read_file(&rel),
+                McpCall::RunCommand(command) => {
+                    self.notify(format!("MCP client ran `{}`", command.trim()));
+                    self.run_terminal_command_for(command, String::new(), Some(reply));
+                    continue;
+                }
+                McpCall::BuildPrompt => {
+                    let prompt = build_prompt(self);
+                    self.token_count = crate::token_count::count_tokens(&prompt);
+                    self.generated_prompt = prompt.clone();
+                    Ok(prompt)
+                }
+            };
+            let _ = reply.send(answer);
+        }
+    }
+
+    /// Answers pending HTTP API calls.
+    fn serve_http_api(&mut self) {
+        use crate::http_api::{ApiCall, ApiRequest};
+        let Some(server) = &self.http_api else {
+            return;
+        };
+        let requests: Vec<ApiRequest> = std::iter::from_fn(|| server.next()).collect();
+        for ApiRequest { call, reply } in requests {
+            let answer = match call {
+                ApiCall::SetInstruction(text) => {
+                    self.extra_text = text;
+                    Ok(serde_json::json!({ "ok": true }).to_string())
+                }
+                ApiCall::Select { glob, selected } => match globset::Glob::new(&glob) {
+                    Ok(glob) => {
+                        let matcher = glob.compile_matcher();
+                        let mut matched = 0;
+                        for f in self.files.iter_mut().filter(|f| !f.tree_only) {
+                            if matcher.is_match(f.rel_path.replace('\\', "/")) {
+                                f.selected = selected;
+                                matched += 1;
+                            }
+                        }
+                        Ok(serde_json::json!({ "matched": matched }).to_string())
+                    }
+                    Err(e) => Err(e.to_string()),
+                },
+                ApiCall::Generate => {
+                    let kind = if self.conversation.follow_up_active() {
+                        PromptKind::FollowUp
+                    } else {
+                        PromptKind::Full
+                    };
+                    let prompt = build_and_record(self, kind);
+                    self.save_to_history(&prompt);
+                    Ok(serde_json::json!({ "tokens": self.token_count }).to_string())
+                }
+                ApiCall::Prompt => Ok(self.generated_prompt.clone()),
+            };
+            let _ = reply.send(answer);
+        }
+    }
+
+    /// A project file's text, redacted as in prompts.
+    fn mcp_read_file(&self, rel: &str) -> std::result::Result<String, String> {
+        let base = self
+            .current_folder
+            .as_deref()
+            .ok_or("no project folder is open")?;
+        let path = crate::responses::project_path(base, rel)
+            .ok_or_else(|| format!("{:?} is outside the project folder", rel))?;
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| PromptError::io("read", &path, e).to_string())?;
+        Ok(self.redactions.apply(&text).into_owned())
+    }
+
+    /// Keeps the file watcher running while an armed "on change" schedule or read file
+    /// contents need it.
+    fn keep_file_watch(&mut self) {
+        let wanted = self.current_folder.is_some()
+            && (self.terminal.history.iter().any(|e| e.armed && e.on_change)
+                || self.files.iter().any(|f| f.content.is_some()));
+        let running = self
+            .terminal
+            .watch
+            .is_some_and(|id| self.jobs.is_running(id));
+        match (wanted, running) {
+            (true, false) => {
+                if let Some(folder) = self.current_folder.clone() {
+                    self.terminal.watch = Some(crate::file_watch::spawn(
+                        folder,
+                        &self.files,
+                        &mut self.jobs,
+                    ));
+                }
+            }
+            (false, true) => self.stop_file_watch(),
+            _ => {}
+        }
+    }
+
+    /// Stops the watcher; `keep_file_watch` starts it again, on the current file list.
+    fn stop_file_watch(&mut self) {
+        if let Some(id) = self.terminal.watch.take() {
+            self.jobs.cancel(id);
+        }
+    }
+
+    /// Starts the first history command due for an automatic run (one at a time, never
+    /// while another command is running).
+    fn run_scheduled_commands(&mut self, ctx: &egui::Context) {
+        self.keep_file_watch();
+        if !self
+            .terminal
+            .history
+            .iter()
+            .any(HistoryEntry::runs_automatically)
+        {
+            return;
+        }
+        ctx.request_repaint_after(Duration::from_secs(1));
+        if self.current_folder.is_none() || self.terminal_running() {
+            return;
+        }
+        let now = chrono::Local::now();
+        let newest = self.terminal.newest_change;
+        let Some(entry) = self
+            .terminal
+            .history
+            .iter_mut()
+            .find(|e| e.is_due(now, newest))
+        else {
+            return;
+        };
+        entry.last_run = Some(now);
+        let (command, working_dir) = (entry.command.clone(), entry.working_dir.clone());
+        self.run_terminal_command(command, working_dir);
+    }
+
+    /// Drops the read contents of files the watcher saw change on disk since they went
+    /// into a prompt, so nothing reuses outdated text, and flags them in the tree until
+    /// the next build.
+    fn drop_stale_contents(&mut self, changes: &[crate::file_watch::FileChange]) {
+        let mut changed = Vec::new();
+        for change in changes {
+            for &index in &change.files {
+                // The list may have been rescanned since the watcher started
+                let Some(file) = self.files.get_mut(index).filter(|f| f.path == change.path) else {
+                    continue;
+                };
+                if file.content.is_none() || change.modified == file.content_modified {
+                    continue;
+                }
+                file.content = None;
+                file.content_stale = true;
+                file.modified = change.modified;
+                if file.archive_entry.is_none() {
+                    file.size = change.size.unwrap_or(0);
+                }
+                if file.exact_tokens && !file.summarize_table && !file.diff_only {
+                    file.exact_tokens = false;
+                    if !crate::image_info::is_image(&file.rel_path) {
+                        file.token_count =
+                            self.token_calibration.estimate(&file.rel_path, file.size);
+                    }
+                }
+                changed.push(file.rel_path.clone());
+            }
+        }
+        match changed.as_slice() {
+            [] => {}
+            [one] => self.notify(format!(
+                "{} changed on disk since the last prompt; it will be re-read",
+                one
+            )),
+            many => self.notify(format!(
+                "{} included files changed on disk since the last prompt; they will be re-read",
+                many.len()
+            )),
+        }
+    }
+
+    fn terminal_running(&self) -> bool {
+        self.terminal.job.is_some_and(|id| self.jobs.is_running(id))
+    }
+
+    /// Runs the verify command (or `cargo test`) in the terminal; when it finishes,
+    /// `focus_failing_tests` narrows the prompt to what failed.
+    fn run_failing_tests(&mut self) {
+        let command = match self.config.verify_command.trim() {
+            "" => crate::test_focus::DEFAULT_TEST_COMMAND.to_string(),
+            command => command.to_string(),
+        };
+        self.terminal.terminal_command = command.clone();
+        self.add_to_history(&command);
+        self.focus_tests_pending = true;
+        self.run_terminal_command(command, self.terminal.working_dir.clone());
+    }
+
+    /// Replaces the selection with the failing tests and the definitions they exercise, as
+    /// snippets; the run's output stays in the terminal section.
+    fn focus_failing_tests(&mut self) {
+        let failures = crate::test_focus::failures(&self.terminal.terminal_output);
+        if failures.is_empty() {
+            self.notify("No failing tests found in the output".into());
+            return;
+        }
+        let Some(base) = self.current_folder.clone() else {
+            return;
+        };
+        // The index may predate edits made since the picker was last opened
+        self.symbol_picker.index = crate::symbols::index_project(&self.files);
+        let plan =
+            crate::test_focus::plan(&self.files, &self.symbol_picker.index, &base, &failures);
+        for f in &mut self.files {
+            f.selected = false;
+        }
+        self.symbol_picker.picked.clear();
+        for symbol in plan.tests.iter().chain(&plan.under_test) {
+            self.symbol_picker.pick(symbol);
+        }
+        let mut message = format!(
+            "{} failing tests: picked {} test functions and {} definitions under test",
+            plan.failures,
+            plan.tests.len(),
+            plan.under_test.len()
+        );
+        if !plan.missing.is_empty() {
+            message.push_str(&format!("; not found: {}", plan.missing.join(", ")));
+        }
+        self.notify(message);
+    }
+
+    /// One tab per kept run: ticked runs go into the prompt, the selected one is shown below.
+    fn run_tabs(&mut self, ui: &mut egui::Ui) {
+        let mut select = None;
+        let mut close = None;
+        ui.horizontal_wrapped(|ui| {
+            for (i, run) in self.terminal.runs.iter_mut().enumerate() {
+                ui.checkbox(&mut run.include, "")
+                    .on_hover_text("Include this run's output in the prompt");
+                let status = match run.exit_code {
+                    Some(0) => "✔".to_string(),
+                    Some(code) => format!("✘ {}", code),
+                    None => "⊘".to_string(),
+                };
+                let mut title: String = run.command.chars().take(32).collect();
+                if title.len() < run.command.len() {
+                    title.push('…');
+                }
+                let detail = if run.snapshot {
+                    "⎘".to_string()
+                } else {
+                    format!("{} {:.1}s", status, run.duration.as_secs_f32())
+                };
+                let tab = ui
+                    .selectable_label(
+                        self.terminal.active_run == Some(i),
+                        format!("#{} {} {}", run.number, title, detail),
+                    )
+                    .on_hover_text(format!(
+                        "{}\nin {}\n{}, ≈ {} tokens{}",
+                        run.command,
+                        if run.working_dir.is_empty() {
+                            "(project folder)"
+                        } else {
+                            &run.working_dir
+                        },
+                        run.status(),
+                        run.tokens,
+                        if run.truncated {
+                            ", output cut to head/tail lines"
+                        } else {
+                            ""
+                        }
+                    ));
+                if tab.clicked() {
+                    select = Some(i);
+                }
+                if ui.small_button("x").on_hover_text("Close").clicked() {
+                    close = Some(i);
+                }
+                ui.separator();
+            }
         });
+        if let Some(i) = select {
+            self.terminal.select_run(i);
+        }
+        if let Some(i) = close {
+            self.terminal.close_run(i);
+        }
+    }
+
+    /// Starts the shell in the terminal's working directory with the chosen env preset.
+    fn start_shell(&mut self, ctx: &egui::Context) {
+        let Some(base) = self.current_folder.clone() else {
+            self.notify("Open a project folder first".into());
+            return;
+        };
+        let working_dir = self.terminal.working_dir.trim().to_string();
+        let dir = crate::command_runner::resolve_dir(&base, &working_dir);
+        let preset = self.env_presets.get(&self.config.env_preset);
+        let env: Vec<(String, String)> = preset
+            .map(|p| p.vars.clone().into_iter().collect())
+            .unwrap_or_default();
+        let secrets = preset.map(|p| p.secret_values()).unwrap_or_default();
+        match crate::pty_session::PtySession::start(&dir, working_dir, &env, secrets, ctx) {
+            Ok(session) => self.shell.session = Some(session),
+            Err(e) => self.report_error(e),
+        }
+    }
+
+    fn shell_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.shell.open;
+        let mut start = false;
+        let mut snapshot = None;
+        let mut failure = None;
+        egui::Window::new("Interactive shell")
+            .open(&mut open)
+            .default_width(760.0)
+            .show(ctx, |ui| {
+                let shell = &mut self.shell;
+                let Some(session) = shell.session.as_mut() else {
+                    ui.label("Runs your shell in a pseudo-terminal in the working directory.");
+                    start = ui.button("Start shell").clicked();
+                    return;
+                };
+                let exited = session.exit_status().map(str::to_string);
+                ui.horizontal(|ui| {
+                    match &exited {
+                        Some(status) => {
+                            ui.weak(format!("Shell ended ({})", status));
+                            start = ui.button("Restart").clicked();
+                        }
+                        None => {
+                            ui.weak(if session.working_dir.is_empty() {
+                                "Running in the project folder".to_string()
+                            } else {
+                                format!("Running in {}", session.working_dir)
+                            });
+                            if ui
+                                .button("Ctrl-C")
+                                .on_hover_text("Interrupt the running program")
+                                .clicked()
+                            {
+                                failure = session.interrupt().err();
+                            }
+                        }
+                    }
+                    if ui.button("Clear").clicked() {
+                        session.clear();
+                    }
+                    if ui
+                        .button("Snapshot into prompt")
+                        .on_hover_text(
+                            "Add the scrollback as an output tab that goes into the prompt",
+                        )
+                        .clicked()
+                    {
+                        snapshot = Some((session.working_dir.clone(), session.text()));
+                    }
+                });
+                ui.separator();
+                let char_width = ui
+                    .fonts(|f| f.glyph_width(&egui::TextStyle::Monospace.resolve(ui.style()), 'M'));
+                session.fit_width((ui.available_width() / char_width) as u16);
+                let text = session.text();
+                egui::ScrollArea::vertical()
+                    .max_height(420.0)
+                    .stick_to_bottom(true)
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        ui.add(
+                            egui::TextEdit::multiline(&mut text.as_str())
+                                .font(egui::TextStyle::Monospace)
+                                .desired_width(f32::INFINITY),
+                        );
+                    });
+                if exited.is_some() {
+                    return;
+                }
+                ui.horizontal(|ui| {
+                    let input = ui.add(
+                        egui::TextEdit::singleline(&mut shell.input)
+                            .password(shell.hide_input)
+                            .hint_text("Type a line, Enter to send")
+                            .font(egui::TextStyle::Monospace)
+                            .desired_width(ui.available_width() - 160.0),
+                    );
+                    let entered =
+                        input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if entered || ui.button("Send").clicked() {
+                        let line = std::mem::take(&mut shell.input);
+                        failure = session.send_line(&line).err();
+                        input.request_focus();
+                    }
+                    ui.checkbox(&mut shell.hide_input, "Hide")
+                        .on_hover_text("Mask what you type, for password prompts");
+                });
+            });
+        self.shell.open = open;
+        if let Some(e) = failure {
+            self.report_error(e);
+        }
+        if start {
+            self.start_shell(ctx);
+        }
+        if let Some((working_dir, text)) = snapshot {
+            self.terminal.push_snapshot(working_dir, text);
+            self.notify("Shell scrollback added as an output tab".into());
+        }
+    }
+
+    fn lint_running(&self) -> bool {
+        self.lint.job.is_some_and(|id| self.jobs.is_running(id))
+    }
+
+    fn run_lint(&mut self) {
+        if let Some(base) = self.current_folder.clone() {
+            self.lint.open = true;
+            self.lint.run(&base, &mut self.jobs);
+        }
     }
 
     fn open_prompt_folder(&mut self) {
@@ -287,7 +1081,10 @@ impl MyApp {
             },
         };
         let dir = Self::project_config_dir(&base);
-        let _ = std::fs::create_dir_all(&dir);
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            self.report_error(PromptError::io("create", &dir, e));
+            return;
+        }
 
         #[cfg(target_os = "macos")]
         let mut cmd = SysCommand::new("open");
@@ -296,8 +1093,69 @@ impl MyApp {
         #[cfg(target_os = "windows")]
         let mut cmd = SysCommand::new("explorer");
 
-        let _ = cmd.arg(&dir).spawn();
-        self.notification = Some((format!("Opened {}", dir.display()), Instant::now()));
+        match cmd.arg(&dir).spawn() {
+            Ok(_) => {
+                self.notify(format!("Opened {}", dir.display()));
+            }
+            Err(source) => self.report_error(PromptError::Command {
+                command: format!("{:?}", cmd.get_program()),
+                source,
+            }),
+        }
+    }
+
+    fn offer_scaffold(&mut self) {
+        self.scaffold_offer = self
+            .current_folder
+            .as_deref()
+            .filter(|base| crate::scaffold::needs_scaffold(base))
+            .map(crate::scaffold::detect);
+    }
+
+    fn scaffold_window(&mut self, ctx: &egui::Context) {
+        let Some(kind) = self.scaffold_offer else {
+            return;
+        };
+        let mut create = false;
+        let mut dismiss = false;
+        egui::Window::new("Set up .prompt")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Detected a {} project. Create the project's .prompt folder with:",
+                    kind.label()
+                ));
+                ui.label("• .promptignore with common and project-specific ignore rules");
+                ui.label("• system_prompt_addon.txt");
+                ui.label("• config.toml (metadata and verify command preset for the project type)");
+                ui.label("• starter instruction snippets");
+                ui.weak("Existing files are left untouched.");
+                ui.horizontal(|ui| {
+                    create = ui.button("Create").clicked();
+                    dismiss = ui.button("Not now").clicked();
+                });
+            });
+        if dismiss {
+            self.scaffold_offer = None;
+        }
+        if !create {
+            return;
+        }
+        self.scaffold_offer = None;
+        let Some(base) = self.current_folder.clone() else {
+            return;
+        };
+        match crate::scaffold::create(&base, kind) {
+            Ok(created) if created.is_empty() => {
+                self.notify(".prompt is already fully set up".into())
+            }
+            Ok(created) => {
+                self.refresh_files();
+                self.notify(format!("Created .prompt/{}", created.join(", .prompt/")));
+            }
+            Err(e) => self.report_error(e),
+        }
     }
 
     fn create_addon_template(&mut self) {
@@ -306,36 +1164,20 @@ impl MyApp {
         };
         let dir = Self::project_config_dir(base);
         let path = dir.join("system_prompt_addon.txt");
-        let _ = std::fs::create_dir_all(&dir);
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            self.report_error(PromptError::io("create", &dir, e));
+            return;
+        }
         if path.exists() {
-            self.notification = Some((
-                format!("Addon already exists at {}", path.display()),
-                Instant::now(),
-            ));
+            self.notify(format!("Addon already exists at {}", path.display()));
             return;
         }
-        let template = r"# Project System Prompt Addon
-
-Use this file to add project-specific guidance. It is appended after the base system prompt.
-
-- Context: Briefly explain the project domain and any unusual conventions.
-- Commands: Typical run/test commands or env you want the assistant to be aware of.
-- Constraints: Any do/don't rules unique to this repo.
-- Terminology: Domain terms, file extensions, or technologies to use correctly.
-
-Example notes:
-- Prefer `cargo run --bin <name>` for executables here.
-- Keep shader filenames and extensions consistent (e.g., .slang, .glsl, .wgsl as appropriate).
-- Large files should be summarized; avoid inlining binaries.
-";
+        let template = crate::scaffold::addon(crate::scaffold::detect(base));
         match std::fs::write(&path, template) {
             Ok(_) => {
-                self.notification = Some((format!("Created {}", path.display()), Instant::now()));
-            }
-            Err(e) => {
-                self.notification =
-                    Some((format!("Failed to create addon: {}", e), Instant::now()));
+                self.notify(format!("Created {}", path.display()));
             }
+            Err(e) => self.report_error(PromptError::io("write", &path, e)),
         }
     }
 
@@ -345,126 +1187,3105 @@ Example notes:
         };
         let dir = base.join(".prompt");
         let path = dir.join(".promptignore");
-        let _ = std::fs::create_dir_all(&dir);
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            self.report_error(PromptError::io("create", &dir, e));
+            return;
+        }
         if path.exists() {
-            self.notification = Some((
-                format!(".promptignore already exists at {}", path.display()),
-                Instant::now(),
+            self.notify(format!(
+                ".promptignore already exists at {}",
+                path.display()
             ));
             return;
         }
-        let template = r"# .promptignore
-# Lines starting with '#' are comments.
-# Globs match paths relative to the project root.
-
-# Common large or generated directories
-**/target/**
-**/.git/**
-**/node_modules/**
-**/out/**
-*.lock
-*.DS_Store
-
-# Temporary files
-*.tmp
-
-# Add your own patterns below
-";
+        let template = crate::scaffold::promptignore(&crate::language_packs::detect(base));
         match std::fs::write(&path, template) {
             Ok(_) => {
                 // Reload ignore set and file list to reflect new rules
-                self.ignore_set = crate::file_item::load_ignore_set_from(base);
-                self.refresh_files();
-                self.notification = Some((format!("Created {}", path.display()), Instant::now()));
-            }
-            Err(e) => {
-                self.notification = Some((
-                    format!("Failed to create .promptignore: {}", e),
-                    Instant::now(),
-                ));
+                self.start_scan();
+                self.notify(format!("Created {}", path.display()));
             }
+            Err(e) => self.report_error(PromptError::io("write", &path, e)),
         }
     }
-    pub fn refresh_files(&mut self) {
-        if let Some(ref folder) = self.current_folder {
-            let previous_selection: std::collections::HashMap<_, _> = self
-                .files
-                .iter()
-                .map(|f| (f.path.clone(), f.selected))
-                .collect();
-
-            self.ignore_set = crate::file_item::load_ignore_set_from(folder);
-            let (file_paths, scanned, ignored_files, ignored_dirs, syms) =
-                crate::file_item::get_all_files_limited(folder, MAX_FILES, &self.ignore_set);
-            self.scanned_files = scanned;
-            self.ignored_files = ignored_files;
-            self.ignored_dirs = ignored_dirs;
-            self.symlinks_skipped = syms;
-
-            self.files.clear();
-            for path in file_paths {
-                let rel_path = match path.strip_prefix(folder) {
-                    Ok(rel) => rel.to_string_lossy().to_string(),
-                    Err(_) => path.to_string_lossy().to_string(),
-                };
-                if self.ignore_set.is_match(&rel_path) {
-                    continue;
-                }
-
-                // 🤖 FAST estimate from file size (no disk read)
-                let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
-                let tok = ((size as f32) / 4.0).ceil() as usize; // 🤖 ~4 chars/token
+    fn clipboard(&mut self) -> Result<&mut clipboard::ClipboardContext, PromptError> {
+        if self.clipboard.is_none() {
+            let cb = clipboard::ClipboardContext::new()
+                .map_err(|e| PromptError::Clipboard(e.to_string()))?;
+            self.clipboard = Some(cb);
+        }
+        Ok(self
+            .clipboard
+            .as_mut()
+            .expect("clipboard initialized above"))
+    }
 
-                let selected = previous_selection.get(&path).cloned().unwrap_or(false);
-                self.files.push(FileItem {
-                    path,
-                    rel_path,
-                    selected,
-                    content: None, // 🤖 we only load contents when copying
-                    token_count: tok,
-                });
-            }
+    /// Sets the clipboard and reads it back; large prompts can be dropped silently otherwise.
+    fn copy_verified(&mut self, text: &str) -> Result<(), PromptError> {
+        let cb = self.clipboard()?;
+        cb.set_contents(text.to_owned())
+            .map_err(|e| PromptError::Clipboard(e.to_string()))?;
+        let back = cb
+            .get_contents()
+            .map_err(|e| PromptError::Clipboard(format!("copy could not be verified: {}", e)))?;
+        if back != text {
+            return Err(PromptError::Clipboard(format!(
+                "copy verification failed ({} bytes expected, {} read back)",
+                text.len(),
+                back.len()
+            )));
         }
+        Ok(())
     }
 
-    fn remote_url_panel(&mut self, ctx: &egui::Context) {
-        egui::TopBottomPanel::top("remote_url_panel").show(ctx, |ui| {
-            ui.horizontal(|ui| {
-                ui.label("Remote URL:");
-                ui.text_edit_singleline(&mut self.remote.new_url);
-                if ui.button("Add URL").clicked() && !self.remote.new_url.is_empty() {
-                    self.remote.remote_urls.push(RemoteUrl {
-                        url: self.remote.new_url.clone(),
-                        content: None,
-                        include: false,
-                    });
-                    let index = self.remote.remote_urls.len() - 1;
-                    let url = self.remote.remote_urls[index].url.clone();
-                    let tx = self.remote.remote_update_tx.clone();
-                    std::thread::spawn(move || {
-                        match reqwest::blocking::get(&url).and_then(|resp| resp.text()) {
-                            Ok(text) => {
-                                let _ = tx.send(RemoteUpdate::Fetched {
-                                    index,
-                                    content: extract_text(&text),
-                                });
-                            }
-                            Err(err) => {
-                                eprintln!("Error fetching {}: {:?}", url, err);
-                            }
+    fn conversation_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.conversation.open;
+        let mut eval_request: Option<(usize, bool)> = None;
+        let mut verify_changed = false;
+        let eval_running = self.jobs.any_running(JobKind::Eval);
+        egui::Window::new("Conversation")
+            .open(&mut open)
+            .default_width(640.0)
+            .default_height(520.0)
+            .show(ctx, |ui| {
+                let conv = &mut self.conversation;
+                let evaluator = &mut self.evaluator;
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut evaluator.enabled, "Evaluation mode").on_hover_text(
+                        "Apply a response's patch, run the verify command, record pass/fail, revert the patch",
+                    );
+                    if evaluator.enabled {
+                        ui.label("Verify:");
+                        let edit = ui.add(
+                            egui::TextEdit::singleline(&mut self.config.verify_command)
+                                .hint_text("cargo test")
+                                .desired_width(200.0),
+                        );
+                        verify_changed = edit.lost_focus();
+                        if eval_running {
+                            ui.spinner();
                         }
-                    });
-                    self.remote.new_url.clear();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Next Copy Prompt:");
+                    ui.radio_value(&mut conv.mode, PromptMode::Fresh, "Fresh prompt")
+                        .on_hover_text("Full prompt; starts a new conversation");
+                    let follow_up = ui
+                        .add_enabled(
+                            !conv.turns.is_empty(),
+                            egui::RadioButton::new(conv.mode == PromptMode::FollowUp, "Follow-up"),
+                        )
+                        .on_hover_text("Only new/changed files and the new instruction")
+                        .on_disabled_hover_text("Copy a fresh prompt first");
+                    if follow_up.clicked() {
+                        conv.mode = PromptMode::FollowUp;
+                    }
+                    if ui.button("Reset").clicked() {
+                        conv.reset();
+                    }
+                });
+                ui.separator();
+                if conv.turns.is_empty() {
+                    ui.label("No turns yet. Copy a prompt to start a conversation.");
+                    return;
                 }
-                // Right-aligned project controls on the same row
-                let avail = ui.available_width();
-                ui.allocate_ui_with_layout(
-                    egui::Vec2::new(avail, 0.0),
-                    egui::Layout::right_to_left(egui::Align::Center),
-                    |ui| {
-                        if ui
-                            .button("Create .promptignore file")
-                            .on_hover_text("Create default .promptignore in .prompt")
+                let last = conv.turns.len() - 1;
+                egui::ScrollArea::vertical()
+                    .id_salt("conversation_scroll")
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        for (i, turn) in conv.turns.iter_mut().enumerate() {
+                            let summary: String = turn.instruction.chars().take(60).collect();
+                            egui::CollapsingHeader::new(format!(
+                                "Turn {}: {} files — {}",
+                                i + 1,
+                                turn.files.len(),
+                                summary
+                            ))
+                            .id_salt(("turn", i))
+                            .default_open(i == last)
+                            .show(ui, |ui| {
+                                if turn.copied_again > 0 {
+                                    ui.label(format!(
+                                        "Prompt: {} bytes (copied again ×{})",
+                                        turn.prompt.len(),
+                                        turn.copied_again
+                                    ));
+                                } else {
+                                    ui.label(format!("Prompt: {} bytes", turn.prompt.len()));
+                                }
+                                if let Some(snapshot) = &turn.snapshot {
+                                    ui.weak(format!("Snapshot {}", snapshot.describe()));
+                                }
+                                ui.label("Response (paste the model's reply):");
+                                ui.add(
+                                    egui::TextEdit::multiline(&mut turn.response)
+                                        .desired_rows(if i == last { 12 } else { 4 })
+                                        .desired_width(f32::INFINITY)
+                                        .code_editor(),
+                                );
+                                if evaluator.enabled {
+                                    ui.horizontal(|ui| {
+                                        let idle = !eval_running;
+                                        let has_patch =
+                                            crate::eval::extract_patch(&turn.response).is_some();
+                                        if ui
+                                            .add_enabled(
+                                                idle && has_patch,
+                                                egui::Button::new("Apply patch & verify"),
+                                            )
+                                            .on_hover_text(
+                                                "The patch is reverted once the verify command finishes",
+                                            )
+                                            .on_disabled_hover_text(
+                                                "Needs a ```diff block or bare diff in the response",
+                                            )
+                                            .clicked()
+                                        {
+                                            eval_request = Some((i, true));
+                                        }
+                                        if ui
+                                            .add_enabled(idle, egui::Button::new("Verify only"))
+                                            .clicked()
+                                        {
+                                            eval_request = Some((i, false));
+                                        }
+                                        if let Some(result) = &turn.eval {
+                                            let (text, color) = if result.passed {
+                                                ("✔ passed", egui::Color32::LIGHT_GREEN)
+                                            } else {
+                                                ("✘ failed", egui::Color32::LIGHT_RED)
+                                            };
+                                            ui.label(
+                                                egui::RichText::new(format!(
+                                                    "{} at {}",
+                                                    text,
+                                                    result.at.format("%H:%M:%S")
+                                                ))
+                                                .color(color),
+                                            )
+                                            .on_hover_text(&result.summary);
+                                        }
+                                    });
+                                }
+                            });
+                        }
+                    });
+            });
+        self.conversation.open = open;
+
+        if verify_changed {
+            self.save_config();
+        }
+        let Some(base) = self.current_folder.clone() else {
+            return;
+        };
+        if let Some((turn, apply)) = eval_request {
+            let verify = self.config.verify_command.trim().to_string();
+            if verify.is_empty() {
+                self.notify("Set a verify command first (e.g. cargo test)".into());
+                return;
+            }
+            let patch = if apply {
+                crate::eval::extract_patch(&self.conversation.turns[turn].response)
+            } else {
+                None
+            };
+            let label = format!("turn {}: {}", turn + 1, verify);
+            self.jobs.spawn(JobKind::Eval, label, move |ctx| {
+                JobOutput::Eval(crate::eval::evaluate_turn(
+                    turn,
+                    &base,
+                    patch.as_deref(),
+                    &verify,
+                    ctx.cancel_flag(),
+                ))
+            });
+        }
+    }
+
+    /// Imports clipboard text that looks like a model reply. Our own prompt is skipped so
+    /// "Copy Prompt" doesn't bounce straight back into the Response tab.
+    fn poll_clipboard_for_response(&mut self) {
+        if !self.response.poll_due() {
+            return;
+        }
+        let Ok(text) = self.clipboard().and_then(|cb| {
+            cb.get_contents()
+                .map_err(|e| PromptError::Clipboard(e.to_string()))
+        }) else {
+            return;
+        };
+        if text == self.generated_prompt || !self.response.is_new(&text) {
+            return;
+        }
+        if !crate::responses::looks_like_reply(&text) {
+            return;
+        }
+        self.response.load(text);
+        self.response.open = true;
+        self.notify(format!(
+            "Imported response from clipboard ({} code blocks)",
+            self.response.blocks.len()
+        ));
+    }
+
+    fn response_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.response.open;
+        let mut reparse = false;
+        let mut save: Option<usize> = None;
+        let mut apply: Option<usize> = None;
+        let mut review: Option<usize> = None;
+        let mut to_conversation = false;
+        let mut revert = false;
+        egui::Window::new("Response")
+            .open(&mut open)
+            .default_width(640.0)
+            .default_height(520.0)
+            .show(ctx, |ui| {
+                let pane = &mut self.response;
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut pane.watch_clipboard, "Watch clipboard")
+                        .on_hover_text("Import copied replies that contain code blocks or diffs");
+                    if ui.button("Paste from clipboard").clicked() {
+                        reparse = true;
+                    }
+                    if ui
+                        .add_enabled(
+                            !self.conversation.turns.is_empty() && !pane.text.is_empty(),
+                            egui::Button::new("Send to conversation"),
+                        )
+                        .on_hover_text("Use as the latest turn's response")
+                        .clicked()
+                    {
+                        to_conversation = true;
+                    }
+                    let has_backup = self
+                        .current_folder
+                        .as_deref()
+                        .and_then(crate::backups::latest)
+                        .is_some();
+                    if ui
+                        .add_enabled(has_backup, egui::Button::new("Revert last apply"))
+                        .on_hover_text(
+                            "Restore the files from the most recent backup in .prompt/backups",
+                        )
+                        .clicked()
+                    {
+                        revert = true;
+                    }
+                });
+                ui.separator();
+                if pane.streaming {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Receiving reply…");
+                    });
+                    egui::ScrollArea::vertical()
+                        .id_salt("response_stream")
+                        .auto_shrink([false, false])
+                        .stick_to_bottom(true)
+                        .show(ui, |ui| {
+                            ui.label(egui::RichText::new(pane.text.as_str()).monospace());
+                        });
+                    return;
+                }
+                if pane.blocks.is_empty() {
+                    ui.label("No code blocks yet. Copy a model reply with ``` fences or a diff.");
+                    return;
+                }
+                egui::ScrollArea::vertical()
+                    .id_salt("response_scroll")
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        for (i, rb) in pane.blocks.iter_mut().enumerate() {
+                            let kind = if rb.block.is_diff() {
+                                "diff"
+                            } else if rb.block.lang.is_empty() {
+                                "text"
+                            } else {
+                                rb.block.lang.as_str()
+                            };
+                            egui::CollapsingHeader::new(format!(
+                                "Block {} ({}, {} lines)",
+                                i + 1,
+                                kind,
+                                rb.block.body.lines().count()
+                            ))
+                            .id_salt(("response_block", i))
+                            .default_open(true)
+                            .show(ui, |ui| {
+                                ui.horizontal(|ui| {
+                                    if rb.block.is_diff() {
+                                        if ui
+                                            .button("Review…")
+                                            .on_hover_text("Per-hunk diff before writing")
+                                            .clicked()
+                                        {
+                                            review = Some(i);
+                                        }
+                                        if ui.button("Apply patch").clicked() {
+                                            apply = Some(i);
+                                        }
+                                    } else {
+                                        ui.label("Path:");
+                                        ui.add(
+                                            egui::TextEdit::singleline(&mut rb.target)
+                                                .hint_text("src/file.rs")
+                                                .desired_width(260.0),
+                                        );
+                                        if ui
+                                            .add_enabled(
+                                                !rb.target.trim().is_empty(),
+                                                egui::Button::new("Save to file"),
+                                            )
+                                            .on_hover_text("Overwrite this path in the project")
+                                            .clicked()
+                                        {
+                                            save = Some(i);
+                                        }
+                                    }
+                                });
+                                let mut body = rb.block.body.as_str();
+                                ui.add(
+                                    egui::TextEdit::multiline(&mut body)
+                                        .desired_rows(rb.block.body.lines().count().min(16))
+                                        .desired_width(f32::INFINITY)
+                                        .code_editor(),
+                                );
+                            });
+                        }
+                    });
+            });
+        self.response.open = open;
+
+        if reparse {
+            match self.clipboard().and_then(|cb| {
+                cb.get_contents()
+                    .map_err(|e| PromptError::Clipboard(e.to_string()))
+            }) {
+                Ok(text) => self.response.load(text),
+                Err(e) => self.report_error(e),
+            }
+        }
+        if to_conversation {
+            if let Some(turn) = self.conversation.turns.last_mut() {
+                turn.response = self.response.text.clone();
+                self.notify(format!(
+                    "Response attached to turn {}",
+                    self.conversation.turns.len()
+                ));
+            }
+        }
+        let Some(base) = self.current_folder.clone() else {
+            if save.is_some() || apply.is_some() || review.is_some() || revert {
+                self.notify("Open a project folder first".into());
+            }
+            return;
+        };
+        if revert {
+            match crate::backups::revert_latest(&base) {
+                Ok(Some((stamp, count))) => {
+                    self.notify(format!("Reverted {} file(s) from backup {}", count, stamp));
+                    self.refresh_files();
+                }
+                Ok(None) => self.notify("No backups to revert".into()),
+                Err(e) => self.report_error(e),
+            }
+        }
+        if let Some(i) = save {
+            let rb = &self.response.blocks[i];
+            let rel = rb.target.trim().to_string();
+            let Some(path) = crate::responses::project_path(&base, &rel) else {
+                self.notify(format!("Refusing to write outside the project: {}", rel));
+                return;
+            };
+            let written = crate::backups::snapshot(&base, std::slice::from_ref(&rel))
+                .and_then(|_| crate::responses::write_file(&path, &rb.block.body));
+            match written {
+                Ok(()) => {
+                    self.notify(format!("Saved {}", rel));
+                    self.refresh_files();
+                }
+                Err(e) => self.report_error(e),
+            }
+        }
+        if let Some(i) = review {
+            let mut import = CodeImport {
+                open: true,
+                ..Default::default()
+            };
+            import.add_patch(&base, &self.response.blocks[i].block.body);
+            if import.entries.is_empty() {
+                self.notify("Patch did not match the files on disk".into());
+            } else {
+                self.code_import = import;
+            }
+        }
+        if let Some(i) = apply {
+            match crate::eval::apply_patch(&base, &self.response.blocks[i].block.body) {
+                Ok((true, _)) => {
+                    self.notify(format!("Applied patch from block {}", i + 1));
+                    self.refresh_files();
+                }
+                Ok((false, out)) => self.notify(format!("Patch did not apply:\n{}", out.trim())),
+                Err(e) => self.report_error(e),
+            }
+        }
+    }
+
+    /// False when nothing was written: no folder, a failed write, or a config.toml that
+    /// failed to load, which saving the config in memory would overwrite.
+    fn save_config(&mut self) -> bool {
+        let Some(base) = self.current_folder.clone() else {
+            return false;
+        };
+        if self.config_load_failed {
+            self.notify(
+                "Settings not saved: .prompt/config.toml failed to load. Fix it and rescan.".into(),
+            );
+            return false;
+        }
+        match self.config.save(&base) {
+            Ok(()) => true,
+            Err(e) => {
+                self.report_error(e);
+                false
+            }
+        }
+    }
+
+    fn send_to_provider(&mut self) {
+        let Some(provider) = self.config.active_provider().cloned() else {
+            return;
+        };
+        let kind = if self.conversation.follow_up_active() {
+            PromptKind::FollowUp
+        } else {
+            PromptKind::Full
+        };
+        let prompt = build_and_record(self, kind);
+        self.response.text.clear();
+        self.response.blocks.clear();
+        self.response.streaming = true;
+        self.response.open = true;
+        self.notify(format!(
+            "Sending prompt to {} ({})",
+            provider.name, provider.model
+        ));
+        self.providers.completion = Some(crate::providers::spawn_complete(
+            provider,
+            prompt,
+            self.cache_breakpoints.clone(),
+            &mut self.jobs,
+        ));
+    }
+
+    fn send_to_command(&mut self) {
+        let command = self.config.send_command.trim().to_string();
+        let dir = self
+            .current_folder
+            .clone()
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_else(|| PathBuf::from("."));
+        let kind = if self.conversation.follow_up_active() {
+            PromptKind::FollowUp
+        } else {
+            PromptKind::Full
+        };
+        let prompt = build_and_record(self, kind);
+        self.response.text.clear();
+        self.response.blocks.clear();
+        self.response.streaming = true;
+        self.response.open = true;
+        self.notify(format!("Piping prompt to `{}`", command));
+        self.providers.completion = Some(crate::providers::spawn_send_command(
+            command,
+            dir,
+            prompt,
+            &mut self.jobs,
+        ));
+    }
+
+    /// One dropdown over every provider's models, cloud and local alike; picking one makes
+    /// its provider active. Offers a local Ollama server when none is configured.
+    fn model_picker(&mut self, ui: &mut egui::Ui) {
+        let current = match self.config.active_provider() {
+            Some(p) if !p.model.is_empty() => format!("{} / {}", p.name, p.model),
+            _ => "(no model)".to_string(),
+        };
+        let mut picked: Option<(usize, String)> = None;
+        let mut add_ollama = false;
+        egui::ComboBox::from_id_salt("model_picker")
+            .selected_text(current)
+            .show_ui(ui, |ui| {
+                for (i, p) in self.config.providers.iter().enumerate() {
+                    ui.label(egui::RichText::new(&p.name).weak());
+                    if p.models.is_empty() {
+                        ui.weak("  no models listed; Fetch models under Providers…");
+                    }
+                    for m in &p.models {
+                        let active = p.name == self.config.active_provider && p.model == *m;
+                        if ui.selectable_label(active, format!("  {}", m)).clicked() {
+                            picked = Some((i, m.clone()));
+                        }
+                    }
+                }
+                let has_ollama = self
+                    .config
+                    .providers
+                    .iter()
+                    .any(|p| p.kind == crate::providers::ProviderKind::Ollama);
+                if !has_ollama {
+                    ui.separator();
+                    if ui
+                        .button("Use local Ollama")
+                        .on_hover_text(
+                            "Add a provider for http://localhost:11434 and list its models",
+                        )
+                        .clicked()
+                    {
+                        add_ollama = true;
+                    }
+                }
+            });
+
+        if let Some((i, model)) = picked {
+            let p = &mut self.config.providers[i];
+            if p.model != model {
+                p.model = model;
+                p.context_length = None;
+                if p.kind.is_local() {
+                    crate::providers::spawn_probe_context(i, p.clone(), &mut self.jobs);
+                }
+            }
+            self.config.active_provider = p.name.clone();
+            self.save_config();
+        }
+        if add_ollama {
+            let mut p = Provider::new(crate::providers::ProviderKind::Ollama);
+            let base = p.name.clone();
+            let mut n = 2;
+            while self.config.providers.iter().any(|q| q.name == p.name) {
+                p.name = format!("{}-{}", base, n);
+                n += 1;
+            }
+            let index = self.config.providers.len();
+            crate::providers::spawn_fetch_models(index, p.clone(), &mut self.jobs);
+            if self.config.active_provider().is_none() {
+                self.config.active_provider = p.name.clone();
+            }
+            self.config.providers.push(p);
+            self.save_config();
+        }
+    }
+
+    fn providers_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.providers.open;
+        let mut changed = false;
+        let mut fetch: Option<usize> = None;
+        let mut probe: Option<usize> = None;
+        let mut remove: Option<usize> = None;
+        egui::Window::new("Providers")
+            .open(&mut open)
+            .default_width(520.0)
+            .show(ctx, |ui| {
+                let config = &mut self.config;
+                ui.label("Request options (temperature, num_ctx, ...) go under [providers.options] in .prompt/config.toml.");
+                ui.horizontal(|ui| {
+                    ui.label("Send command")
+                        .on_hover_text("Run in the project folder with the prompt on stdin; its stdout is the reply");
+                    changed |= ui
+                        .add(
+                            egui::TextEdit::singleline(&mut config.send_command)
+                                .hint_text("llm -m gpt-4")
+                                .desired_width(300.0),
+                        )
+                        .lost_focus();
+                });
+                changed |= ui
+                    .checkbox(&mut config.cache_layout, "Cache-friendly layout for Anthropic")
+                    .on_hover_text(
+                        "Stable sections (system prompt, tree, unchanged files) first, with \
+                         prompt-cache breakpoints after them",
+                    )
+                    .changed();
+                ui.horizontal(|ui| {
+                    ui.label("Context limit");
+                    let r = ui
+                        .add(
+                            egui::DragValue::new(&mut config.context_limit)
+                                .speed(1000.0)
+                                .range(1..=10_000_000),
+                        )
+                        .on_hover_text("Token budget when the active provider has no context size");
+                    changed |= r.drag_stopped() || r.lost_focus();
+                });
+                ui.label("Token meter:");
+                changed |= config.meter.edit(ui);
+                ui.separator();
+                for (i, p) in config.providers.iter_mut().enumerate() {
+                    ui.push_id(("provider", i), |ui| {
+                        ui.horizontal(|ui| {
+                            changed |= ui
+                                .radio_value(&mut config.active_provider, p.name.clone(), "")
+                                .on_hover_text("Use for Send to LLM")
+                                .changed();
+                            changed |= ui
+                                .add(egui::TextEdit::singleline(&mut p.name).desired_width(100.0))
+                                .lost_focus();
+                            ui.label(p.kind.label());
+                            if ui.small_button("🗑").clicked() {
+                                remove = Some(i);
+                            }
+                        });
+                        egui::Grid::new("provider_grid").num_columns(2).show(ui, |ui| {
+                            ui.label("Base URL");
+                            changed |= ui
+                                .add(egui::TextEdit::singleline(&mut p.base_url).desired_width(300.0))
+                                .lost_focus();
+                            ui.end_row();
+                            ui.label("Key env var");
+                            changed |= ui
+                                .add(
+                                    egui::TextEdit::singleline(&mut p.api_key_env)
+                                        .hint_text("none")
+                                        .desired_width(300.0),
+                                )
+                                .on_hover_text(
+                                    "Read at request time, and only sent to the default base URL; \
+                                     keys for other servers go in ~/.config/prompt/keys.toml",
+                                )
+                                .lost_focus();
+                            ui.end_row();
+                            ui.label("Model");
+                            ui.horizontal(|ui| {
+                                egui::ComboBox::from_id_salt("model")
+                                    .selected_text(if p.model.is_empty() {
+                                        "(choose)"
+                                    } else {
+                                        p.model.as_str()
+                                    })
+                                    .show_ui(ui, |ui| {
+                                        for m in &p.models {
+                                            if ui
+                                                .selectable_value(&mut p.model, m.clone(), m)
+                                                .changed()
+                                            {
+                                                changed = true;
+                                                p.context_length = None;
+                                                if p.kind.is_local() {
+                                                    probe = Some(i);
+                                                }
+                                            }
+                                        }
+                                    });
+                                if ui
+                                    .button("Fetch models")
+                                    .on_hover_text("Ask the server which models it offers")
+                                    .clicked()
+                                {
+                                    fetch = Some(i);
+                                }
+                            });
+                            ui.end_row();
+                            ui.label("Context");
+                            ui.horizontal(|ui| {
+                                let mut limit = p.context_length.unwrap_or(0);
+                                let r = ui
+                                    .add(
+                                        egui::DragValue::new(&mut limit)
+                                            .speed(1000.0)
+                                            .range(0..=10_000_000)
+                                            .suffix(" tokens"),
+                                    )
+                                    .on_hover_text("The model's context window; 0 = use the project's limit");
+                                if r.changed() {
+                                    p.context_length = (limit > 0).then_some(limit);
+                                }
+                                changed |= r.drag_stopped() || r.lost_focus();
+                                if p.kind.is_local()
+                                    && ui
+                                        .add_enabled(
+                                            !p.model.is_empty(),
+                                            egui::Button::new("Probe"),
+                                        )
+                                        .on_hover_text(
+                                            "Ask the server for the model's context length; sets the token budget",
+                                        )
+                                        .clicked()
+                                {
+                                    probe = Some(i);
+                                }
+                            });
+                            ui.end_row();
+                            ui.label("Meter");
+                            ui.vertical(|ui| {
+                                let mut own = p.meter.is_some();
+                                if ui
+                                    .checkbox(&mut own, "Own locale and thresholds")
+                                    .changed()
+                                {
+                                    p.meter = own.then(|| config.meter.clone());
+                                    changed = true;
+                                }
+                                if let Some(meter) = &mut p.meter {
+                                    changed |= meter.edit(ui);
+                                }
+                            });
+                            ui.end_row();
+                        });
+                        ui.separator();
+                    });
+                }
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_salt("new_provider_kind")
+                        .selected_text(self.providers.new_kind.label())
+                        .show_ui(ui, |ui| {
+                            for kind in crate::providers::ProviderKind::ALL {
+                                ui.selectable_value(&mut self.providers.new_kind, kind, kind.label());
+                            }
+                        });
+                    if ui.button("Add provider").clicked() {
+                        let mut p = Provider::new(self.providers.new_kind);
+                        let taken = |n: &str| config.providers.iter().any(|q| q.name == n);
+                        let base = p.name.clone();
+                        let mut n = 2;
+                        while taken(&p.name) {
+                            p.name = format!("{}-{}", base, n);
+                            n += 1;
+                        }
+                        if config.providers.is_empty() {
+                            config.active_provider = p.name.clone();
+                        }
+                        config.providers.push(p);
+                        changed = true;
+                    }
+                });
+                ui.separator();
+                egui::CollapsingHeader::new("Prices (USD per 1M tokens)")
+                    .id_salt("price_table")
+                    .show(ui, |ui| {
+                        let mut remove_price: Option<String> = None;
+                        egui::Grid::new("price_grid").num_columns(4).show(ui, |ui| {
+                            ui.strong("Model (id or prefix)");
+                            ui.strong("Input");
+                            ui.strong("Output");
+                            ui.end_row();
+                            for (model, price) in config.prices.iter_mut() {
+                                ui.label(model);
+                                for value in [&mut price.input_per_mtok, &mut price.output_per_mtok] {
+                                    let r = ui.add(
+                                        egui::DragValue::new(value)
+                                            .speed(0.01)
+                                            .range(0.0..=1000.0)
+                                            .prefix("$"),
+                                    );
+                                    changed |= r.drag_stopped() || r.lost_focus();
+                                }
+                                if ui.small_button("🗑").clicked() {
+                                    remove_price = Some(model.clone());
+                                }
+                                ui.end_row();
+                            }
+                        });
+                        if let Some(model) = remove_price {
+                            config.prices.remove(&model);
+                            changed = true;
+                        }
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.providers.new_price_model)
+                                    .hint_text("model id")
+                                    .desired_width(160.0),
+                            );
+                            let name = self.providers.new_price_model.trim().to_string();
+                            if ui
+                                .add_enabled(!name.is_empty(), egui::Button::new("Add price"))
+                                .clicked()
+                            {
+                                config.prices.entry(name).or_default();
+                                self.providers.new_price_model.clear();
+                                changed = true;
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Expected output tokens:");
+                            let r = ui
+                                .add(
+                                    egui::DragValue::new(&mut config.expected_output_tokens)
+                                        .speed(50.0)
+                                        .range(0..=200_000),
+                                )
+                                .on_hover_text("Adds an output cost guess next to the token count; 0 hides it");
+                            changed |= r.drag_stopped() || r.lost_focus();
+                        });
+                    });
+            });
+        self.providers.open = open;
+
+        if let Some(i) = remove {
+            self.config.providers.remove(i);
+            changed = true;
+        }
+        if let Some(i) = fetch {
+            if let Some(p) = self.config.providers.get(i) {
+                crate::providers::spawn_fetch_models(i, p.clone(), &mut self.jobs);
+            }
+        }
+        if let Some(i) = probe {
+            if let Some(p) = self.config.providers.get(i) {
+                crate::providers::spawn_probe_context(i, p.clone(), &mut self.jobs);
+            }
+        }
+        if changed {
+            self.save_config();
+        }
+    }
+
+    fn import_code_from_clipboard(&mut self) {
+        let Some(base) = self.current_folder.clone() else {
+            self.notify("Open a project folder first".into());
+            return;
+        };
+        let text = match self.clipboard().and_then(|cb| {
+            cb.get_contents()
+                .map_err(|e| PromptError::Clipboard(e.to_string()))
+        }) {
+            Ok(text) => text,
+            Err(e) => {
+                self.report_error(e);
+                return;
+            }
+        };
+        let import = CodeImport::prepare(&base, &text);
+        if import.entries.is_empty() {
+            self.notify(
+                "No path-annotated code blocks in clipboard (```rust title=src/x.rs or <file path=...>)"
+                    .into(),
+            );
+            return;
+        }
+        self.code_import = import;
+    }
+
+    fn code_import_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.code_import.open;
+        let mut write = false;
+        egui::Window::new("Import code")
+            .open(&mut open)
+            .default_width(640.0)
+            .default_height(520.0)
+            .show(ctx, |ui| {
+                let import = &mut self.code_import;
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut import.layout, Layout::Unified, "Unified");
+                    ui.radio_value(&mut import.layout, Layout::SideBySide, "Side by side");
+                    if import.skipped > 0 {
+                        ui.weak(format!(
+                            "{} block(s) skipped (no path, outside the project, or hunk mismatch)",
+                            import.skipped
+                        ));
+                    }
+                });
+                egui::ScrollArea::vertical()
+                    .id_salt("code_import_scroll")
+                    .auto_shrink([false, false])
+                    .max_height(ui.available_height() - 32.0)
+                    .show(ui, |ui| {
+                        for (i, entry) in import.entries.iter_mut().enumerate() {
+                            ui.horizontal(|ui| {
+                                ui.add_enabled(
+                                    !entry.is_unchanged(),
+                                    egui::Checkbox::new(&mut entry.selected, &entry.rel_path),
+                                );
+                                ui.weak(entry.status());
+                            });
+                            if entry.is_unchanged() {
+                                continue;
+                            }
+                            let layout = import.layout;
+                            egui::CollapsingHeader::new("Diff")
+                                .id_salt(("import_diff", i))
+                                .show(ui, |ui| {
+                                    crate::diff_view::show(
+                                        ui,
+                                        &mut entry.diff,
+                                        layout,
+                                        egui::Id::new(("import_hunks", i)),
+                                    );
+                                });
+                        }
+                    });
+                ui.separator();
+                let count = import.entries.iter().filter(|e| e.selected).count();
+                if ui
+                    .add_enabled(
+                        count > 0,
+                        egui::Button::new(format!("Write {} file(s)", count)),
+                    )
+                    .clicked()
+                {
+                    write = true;
+                }
+            });
+        self.code_import.open = open;
+
+        if !write {
+            return;
+        }
+        let Some(base) = self.current_folder.clone() else {
+            return;
+        };
+        let entries = std::mem::take(&mut self.code_import.entries);
+        self.code_import.open = false;
+        let chosen: Vec<&crate::code_import::ImportEntry> = entries
+            .iter()
+            .filter(|e| e.selected && e.diff.accepted() > 0)
+            .collect();
+        let paths: Vec<String> = chosen.iter().map(|e| e.rel_path.clone()).collect();
+        if let Err(e) = crate::backups::snapshot(&base, &paths) {
+            // Don't write what we couldn't back up
+            self.report_error(e);
+            return;
+        }
+        let mut written = 0;
+        for entry in chosen {
+            let Some(path) = crate::responses::project_path(&base, &entry.rel_path) else {
+                continue;
+            };
+            match crate::responses::write_file(&path, &entry.diff.merged()) {
+                Ok(()) => written += 1,
+                Err(e) => self.report_error(e),
+            }
+        }
+        self.notify(format!(
+            "Wrote {} file(s); \"Revert last apply\" in Response undoes this",
+            written
+        ));
+        self.refresh_files();
+    }
+
+    fn project_notes_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.project_notes.open;
+        let mut save = false;
+        let mut include_changed = false;
+        egui::Window::new("Project notes")
+            .open(&mut open)
+            .default_width(560.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    include_changed = ui
+                        .checkbox(&mut self.config.include_project_notes, "Include in prompt")
+                        .changed();
+                    save = ui
+                        .add_enabled(self.project_notes.is_dirty(), egui::Button::new("Save"))
+                        .clicked();
+                    ui.weak(".prompt/notes.md");
+                });
+                let response = ui.add(
+                    egui::TextEdit::multiline(&mut self.project_notes.text)
+                        .hint_text(
+                            "Architecture, conventions, things the model should always know…",
+                        )
+                        .desired_rows(16)
+                        .desired_width(f32::INFINITY),
+                );
+                save |= response.lost_focus();
+            });
+        // Closing the window saves too
+        save |= self.project_notes.open && !open;
+        self.project_notes.open = open;
+        if include_changed {
+            self.save_config();
+        }
+        if save && self.project_notes.is_dirty() {
+            if let Err(e) = self.project_notes.save() {
+                self.report_error(e);
+            }
+        }
+    }
+
+    fn note_editor_window(&mut self, ctx: &egui::Context) {
+        let Some(editor) = &mut self.note_editor else {
+            return;
+        };
+        let mut open = true;
+        let mut save = false;
+        egui::Window::new(format!("Note: {}", editor.rel_path))
+            .id(egui::Id::new("note_editor"))
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                ui.label("Sent with this file in the prompt. Leave empty to remove.");
+                ui.add(
+                    egui::TextEdit::multiline(&mut editor.text)
+                        .desired_rows(3)
+                        .desired_width(f32::INFINITY),
+                );
+                if ui.button("Save").clicked() {
+                    save = true;
+                }
+            });
+        if save {
+            let Some(editor) = self.note_editor.take() else {
+                return;
+            };
+            let Some(base) = self.current_folder.clone() else {
+                return;
+            };
+            self.notes.set(&editor.rel_path, &editor.text);
+            if let Err(e) = self.notes.save(&base) {
+                self.report_error(e);
+                return;
+            }
+            let note = self.notes.get(&editor.rel_path).map(str::to_string);
+            for f in self.files.iter_mut() {
+                if f.rel_path.replace('\\', "/") == editor.rel_path {
+                    f.note = note.clone();
+                }
+            }
+        } else if !open {
+            self.note_editor = None;
+        }
+    }
+
+    fn open_preview(&mut self, rel_path: RelPath, path: PathBuf) {
+        let entry = self
+            .files
+            .iter()
+            .find(|f| f.rel_path == rel_path)
+            .and_then(|f| f.archive_entry.clone());
+        if let Some(entry) = entry {
+            let read = crate::archives::read_entry(&path, &entry, MAX_PER_FILE_BYTES);
+            match read {
+                Ok((bytes, _)) => {
+                    let text = String::from_utf8_lossy(&bytes).into_owned();
+                    let p = Preview::from_text(rel_path, path, text);
+                    self.preview_range = (1, p.text.lines().count().max(1));
+                    self.preview_highlight = None;
+                    self.preview = Some(p);
+                }
+                Err(e) => self.report_error(e),
+            }
+            return;
+        }
+        match Preview::load(rel_path, path.clone()) {
+            Ok(p) => {
+                self.preview_range = (1, p.text.lines().count().max(1));
+                self.preview_highlight = None;
+                self.preview = Some(p);
+            }
+            Err(e) => self.report_error(PromptError::io("read", path, e)),
+        }
+    }
+
+    fn preview_window(&mut self, ctx: &egui::Context) {
+        let Some(preview) = &self.preview else {
+            return;
+        };
+        let dark = ctx.style().visuals.dark_mode;
+        if self
+            .preview_highlight
+            .as_ref()
+            .is_none_or(|h| h.dark != dark)
+        {
+            self.preview_highlight = Some(crate::highlight::highlight(
+                &preview.rel_path,
+                &preview.text,
+                dark,
+            ));
+        }
+        let mut open = true;
+        let mut toggle: Option<Fold> = None;
+        let mut replace_folds: Option<Vec<Fold>> = None;
+        let mut select: Option<bool> = None;
+        let mut open_editor = false;
+        let mut reload = false;
+        let folds = self.folds.get(&preview.rel_path);
+        let file = self.files.iter().find(|f| f.rel_path == preview.rel_path);
+        let highlight = self.preview_highlight.as_ref();
+        let range = &mut self.preview_range;
+        egui::Window::new(format!("Preview: {}", preview.rel_path))
+            .id(egui::Id::new("file_preview"))
+            .open(&mut open)
+            .default_width(640.0)
+            .default_height(480.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if let Some(file) = file {
+                        ui.label(format!("{} tokens", file.token_count));
+                        let mut selected = file.selected;
+                        if ui
+                            .add_enabled(
+                                !file.tree_only,
+                                egui::Checkbox::new(&mut selected, "Selected"),
+                            )
+                            .changed()
+                        {
+                            select = Some(selected);
+                        }
+                    }
+                    if ui
+                        .button("Outline only")
+                        .on_hover_text("Collapse function bodies, keeping signatures")
+                        .clicked()
+                    {
+                        replace_folds = Some(preview.outline_folds());
+                        select = Some(true);
+                    }
+                    if ui
+                        .add_enabled(folds.is_some(), egui::Button::new("Show all"))
+                        .on_hover_text("Expand every collapsed region")
+                        .clicked()
+                    {
+                        replace_folds = Some(Vec::new());
+                    }
+                    ui.separator();
+                    let total = preview.text.lines().count().max(1);
+                    ui.label("Lines");
+                    ui.add(egui::DragValue::new(&mut range.0).range(1..=total));
+                    ui.label("–");
+                    ui.add(egui::DragValue::new(&mut range.1).range(1..=total));
+                    if ui
+                        .button("Select range")
+                        .on_hover_text("Include only these lines of the file")
+                        .clicked()
+                    {
+                        let (first, last) = (range.0.min(range.1), range.0.max(range.1));
+                        replace_folds = Some(preview.range_folds(first, last));
+                        select = Some(true);
+                    }
+                    ui.separator();
+                    if ui.button("Open in editor").clicked() {
+                        open_editor = true;
+                    }
+                    if ui.button("Reload").clicked() {
+                        reload = true;
+                    }
+                });
+                ui.label(format!(
+                    "{} foldable regions; collapsed regions are omitted from the prompt",
+                    preview.regions.len()
+                ));
+                let search = &mut self.preview_search;
+                search.bar(ui, &preview.text);
+                ui.separator();
+                let lines: Vec<&str> = preview.text.lines().collect();
+                let starts = crate::text_search::line_starts(&preview.text);
+                let target = search
+                    .current_offset()
+                    .filter(|_| search.scroll_pending)
+                    .map(|offset| crate::text_search::line_of(&starts, offset) + 1);
+                let mut show_line = |ui: &mut egui::Ui, n: usize| {
+                    let colors = highlight.map_or(&[][..], |h| h.line(n - 1));
+                    let job = search.line_job(
+                        ui,
+                        &format!("{:>5}  ", n),
+                        lines[n - 1],
+                        starts[n - 1],
+                        colors,
+                    );
+                    let r = ui.label(job);
+                    if target == Some(n) {
+                        r.scroll_to_me(Some(egui::Align::Center));
+                        search.scroll_pending = false;
+                    }
+                };
+                egui::ScrollArea::both()
+                    .id_salt("preview_scroll")
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        let mut n = 1;
+                        while n <= lines.len() {
+                            // A collapsed region, outline body or range starting here
+                            let folded = folds.and_then(|f| {
+                                f.iter().filter(|f| f.start == n).max_by_key(|f| f.end)
+                            });
+                            if let Some(fold) = folded {
+                                ui.horizontal(|ui| {
+                                    if ui.small_button("▶").on_hover_text("Expand").clicked() {
+                                        toggle = Some(fold.clone());
+                                    }
+                                    show_line(ui, n);
+                                });
+                                ui.monospace(
+                                    egui::RichText::new(format!(
+                                        "       … {} lines omitted …",
+                                        fold.end - fold.start
+                                    ))
+                                    .weak(),
+                                );
+                                n = fold.end.max(n) + 1;
+                                continue;
+                            }
+                            match preview.regions.iter().find(|r| r.start == n) {
+                                Some(region) => {
+                                    ui.horizontal(|ui| {
+                                        if ui
+                                            .small_button("▼")
+                                            .on_hover_text(format!("Toggle {}", region.label))
+                                            .clicked()
+                                        {
+                                            toggle = Some(preview.fold_for(region));
+                                        }
+                                        show_line(ui, n);
+                                    });
+                                }
+                                None => show_line(ui, n),
+                            }
+                            n += 1;
+                        }
+                    });
+            });
+        let rel = preview.rel_path.clone();
+        if let Some(fold) = toggle {
+            let entry = self.folds.entry(rel.clone()).or_default();
+            if let Some(pos) = entry.iter().position(|f| *f == fold) {
+                entry.remove(pos);
+            } else {
+                entry.push(fold);
+            }
+            if entry.is_empty() {
+                self.folds.remove(&rel);
+            }
+        }
+        if let Some(folds) = replace_folds {
+            if folds.is_empty() {
+                self.folds.remove(&rel);
+            } else {
+                self.folds.insert(rel.clone(), folds);
+            }
+        }
+        if let Some(selected) = select {
+            if let Some(file) = self.files.iter_mut().find(|f| f.rel_path == rel) {
+                file.selected = selected;
+            }
+        }
+        if open_editor {
+            self.open_in_editor(&rel, None);
+        }
+        let Some(preview) = &self.preview else {
+            return;
+        };
+        if !open {
+            self.preview = None;
+        } else if reload {
+            let (rel, path) = (preview.rel_path.clone(), preview.path.clone());
+            self.open_preview(rel, path);
+        }
+    }
+
+    /// Builds the prompt the next Copy would produce, without copying or recording a turn.
+    fn preview_prompt(&mut self) {
+        let prompt = build_prompt(self);
+        self.token_count = crate::token_count::count_tokens(&prompt);
+        self.generated_prompt = prompt;
+        self.show_prompt_viewer = true;
+    }
+
+    fn prompt_viewer_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_prompt_viewer;
+        let mut rebuild = false;
+        let mut copy = false;
+        egui::Window::new("Prompt")
+            .open(&mut open)
+            .default_width(800.0)
+            .default_height(600.0)
+            .show(ctx, |ui| {
+                let text = &self.generated_prompt;
+                let lines: Vec<&str> = text.lines().collect();
+                let regions = crate::prompt_outline::regions(&lines);
+                let folds = &mut self.prompt_folds;
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} bytes, {} tokens", text.len(), self.token_count));
+                    if ui
+                        .button("Rebuild")
+                        .on_hover_text("Rebuild from the current selection")
+                        .clicked()
+                    {
+                        rebuild = true;
+                    }
+                    if ui.button("Copy").clicked() {
+                        copy = true;
+                    }
+                    if ui.button("Fold all").clicked() {
+                        folds.extend(regions.iter().map(|r| r.key.clone()));
+                    }
+                    if ui.button("Unfold all").clicked() {
+                        folds.clear();
+                    }
+                });
+                let search = &mut self.prompt_search;
+                search.bar(ui, text);
+                ui.separator();
+                let starts = crate::text_search::line_starts(text);
+
+                // A search hit inside a folded section unfolds it
+                let target = search
+                    .scroll_pending
+                    .then(|| search.current_offset())
+                    .flatten()
+                    .map(|offset| crate::text_search::line_of(&starts, offset));
+                if let Some(line) = target {
+                    for r in &regions {
+                        if r.start < line && line <= r.end {
+                            folds.remove(&r.key);
+                        }
+                    }
+                }
+                let mut visible = Vec::with_capacity(lines.len());
+                let mut header_of = std::collections::HashMap::new();
+                let mut next = 0;
+                for (n, r) in regions.iter().enumerate() {
+                    visible.extend(next..=r.start);
+                    header_of.insert(r.start, n);
+                    next = if folds.contains(&r.key) {
+                        r.end + 1
+                    } else {
+                        r.start + 1
+                    };
+                }
+                visible.extend(next..lines.len());
+
+                let row_height =
+                    ui.text_style_height(&egui::TextStyle::Monospace) + ui.spacing().item_spacing.y;
+                let mut scroll = egui::ScrollArea::both()
+                    .id_salt("prompt_viewer_scroll")
+                    .auto_shrink([false, false]);
+                if let Some(line) = target {
+                    let row = visible.partition_point(|&l| l < line);
+                    let y = (row as f32 * row_height - ui.available_height() / 2.0).max(0.0);
+                    scroll = scroll.vertical_scroll_offset(y);
+                }
+                search.scroll_pending = false;
+                // Only visible rows are laid out; prompts run to tens of thousands of lines
+                scroll.show_rows(ui, row_height, visible.len(), |ui, range| {
+                    for &i in &visible[range] {
+                        ui.horizontal(|ui| {
+                            match header_of.get(&i).map(|&n| &regions[n]) {
+                                Some(r) => {
+                                    let folded = folds.contains(&r.key);
+                                    let icon = if folded { "▶" } else { "▼" };
+                                    if ui
+                                        .small_button(icon)
+                                        .on_hover_text(format!(
+                                            "{}: {} lines",
+                                            r.name,
+                                            r.end + 1 - r.start
+                                        ))
+                                        .clicked()
+                                        && !folds.remove(&r.key)
+                                    {
+                                        folds.insert(r.key.clone());
+                                    }
+                                }
+                                None => {
+                                    ui.add_space(ui.spacing().interact_size.y);
+                                }
+                            }
+                            let mut job = search.line_job(
+                                ui,
+                                &format!("{:>6}  ", i + 1),
+                                lines[i],
+                                starts[i],
+                                &[],
+                            );
+                            if header_of
+                                .get(&i)
+                                .is_some_and(|&n| folds.contains(&regions[n].key))
+                            {
+                                job.append(
+                                    &format!(" … {} lines", regions[header_of[&i]].end - i),
+                                    0.0,
+                                    egui::TextFormat {
+                                        font_id: egui::TextStyle::Monospace.resolve(ui.style()),
+                                        color: ui.visuals().weak_text_color(),
+                                        ..Default::default()
+                                    },
+                                );
+                            }
+                            ui.label(job);
+                        });
+                    }
+                });
+            });
+        self.show_prompt_viewer = open;
+        if rebuild {
+            self.preview_prompt();
+        }
+        if copy {
+            let kind = if self.conversation.follow_up_active() {
+                PromptKind::FollowUp
+            } else {
+                PromptKind::Full
+            };
+            compute_and_copy_prompt(self, ctx, kind);
+        }
+    }
+
+    fn unsaved_prompt_window(&mut self, ctx: &egui::Context) {
+        let Some(prompt) = &self.unsaved_prompt else {
+            return;
+        };
+        let mut keep = true;
+        let mut save = false;
+        egui::Window::new("Clipboard copy failed")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "The prompt ({} bytes) may not be on the clipboard. See the log for details.",
+                    prompt.len()
+                ));
+                ui.horizontal(|ui| {
+                    if ui.button("Save to file instead…").clicked() {
+                        save = true;
+                    }
+                    if ui.button("Dismiss").clicked() {
+                        keep = false;
+                    }
+                });
+            });
+        if save {
+            let prompt = prompt.clone();
+            if self.save_prompt_dialog(&prompt) {
+                keep = false;
+            }
+        }
+        if !keep {
+            self.unsaved_prompt = None;
+        }
+    }
+
+    /// Asks where to save `prompt`, starting in `.prompt/prompts/` with a dated name taken
+    /// from the instruction. Returns whether it was written.
+    fn save_prompt_dialog(&mut self, prompt: &str) -> bool {
+        let format = self.config.output_format;
+        let name = crate::prompt_history::saved_name(&self.instruction(), format.extension());
+        let mut dialog = rfd::FileDialog::new()
+            .set_file_name(&name)
+            .add_filter(format.label(), &[format.extension()]);
+        if let Some(base) = &self.current_folder {
+            let dir = crate::prompt_history::saved_dir(base);
+            if let Err(e) = std::fs::create_dir_all(&dir) {
+                self.report_error(PromptError::io("create", &dir, e));
+                return false;
+            }
+            dialog = dialog.set_directory(dir);
+        }
+        let Some(path) = dialog.save_file() else {
+            return false;
+        };
+        match std::fs::write(&path, prompt) {
+            Ok(()) => {
+                self.notify(format!("Prompt saved to {}", path.display()));
+                true
+            }
+            Err(e) => {
+                self.report_error(PromptError::io("write", &path, e));
+                false
+            }
+        }
+    }
+
+    /// Enabled instruction snippets followed by the free-form text.
+    pub fn instruction(&self) -> String {
+        self.instructions
+            .compose(&self.config.enabled_snippets, &self.extra_text)
+    }
+
+    fn system_profile_row(&mut self, ui: &mut egui::Ui) {
+        let mut chosen: Option<String> = None;
+        let mut starters = false;
+        ui.horizontal(|ui| {
+            ui.label("System prompt:");
+            let current = &self.config.system_profile;
+            let selected = if current.is_empty() {
+                "Default"
+            } else {
+                current.as_str()
+            };
+            egui::ComboBox::from_id_salt("system_profile")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    if ui
+                        .selectable_label(current.is_empty(), "Default")
+                        .on_hover_text("system_prompt.txt (project, $PROMPT_SYSTEM_PROMPT, or built-in path)")
+                        .clicked()
+                    {
+                        chosen = Some(String::new());
+                    }
+                    for profile in &self.system_profiles.profiles {
+                        let label = if profile.project_only {
+                            format!("{} (project)", profile.name)
+                        } else {
+                            profile.name.clone()
+                        };
+                        if ui
+                            .selectable_label(*current == profile.name, label)
+                            .on_hover_text(&profile.text)
+                            .clicked()
+                        {
+                            chosen = Some(profile.name.clone());
+                        }
+                    }
+                });
+            if self.system_profiles.profiles.is_empty()
+                && ui
+                    .small_button("Create starter profiles")
+                    .on_hover_text("Write code review, debugging, architecture and docs writing prompts to ~/.config/prompt/system_prompts/")
+                    .clicked()
+            {
+                starters = true;
+            }
+        });
+        if starters {
+            if let Err(e) = self
+                .system_profiles
+                .create_starters(self.current_folder.as_deref())
+            {
+                self.report_error(e);
+            }
+        }
+        if let Some(name) = chosen {
+            self.select_system_profile(name);
+        }
+    }
+
+    /// Switches to the named system prompt, and to its snippet set if it declares one.
+    fn select_system_profile(&mut self, name: String) {
+        if name == self.config.system_profile {
+            return;
+        }
+        let snippets = self
+            .system_profiles
+            .get(&name)
+            .and_then(|p| p.snippets.clone());
+        if let Some(snippets) = snippets {
+            let (known, missing): (Vec<String>, Vec<String>) = snippets
+                .into_iter()
+                .partition(|s| self.instructions.get(s).is_some());
+            if !missing.is_empty() {
+                self.notify(format!(
+                    "{} names snippets not in .prompt/snippets/: {}",
+                    name,
+                    missing.join(", ")
+                ));
+            }
+            self.config.enabled_snippets = known;
+        }
+        self.config.system_profile = name;
+        self.save_config();
+    }
+
+    fn instruction_library_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.instructions.editor_open;
+        let mut save = false;
+        let mut delete: Option<String> = None;
+        let mut starters = false;
+        egui::Window::new("Instruction snippets")
+            .open(&mut open)
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                let lib = &mut self.instructions;
+                if lib.snippets.is_empty() {
+                    ui.label("No snippets in .prompt/snippets/ yet.");
+                    if ui.button("Create starter snippets").clicked() {
+                        starters = true;
+                    }
+                }
+                for snippet in &lib.snippets {
+                    ui.horizontal(|ui| {
+                        ui.strong(&snippet.name);
+                        if ui.small_button("Edit").clicked() {
+                            lib.draft_name = snippet.name.clone();
+                            lib.draft_text = snippet.text.clone();
+                        }
+                        if ui.small_button("🗑").clicked() {
+                            delete = Some(snippet.name.clone());
+                        }
+                    });
+                    let preview: String = snippet.text.chars().take(120).collect();
+                    ui.weak(preview);
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("Name:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut lib.draft_name)
+                            .hint_text("respond-in-diff")
+                            .desired_width(200.0),
+                    );
+                });
+                ui.add(
+                    egui::TextEdit::multiline(&mut lib.draft_text)
+                        .desired_rows(4)
+                        .desired_width(f32::INFINITY),
+                );
+                let name = crate::instructions::sanitize_name(&lib.draft_name);
+                let label = if lib.get(&name).is_some() {
+                    "Update"
+                } else {
+                    "Add"
+                };
+                if ui
+                    .add_enabled(
+                        !name.is_empty() && !lib.draft_text.trim().is_empty(),
+                        egui::Button::new(label),
+                    )
+                    .clicked()
+                {
+                    save = true;
+                }
+            });
+        self.instructions.editor_open = open;
+
+        let Some(base) = self.current_folder.clone() else {
+            return;
+        };
+        let result = if starters {
+            self.instructions.create_starters(&base)
+        } else if let Some(name) = delete {
+            self.config.enabled_snippets.retain(|n| *n != name);
+            self.save_config();
+            self.instructions.delete(&base, &name)
+        } else if save {
+            let name = crate::instructions::sanitize_name(&self.instructions.draft_name);
+            let text = std::mem::take(&mut self.instructions.draft_text);
+            self.instructions.draft_name.clear();
+            self.instructions.save(&base, &name, &text)
+        } else {
+            Ok(())
+        };
+        if let Err(e) = result {
+            self.report_error(e);
+        }
+    }
+
+    fn duplicates_label(&mut self, ui: &mut egui::Ui) {
+        let wasted: usize = self
+            .duplicates
+            .iter()
+            .map(|d| self.files[d.index].token_count)
+            .sum();
+        let mut details = String::new();
+        for d in &self.duplicates {
+            details.push_str(&format!(
+                "{} {} {}\n",
+                self.files[d.index].rel_path,
+                if d.exact { "=" } else { "≈" },
+                self.files[d.original].rel_path
+            ));
+        }
+        let text = egui::RichText::new(format!(
+            "⚠ {} duplicates (~{} tokens)",
+            self.duplicates.len(),
+            wasted
+        ))
+        .color(egui::Color32::YELLOW);
+        let response = ui
+            .add(egui::Label::new(text).sense(egui::Sense::click()))
+            .on_hover_text(format!(
+                "{}\n= same up to whitespace, ≈ nearly the same. Right-click to send exact copies once.",
+                details.trim_end()
+            ));
+        response.context_menu(|ui| {
+            if ui
+                .checkbox(&mut self.config.dedupe_files, "Send exact copies once")
+                .on_hover_text("Later copies become empty entries naming the file they repeat")
+                .changed()
+            {
+                self.save_config();
+            }
+            if ui.button("Deselect the copies").clicked() {
+                for d in &self.duplicates {
+                    self.files[d.index].selected = false;
+                }
+                self.duplicates.clear();
+                ui.close_menu();
+            }
+        });
+    }
+
+    fn file_order_window(&mut self, ctx: &egui::Context) {
+        if !self.show_file_order {
+            return;
+        }
+        let mut selected: Vec<usize> = (0..self.files.len())
+            .filter(|&i| self.files[i].selected && !self.files[i].tree_only)
+            .collect();
+        selected.sort_by_key(|&i| self.files[i].rel_path.clone());
+        let order = self.config.file_order;
+        let stale =
+            !matches!(&self.arranged_files, Some((o, s, _)) if *o == order && *s == selected);
+        if stale {
+            let mut arranged = selected.clone();
+            crate::file_order::arrange(
+                &self.files,
+                &mut arranged,
+                order,
+                &self.config.manual_order,
+            );
+            self.arranged_files = Some((order, selected, arranged));
+        }
+        let Some((_, _, arranged)) = &mut self.arranged_files else {
+            return;
+        };
+
+        let mut open = true;
+        let mut changed = false;
+        egui::Window::new("File order")
+            .open(&mut open)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                egui::ComboBox::from_id_salt("file_order")
+                    .selected_text(self.config.file_order.label())
+                    .show_ui(ui, |ui| {
+                        for o in FileOrder::ALL {
+                            changed |= ui
+                                .selectable_value(&mut self.config.file_order, o, o.label())
+                                .changed();
+                        }
+                    });
+                ui.weak(self.config.file_order.description());
+                ui.weak("Drag to reorder; that switches to Manual.");
+                ui.separator();
+                if arranged.is_empty() {
+                    ui.label("No files selected.");
+                }
+                let mut moved: Option<(usize, usize)> = None;
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        for (n, &i) in arranged.iter().enumerate() {
+                            let id = egui::Id::new("file_order_row").with(i);
+                            let row = ui.dnd_drag_source(id, n, |ui| {
+                                ui.label(format!(
+                                    "☰ {}. {} ({})",
+                                    n + 1,
+                                    self.files[i].rel_path,
+                                    self.files[i].token_count
+                                ));
+                            });
+                            if let Some(from) = row.response.dnd_release_payload::<usize>() {
+                                moved = Some((*from, n));
+                            }
+                        }
+                    });
+                if let Some((from, to)) = moved.filter(|(from, to)| from != to) {
+                    let file = arranged.remove(from);
+                    arranged.insert(to, file);
+                    self.config.file_order = FileOrder::Manual;
+                    self.config.manual_order = arranged
+                        .iter()
+                        .map(|&i| self.files[i].rel_path.to_string())
+                        .collect();
+                    changed = true;
+                }
+                if !self.config.manual_order.is_empty()
+                    && ui
+                        .button("Forget manual order")
+                        .on_hover_text("Back to path order")
+                        .clicked()
+                {
+                    self.config.manual_order.clear();
+                    self.config.file_order = FileOrder::Path;
+                    changed = true;
+                }
+            });
+        self.show_file_order = open;
+        if changed {
+            // The list is rebuilt from the saved order next frame
+            self.arranged_files = None;
+            self.save_config();
+        }
+    }
+
+    fn layout_editor_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_layout_editor;
+        let mut changed = false;
+        egui::Window::new("Prompt layout")
+            .open(&mut open)
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                ui.weak("Drag to reorder. A section can appear more than once.");
+                let sections = &mut self.config.sections;
+                let mut moved: Option<(usize, usize)> = None;
+                let mut removed: Option<usize> = None;
+                for (i, section) in sections.iter().enumerate() {
+                    let row = ui.horizontal(|ui| {
+                        let id = egui::Id::new("layout_section").with(i);
+                        ui.dnd_drag_source(id, i, |ui| {
+                            ui.label(format!("☰ {}", section.label()));
+                        });
+                        if ui.small_button("✖").on_hover_text("Remove").clicked() {
+                            removed = Some(i);
+                        }
+                    });
+                    if let Some(from) = row.response.dnd_release_payload::<usize>() {
+                        moved = Some((*from, i));
+                    }
+                }
+                if let Some((from, to)) = moved.filter(|(from, to)| from != to) {
+                    let section = sections.remove(from);
+                    sections.insert(to, section);
+                    changed = true;
+                }
+                if let Some(i) = removed {
+                    sections.remove(i);
+                    changed = true;
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_salt("layout_add")
+                        .selected_text("Add section")
+                        .show_ui(ui, |ui| {
+                            for section in Section::ALL {
+                                if ui.selectable_label(false, section.label()).clicked() {
+                                    sections.push(section);
+                                    changed = true;
+                                }
+                            }
+                        });
+                    if ui.button("Reset").clicked() {
+                        *sections = crate::sections::default_order();
+                        changed = true;
+                    }
+                });
+                if !sections.contains(&Section::Instruction) {
+                    ui.colored_label(egui::Color32::YELLOW, "No instruction section");
+                }
+            });
+        self.show_layout_editor = open;
+        if changed {
+            self.save_config();
+        }
+    }
+
+    fn lint_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.lint.open;
+        let mut run = false;
+        let mut ticked = Vec::new();
+        let mut open_location = None;
+        egui::Window::new("Lint")
+            .open(&mut open)
+            .default_width(760.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let running = self.lint_running();
+                    run = ui
+                        .add_enabled(!running, egui::Button::new("Run clippy"))
+                        .on_hover_text(crate::lint::COMMAND)
+                        .clicked();
+                    if running {
+                        ui.spinner();
+                    }
+                    let included = self.lint.items.iter().filter(|i| i.include).count();
+                    ui.label(format!(
+                        "{} diagnostics, {} in prompt",
+                        self.lint.items.len(),
+                        included
+                    ));
+                    if ui.button("Tick all").clicked() {
+                        for item in &mut self.lint.items {
+                            item.include = true;
+                        }
+                        ticked = (0..self.lint.items.len()).collect();
+                    }
+                    if ui.button("Untick all").clicked() {
+                        for item in &mut self.lint.items {
+                            item.include = false;
+                        }
+                    }
+                });
+                ui.weak(
+                    "Ticked diagnostics go into the prompt as <lints>; their files get selected.",
+                );
+                ui.separator();
+                if self.lint.items.is_empty() {
+                    ui.label(if self.lint_running() {
+                        "Running clippy…"
+                    } else {
+                        "No diagnostics."
+                    });
+                    return;
+                }
+                ticked.extend(self.lint.table(ui, &mut open_location));
+            });
+        self.lint.open = open;
+        if let Some((file, line)) = open_location {
+            self.open_in_editor(&file, line);
+        }
+        if run {
+            self.run_lint();
+        }
+        let Some(base) = self.current_folder.clone() else {
+            return;
+        };
+        for i in ticked {
+            let Some(file) = self.lint.items[i].diagnostic.file.clone() else {
+                continue;
+            };
+            if let Some(f) = crate::error_refs::resolve(&self.files, &base, &file) {
+                if !self.files[f].tree_only {
+                    self.files[f].selected = true;
+                }
+            }
+        }
+    }
+
+    fn commit_message_prompt(&mut self) -> Option<String> {
+        let base = self.current_folder.clone()?;
+        match crate::commit_message::build(&base, self.commit_message.examples) {
+            Ok(prompt) => Some(self.redactions.apply(&prompt).into_owned()),
+            Err(e) => {
+                self.report_error(e);
+                None
+            }
+        }
+    }
+
+    fn write_commit_message(&mut self) {
+        let Some(base) = self.current_folder.clone() else {
+            return;
+        };
+        match crate::commit_message::write(&base, &self.commit_message.message) {
+            Ok(path) => self.notify(format!("Wrote the commit message to {}", path.display())),
+            Err(e) => self.report_error(e),
+        }
+    }
+
+    fn commit_message_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.commit_message.open;
+        let mut copy = false;
+        let mut send = false;
+        let mut write = false;
+        let provider = self.config.active_provider().cloned();
+        let running = self
+            .commit_message
+            .job
+            .is_some_and(|id| self.jobs.is_running(id));
+        egui::Window::new("Commit message")
+            .open(&mut open)
+            .default_width(560.0)
+            .show(ctx, |ui| {
+                ui.weak(
+                    "Builds a prompt from the staged diff and recent commit messages. Nothing is staged or committed here.",
+                );
+                ui.horizontal(|ui| {
+                    ui.label("Style examples");
+                    ui.add(egui::DragValue::new(&mut self.commit_message.examples).range(0..=50))
+                        .on_hover_text("How many recent commit messages to include");
+                });
+                ui.horizontal(|ui| {
+                    copy = ui
+                        .button("Copy prompt")
+                        .on_hover_text("Copy the commit message prompt to the clipboard")
+                        .clicked();
+                    let send_hover = match &provider {
+                        Some(p) => format!(
+                            "Send to {} ({}) and write the reply to .git/COMMIT_EDITMSG",
+                            p.name, p.model
+                        ),
+                        None => "No provider configured; add one under Providers…".to_string(),
+                    };
+                    send = ui
+                        .add_enabled(
+                            provider.is_some() && !running,
+                            egui::Button::new("Send to model"),
+                        )
+                        .on_hover_text(send_hover)
+                        .clicked();
+                    if running {
+                        ui.spinner();
+                    }
+                });
+                if self.commit_message.message.is_empty() {
+                    return;
+                }
+                ui.separator();
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.commit_message.message)
+                        .font(egui::TextStyle::Monospace)
+                        .desired_rows(8)
+                        .desired_width(f32::INFINITY),
+                );
+                write = ui
+                    .button("Write to COMMIT_EDITMSG")
+                    .on_hover_text("Save the edited message for the next git commit")
+                    .clicked();
+            });
+        self.commit_message.open = open;
+        if copy {
+            if let Some(prompt) = self.commit_message_prompt() {
+                match self.copy_verified(&prompt) {
+                    Ok(()) => self.notify("Copied the commit message prompt".into()),
+                    Err(e) => self.report_error(e),
+                }
+            }
+        }
+        if send {
+            if let (Some(provider), Some(prompt)) = (provider, self.commit_message_prompt()) {
+                self.notify(format!(
+                    "Asking {} ({}) for a commit message",
+                    provider.name, provider.model
+                ));
+                self.commit_message.job = Some(crate::commit_message::spawn(
+                    provider,
+                    prompt,
+                    &mut self.jobs,
+                ));
+            }
+        }
+        if write {
+            self.write_commit_message();
+        }
+    }
+
+    fn pr_description_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.pr_description.open;
+        let mut copy_prompt = false;
+        let mut send = false;
+        let mut copy = false;
+        let provider = self.config.active_provider().cloned();
+        let running = self
+            .pr_description
+            .job
+            .is_some_and(|id| self.jobs.is_running(id));
+        egui::Window::new("PR description")
+            .open(&mut open)
+            .default_width(620.0)
+            .show(ctx, |ui| {
+                egui::Grid::new("pr_description_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Base ref");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.pr_description.base_ref)
+                                .desired_width(160.0),
+                        )
+                        .on_hover_text("The branch is compared against this: main, origin/main, a tag");
+                        ui.end_row();
+                        ui.label("Issues");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.pr_description.issues)
+                                .hint_text("#12 owner/repo#34 or issue URLs")
+                                .desired_width(f32::INFINITY),
+                        )
+                        .on_hover_text(
+                            "Fetched along with the issues the commit messages mention.                              Set GITHUB_TOKEN for private repositories.",
+                        );
+                        ui.end_row();
+                    });
+                ui.horizontal(|ui| {
+                    copy_prompt = ui
+                        .add_enabled(!running, egui::Button::new("Copy prompt"))
+                        .on_hover_text("Gather the diff, commits and issues and copy the prompt")
+                        .clicked();
+                    let send_hover = match &provider {
+                        Some(p) => format!("Send to {} ({})", p.name, p.model),
+                        None => "No provider configured; add one under Providers…".to_string(),
+                    };
+                    send = ui
+                        .add_enabled(
+                            provider.is_some() && !running,
+                            egui::Button::new("Send to model"),
+                        )
+                        .on_hover_text(send_hover)
+                        .clicked();
+                    if running {
+                        ui.spinner();
+                    }
+                });
+                if self.pr_description.description.is_empty() {
+                    return;
+                }
+                ui.separator();
+                copy = ui
+                    .button("📋 Copy Markdown")
+                    .on_hover_text("Copy the description, ready to paste into GitHub")
+                    .clicked();
+                egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    ui.add(
+                        egui::TextEdit::multiline(&mut self.pr_description.description)
+                            .font(egui::TextStyle::Monospace)
+                            .desired_rows(12)
+                            .desired_width(f32::INFINITY),
+                    );
+                });
+            });
+        self.pr_description.open = open;
+        if copy {
+            let text = self.pr_description.description.clone();
+            match self.copy_verified(&text) {
+                Ok(()) => self.notify("Copied the PR description".into()),
+                Err(e) => self.report_error(e),
+            }
+        }
+        if !copy_prompt && !send {
+            return;
+        }
+        let Some(base) = self.current_folder.clone() else {
+            return;
+        };
+        self.pr_description.job = Some(crate::pr_description::spawn(
+            base,
+            self.pr_description.base_ref.clone(),
+            self.pr_description.issues.clone(),
+            if send { provider } else { None },
+            self.redactions.clone(),
+            &mut self.jobs,
+        ));
+    }
+
+    fn histogram_window(&mut self, ctx: &egui::Context) {
+        use crate::token_histogram::HistogramView;
+
+        let mut open = self.show_histogram;
+        let mut deselect: Option<String> = None;
+        let mut preview: Option<usize> = None;
+        egui::Window::new("Tokens by directory")
+            .open(&mut open)
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.histogram_view, HistogramView::Bars, "Bars");
+                    ui.selectable_value(
+                        &mut self.histogram_view,
+                        HistogramView::Treemap,
+                        "Treemap",
+                    );
+                });
+                if self.histogram_view == HistogramView::Treemap {
+                    self.treemap_ui(ui, &mut preview);
+                    return;
+                }
+                let bars = crate::token_histogram::by_top_dir(&self.files);
+                let files_total: usize = bars.iter().map(|b| b.tokens).sum();
+                let other = self.token_count.saturating_sub(files_total);
+                let budget = self.config.token_budget();
+                ui.label(format!(
+                    "{} tokens in files, {} in instruction/tree/remote; budget {}",
+                    files_total, other, budget
+                ));
+                if self.token_count > budget {
+                    ui.colored_label(
+                        egui::Color32::LIGHT_RED,
+                        format!("Over budget by {} tokens", self.token_count - budget),
+                    );
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Flag single items above");
+                    let r = ui.add(
+                        egui::DragValue::new(&mut self.config.item_warn_percent)
+                            .range(0..=100)
+                            .suffix("%"),
+                    )
+                    .on_hover_text("Share of the budget one terminal output or remote source may take; 0 = never");
+                    ui.label("of the budget");
+                    if r.drag_stopped() || r.lost_focus() {
+                        self.save_config();
+                    }
+                });
+                let mut items: Vec<(String, usize)> = self
+                    .remote
+                    .sources
+                    .iter()
+                    .filter(|s| s.include && s.content.is_some())
+                    .map(|s| (s.source.label(), s.tokens))
+                    .collect();
+                let mut runs = self.terminal.prompt_run_indices();
+                if self.terminal.runs.is_empty() && self.terminal.terminal_output.is_empty() {
+                    runs.clear();
+                }
+                for run in runs {
+                    let label = match run {
+                        Some(i) => format!("$ {}", self.terminal.runs[i].command),
+                        None => "terminal output".to_string(),
+                    };
+                    items.push((label, self.run_prompt_tokens(run)));
+                }
+                if !items.is_empty() {
+                    egui::Grid::new("histogram_items_grid")
+                        .num_columns(2)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for (label, tokens) in &items {
+                                let mut short: String = label.chars().take(60).collect();
+                                if short.len() < label.len() {
+                                    short.push('…');
+                                }
+                                ui.label(short).on_hover_text(label);
+                                item_tokens_label(ui, &self.config, *tokens);
+                                ui.end_row();
+                            }
+                        });
+                }
+                ui.separator();
+                if bars.is_empty() {
+                    ui.label("No files selected.");
+                    return;
+                }
+                let max = bars[0].tokens.max(1) as f32;
+                egui::Grid::new("histogram_grid")
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for bar in &bars {
+                            ui.label(&bar.dir)
+                                .on_hover_text(format!("{} files", bar.files));
+                            let width = 220.0;
+                            let (rect, response) = ui
+                                .allocate_exact_size(egui::vec2(width, 14.0), egui::Sense::hover());
+                            let filled = egui::Rect::from_min_size(
+                                rect.min,
+                                egui::vec2(width * bar.tokens as f32 / max, rect.height()),
+                            );
+                            let share = bar.tokens as f32 / budget.max(1) as f32;
+                            let color = if share > 0.25 {
+                                egui::Color32::from_rgb(200, 90, 80)
+                            } else {
+                                egui::Color32::from_rgb(90, 140, 200)
+                            };
+                            ui.painter().rect_filled(filled, 2.0, color);
+                            response.on_hover_text(format!("{:.1}% of budget", share * 100.0));
+                            ui.label(bar.tokens.to_string());
+                            if ui
+                                .small_button("Deselect")
+                                .on_hover_text("Unselect every file under this directory")
+                                .clicked()
+                            {
+                                deselect = Some(bar.dir.clone());
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        self.show_histogram = open;
+
+        if let Some(i) = preview {
+            let (rel, path) = (self.files[i].rel_path.clone(), self.files[i].path.clone());
+            self.open_preview(rel, path);
+        }
+        if let Some(dir) = deselect {
+            let root = dir == crate::token_histogram::ROOT_FILES;
+            for f in self.files.iter_mut() {
+                let top = f.rel_path.split_once(['/', '\\']).map(|(t, _)| t);
+                if (root && top.is_none()) || top == Some(dir.as_str()) {
+                    f.selected = false;
+                }
+            }
+            self.notify(format!("Deselected {}", dir));
+        }
+    }
+
+    /// Treemap of one directory level; clicking a directory zooms into it, a file opens
+    /// its preview.
+    fn treemap_ui(&mut self, ui: &mut egui::Ui, preview: &mut Option<usize>) {
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.treemap_whole_project, false, "Selection");
+            ui.radio_value(&mut self.treemap_whole_project, true, "Whole project");
+        });
+        ui.horizontal_wrapped(|ui| {
+            if ui
+                .selectable_label(self.treemap_dir.is_empty(), "(root)")
+                .clicked()
+            {
+                self.treemap_dir.clear();
+            }
+            let mut up_to = None;
+            let parts: Vec<&str> = self
+                .treemap_dir
+                .split('/')
+                .filter(|p| !p.is_empty())
+                .collect();
+            for (n, part) in parts.iter().enumerate() {
+                ui.label("/");
+                if ui.selectable_label(n + 1 == parts.len(), *part).clicked() {
+                    up_to = Some(parts[..=n].join("/"));
+                }
+            }
+            if let Some(dir) = up_to {
+                self.treemap_dir = dir;
+            }
+        });
+
+        let cells = crate::token_histogram::cells(
+            &self.files,
+            &self.treemap_dir,
+            self.treemap_whole_project,
+        );
+        let total: usize = cells.iter().map(|c| c.tokens).sum();
+        ui.label(format!(
+            "{} tokens in {} files",
+            total,
+            cells.iter().map(|c| c.files).sum::<usize>()
+        ));
+        if cells.is_empty() {
+            ui.label(if self.treemap_whole_project {
+                "No files here."
+            } else {
+                "No selected files here."
+            });
+            return;
+        }
+        let size = egui::vec2(ui.available_width().max(200.0), 320.0);
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
+        let sizes: Vec<usize> = cells.iter().map(|c| c.tokens).collect();
+        let rects = crate::token_histogram::squarify(&sizes, rect);
+        let painter = ui.painter_at(rect);
+        let hover = response.hover_pos();
+        let mut hovered = None;
+        for (n, (cell, cell_rect)) in cells.iter().zip(&rects).enumerate() {
+            let inner = cell_rect.shrink(1.0);
+            let mut color = self.tree_view.heat_color(cell.tokens);
+            if cell.file.is_some() {
+                color = color.gamma_multiply(0.6);
+            }
+            if hover.is_some_and(|p| cell_rect.contains(p)) {
+                hovered = Some(n);
+                color = color.gamma_multiply(1.3);
+            }
+            painter.rect_filled(inner, 2.0, color);
+            if inner.width() > 40.0 && inner.height() > 16.0 {
+                painter.with_clip_rect(inner).text(
+                    inner.min + egui::vec2(4.0, 2.0),
+                    egui::Align2::LEFT_TOP,
+                    format!("{} {}", cell.name, cell.tokens),
+                    egui::FontId::proportional(12.0),
+                    egui::Color32::BLACK,
+                );
+            }
+        }
+        if let Some(cell) = hovered.map(|n| &cells[n]) {
+            let share = cell.tokens as f32 / total.max(1) as f32 * 100.0;
+            let what = if cell.file.is_some() {
+                "click to preview".to_string()
+            } else {
+                format!("{} files; click to zoom in", cell.files)
+            };
+            response.clone().on_hover_text(format!(
+                "{}\n{} ({:.1}% of this view)\n{}",
+                cell.path,
+                self.tree_view.budget_tooltip(cell.tokens),
+                share,
+                what
+            ));
+            if response.clicked() {
+                match cell.file {
+                    Some(i) => *preview = Some(i),
+                    None => self.treemap_dir = cell.path.clone(),
+                }
+            }
+        }
+    }
+
+    fn notification_log_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.notifications.open;
+        egui::Window::new("Notification log")
+            .open(&mut open)
+            .default_width(520.0)
+            .default_height(320.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} entries", self.notifications.entries.len()));
+                    if ui.button("Clear").clicked() {
+                        self.notifications.clear();
+                    }
+                });
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .id_salt("notification_log_scroll")
+                    .auto_shrink([false, false])
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for entry in &self.notifications.entries {
+                            let (tag, color) = match entry.level {
+                                Level::Info => ("info", ui.visuals().text_color()),
+                                Level::Error => ("error", egui::Color32::LIGHT_RED),
+                            };
+                            // Plain text rows so screen readers announce time, level and message
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "{}  [{}]  {}",
+                                    entry.at.format("%H:%M:%S"),
+                                    tag,
+                                    entry.message
+                                ))
+                                .color(color),
+                            );
+                        }
+                    });
+            });
+        self.notifications.open = open;
+        if open {
+            self.notifications.unread = 0;
+        }
+    }
+
+    fn symbol_picker_window(&mut self, ctx: &egui::Context) {
+        const MAX_RESULTS: usize = 200;
+        let mut open = self.symbol_picker.open;
+        let mut open_location: Option<(String, Option<usize>)> = None;
+        if open && self.symbol_picker.index.is_empty() && !self.files.is_empty() {
+            self.symbol_picker.index = crate::symbols::index_project(&self.files);
+        }
+        egui::Window::new("Symbols")
+            .open(&mut open)
+            .default_width(560.0)
+            .default_height(420.0)
+            .show(ctx, |ui| {
+                let picker = &mut self.symbol_picker;
+                ui.horizontal(|ui| {
+                    ui.label("Search:");
+                    ui.text_edit_singleline(&mut picker.query);
+                    if ui.button("Re-index").clicked() {
+                        picker.index = crate::symbols::index_project(&self.files);
+                    }
+                    ui.label(format!("{} symbols", picker.index.len()));
+                });
+                ui.separator();
+
+                let query = picker.query.to_lowercase();
+                let matches: Vec<usize> = picker
+                    .index
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, s)| query.is_empty() || s.name.to_lowercase().contains(&query))
+                    .map(|(i, _)| i)
+                    .take(MAX_RESULTS)
+                    .collect();
+                egui::ScrollArea::vertical()
+                    .id_salt("symbol_results_scroll")
+                    .max_height(220.0)
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        for i in matches {
+                            let symbol = picker.index[i].clone();
+                            ui.horizontal(|ui| {
+                                let picked = picker.is_picked(&symbol);
+                                if ui.add_enabled(!picked, egui::Button::new("Add")).clicked() {
+                                    picker.pick(&symbol);
+                                }
+                                ui.monospace(format!(
+                                    "{} {}  {}:{}",
+                                    symbol.kind.label(),
+                                    symbol.name,
+                                    symbol.rel_path,
+                                    symbol.line
+                                ));
+                                if ui
+                                    .small_button("↗")
+                                    .on_hover_text("Open in editor")
+                                    .clicked()
+                                {
+                                    open_location =
+                                        Some((symbol.rel_path.to_string(), Some(symbol.line)));
+                                }
+                            });
+                        }
+                    });
+
+                ui.separator();
+                ui.label(format!("Included in prompt ({})", picker.picked.len()));
+                let mut remove = None;
+                for (i, p) in picker.picked.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.small_button("✕").clicked() {
+                            remove = Some(i);
+                        }
+                        ui.monospace(format!("{} {}  {}", p.kind.label(), p.name, p.rel_path));
+                        ui.checkbox(&mut p.include_doc, "doc");
+                        ui.checkbox(&mut p.include_callers, "callers");
+                        ui.checkbox(&mut p.include_blame, "blame").on_hover_text(
+                            "Add who last changed these lines, when, and why (git blame)",
+                        );
+                        if ui
+                            .small_button("↗")
+                            .on_hover_text("Open in editor")
+                            .clicked()
+                        {
+                            let line = picker
+                                .index
+                                .iter()
+                                .find(|s| {
+                                    s.rel_path == p.rel_path && s.name == p.name && s.kind == p.kind
+                                })
+                                .map(|s| s.line);
+                            open_location = Some((p.rel_path.to_string(), line));
+                        }
+                    });
+                }
+                if let Some(i) = remove {
+                    picker.picked.remove(i);
+                }
+            });
+        self.symbol_picker.open = open;
+        if let Some((file, line)) = open_location {
+            self.open_in_editor(&file, line);
+        }
+    }
+
+    fn ignored_paths_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_ignored_paths;
+        egui::Window::new("Ignored paths")
+            .open(&mut open)
+            .default_width(500.0)
+            .default_height(400.0)
+            .show(ctx, |ui| {
+                let stats = &self.scan_stats;
+                ui.label(format!(
+                    "{} files in {} pruned directories and individual matches",
+                    stats.ignored_files, stats.ignored_dirs
+                ));
+                if stats.ignored_paths_truncated() {
+                    ui.small("(list truncated)");
+                }
+                ui.separator();
+                let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+                egui::ScrollArea::vertical()
+                    .id_salt("ignored_paths_scroll")
+                    .auto_shrink([false, false])
+                    .show_rows(ui, row_height, stats.ignored_paths.len(), |ui, range| {
+                        for p in &stats.ignored_paths[range] {
+                            ui.monospace(p);
+                        }
+                    });
+            });
+        self.show_ignored_paths = open;
+    }
+
+    fn ignore_editor_window(&mut self, ctx: &egui::Context) {
+        if !self.ignore_editor.open {
+            return;
+        }
+        let mut open = true;
+        let mut save_clicked = false;
+        egui::Window::new("Edit .promptignore")
+            .open(&mut open)
+            .default_width(700.0)
+            .show(ctx, |ui| {
+                let editor = &mut self.ignore_editor;
+                if let Some(path) = &editor.path {
+                    ui.small(path.display().to_string());
+                }
+                egui::ScrollArea::vertical()
+                    .id_salt("ignore_editor_text")
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        ui.add(
+                            egui::TextEdit::multiline(&mut editor.text)
+                                .code_editor()
+                                .desired_width(f32::INFINITY)
+                                .desired_rows(14),
+                        );
+                    });
+                editor.update_preview();
+
+                for err in &editor.pattern_errors {
+                    ui.colored_label(egui::Color32::LIGHT_RED, err);
+                }
+                ui.separator();
+                ui.label(format!(
+                    "{} of {} files ignored  |  +{} newly ignored  |  -{} newly unignored",
+                    editor.ignored_total,
+                    editor.total_files(),
+                    editor.newly_ignored,
+                    editor.newly_unignored
+                ));
+                ui.columns(2, |cols| {
+                    cols[0].label(
+                        egui::RichText::new("Newly ignored").color(egui::Color32::LIGHT_RED),
+                    );
+                    for p in &editor.sample_ignored {
+                        cols[0].small(p);
+                    }
+                    if editor.newly_ignored > editor.sample_ignored.len() {
+                        cols[0].small(format!(
+                            "... and {} more",
+                            editor.newly_ignored - editor.sample_ignored.len()
+                        ));
+                    }
+                    cols[1].label(
+                        egui::RichText::new("Newly unignored").color(egui::Color32::LIGHT_GREEN),
+                    );
+                    for p in &editor.sample_unignored {
+                        cols[1].small(p);
+                    }
+                    if editor.newly_unignored > editor.sample_unignored.len() {
+                        cols[1].small(format!(
+                            "... and {} more",
+                            editor.newly_unignored - editor.sample_unignored.len()
+                        ));
+                    }
+                });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(editor.is_dirty(), egui::Button::new("Save & Apply"))
+                        .clicked()
+                    {
+                        save_clicked = true;
+                    }
+                    if ui
+                        .add_enabled(editor.is_dirty(), egui::Button::new("Revert"))
+                        .clicked()
+                    {
+                        editor.text = editor.saved_text.clone();
+                    }
+                });
+            });
+        if save_clicked {
+            match self.ignore_editor.save() {
+                Ok(path) => {
+                    self.start_scan();
+                    self.notify(format!("Saved {}", path.display()));
+                }
+                Err(e) => self.report_error(e),
+            }
+        }
+        self.ignore_editor.open = open;
+    }
+
+    /// Rescans the project folder on this thread; see `start_scan` for the UI's version.
+    pub fn refresh_files(&mut self) {
+        let Some(folder) = self.load_project_state() else {
+            return;
+        };
+        let (found, stats) = crate::file_item::get_all_files_limited(
+            &folder,
+            &folder,
+            &self.scan_options(),
+            &|_| {},
+            &AtomicBool::new(false),
+        );
+        self.apply_scan(found, stats, None);
+    }
+
+    /// Once a scan has deferred folders, rescans keep exactly those deferred (and walk
+    /// everything else) rather than capping again, so the tree doesn't reshuffle.
+    fn scan_options(&self) -> ScanOptions {
+        ScanOptions {
+            ignore_set: self.ignore_set.clone(),
+            ignore_rules: self.ignore_rules,
+            limit: if self.deferred_dirs.is_empty() {
+                MAX_FILES
+            } else {
+                usize::MAX
+            },
+            deferred: self.deferred_dirs.clone(),
+            follow_symlinks: self.config.follow_symlinks,
+            symlink_depth: self.config.symlink_depth,
+        }
+    }
+
+    /// Rescans on a job, so a big folder doesn't freeze the window. The current list stays
+    /// up (and selectable) until the new one lands; starting again cancels a running scan.
+    pub fn start_scan(&mut self) {
+        let Some(folder) = self.load_project_state() else {
+            return;
+        };
+        if let Some(id) = self.scan_job.take() {
+            self.jobs.cancel(id);
+        }
+        let options = self.scan_options();
+        let label = folder.display().to_string();
+        self.scan_job = Some(self.jobs.spawn(JobKind::Scan, label, move |ctx| {
+            let (files, stats) = crate::file_item::get_all_files_limited(
+                &folder,
+                &folder,
+                &options,
+                &|n| ctx.progress(format!("{} files", n)),
+                ctx.cancel_flag(),
+            );
+            JobOutput::Scan {
+                folder,
+                under: None,
+                files,
+                stats,
+            }
+        }));
+    }
+
+    /// Reloads the project's config, notes and ignore rules; returns the folder to walk.
+    fn load_project_state(&mut self) -> Option<PathBuf> {
+        let folder = self.current_folder.clone()?;
+        let folder = folder.as_path();
+        self.symbol_picker.reset_for(folder);
+        match ProjectConfig::load(folder) {
+            Ok(cfg) => {
+                self.config = cfg;
+                self.config_load_failed = false;
+            }
+            Err(e) => {
+                self.config_load_failed = true;
+                self.report_error(e);
+            }
+        }
+        match crate::workspace::detect(folder) {
+            Ok(crates) => self.workspace = crates,
+            Err(e) => {
+                self.workspace.clear();
+                self.report_error(e);
+            }
+        }
+        match Notes::load(folder) {
+            Ok(notes) => self.notes = notes,
+            Err(e) => self.report_error(e),
+        }
+        match crate::token_calibration::TokenCalibration::load(folder) {
+            Ok(calibration) => self.token_calibration = calibration,
+            Err(e) => {
+                self.token_calibration = Default::default();
+                self.report_error(e);
+            }
+        }
+        if let Err(e) = self.project_notes.load(folder) {
+            self.report_error(e);
+        }
+        match crate::attachments::Attachments::load(folder) {
+            Ok(attachments) => self.attachments = attachments,
+            Err(e) => {
+                self.attachments = Default::default();
+                self.report_error(e);
+            }
+        }
+        match Redactions::load(folder) {
+            Ok(redactions) => self.redactions = redactions,
+            Err(e) => {
+                self.redactions = Redactions::default();
+                self.report_error(e);
+            }
+        }
+        match crate::env_presets::EnvPresets::load(folder) {
+            Ok(presets) => self.env_presets = presets,
+            Err(e) => {
+                self.env_presets = Default::default();
+                self.report_error(e);
+            }
+        }
+        if let Err(e) = self.instructions.load(folder) {
+            self.report_error(e);
+        }
+        if let Err(e) = self.system_profiles.load(Some(folder)) {
+            self.report_error(e);
+        }
+        let (file_types, file_type_errors) = crate::file_types::FileTypes::new(
+            &self.config.file_types,
+            &ProjectConfig::path(folder),
+        );
+        self.tree_view.file_types = file_types;
+        if let Some(e) = file_type_errors.into_iter().next() {
+            self.report_error(e);
+        }
+        for e in PromptLabels::problems(&self.config.labels, &ProjectConfig::path(folder)) {
+            self.report_error(e);
+        }
+        self.language_packs = if self.config.language_packs {
+            crate::language_packs::detect(folder)
+        } else {
+            Vec::new()
+        };
+        let (ignore_set, ignore_rules, ignore_errors) =
+            crate::file_item::load_ignore_set_from(folder, &self.language_packs);
+        self.ignore_set = ignore_set;
+        self.ignore_rules = ignore_rules;
+        if let Some(e) = ignore_errors.into_iter().next() {
+            self.report_error(e);
+        }
+        Some(folder.to_path_buf())
+    }
+
+    /// Walks a folder the scan deferred, adding its files to the list when done.
+    fn load_deferred_folder(&mut self, rel: String) {
+        let Some(base) = self.current_folder.clone() else {
+            return;
+        };
+        let loading = self
+            .loading_dirs
+            .get(&rel)
+            .is_some_and(|&id| self.jobs.is_running(id));
+        if !self.deferred_dirs.contains(&rel) || loading {
+            return;
+        }
+        let mut options = ScanOptions {
+            limit: MAX_FILES,
+            ..self.scan_options()
+        };
+        options.deferred.remove(&rel);
+        let key = rel.clone();
+        let id = self.jobs.spawn(JobKind::Scan, rel.clone(), move |ctx| {
+            let (files, stats) = crate::file_item::get_all_files_limited(
+                &base,
+                &base.join(&rel),
+                &options,
+                &|n| ctx.progress(format!("{} files", n)),
+                ctx.cancel_flag(),
+            );
+            JobOutput::Scan {
+                folder: base,
+                under: Some(rel),
+                files,
+                stats,
+            }
+        });
+        self.loading_dirs.insert(key, id);
+    }
+
+    /// Lists the entries of the archive at `index` in the tree, on a job.
+    fn open_archive(&mut self, index: usize) {
+        let Some(base) = self.current_folder.clone() else {
+            return;
+        };
+        let file = &self.files[index];
+        let (path, rel_path) = (file.path.clone(), file.rel_path.to_string());
+        if !self.open_archives.insert(rel_path.clone()) {
+            return;
+        }
+        self.jobs.spawn(JobKind::Scan, rel_path.clone(), move |_| {
+            JobOutput::Archive {
+                folder: base,
+                result: crate::archives::list(&path, &rel_path, MAX_FILES),
+                rel_path,
+            }
+        });
+    }
+
+    fn close_archive(&mut self, rel_path: String) {
+        if !self.open_archives.remove(&rel_path) {
+            return;
+        }
+        let prefix = format!("{}/", rel_path);
+        self.files
+            .retain(|f| f.archive_entry.is_none() || !f.rel_path.starts_with(&prefix));
+        self.file_tree = build_file_tree(&self.files);
+        add_deferred_dirs(&mut self.file_tree, &self.deferred_dirs);
+        sort_file_tree(&mut self.file_tree, &self.files);
+    }
+
+    /// After a rescan, puts back the entries (and selections) of the archives still open.
+    /// An archive that changed on disk, or has gone, is listed again or closed.
+    fn keep_archive_entries(&mut self, entries: Vec<FileItem>) {
+        let archives = std::mem::take(&mut self.open_archives);
+        for archive in archives {
+            let Some(index) = self.files.iter().position(|f| *f.rel_path == archive) else {
+                continue;
+            };
+            let prefix = format!("{}/", archive);
+            let modified = self.files[index].modified;
+            let mine: Vec<&FileItem> = entries
+                .iter()
+                .filter(|e| e.rel_path.starts_with(&prefix))
+                .collect();
+            if mine.first().is_some_and(|e| e.modified == modified) {
+                self.files.extend(mine.into_iter().cloned());
+                self.open_archives.insert(archive);
+            } else {
+                self.open_archive(index);
+            }
+        }
+    }
+
+    /// Replaces the file list with a scan's result, keeping selections, exact counts and
+    /// text flags of the files that are still there. With `under` (a deferred folder's
+    /// walk) the files are added to the list instead.
+    fn apply_scan(&mut self, found: Vec<ScannedFile>, stats: ScanStats, under: Option<&str>) {
+        // Built from the old file list; rebuilt from the new one when next needed
+        self.symbol_picker.index.clear();
+        if self.current_folder.is_none() {
+            return;
+        }
+        type Previous = (
+            bool,
+            TextIssues,
+            Option<(Option<std::time::SystemTime>, usize)>,
+        );
+        let previous: std::collections::HashMap<RelPath, Previous> = self
+            .files
+            .iter()
+            .map(|f| {
+                let exact = f.exact_tokens.then_some((f.modified, f.token_count));
+                (f.rel_path.clone(), (f.selected, f.text_issues, exact))
+            })
+            .collect();
+
+        // Entries of open archives, which a walk of the disk doesn't find again
+        let mut archive_entries: Vec<FileItem> = Vec::new();
+        if let Some(under) = under {
+            self.deferred_dirs.remove(under);
+            self.loading_dirs.remove(under);
+            self.deferred_dirs
+                .extend(stats.deferred_dirs.iter().cloned());
+            let total = &mut self.scan_stats;
+            total.scanned_files += stats.scanned_files;
+            total.ignored_files += stats.ignored_files;
+            total.ignored_dirs += stats.ignored_dirs;
+            total.symlinks_skipped += stats.symlinks_skipped;
+            total.deferred_dirs.retain(|d| d != under);
+            total.deferred_dirs.extend(stats.deferred_dirs);
+        } else {
+            if self.scan_stats.deferred_dirs.is_empty() && !stats.deferred_dirs.is_empty() {
+                self.notify(format!(
+                    "Large project: {} folders were left unloaded after {} files. Expand one in the tree to load it.",
+                    stats.deferred_dirs.len(),
+                    MAX_FILES
+                ));
+            }
+            self.deferred_dirs = stats.deferred_dirs.iter().cloned().collect();
+            self.scan_stats = stats;
+            archive_entries = self
+                .files
+                .drain(..)
+                .filter(|f| f.archive_entry.is_some())
+                .collect();
+        }
+
+        for ScannedFile {
+            path,
+            rel_path,
+            size,
+            modified,
+            archive_entry,
+        } in found
+        {
+            if self.ignore_set.is_match(&rel_path) {
+                continue;
+            }
+            let rel_path = RelPath::from(rel_path);
+            if under.is_some() && previous.contains_key(&rel_path) {
+                continue;
+            }
+
+            // 🤖 FAST estimate from file size (no disk read), at the ratio learned for its
+            // extension. Images are sent as a one-line description, not their bytes
+            let tok = self.token_calibration.estimate(&rel_path, size);
+            let tok = if crate::image_info::is_image(&rel_path) {
+                crate::image_info::DESCRIPTION_TOKENS
+            } else {
+                tok
+            };
+
+            let key = rel_path.replace('\\', "/");
+            let tree_only = self.config.is_tree_only(&key);
+            let summarize_table = self.config.summarize_tables.contains(&key);
+            let diff_only = self.config.diff_only.contains(&key);
+            let note = self.notes.get(&key).map(str::to_string);
+            let (was_selected, text_issues, exact) =
+                previous.get(&rel_path).copied().unwrap_or_default();
+            // An exact recount survives a rescan as long as the file is unchanged
+            let exact = exact.filter(|&(at, _)| at == modified).map(|(_, n)| n);
+            let selected = !tree_only && was_selected;
+            self.files.push(FileItem {
+                path,
+                rel_path,
+                selected,
+                content: None, // 🤖 we only load contents when copying
+                content_modified: None,
+                content_stale: false,
+                content_raw: false,
+                token_count: exact.unwrap_or(tok),
+                exact_tokens: exact.is_some(),
+                tree_only,
+                summarize_table,
+                diff_only,
+                archive_entry,
+                size,
+                modified,
+                text_issues,
+                language: None,
+                note,
+            });
+        }
+        if under.is_none() {
+            self.keep_archive_entries(archive_entries);
+        }
+        self.stop_file_watch();
+        // Built once per scan; the panel walks it every frame
+        self.file_tree = build_file_tree(&self.files);
+        add_deferred_dirs(&mut self.file_tree, &self.deferred_dirs);
+        sort_file_tree(&mut self.file_tree, &self.files);
+    }
+
+    fn remote_url_panel(&mut self, ctx: &egui::Context) {
+        egui::TopBottomPanel::top("remote_url_panel").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Remote:");
+                let kind = &SOURCE_TYPES[self.remote.new_kind];
+                egui::ComboBox::from_id_salt("remote_source_kind")
+                    .selected_text(kind.name)
+                    .show_ui(ui, |ui| {
+                        for (i, t) in SOURCE_TYPES.iter().enumerate() {
+                            ui.selectable_value(&mut self.remote.new_kind, i, t.name);
+                        }
+                    });
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.remote.new_input).hint_text(kind.hint),
+                );
+                if ui.button("Add").clicked() && !self.remote.new_input.trim().is_empty() {
+                    let base = self
+                        .current_folder
+                        .clone()
+                        .unwrap_or_else(|| PathBuf::from("."));
+                    match (kind.build)(self.remote.new_input.trim(), &base) {
+                        Ok(source) => {
+                            self.remote.add(source, &mut self.jobs);
+                            self.remote.new_input.clear();
+                        }
+                        Err(e) => self.report_error(e),
+                    }
+                }
+                // Right-aligned project controls on the same row
+                let avail = ui.available_width();
+                ui.allocate_ui_with_layout(
+                    egui::Vec2::new(avail, 0.0),
+                    egui::Layout::right_to_left(egui::Align::Center),
+                    |ui| {
+                        if ui
+                            .button("Symbols…")
+                            .on_hover_text("Include individual definitions instead of whole files")
+                            .clicked()
+                        {
+                            self.symbol_picker.open = !self.symbol_picker.open;
+                        }
+                        if ui
+                            .button("Commit message…")
+                            .on_hover_text("Have a model write the message for what's staged")
+                            .clicked()
+                        {
+                            self.commit_message.open = !self.commit_message.open;
+                        }
+                        if ui
+                            .button("PR description…")
+                            .on_hover_text("Have a model describe this branch for a pull request")
+                            .clicked()
+                        {
+                            self.pr_description.open = !self.pr_description.open;
+                        }
+                        if ui
+                            .button("Edit .promptignore")
+                            .on_hover_text("Edit ignore rules with a live preview")
+                            .clicked()
+                        {
+                            if let Some(base) = self.current_folder.clone() {
+                                self.ignore_editor.open_for(&base, &self.language_packs);
+                            }
+                        }
+                        if ui
+                            .button("Set up .prompt…")
+                            .on_hover_text("Create whatever is missing of .promptignore, addon, config.toml and starter snippets")
+                            .clicked()
+                        {
+                            if let Some(base) = self.current_folder.as_deref() {
+                                self.scaffold_offer = Some(crate::scaffold::detect(base));
+                            }
+                        }
+                        if ui
+                            .button("Create .promptignore file")
+                            .on_hover_text("Create default .promptignore in .prompt")
                             .clicked()
                         {
                             self.create_promptignore();
@@ -486,38 +4307,46 @@ Example notes:
                     },
                 );
             });
-            for i in (0..self.remote.remote_urls.len()).rev() {
+            let marker = PromptLabels::resolve(&self.config.labels).omitted;
+            for i in (0..self.remote.sources.len()).rev() {
+                let mut remove = false;
                 ui.horizontal(|ui| {
-                    ui.checkbox(&mut self.remote.remote_urls[i].include, "Include");
-                    ui.label(&self.remote.remote_urls[i].url);
-                    if ui.button("Re-fetch").clicked() {
-                        let url = self.remote.remote_urls[i].url.clone();
-                        let tx = self.remote.remote_update_tx.clone();
-                        let index = i;
-                        std::thread::spawn(move || {
-                            match reqwest::blocking::get(&url).and_then(|resp| resp.text()) {
-                                Ok(text) => {
-                                    let _ = tx.send(RemoteUpdate::Fetched {
-                                        index,
-                                        content: extract_text(&text),
-                                    });
-                                }
-                                Err(err) => {
-                                    eprintln!("Error re-fetching {}: {:?}", url, err);
-                                }
-                            }
-                        });
+                    let entry = &mut self.remote.sources[i];
+                    ui.checkbox(&mut entry.include, "Include");
+                    ui.label(entry.source.label());
+                    if entry.job.is_some_and(|id| self.jobs.is_running(id)) {
+                        ui.spinner();
+                    } else if let Some(err) = &entry.error {
+                        ui.colored_label(egui::Color32::LIGHT_RED, "fetch failed")
+                            .on_hover_text(err);
+                    } else if entry.content.is_some() {
+                        item_tokens_label(ui, &self.config, entry.tokens);
+                        if entry.trim.is_active() {
+                            ui.weak(format!("trimmed from {}", entry.full_tokens));
+                        }
+                        ui.toggle_value(&mut entry.preview_open, "Preview")
+                            .on_hover_text("Show the extracted text and choose what part to include");
                     }
-                    if ui.button("Remove").clicked() {
-                        self.remote.remote_urls.remove(i);
+                    if ui.button("Refresh").clicked() {
+                        self.remote.refresh(i, &mut self.jobs);
                     }
+                    remove = ui.button("Remove").clicked();
                 });
+                if remove {
+                    self.remote.sources.remove(i);
+                    continue;
+                }
+                let entry = &mut self.remote.sources[i];
+                if entry.preview_open && entry.content.is_some() {
+                    remote_preview(ui, entry, i, &marker);
+                }
             }
         });
     }
 
     fn file_panel(&mut self, ctx: &egui::Context) {
         const BOTTOM_MARGIN: f32 = 8.0;
+        let mut tree_actions: Vec<TreeAction> = Vec::new();
         egui::SidePanel::left("left_panel")
             .resizable(true)
             .default_width(500.0)
@@ -526,82 +4355,1000 @@ Example notes:
                     if ui.button("Select Folder").clicked() {
                         if let Some(folder) = rfd::FileDialog::new().pick_folder() {
                             self.current_folder = Some(folder.clone());
-                            self.refresh_files();
+                            // Nothing from the previous folder while the new one is scanned
+                            self.files.clear();
+                            self.file_tree = FileTree::default();
+                            self.deferred_dirs.clear();
+                            self.loading_dirs.clear();
+                            self.open_archives.clear();
+                            self.start_scan();
                             self.load_history();
+                            self.offer_scaffold();
                         }
                     }
                     if ui.button("Refresh").clicked() {
-                        self.refresh_files();
+                        self.start_scan();
                     }
                     if ui.button("Clear Selection").clicked() {
                         for file in self.files.iter_mut() {
                             file.selected = false;
                         }
                     }
+                    if ui
+                        .button("Attach…")
+                        .on_hover_text("Add files from outside the project folder to the prompt")
+                        .clicked()
+                    {
+                        self.attach_files();
+                    }
+                    if ui
+                        .add_enabled(!self.language_packs.is_empty(), egui::Button::new("Smart select"))
+                        .on_hover_text(format!(
+                            "Select core files (manifests, entry points) for: {}",
+                            crate::language_packs::names(&self.language_packs)
+                        ))
+                        .on_disabled_hover_text("No language pack detected for this folder")
+                        .clicked()
+                    {
+                        self.smart_select();
+                    }
+                    if ui
+                        .button("Share")
+                        .on_hover_text("Copy a string that reproduces this selection in a teammate's app")
+                        .clicked()
+                    {
+                        self.share_selection();
+                    }
+                    if ui
+                        .button("Paste selection")
+                        .on_hover_text("Select the files from a shared selection string in the clipboard")
+                        .clicked()
+                    {
+                        self.paste_selection();
+                    }
                 });
-                ui.small(
-                    egui::RichText::new(format!(
-                        "Scanned: {}  |  Ignored dirs: {}  |  Ignored: {}  |  Symlinks: {}  |  Loaded: {}",
-                        self.scanned_files, self.ignored_dirs, self.ignored_files, self.symlinks_skipped, self.files.len()
-                    ))
-                    .monospace(),
-                );
+                let meter = self.config.meter().clone();
+                let fmt = |n: usize| meter.format(n);
+                if let Some(id) = self.scan_job {
+                    let progress = self.jobs.progress(id).map(str::to_string);
+                    if let Some(progress) = progress {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label(format!("Scanning… {}", progress));
+                            if ui.small_button("Cancel").clicked() {
+                                self.jobs.cancel(id);
+                                self.scan_job = None;
+                            }
+                        });
+                    }
+                }
+                let stats = &self.scan_stats;
+                let status = ui
+                    .add(
+                        egui::Label::new(
+                            egui::RichText::new(format!(
+                                "{} scanned, {} ignored by {} patterns, {} symlinks skipped, {} loaded{}",
+                                fmt(stats.scanned_files),
+                                fmt(stats.ignored_files),
+                                stats.pattern_count,
+                                fmt(stats.symlinks_skipped),
+                                fmt(self.files.len()),
+                                if self.deferred_dirs.is_empty() {
+                                    String::new()
+                                } else {
+                                    format!(", {} folders not loaded", fmt(self.deferred_dirs.len()))
+                                }
+                            ))
+                            .small()
+                            .monospace(),
+                        )
+                        .sense(egui::Sense::click()),
+                    )
+                    .on_hover_text(format!(
+                        "Scanned files: {}\nIgnored files: {}\nIgnored directories (pruned): {}\nSymlinks skipped: {}\nIgnore patterns: {}\n\nClick to list ignored paths; right-click for symlink options",
+                        fmt(stats.scanned_files),
+                        fmt(stats.ignored_files),
+                        fmt(stats.ignored_dirs),
+                        fmt(stats.symlinks_skipped),
+                        stats.pattern_count
+                    ));
+                if status.clicked() {
+                    self.show_ignored_paths = !self.show_ignored_paths;
+                }
+                let mut rescan = false;
+                status.context_menu(|ui| {
+                    rescan |= ui
+                        .checkbox(&mut self.config.follow_symlinks, "Follow symlinks")
+                        .on_hover_text(
+                            "Include symlinked files and walk symlinked folders; links back into \
+                             a folder already walked are skipped",
+                        )
+                        .changed();
+                    ui.add_enabled_ui(self.config.follow_symlinks, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Links deep:");
+                            rescan |= ui
+                                .add(egui::DragValue::new(&mut self.config.symlink_depth).range(1..=8))
+                                .on_hover_text("How many symlinks to follow through, one inside another")
+                                .changed();
+                        });
+                    });
+                });
+                if rescan {
+                    self.save_config();
+                    self.start_scan();
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Sort:");
+                    egui::ComboBox::from_id_salt("tree_sort")
+                        .selected_text(self.tree_view.sort.label())
+                        .show_ui(ui, |ui| {
+                            for mode in TreeSort::ALL {
+                                ui.selectable_value(&mut self.tree_view.sort, mode, mode.label());
+                            }
+                        });
+                    let mut hide = self.tree_view.hide_over_tokens.is_some();
+                    ui.checkbox(&mut hide, "Hide files over");
+                    ui.add(
+                        egui::DragValue::new(&mut self.hide_token_threshold)
+                            .speed(100)
+                            .suffix(" tokens"),
+                    );
+                    self.tree_view.hide_over_tokens = hide.then_some(self.hide_token_threshold);
+                    ui.checkbox(&mut self.tree_view.heat_map, "Heat map")
+                        .on_hover_text("Color files and folders by their share of the token budget");
+                });
+                self.tree_view.budget = self.config.token_budget();
+                if !self.attachments.items.is_empty() {
+                    self.attachments_list(ui);
+                }
                 ui.separator();
                 let available_height = ui.available_height();
                 let scroll_height = (available_height - BOTTOM_MARGIN).max(0.0);
+                let rows = visible_rows(
+                    ui.ctx(),
+                    &self.file_tree,
+                    &self.files,
+                    &self.tree_view,
+                    &self.workspace,
+                );
                 egui::ScrollArea::vertical()
                     .id_salt("file_tree_scroll_area")
                     .max_height(scroll_height)
                     .auto_shrink([false, false])
-                    .show(ui, |ui| {
-                        let mut tree = build_file_tree(&self.files);
-                        sort_file_tree(&mut tree, &self.files);
-                        show_file_tree(ui, &tree, &mut self.files);
+                    .show_rows(ui, tree_row_height(ui), rows.len(), |ui, range| {
+                        for row in &rows[range] {
+                            show_tree_row(
+                                ui,
+                                row,
+                                &mut self.files,
+                                &self.tree_view,
+                                &mut tree_actions,
+                            );
+                        }
                     });
                 ui.add_space(BOTTOM_MARGIN);
             });
+        for action in tree_actions {
+            self.handle_tree_action(action);
+        }
+    }
+
+    fn attach_files(&mut self) {
+        let Some(base) = self.current_folder.clone() else {
+            self.notify("Open a project folder first".into());
+            return;
+        };
+        let Some(paths) = rfd::FileDialog::new().pick_files() else {
+            return;
+        };
+        let added = self.attachments.add(paths);
+        self.save_attachments(&base);
+        self.notify(format!("{} file(s) attached", added));
+    }
+
+    fn save_attachments(&mut self, base: &std::path::Path) {
+        if let Err(e) = self.attachments.save(base) {
+            self.report_error(e);
+        }
+    }
+
+    fn attachments_list(&mut self, ui: &mut egui::Ui) {
+        let mut remove = None;
+        let mut changed = false;
+        egui::CollapsingHeader::new(format!("Attachments ({})", self.attachments.items.len()))
+            .default_open(true)
+            .show(ui, |ui| {
+                for (i, item) in self.attachments.items.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        changed |= ui
+                            .add_enabled(!item.missing, egui::Checkbox::new(&mut item.include, ""))
+                            .on_hover_text("Include in the prompt")
+                            .changed();
+                        let name = item
+                            .path
+                            .file_name()
+                            .map(|n| n.to_string_lossy().into_owned())
+                            .unwrap_or_else(|| item.display_path());
+                        ui.label(name).on_hover_text(item.display_path());
+                        if item.missing {
+                            ui.colored_label(egui::Color32::LIGHT_RED, "missing");
+                        } else {
+                            ui.weak(format!("{} tokens", item.token_count));
+                        }
+                        if ui.small_button("x").on_hover_text("Detach").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                }
+            });
+        if let Some(i) = remove {
+            self.attachments.items.remove(i);
+            changed = true;
+        }
+        if changed {
+            if let Some(base) = self.current_folder.clone() {
+                self.save_attachments(&base);
+            }
+        }
+    }
+
+    fn select_crate_src(&mut self, index: usize) {
+        let prefix = format!("{}/", self.workspace[index].src_dir());
+        let mut count = 0;
+        for f in self.files.iter_mut() {
+            if !f.tree_only && f.rel_path.starts_with(&prefix) {
+                f.selected = true;
+                count += 1;
+            }
+        }
+        self.notify(format!("Selected {} files in {}", count, prefix));
+    }
+
+    /// Opens a project file (at `line`) with the configured editor command.
+    fn open_in_editor(&mut self, rel_path: &str, line: Option<usize>) {
+        let Some(base) = self.current_folder.clone() else {
+            return;
+        };
+        let in_archive = self
+            .files
+            .iter()
+            .any(|f| *f.rel_path == *rel_path && f.archive_entry.is_some());
+        if in_archive {
+            self.notify(format!("{} is inside an archive", rel_path));
+            return;
+        }
+        let Some(path) = crate::responses::project_path(&base, rel_path) else {
+            self.notify(format!("{} is outside the project folder", rel_path));
+            return;
+        };
+        if let Err(e) = crate::editor::open(&self.config.editor_command, &path, line) {
+            self.report_error(e);
+        }
+    }
+
+    fn handle_tree_action(&mut self, action: TreeAction) {
+        match action {
+            TreeAction::ToggleTreeOnly(folder) => {
+                if self.current_folder.is_none() {
+                    return;
+                }
+                if let Some(pos) = self.config.tree_only.iter().position(|d| *d == folder) {
+                    self.config.tree_only.remove(pos);
+                } else if let Some(root) = self.config.tree_only_root(&folder) {
+                    self.notify(format!("Tree-only mode is inherited from {}/", root));
+                    return;
+                } else {
+                    // A new mark subsumes any marked subfolders
+                    let prefix = format!("{}/", folder);
+                    self.config.tree_only.retain(|d| !d.starts_with(&prefix));
+                    self.config.tree_only.push(folder.clone());
+                    self.config.tree_only.sort();
+                }
+                if !self.save_config() {
+                    return;
+                }
+                self.start_scan();
+            }
+            TreeAction::IncludeDependencies(index) => {
+                let depth = self.config.dependency_depth;
+                let (deps, errors) = crate::deps::collect_dependencies(&self.files, index, depth);
+                self.select_related(index, &deps, "dependencies", errors);
+            }
+            TreeAction::EditNote(index) => {
+                let rel_path = self.files[index].rel_path.replace('\\', "/");
+                let text = self.notes.get(&rel_path).unwrap_or("").to_string();
+                self.note_editor = Some(NoteEditor { rel_path, text });
+            }
+            TreeAction::Preview(index) => {
+                let (rel, path) = (
+                    self.files[index].rel_path.clone(),
+                    self.files[index].path.clone(),
+                );
+                self.open_preview(rel, path);
+            }
+            TreeAction::OpenInEditor(index) => {
+                let rel = self.files[index].rel_path.to_string();
+                self.open_in_editor(&rel, None);
+            }
+            TreeAction::SelectDependents(index) => {
+                let depth = self.config.dependency_depth;
+                let (deps, errors) = crate::deps::collect_dependents(&self.files, index, depth);
+                self.select_related(index, &deps, "dependents", errors);
+            }
+            TreeAction::SelectSymbolUsers(index, symbol) => {
+                let (users, errors) =
+                    crate::deps::collect_symbol_dependents(&self.files, index, &symbol);
+                let what = format!("users of {}", symbol.name);
+                self.select_related(index, &users, &what, errors);
+            }
+            TreeAction::RecountTokens(index) => self.recount_tokens(index),
+            TreeAction::ToggleTableSummary(index) => self.toggle_table_summary(index),
+            TreeAction::ToggleDiffOnly(index) => self.toggle_diff_only(index),
+            TreeAction::LoadFolder(rel) => self.load_deferred_folder(rel),
+            TreeAction::SelectCrateSrc(index) => self.select_crate_src(index),
+            TreeAction::OpenArchive(index) => self.open_archive(index),
+            TreeAction::CloseArchive(rel) => self.close_archive(rel),
+        }
+    }
+
+    /// Adds a copied prompt to `.prompt/history/`; returns how many times in a row the same
+    /// prompt has now been copied again.
+    fn save_to_history(&mut self, prompt: &str) -> usize {
+        let Some(base) = self.current_folder.clone() else {
+            return 0;
+        };
+        let extension = self.config.output_format.extension();
+        match crate::prompt_history::record(&base, prompt, &self.instruction(), extension) {
+            Ok(entry) => entry.copied_again,
+            Err(e) => {
+                self.report_error(e);
+                0
+            }
+        }
+    }
+
+    /// Counts one file with the tokenizer as a job; the result replaces its size-based
+    /// estimate when it arrives.
+    fn recount_tokens(&mut self, index: usize) {
+        let file = &self.files[index];
+        let (path, rel_path) = (file.path.clone(), file.rel_path.clone());
+        let table_rows = file.summarize_table.then_some(self.config.table_rows);
+        let entry = file.archive_entry.clone();
+        let image = crate::image_info::is_image(&rel_path);
+        let thumbnails = self.config.image_thumbnails;
+        let diff_of = (file.diff_only && entry.is_none()).then(|| {
+            let base = self.current_folder.clone().unwrap_or_default();
+            (
+                base,
+                rel_path.replace('\\', "/"),
+                self.config.diff_ref.clone(),
+            )
+        });
+        self.jobs
+            .spawn(JobKind::Tokenize, rel_path.to_string(), move |_| {
+                let diff = diff_of.and_then(|(base, rel, reference)| {
+                    crate::file_diff::diff(&base, &rel, &reference).transpose()
+                });
+                if let Some(diff) = diff {
+                    return JobOutput::Tokens {
+                        index,
+                        rel_path,
+                        result: diff.map(|text| crate::token_count::count_tokens(&text)),
+                    };
+                }
+                let result = match (entry, table_rows) {
+                    (Some(entry), _) => {
+                        crate::archives::read_entry(&path, &entry, usize::MAX).map(|(bytes, _)| {
+                            crate::token_count::count_tokens(&String::from_utf8_lossy(&bytes))
+                        })
+                    }
+                    (None, Some(rows)) => crate::tables::summarize(&path, rows)
+                        .map(|summary| crate::token_count::count_tokens(&summary)),
+                    (None, None) if image => crate::image_info::describe(&path, thumbnails)
+                        .map(|text| crate::token_count::count_tokens(&text)),
+                    (None, None) => std::fs::read(&path)
+                        .map(|bytes| {
+                            crate::token_count::count_tokens(&String::from_utf8_lossy(&bytes))
+                        })
+                        .map_err(|e| PromptError::io("read", &path, e)),
+                };
+                JobOutput::Tokens {
+                    index,
+                    rel_path,
+                    result,
+                }
+            });
+    }
+
+    /// Switches a data file between its contents and a table summary, and recounts its
+    /// tokens since the size estimate no longer applies to a summary.
+    fn toggle_table_summary(&mut self, index: usize) {
+        let file = &mut self.files[index];
+        file.summarize_table = !file.summarize_table;
+        let key = file.rel_path.replace('\\', "/");
+        if file.summarize_table {
+            self.config.summarize_tables.push(key);
+            self.config.summarize_tables.sort();
+        } else {
+            self.config.summarize_tables.retain(|p| *p != key);
+        }
+        self.save_config();
+        self.recount_tokens(index);
+    }
+
+    /// Switches a file between its contents and its git diff, and recounts its tokens.
+    fn toggle_diff_only(&mut self, index: usize) {
+        let file = &mut self.files[index];
+        file.diff_only = !file.diff_only;
+        let key = file.rel_path.replace('\\', "/");
+        if file.diff_only {
+            self.config.diff_only.push(key);
+            self.config.diff_only.sort();
+        } else {
+            self.config.diff_only.retain(|p| *p != key);
+        }
+        self.save_config();
+        self.recount_tokens(index);
+    }
+
+    /// Notes when the exact count and the estimate differ by more than a quarter.
+    fn apply_exact_tokens(&mut self, index: usize, rel_path: RelPath, exact: usize) {
+        // The list may have been rescanned while counting
+        let Some(file) = self.files.get_mut(index).filter(|f| f.rel_path == rel_path) else {
+            return;
+        };
+        let estimate = file.token_count;
+        file.token_count = exact;
+        file.exact_tokens = true;
+        let plain =
+            !file.summarize_table && !file.diff_only && !crate::image_info::is_image(&rel_path);
+        if plain {
+            let size = file.size as usize;
+            self.token_calibration.record(&rel_path, size, exact);
+            self.save_token_calibration();
+        }
+        let diverged = estimate.abs_diff(exact) * 4 > exact.max(1);
+        self.notify(if diverged {
+            format!(
+                "{}: {} tokens exactly; the size estimate of {} was off by {:+.0}%",
+                rel_path,
+                exact,
+                estimate,
+                (estimate as f64 / exact.max(1) as f64 - 1.0) * 100.0
+            )
+        } else {
+            format!(
+                "{}: {} tokens exactly (estimate {})",
+                rel_path, exact, estimate
+            )
+        });
+    }
+
+    /// Counts what was just built into the prompt as a job: each count replaces its
+    /// file's estimate, and those of files sent as read calibrate the rest.
+    fn reconcile_file_tokens(&mut self) {
+        let contents: Vec<(usize, RelPath, String, bool)> = self
+            .files
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| f.selected && !f.tree_only && !f.summarize_table && !f.diff_only)
+            .filter(|(_, f)| !crate::image_info::is_image(&f.rel_path))
+            .filter_map(|(i, f)| {
+                let content = f.content.clone()?;
+                Some((i, f.rel_path.clone(), content, f.content_raw))
+            })
+            .collect();
+        if contents.is_empty() {
+            return;
+        }
+        let label = format!("{} prompt file(s)", contents.len());
+        self.jobs.spawn(JobKind::Tokenize, label, move |ctx| {
+            let mut counts = Vec::with_capacity(contents.len());
+            for (index, rel_path, content, raw) in contents {
+                if ctx.cancel_flag().load(std::sync::atomic::Ordering::Relaxed) {
+                    break;
+                }
+                counts.push(crate::jobs::FileTokens {
+                    index,
+                    rel_path,
+                    tokens: crate::token_count::count_tokens(&content),
+                    raw,
+                });
+            }
+            JobOutput::PromptTokens(counts)
+        });
+    }
+
+    /// Applies the counts of a built prompt; see `reconcile_file_tokens`.
+    fn apply_prompt_tokens(&mut self, counts: Vec<crate::jobs::FileTokens>) {
+        let mut learned = false;
+        for count in counts {
+            // The list may have been rescanned while counting
+            let Some(file) = self
+                .files
+                .get_mut(count.index)
+                .filter(|f| f.rel_path == count.rel_path)
+            else {
+                continue;
+            };
+            file.token_count = count.tokens;
+            file.exact_tokens = true;
+            if count.raw {
+                let size = file.size as usize;
+                self.token_calibration
+                    .record(&count.rel_path, size, count.tokens);
+                learned = true;
+            }
+        }
+        if learned {
+            self.save_token_calibration();
+        }
+    }
+
+    /// Re-estimates the files without an exact count at the current ratios, and saves them.
+    fn save_token_calibration(&mut self) {
+        for file in self.files.iter_mut() {
+            if file.exact_tokens || file.summarize_table || file.diff_only {
+                continue;
+            }
+            if !crate::image_info::is_image(&file.rel_path) {
+                file.token_count = self.token_calibration.estimate(&file.rel_path, file.size);
+            }
+        }
+        let Some(base) = self.current_folder.clone() else {
+            return;
+        };
+        if let Err(e) = self.token_calibration.save(&base) {
+            self.report_error(e);
+        }
+    }
+
+    fn apply_job_output(&mut self, output: JobOutput) {
+        match output {
+            JobOutput::Scan {
+                folder,
+                under,
+                files,
+                stats,
+            } => {
+                if under.is_none() {
+                    self.scan_job = None;
+                }
+                if self.current_folder.as_ref() == Some(&folder) {
+                    self.apply_scan(files, stats, under.as_deref());
+                }
+            }
+            JobOutput::FileChanges { folder, changes } => {
+                if self.current_folder.as_ref() != Some(&folder) {
+                    return;
+                }
+                // A deleted file counts as changed now
+                let newest = changes
+                    .iter()
+                    .map(|c| c.modified.unwrap_or_else(std::time::SystemTime::now))
+                    .max();
+                self.terminal.newest_change = self.terminal.newest_change.max(newest);
+                self.drop_stale_contents(&changes);
+            }
+            JobOutput::Archive {
+                folder,
+                rel_path,
+                result,
+            } => {
+                if self.current_folder.as_ref() != Some(&folder) {
+                    return;
+                }
+                match result {
+                    Ok(entries) => {
+                        if entries.is_empty() {
+                            self.notify(format!("{} has no files", rel_path));
+                        }
+                        self.apply_scan(entries, ScanStats::default(), Some(&rel_path));
+                    }
+                    Err(e) => {
+                        self.open_archives.remove(&rel_path);
+                        self.report_error(e);
+                    }
+                }
+            }
+            JobOutput::Remote(RemoteUpdate::Fetched { index, content }) => {
+                if let Some(remote) = self.remote.sources.get_mut(index) {
+                    let content = self.redactions.apply(&content).into_owned();
+                    let marker = PromptLabels::resolve(&self.config.labels).omitted;
+                    remote.set_content(content, &marker);
+                    remote.error = None;
+                    let label = remote.source.label();
+                    self.notifications
+                        .push(Level::Info, format!("Fetched {}", label));
+                }
+            }
+            JobOutput::Remote(RemoteUpdate::Failed { index, error }) => {
+                if let Some(remote) = self.remote.sources.get_mut(index) {
+                    remote.error = Some(error.to_string());
+                }
+                self.report_error(error);
+            }
+            JobOutput::Terminal {
+                command,
+                working_dir,
+                result,
+            } => {
+                let output = result.unwrap_or_else(|e| CommandOutput {
+                    text: format!("Error: {}", e),
+                    ..Default::default()
+                });
+                self.notify(format!("Command finished: {}", command));
+                self.terminal.push_run(command, working_dir, output);
+                if std::mem::take(&mut self.focus_tests_pending) {
+                    self.focus_failing_tests();
+                }
+            }
+            JobOutput::Lint(result) => {
+                self.lint.job = None;
+                match result {
+                    Ok(diagnostics) => {
+                        self.notify(format!("Clippy reported {} diagnostics", diagnostics.len()));
+                        self.lint.set_results(diagnostics);
+                    }
+                    Err(e) => self.report_error(e),
+                }
+            }
+            JobOutput::CommitMessage(result) => {
+                self.commit_message.job = None;
+                match result {
+                    Ok(message) => {
+                        self.commit_message.message = message;
+                        self.write_commit_message();
+                    }
+                    Err(e) => self.report_error(e),
+                }
+            }
+            JobOutput::PrDescription(update) => {
+                self.pr_description.job = None;
+                match update {
+                    crate::pr_description::PrUpdate::Prompt(prompt) => {
+                        match self.copy_verified(&prompt) {
+                            Ok(()) => self.notify("Copied the PR description prompt".into()),
+                            Err(e) => self.report_error(e),
+                        }
+                    }
+                    crate::pr_description::PrUpdate::Description(text) => {
+                        self.pr_description.description = text;
+                        self.pr_description.open = true;
+                        self.notify("PR description received".into());
+                    }
+                    crate::pr_description::PrUpdate::Failed(e) => self.report_error(e),
+                }
+            }
+            JobOutput::Tokens {
+                index,
+                rel_path,
+                result,
+            } => match result {
+                Ok(exact) => self.apply_exact_tokens(index, rel_path, exact),
+                Err(e) => self.report_error(e),
+            },
+            JobOutput::PromptTokens(counts) => self.apply_prompt_tokens(counts),
+            JobOutput::Eval(update) => {
+                let passed = update.result.passed;
+                if let (Some(base), Some(turn)) = (
+                    self.current_folder.clone(),
+                    self.conversation.turns.get_mut(update.turn),
+                ) {
+                    turn.eval = Some(update.result.clone());
+                    let recorded = crate::eval::append_history(
+                        &base,
+                        &turn.instruction,
+                        &turn.prompt,
+                        &self.config.verify_command,
+                        turn.snapshot.as_ref(),
+                        &update.result,
+                    );
+                    if let Err(e) = recorded {
+                        self.report_error(e);
+                    }
+                }
+                self.notify(format!(
+                    "Evaluation of turn {} {}",
+                    update.turn + 1,
+                    if passed { "passed" } else { "failed" }
+                ));
+            }
+            JobOutput::Provider(update) => self.apply_provider_update(update),
+        }
     }
 
-    fn strip_comments(text: &str) -> String {
-        text.lines()
-            .map(|line| {
-                let mut in_string = false;
-                let mut string_char = '\0';
-                let mut prev_escape = false;
-                let mut out = String::new();
-                let chars: Vec<char> = line.chars().collect();
-                let mut i = 0;
+    fn apply_provider_update(&mut self, update: ProviderUpdate) {
+        match update {
+            ProviderUpdate::Models { index, models } => {
+                if let Some(p) = self.config.providers.get_mut(index) {
+                    let count = models.len();
+                    // A fresh provider (e.g. from Use local Ollama) starts on its first model
+                    let start = p.model.is_empty() && !models.is_empty();
+                    if start {
+                        p.model = models[0].clone();
+                    }
+                    p.models = models;
+                    let name = p.name.clone();
+                    if start && p.kind.is_local() {
+                        crate::providers::spawn_probe_context(index, p.clone(), &mut self.jobs);
+                    }
+                    self.notify(format!("{}: {} models", name, count));
+                    self.save_config();
+                }
+            }
+            ProviderUpdate::Context { index, tokens } => {
+                if let Some(p) = self.config.providers.get_mut(index) {
+                    p.context_length = tokens;
+                    let message = match tokens {
+                        Some(n) => format!("{}: {} context, token budget updated", p.model, n),
+                        None => format!("{}: server did not report a context length", p.model),
+                    };
+                    self.notify(message);
+                    self.save_config();
+                }
+            }
+            ProviderUpdate::Chunk { text } => {
+                self.response.text.push_str(&text);
+            }
+            ProviderUpdate::Completion { text } => {
+                self.response.streaming = false;
+                if let Some(turn) = self.conversation.turns.last_mut() {
+                    turn.response = text.clone();
+                }
+                self.response.load(text);
+                self.response.open = true;
+                self.notify("Model reply received".into());
+            }
+            ProviderUpdate::Failed(e) => {
+                self.response.streaming = false;
+                self.report_error(e);
+            }
+        }
+    }
 
-                while i < chars.len() {
-                    let c = chars[i];
+    fn smart_select(&mut self) {
+        let core = crate::language_packs::core_set(&self.language_packs);
+        let mut added = 0;
+        for file in self
+            .files
+            .iter_mut()
+            .filter(|f| !f.tree_only && !f.selected)
+        {
+            if core.is_match(file.rel_path.replace('\\', "/")) {
+                file.selected = true;
+                added += 1;
+            }
+        }
+        self.notify(format!(
+            "Smart select ({}): {} core files added",
+            crate::language_packs::names(&self.language_packs),
+            added
+        ));
+    }
 
-                    if !in_string && i + 1 < chars.len() && c == '/' && chars[i + 1] == '/' {
-                        break;
-                    }
+    fn share_selection(&mut self) {
+        let paths: Vec<String> = self
+            .files
+            .iter()
+            .filter(|f| f.selected)
+            .map(|f| f.rel_path.replace('\\', "/"))
+            .collect();
+        if paths.is_empty() {
+            self.notify("Nothing selected to share".into());
+            return;
+        }
+        let count = paths.len();
+        let text = crate::share::encode(paths, &self.instruction());
+        match self.copy_verified(&text) {
+            Ok(()) => self.notify(format!("Copied selection of {} files to clipboard", count)),
+            Err(e) => self.report_error(e),
+        }
+    }
 
-                    if !in_string && c == '#' {
-                        break;
-                    }
+    fn paste_selection(&mut self) {
+        let share = match self.clipboard().and_then(|cb| {
+            cb.get_contents()
+                .map_err(|e| PromptError::Clipboard(e.to_string()))
+        }) {
+            Ok(text) => crate::share::decode(&text),
+            Err(e) => Err(e),
+        };
+        let share = match share {
+            Ok(share) => share,
+            Err(e) => {
+                self.report_error(e);
+                return;
+            }
+        };
+        let wanted: std::collections::HashSet<&str> =
+            share.paths.iter().map(String::as_str).collect();
+        let mut found = 0;
+        for file in self.files.iter_mut() {
+            file.selected = wanted.contains(file.rel_path.replace('\\', "/").as_str());
+            found += usize::from(file.selected);
+        }
+        let missing = wanted.len().saturating_sub(found);
+        let instruction =
+            if share.instruction_hash == crate::share::instruction_hash(&self.instruction()) {
+                "instruction matches"
+            } else {
+                "instruction differs"
+            };
+        let mut message = format!(
+            "Selected {} of {} shared files; {}",
+            found,
+            wanted.len(),
+            instruction
+        );
+        if missing > 0 {
+            message.push_str(&format!(" ({} not in this checkout)", missing));
+        }
+        self.notify(message);
+    }
 
-                    if (c == '"' || c == '\'') && !prev_escape {
-                        if in_string && string_char == c {
-                            in_string = false;
-                        } else if !in_string {
-                            in_string = true;
-                            string_char = c;
-                        }
+    /// `errors` are files whose imports couldn't be read; the first is reported in full.
+    fn select_related(
+        &mut self,
+        index: usize,
+        related: &[usize],
+        what: &str,
+        errors: Vec<PromptError>,
+    ) {
+        let newly = related.iter().filter(|&&i| !self.files[i].selected).count();
+        for &i in related {
+            self.files[i].selected = true;
+        }
+        self.files[index].selected = true;
+        self.notify(format!(
+            "{}: {} {} found, {} newly selected (depth {})",
+            self.files[index].rel_path,
+            related.len(),
+            what,
+            newly,
+            self.config.dependency_depth
+        ));
+        let unreadable = errors.len();
+        if let Some(e) = errors.into_iter().next() {
+            self.report_error(e);
+        }
+        if unreadable > 1 {
+            self.notify(format!(
+                "{} more file(s) couldn't be read while looking for {}",
+                unreadable - 1,
+                what
+            ));
+        }
+    }
+
+    /// Selects the files referenced by the terminal output, or with `errors_as_snippets`
+    /// picks the definitions around each referenced line (whole file when there is none).
+    fn select_from_errors(&mut self) {
+        let Some(base) = self.current_folder.clone() else {
+            return;
+        };
+        let refs = crate::error_refs::parse(&self.terminal.terminal_output);
+        let mut files = 0;
+        let mut snippets = 0;
+        let mut unresolved = 0;
+        for r in &refs {
+            let Some(i) = crate::error_refs::resolve(&self.files, &base, &r.path) else {
+                unresolved += 1;
+                continue;
+            };
+            if self.files[i].tree_only {
+                continue;
+            }
+            if self.errors_as_snippets {
+                let symbol = self.files[i].read_text().ok().and_then(|text| {
+                    crate::symbols::enclosing(&self.files[i].rel_path, &text, r.line)
+                });
+                if let Some(symbol) = symbol {
+                    if !self.symbol_picker.is_picked(&symbol) {
+                        self.symbol_picker.pick(&symbol);
+                        snippets += 1;
                     }
+                    continue;
+                }
+            }
+            if !self.files[i].selected {
+                self.files[i].selected = true;
+                files += 1;
+            }
+        }
+        self.notify(if refs.is_empty() {
+            "No file:line references found in the output".into()
+        } else {
+            format!(
+                "{} references: {} files selected, {} snippets picked, {} not in the project",
+                refs.len(),
+                files,
+                snippets,
+                unresolved
+            )
+        });
+    }
 
-                    prev_escape = c == '\\' && !prev_escape;
-                    out.push(c);
-                    i += 1;
+    /// Tokens saved by comment stripping across the selected files, from a per-file
+    /// cache keyed by mtime so the estimate is ready before copy without rereading.
+    fn comment_savings(&mut self) -> usize {
+        let mut total = 0;
+        for f in self.files.iter().filter(|f| f.selected && !f.tree_only) {
+            let Some(syntax) = crate::comments::syntax_for(&f.rel_path) else {
+                continue;
+            };
+            if let Some((modified, saved)) = self.comment_savings.get(&f.rel_path) {
+                if *modified == f.modified {
+                    total += saved;
+                    continue;
                 }
+            }
+            let saved = if f.size as usize > MAX_PER_FILE_BYTES {
+                0
+            } else {
+                f.read_text()
+                    .map(|text| {
+                        let stripped = crate::comments::strip(&text, syntax, "");
+                        crate::token_count::count_tokens(&text)
+                            .saturating_sub(crate::token_count::count_tokens(&stripped))
+                    })
+                    .unwrap_or(0)
+            };
+            self.comment_savings
+                .insert(f.rel_path.clone(), (f.modified, saved));
+            total += saved;
+        }
+        total
+    }
 
-                out.trim_end().to_string()
-            })
-            .filter(|l| !l.trim().is_empty())
-            .collect::<Vec<_>>()
-            .join("\n")
+    /// Size of the <errors> block the current output summarizes to, None when it holds no
+    /// diagnostics; cached by output hash since the estimate runs every frame.
+    /// What a run's output adds to the prompt: its error summary when that applies, else
+    /// the output under the token cap. `None` is the output field before anything has run.
+    fn run_prompt_tokens(&mut self, run: Option<usize>) -> usize {
+        let summary = if self.config.summarize_errors {
+            self.error_summary_tokens(run)
+        } else {
+            None
+        };
+        summary.unwrap_or_else(|| {
+            let tokens = match run {
+                Some(i) => self.terminal.runs[i].tokens,
+                None => crate::token_count::count_tokens(&self.terminal.terminal_output),
+            };
+            match self.config.terminal_token_cap {
+                0 => tokens,
+                cap => tokens.min(cap),
+            }
+        })
+    }
+
+    fn error_summary_tokens(&mut self, run: Option<usize>) -> Option<usize> {
+        let output = match run {
+            Some(i) => &self.terminal.runs[i].output,
+            None => &self.terminal.terminal_output,
+        };
+        let hash = content_hash(output);
+        if let Some(tokens) = self.error_summary_tokens.get(&hash) {
+            return *tokens;
+        }
+        let labels = PromptLabels::resolve(&self.config.labels);
+        let tokens = error_summary(self, output).map(|(diagnostics, omitted)| {
+            crate::token_count::count_tokens(&crate::build_errors::render(
+                &labels.errors,
+                "",
+                &diagnostics,
+                omitted,
+                &labels,
+                self.config.escaping,
+            ))
+        });
+        if self.error_summary_tokens.len() > 64 {
+            self.error_summary_tokens.clear();
+        }
+        self.error_summary_tokens.insert(hash, tokens);
+        tokens
     }
 
     fn bottom_panel(&mut self, ctx: &egui::Context) {
@@ -623,72 +5370,425 @@ Example notes:
                 ui.horizontal(|ui| {
                     ui.set_height(30.0);
                     ui.checkbox(&mut self.include_file_tree, "Include file tree in prompt");
+                    if ui
+                        .button("Layout…")
+                        .on_hover_text("Order, repeat or drop prompt sections")
+                        .clicked()
+                    {
+                        self.show_layout_editor = !self.show_layout_editor;
+                    }
+                    if ui
+                        .button("File order…")
+                        .on_hover_text(format!(
+                            "Order of the included files: {}",
+                            self.config.file_order.label()
+                        ))
+                        .clicked()
+                    {
+                        self.show_file_order = !self.show_file_order;
+                    }
+                    ui.separator();
+
+                    let before = self.config.labels.language.clone();
+                    egui::ComboBox::from_id_salt("label_language")
+                        .selected_text(format!("Labels: {}", before))
+                        .show_ui(ui, |ui| {
+                            for lang in LANGUAGES {
+                                ui.selectable_value(
+                                    &mut self.config.labels.language,
+                                    lang.to_string(),
+                                    *lang,
+                                );
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Language for prompt markers; overrides live in .prompt/config.toml",
+                        );
+                    let format_before = self.config.output_format;
+                    egui::ComboBox::from_id_salt("output_format")
+                        .selected_text(format!("Format: {}", self.config.output_format.label()))
+                        .show_ui(ui, |ui| {
+                            for f in OutputFormat::ALL {
+                                ui.selectable_value(&mut self.config.output_format, f, f.label());
+                            }
+                        })
+                        .response
+                        .on_hover_text("XML sections for chat UIs, or one JSON object for API pipelines");
+                    let escaping_before = self.config.escaping;
+                    egui::ComboBox::from_id_salt("escaping")
+                        .selected_text(format!("Escaping: {}", self.config.escaping.label()))
+                        .show_ui(ui, |ui| {
+                            for e in Escaping::ALL {
+                                ui.selectable_value(&mut self.config.escaping, e, e.label());
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "How file contents are embedded: CDATA, &lt;-escaped, or between <<<CONTENT sentinel lines (for chat UIs that strip CDATA)",
+                        );
+                    let normalize_before = self.config.normalize_text;
+                    let metadata_before = self.config.include_metadata;
+                    ui.checkbox(&mut self.config.include_metadata, "Include project metadata")
+                        .on_hover_text(
+                            "Summarize Cargo.toml / package.json / pyproject.toml (name, versions, dependencies, features)",
+                        );
+                    let project_notes_before = self.config.include_project_notes;
+                    ui.checkbox(&mut self.config.include_project_notes, "Include project notes")
+                        .on_hover_text("Send .prompt/notes.md as <project_notes>");
+                    if ui.small_button("Notes…").clicked() {
+                        self.project_notes.open = !self.project_notes.open;
+                    }
+                    ui.checkbox(&mut self.config.normalize_text, "Normalize BOM/CRLF")
+                        .on_hover_text(
+                            "Strip byte-order marks and convert CRLF/CR to LF in included files",
+                        );
+                    let line_numbers_before = self.config.line_numbers;
+                    ui.checkbox(&mut self.config.line_numbers, "Line numbers")
+                        .on_hover_text("Prefix each included line with `NNN | ` so answers can cite exact locations");
+                    let strip_before = self.config.strip_comments;
+                    ui.checkbox(&mut self.config.strip_comments, "Strip comments")
+                        .on_hover_text(
+                            "Drop comments and blank lines from included files (Rust, C-like, JS/TS, Python, shell, TOML)",
+                        );
+                    let thumbnails_before = self.config.image_thumbnails;
+                    ui.checkbox(&mut self.config.image_thumbnails, "Image thumbnails")
+                        .on_hover_text(
+                            "Add a small base64 PNG thumbnail to the format/size line sent for included images (for multimodal models)",
+                        );
+                    ui.label("Editor:");
+                    if ui
+                        .add(
+                            egui::TextEdit::singleline(&mut self.config.editor_command)
+                                .hint_text(crate::editor::DEFAULT_COMMAND)
+                                .desired_width(160.0),
+                        )
+                        .on_hover_text("Command for Open in editor; {file} and {line} are filled in")
+                        .lost_focus()
+                    {
+                        self.save_config();
+                    }
+                    if self.config.labels.language != before
+                        || self.config.output_format != format_before
+                        || self.config.escaping != escaping_before
+                        || self.config.normalize_text != normalize_before
+                        || self.config.include_metadata != metadata_before
+                        || self.config.include_project_notes != project_notes_before
+                        || self.config.line_numbers != line_numbers_before
+                        || self.config.strip_comments != strip_before
+                        || self.config.image_thumbnails != thumbnails_before
+                    {
+                        self.save_config();
+                    }
                     ui.separator();
 
                     // ---- FAST APPROX (no huge string, no tokenizer) ----
                     let mut total = 0usize;
 
-                    // FIRST <instruction>
-                    total += approx_tokens(self.extra_text.chars().count());
+                    let instruction_chars = self.instruction().chars().count();
+
+                    let comment_savings = if self.config.strip_comments
+                        && self.config.sections.contains(&Section::Code)
+                    {
+                        self.comment_savings()
+                    } else {
+                        0
+                    };
+
+                    let sections = &self.config.sections;
+                    let has = |section: Section| sections.contains(&section);
+
+                    // every <instruction> copy in the layout
+                    let copies = sections.iter().filter(|&&s| s == Section::Instruction).count();
+                    total += copies * approx_tokens(instruction_chars);
 
-                    if self.include_file_tree {
+                    if self.config.include_project_notes && has(Section::ProjectNotes) {
+                        total += approx_tokens(self.project_notes.text.chars().count());
+                    }
+
+                    if self.include_file_tree && has(Section::FileTree) {
                         total += estimate_file_tree_tokens(&self.files);
                     }
 
                     // selected files: use size-based estimates
-                    total += self
+                    total += usize::from(has(Section::Code))
+                        * self
                         .files
                         .iter()
                         .filter(|f| f.selected)
                         .map(|f| f.token_count)
                         .sum::<usize>();
+                    total = total.saturating_sub(comment_savings);
+                    if has(Section::Attachments) {
+                        total += self.attachments.included_tokens();
+                    }
 
-                    // remote text (if loaded)
-                    total += self
-                        .remote
-                        .remote_urls
-                        .iter()
-                        .filter(|r| r.include)
-                        .map(|r| {
-                            r.content
-                                .as_deref()
-                                .map(|c| approx_tokens(c.chars().count()))
-                                .unwrap_or(0)
-                        })
-                        .sum::<usize>();
+                    // remote text (if loaded), as trimmed
+                    if has(Section::Remote) {
+                        total += self.remote.included_tokens();
+                    }
 
-                    // SECOND <instruction>
-                    total += approx_tokens(self.extra_text.chars().count());
+                    // terminal command + output, as capped when emitted
+                    // each run going into the prompt (or the fields, before any has run)
+                    let runs = self.terminal.prompt_run_indices();
+                    if has(Section::TerminalCommand) {
+                        for &run in &runs {
+                            total += approx_tokens(match run {
+                                Some(i) => self.terminal.runs[i].command.chars().count(),
+                                None => self.terminal.terminal_command.chars().count(),
+                            });
+                        }
+                    }
+                    if has(Section::TerminalOutput) {
+                        total += self
+                            .lint
+                            .items
+                            .iter()
+                            .filter(|i| i.include)
+                            .map(|i| {
+                                let d = &i.diagnostic;
+                                approx_tokens(d.message.len() + d.snippet.len() + 64)
+                            })
+                            .sum::<usize>();
+                        for &run in &runs {
+                            total += self.run_prompt_tokens(run);
+                        }
+                    }
 
                     self.token_count = total; // 🤖 show fast estimate
 
-                    let formatted = num_format::ToFormattedString::to_formatted_string(
-                        &self.token_count,
-                        &num_format::Locale::en,
-                    );
-                    ui.label(format!(
-                        "Token count (approx): {} / 200,000 ({:.2}%)",
-                        formatted,
-                        (self.token_count as f32 / 200_000.0) * 100.0
+                    let budget = self.config.token_budget();
+                    let meter = self.config.meter();
+                    let text = egui::RichText::new(format!(
+                        "Token count (approx): {} / {} ({:.2}%)",
+                        meter.format(self.token_count),
+                        meter.format(budget),
+                        (self.token_count as f32 / budget as f32) * 100.0
                     ));
+                    let level = meter.level(self.token_count, budget);
+                    let text = match level.color(ui.visuals()) {
+                        Some(color) => text.color(color),
+                        None => text,
+                    };
+                    ui.label(text).on_hover_text(format!(
+                        "Warning from {}%, critical from {}% of the context limit; set under Providers…",
+                        meter.warn_percent, meter.critical_percent
+                    ));
+                    if comment_savings > 0 {
+                        ui.label(format!("−{} from stripped comments", comment_savings))
+                            .on_hover_text("Measured on the selected files; already subtracted from the count");
+                    }
+                    if let Some(p) = self.config.active_provider() {
+                        if p.kind.is_local() {
+                            ui.label("$0 (local)");
+                        } else if let Some((key, price)) =
+                            crate::pricing::lookup(&self.config.prices, &p.model)
+                        {
+                            use crate::pricing::format_usd;
+                            let est = crate::pricing::estimate(
+                                price,
+                                self.token_count,
+                                self.config.expected_output_tokens,
+                            );
+                            let text = match est.output {
+                                Some(out) => format!(
+                                    "≈ {} in + {} out",
+                                    format_usd(est.input),
+                                    format_usd(out)
+                                ),
+                                None => format!("≈ {} in", format_usd(est.input)),
+                            };
+                            ui.label(text).on_hover_text(format!(
+                                "{} at ${}/M input, ${}/M output; edit under Providers…",
+                                key, price.input_per_mtok, price.output_per_mtok
+                            ));
+                        }
+                    }
+                    if let Some((cached, fresh)) = self.cache_split {
+                        ui.label(format!("cache: {} cached / {} fresh", cached, fresh))
+                            .on_hover_text(
+                                "Last built prompt: tokens before a cache breakpoint that match \
+                                 the previous prompt, and the rest. Anthropic caches prefixes of \
+                                 1024+ tokens for about five minutes.",
+                            );
+                    }
+                    if !self.duplicates.is_empty() {
+                        self.duplicates_label(ui);
+                    }
+                    if ui
+                        .small_button("📊")
+                        .on_hover_text("Tokens by directory: histogram and treemap")
+                        .clicked()
+                    {
+                        self.show_histogram = !self.show_histogram;
+                    }
                     ui.separator();
 
+                    let mode_label = match self.conversation.mode {
+                        PromptMode::Fresh => "Fresh",
+                        PromptMode::FollowUp => "Follow-up",
+                    };
+                    if ui
+                        .button(format!(
+                            "Conversation ({}, {} turns)",
+                            mode_label,
+                            self.conversation.turns.len()
+                        ))
+                        .on_hover_text("Paste responses and switch between fresh and follow-up prompts")
+                        .clicked()
+                    {
+                        self.conversation.open = !self.conversation.open;
+                    }
+
+                    let response_label = if self.response.blocks.is_empty() {
+                        "Response".to_string()
+                    } else {
+                        format!("Response ({} blocks)", self.response.blocks.len())
+                    };
+                    if ui
+                        .button(response_label)
+                        .on_hover_text("Code blocks from a model reply, with save / apply actions")
+                        .clicked()
+                    {
+                        self.response.open = !self.response.open;
+                    }
+
                     if ui.button("Copy Prompt").clicked() {
                         // 🤖 Build full prompt, load selected contents,
                         // and compute accurate tokens via tiktoken-rs ONCE here.
-                        compute_and_copy_prompt(self, ctx);
+                        let kind = if self.conversation.follow_up_active() {
+                            PromptKind::FollowUp
+                        } else {
+                            PromptKind::Full
+                        };
+                        compute_and_copy_prompt(self, ctx, kind);
+                    }
+                    if ui
+                        .button("Save Prompt…")
+                        .on_hover_text("Build the prompt and write it to .prompt/prompts/ or a chosen file")
+                        .clicked()
+                    {
+                        let prompt = build_prompt(self);
+                        self.token_count = crate::token_count::count_tokens(&prompt);
+                        self.generated_prompt = prompt.clone();
+                        self.save_prompt_dialog(&prompt);
+                    }
+                    if ui
+                        .add_enabled(
+                            !self.conversation.turns.is_empty(),
+                            egui::Button::new("Copy Delta Prompt"),
+                        )
+                        .on_hover_text("Only files changed since the last copied prompt")
+                        .on_disabled_hover_text("Copy a prompt first")
+                        .clicked()
+                    {
+                        compute_and_copy_prompt(self, ctx, PromptKind::Delta);
+                    }
+                    if ui
+                        .button("Preview")
+                        .on_hover_text("Build the prompt and show it (foldable, searchable) without copying")
+                        .clicked()
+                    {
+                        if self.show_prompt_viewer {
+                            self.show_prompt_viewer = false;
+                        } else {
+                            self.preview_prompt();
+                        }
+                    }
+
+                    self.model_picker(ui);
+                    let active = self.config.active_provider().map(|p| p.model.clone());
+                    let send_label = match &active {
+                        Some(model) if !model.is_empty() => format!("Send to {}", model),
+                        _ => "Send to LLM".to_string(),
+                    };
+                    let sending = self
+                        .providers
+                        .completion
+                        .is_some_and(|id| self.jobs.is_running(id));
+                    let can_send = !sending
+                        && active.as_ref().is_some_and(|m| !m.is_empty());
+                    if ui
+                        .add_enabled(can_send, egui::Button::new(send_label))
+                        .on_hover_text("Send the prompt to the active provider; the reply opens in Response")
+                        .on_disabled_hover_text("Pick a provider and model under Providers…")
+                        .clicked()
+                    {
+                        self.send_to_provider();
+                    }
+                    if !self.config.send_command.trim().is_empty()
+                        && ui
+                            .add_enabled(!sending, egui::Button::new("Send to command"))
+                            .on_hover_text(format!(
+                                "Pipe the prompt into `{}`; its output opens in Response",
+                                self.config.send_command.trim()
+                            ))
+                            .clicked()
+                    {
+                        self.send_to_command();
+                    }
+                    if sending {
+                        ui.spinner();
+                    }
+                    if ui.button("Providers…").clicked() {
+                        self.providers.open = !self.providers.open;
+                    }
+                    let running = self.jobs.running_count();
+                    let failed = self.jobs.failed_count();
+                    let jobs_label = match (running, failed) {
+                        (0, 0) => "Jobs".to_string(),
+                        (r, 0) => format!("Jobs ({} running)", r),
+                        (0, f) => format!("Jobs ({} failed)", f),
+                        (r, f) => format!("Jobs ({} running, {} failed)", r, f),
+                    };
+                    if ui
+                        .button(jobs_label)
+                        .on_hover_text("Background fetches, commands, model requests and evaluations")
+                        .clicked()
+                    {
+                        self.jobs.open = !self.jobs.open;
+                    }
+
+                    if ui
+                        .button("Import code from clipboard")
+                        .on_hover_text("Write path-annotated code blocks from a copied reply, after review")
+                        .clicked()
+                    {
+                        self.import_code_from_clipboard();
                     }
 
                     if ui.button("Remove Comments from Clipboard").clicked() {
-                        let mut cb: clipboard::ClipboardContext =
-                            clipboard::ClipboardProvider::new().unwrap();
-                        let contents = cb.get_contents().unwrap_or_default();
-                        let cleaned = MyApp::strip_comments(&contents);
-                        let _ = cb.set_contents(cleaned);
-                        self.notification = Some((
-                            "Comments removed from clipboard!".into(),
-                            std::time::Instant::now(),
-                        ));
+                        let result = self.clipboard().and_then(|cb| {
+                            let contents = cb
+                                .get_contents()
+                                .map_err(|e| PromptError::Clipboard(e.to_string()))?;
+                            cb.set_contents(crate::comments::strip(
+                                &contents,
+                                crate::comments::Syntax::Any,
+                                "",
+                            ))
+                                .map_err(|e| PromptError::Clipboard(e.to_string()))
+                        });
+                        match result {
+                            Ok(()) => {
+                                self.notify("Comments removed from clipboard!".into());
+                            }
+                            Err(e) => self.report_error(e),
+                        }
+                    }
+
+                    let log_label = if self.notifications.unread > 0 {
+                        format!("Log ({})", self.notifications.unread)
+                    } else {
+                        "Log".to_string()
+                    };
+                    if ui
+                        .button(log_label)
+                        .on_hover_text("Show recent notifications with timestamps")
+                        .clicked()
+                    {
+                        self.notifications.open = !self.notifications.open;
+                        self.notifications.unread = 0;
                     }
 
                     const NOTIF_MS: u64 = 3000;
@@ -718,7 +5818,35 @@ Example notes:
     fn central_panel(&mut self, ctx: &egui::Context) {
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.vertical(|ui| {
-                ui.label("User Prompt:");
+                self.system_profile_row(ui);
+                ui.horizontal_wrapped(|ui| {
+                    ui.label("User Prompt:");
+                    let mut toggled = false;
+                    for snippet in &self.instructions.snippets {
+                        let on = self.config.enabled_snippets.contains(&snippet.name);
+                        let r = ui
+                            .selectable_label(on, &snippet.name)
+                            .on_hover_text(&snippet.text);
+                        if r.clicked() {
+                            if on {
+                                self.config.enabled_snippets.retain(|n| *n != snippet.name);
+                            } else {
+                                self.config.enabled_snippets.push(snippet.name.clone());
+                            }
+                            toggled = true;
+                        }
+                    }
+                    if ui
+                        .small_button("Snippets…")
+                        .on_hover_text("Reusable instructions in .prompt/snippets/, combined with the text below")
+                        .clicked()
+                    {
+                        self.instructions.editor_open = !self.instructions.editor_open;
+                    }
+                    if toggled {
+                        self.save_config();
+                    }
+                });
 
                 egui::ScrollArea::vertical()
                     .max_height(350.0)
@@ -730,16 +5858,69 @@ Example notes:
                                 .desired_width(f32::INFINITY)
                                 .desired_rows(8)
                                 .frame(true),
-                        );
+                        )
+                        .on_hover_text(format!(
+                            "Placeholders expanded when the prompt is built:\n{}",
+                            crate::template_vars::help()
+                        ));
                     });
 
                 ui.separator();
-                ui.heading("Terminal Command");
+                ui.horizontal(|ui| {
+                    ui.heading("Terminal Command");
+                    if ui
+                        .small_button("Interactive shell…")
+                        .on_hover_text("A real terminal for REPLs, watchers and password prompts")
+                        .clicked()
+                    {
+                        self.shell.open = !self.shell.open;
+                    }
+                });
                 ui.add(
                     egui::TextEdit::singleline(&mut self.terminal.terminal_command)
                         .desired_width(f32::INFINITY)
                         .frame(true),
                 );
+                ui.horizontal(|ui| {
+                    ui.label("Working dir:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.terminal.working_dir)
+                            .hint_text("(project folder)")
+                            .desired_width(260.0),
+                    )
+                    .on_hover_text("Relative to the project folder, or absolute");
+                    if ui.button("Browse…").clicked() {
+                        if let Some(base) = self.current_folder.clone() {
+                            let picked = rfd::FileDialog::new()
+                                .set_directory(crate::command_runner::resolve_dir(
+                                    &base,
+                                    &self.terminal.working_dir,
+                                ))
+                                .pick_folder();
+                            if let Some(dir) = picked {
+                                self.terminal.working_dir = match dir.strip_prefix(&base) {
+                                    Ok(rel) => rel.to_string_lossy().replace('\\', "/"),
+                                    Err(_) => dir.to_string_lossy().into_owned(),
+                                };
+                            }
+                        }
+                    }
+                    let recent = self.terminal.recent_dirs();
+                    if !recent.is_empty() {
+                        egui::ComboBox::from_id_salt("working_dir_history")
+                            .selected_text("Recent")
+                            .show_ui(ui, |ui| {
+                                if ui.selectable_label(false, "(project folder)").clicked() {
+                                    self.terminal.working_dir.clear();
+                                }
+                                for dir in recent {
+                                    if ui.selectable_label(false, &dir).clicked() {
+                                        self.terminal.working_dir = dir;
+                                    }
+                                }
+                            });
+                    }
+                });
                 ui.horizontal(|ui| {
                     ui.label("Head lines:");
                     ui.add(egui::DragValue::new(&mut self.terminal.head_lines));
@@ -748,32 +5929,130 @@ Example notes:
                     ui.label("Timeout (sec, 0 = no timeout):");
                     ui.add(egui::DragValue::new(&mut self.terminal.timeout_secs));
 
+                    if !self.env_presets.sets.is_empty() {
+                        ui.label("Env:");
+                        let before = self.config.env_preset.clone();
+                        let combo = egui::ComboBox::from_id_salt("env_preset")
+                            .selected_text(if before.is_empty() {
+                                "(none)"
+                            } else {
+                                before.as_str()
+                            })
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(
+                                    &mut self.config.env_preset,
+                                    String::new(),
+                                    "(none)",
+                                );
+                                for set in &self.env_presets.sets {
+                                    ui.selectable_value(
+                                        &mut self.config.env_preset,
+                                        set.name.clone(),
+                                        &set.name,
+                                    )
+                                    .on_hover_text(set.describe());
+                                }
+                            });
+                        if let Some(set) = self.env_presets.get(&before) {
+                            combo.response.on_hover_text(set.describe());
+                        }
+                        if self.config.env_preset != before {
+                            self.save_config();
+                        }
+                    }
+
+                    ui.label("Stdin:").on_hover_text(
+                        "Piped into the command. Shell redirection (`< file`) isn't \
+                         interpreted; use this instead, e.g. `llm -m gpt-4` with the \
+                         generated prompt.",
+                    );
+                    egui::ComboBox::from_id_salt("terminal_stdin")
+                        .selected_text(self.terminal.stdin_mode.label())
+                        .show_ui(ui, |ui| {
+                            for mode in StdinMode::ALL {
+                                ui.selectable_value(
+                                    &mut self.terminal.stdin_mode,
+                                    mode,
+                                    mode.label(),
+                                );
+                            }
+                        });
+
                     ui.separator();
                     let run_clicked = ui
-                        .add_enabled(!self.terminal.is_running, egui::Button::new("Run Command"))
+                        .add_enabled(!self.terminal_running(), egui::Button::new("Run Command"))
                         .clicked();
                     if run_clicked {
                         let command = self.terminal.terminal_command.clone();
                         self.add_to_history(&command);
-                        // Clear previous output and indicate running
-                        self.terminal.terminal_output.clear();
-                        self.terminal.is_running = true;
-                        self.run_terminal_command(command);
+                        self.run_terminal_command(command, self.terminal.working_dir.clone());
                     }
 
-                    if self.terminal.is_running {
+                    if ui
+                        .add_enabled(
+                            !self.terminal_running() && self.current_folder.is_some(),
+                            egui::Button::new("Fix failing tests"),
+                        )
+                        .on_hover_text(
+                            "Run the verify command (default: cargo test), then select only the \
+                             failing tests and the code they call as snippets",
+                        )
+                        .clicked()
+                    {
+                        self.run_failing_tests();
+                    }
+                    if ui
+                        .add_enabled(
+                            !self.lint_running() && self.current_folder.is_some(),
+                            egui::Button::new("Lint"),
+                        )
+                        .on_hover_text(format!("Run {} and list its diagnostics", crate::lint::COMMAND))
+                        .clicked()
+                    {
+                        self.run_lint();
+                    }
+
+                    ui.separator();
+                    if ui
+                        .add_enabled(
+                            !self.terminal.terminal_output.is_empty(),
+                            egui::Button::new("Select files from errors"),
+                        )
+                        .on_hover_text("Select the files named in file:line references in the output")
+                        .clicked()
+                    {
+                        self.select_from_errors();
+                    }
+                    ui.checkbox(&mut self.errors_as_snippets, "Only erroring regions")
+                        .on_hover_text("Pick the enclosing functions/types as snippets instead of whole files");
+
+                    if self.terminal_running() {
                         ui.separator();
                         ui.add(egui::Spinner::new());
                         ui.label("Running...");
+                        if ui.button("Cancel").clicked() {
+                            if let Some(id) = self.terminal.job {
+                                self.jobs.cancel(id);
+                            }
+                        }
                     }
                 });
+                if self.terminal.stdin_mode == StdinMode::Text {
+                    ui.add(
+                        egui::TextEdit::multiline(&mut self.terminal.stdin_text)
+                            .hint_text("stdin for the command")
+                            .code_editor()
+                            .desired_rows(3)
+                            .desired_width(f32::INFINITY),
+                    );
+                }
                 // History UI
                 ui.separator();
                 // Controls above the history header
                 ui.horizontal(|ui| {
                     if ui.button("Clear All").clicked() {
                         self.terminal.history.clear();
-                        self.save_history_silent();
+                        self.persist_history();
                     }
                 });
 
@@ -782,55 +6061,162 @@ Example notes:
                     .max_height(140.0)
                     .show(ui, |ui| {
                         // Work on a snapshot to avoid borrow conflicts during UI callbacks
-                        let snapshot: Vec<String> = self.terminal.history.clone();
-                        let mut remove_cmd: Option<String> = None;
-                        for cmd in snapshot.iter() {
-                            let cmd_str = cmd.clone();
+                        let snapshot: Vec<HistoryEntry> = self.terminal.history.clone();
+                        let mut remove_cmd: Option<HistoryEntry> = None;
+                        let mut schedule: Option<(usize, u32, bool, bool)> = None;
+                        for (i, entry) in snapshot.iter().enumerate() {
+                            let cmd_str = entry.command.clone();
                             ui.horizontal(|ui| {
                                 if ui.small_button("X").on_hover_text("Forget").clicked() {
-                                    remove_cmd = Some(cmd_str.clone());
+                                    remove_cmd = Some(entry.clone());
                                 }
                                 if ui.small_button("Run").clicked() {
                                     self.terminal.terminal_command = cmd_str.clone();
+                                    self.terminal.working_dir = entry.working_dir.clone();
                                     self.add_to_history(&cmd_str);
-                                    // Clear previous output and indicate running
-                                    self.terminal.terminal_output.clear();
-                                    self.terminal.is_running = true;
-                                    self.run_terminal_command(cmd_str.clone());
-                                    self.save_history_silent();
+                                    self.run_terminal_command(
+                                        cmd_str.clone(),
+                                        entry.working_dir.clone(),
+                                    );
+                                    self.persist_history();
                                 }
                                 if ui.link(&cmd_str).clicked() {
                                     self.terminal.terminal_command = cmd_str.clone();
+                                    self.terminal.working_dir = entry.working_dir.clone();
+                                }
+                                if !entry.working_dir.is_empty() {
+                                    ui.weak(format!("in {}", entry.working_dir));
+                                }
+                                let mut every = entry.every_minutes;
+                                let mut on_change = entry.on_change;
+                                let interval = ui
+                                    .add(
+                                        egui::DragValue::new(&mut every)
+                                            .range(0..=1440)
+                                            .prefix("every ")
+                                            .suffix(" min"),
+                                    )
+                                    .on_hover_text("Re-run automatically every N minutes; 0 = off");
+                                let change = ui
+                                    .checkbox(&mut on_change, "on change")
+                                    .on_hover_text("Re-run when a project file changes");
+                                if every != entry.every_minutes || on_change != entry.on_change {
+                                    let persist = interval.drag_stopped()
+                                        || interval.lost_focus()
+                                        || change.changed();
+                                    schedule = Some((i, every, on_change, persist));
+                                } else if interval.drag_stopped() {
+                                    schedule = Some((i, every, on_change, true));
+                                }
+                                if entry.is_scheduled() && !entry.armed {
+                                    if ui
+                                        .small_button("Resume")
+                                        .on_hover_text(
+                                            "Schedules read from the project's terminal history stay \
+                                             paused until resumed",
+                                        )
+                                        .clicked()
+                                    {
+                                        schedule = Some((i, every, on_change, false));
+                                    }
+                                } else if entry.is_scheduled() {
+                                    ui.weak(match entry.last_run {
+                                        Some(at) => format!("last run {}", at.format("%H:%M:%S")),
+                                        None => "not run yet".to_string(),
+                                    });
                                 }
                             });
                         }
+                        if let Some((i, every, on_change, persist)) = schedule {
+                            if let Some(entry) = self.terminal.history.get_mut(i) {
+                                entry.every_minutes = every;
+                                entry.on_change = on_change;
+                                entry.armed = true;
+                            }
+                            if persist {
+                                self.persist_history();
+                            }
+                        }
                         if let Some(cmd_to_remove) = remove_cmd {
                             if let Some(pos) = self
                                 .terminal
                                 .history
                                 .iter()
-                                .position(|c| *c == cmd_to_remove)
+                                .position(|c| c.same_command(&cmd_to_remove))
                             {
                                 self.terminal.history.remove(pos);
-                                self.save_history_silent();
+                                self.persist_history();
                             }
                         }
                     });
 
                 ui.separator();
-                ui.label("Terminal Output:");
+                ui.horizontal(|ui| {
+                    ui.label("Terminal Output:");
+                    let tokens = crate::token_count::count_tokens(&self.terminal.terminal_output);
+                    let cap = self.config.terminal_token_cap;
+                    if cap > 0 && tokens > cap {
+                        ui.colored_label(
+                            egui::Color32::from_rgb(230, 160, 60),
+                            format!("≈ {} tokens, capped to {} in prompt", tokens, cap),
+                        )
+                        .on_hover_text(
+                            "Keeps the first and last blocks and any errors/failures; gaps are marked",
+                        );
+                    } else {
+                        ui.weak(format!("≈ {} tokens", tokens));
+                    }
+                    ui.label("Cap:");
+                    let r = ui
+                        .add(
+                            egui::DragValue::new(&mut self.config.terminal_token_cap)
+                                .speed(100.0)
+                                .range(0..=1_000_000),
+                        )
+                        .on_hover_text("Max tokens of terminal output in the prompt; 0 = no cap");
+                    if r.drag_stopped() || r.lost_focus() {
+                        self.save_config();
+                    }
+                    if ui
+                        .checkbox(&mut self.config.summarize_errors, "Summarize errors")
+                        .on_hover_text(
+                            "Send rustc errors and failing tests as a compact <errors> block \
+                             instead of the raw output (warnings only when there are no errors)",
+                        )
+                        .changed()
+                    {
+                        self.error_summary_tokens.clear();
+                        self.save_config();
+                    }
+                    if self.config.summarize_errors {
+                        match self.error_summary_tokens(self.terminal.active_run) {
+                            Some(summary) => ui.weak(format!("→ ≈ {} tokens summarized", summary)),
+                            None => ui.weak("(no diagnostics found)"),
+                        };
+                    }
+                    let in_prompt = self.run_prompt_tokens(self.terminal.active_run);
+                    heavy_item_warning(ui, &self.config, in_prompt);
+                });
 
+                if !self.terminal.runs.is_empty() {
+                    self.run_tabs(ui);
+                }
                 egui::ScrollArea::vertical()
                     .max_height(350.0)
                     .id_salt("terminal_output_scroll_area")
                     .show(ui, |ui| {
-                        ui.add(
-                            egui::TextEdit::multiline(&mut self.terminal.terminal_output)
-                                .lock_focus(true)
-                                .desired_width(f32::INFINITY)
-                                .desired_rows(8)
-                                .frame(true),
-                        );
+                        let edited = ui
+                            .add(
+                                egui::TextEdit::multiline(&mut self.terminal.terminal_output)
+                                    .lock_focus(true)
+                                    .desired_width(f32::INFINITY)
+                                    .desired_rows(8)
+                                    .frame(true),
+                            )
+                            .changed();
+                        if edited {
+                            self.terminal.store_edit();
+                        }
                     });
             });
         });
@@ -841,31 +6227,238 @@ Example notes:
     }
 }
 
-fn compute_and_copy_prompt(app: &mut MyApp, ctx: &egui::Context) {
+/// A fetched source's text with head/tail limits and "keep selection", indented under its
+/// row in the remote panel.
+fn remote_preview(
+    ui: &mut egui::Ui,
+    entry: &mut crate::remote::RemoteSource,
+    index: usize,
+    marker: &str,
+) {
+    let before = entry.trim.clone();
+    ui.indent(("remote_preview", index), |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Head:");
+            ui.add(egui::DragValue::new(&mut entry.trim.head_lines).range(0..=100_000))
+                .on_hover_text("Keep the first N lines; 0 with tail 0 = everything");
+            ui.label("Tail:");
+            ui.add(egui::DragValue::new(&mut entry.trim.tail_lines).range(0..=100_000))
+                .on_hover_text("Keep the last N lines");
+            if ui
+                .add_enabled(entry.marked.is_some(), egui::Button::new("Keep selection"))
+                .on_hover_text("Include only the text selected below")
+                .clicked()
+            {
+                entry.trim.selection = entry.marked;
+            }
+            if ui
+                .add_enabled(entry.trim.is_active(), egui::Button::new("Include all"))
+                .clicked()
+            {
+                entry.trim = crate::remote::Trim::default();
+            }
+            if let Some((start, end)) = entry.trim.selection {
+                ui.weak(format!(
+                    "selection of {} chars (overrides head/tail)",
+                    end - start
+                ));
+            }
+        });
+        let pages = entry.page_count();
+        if pages > 1 {
+            ui.horizontal(|ui| {
+                let mut on = entry.trim.pages.is_some();
+                ui.checkbox(&mut on, "Pages")
+                    .on_hover_text("Include only a page range of the PDF");
+                let (mut first, mut last) = entry.trim.pages.unwrap_or((1, pages));
+                if on {
+                    ui.add(egui::DragValue::new(&mut first).range(1..=pages));
+                    ui.label("to");
+                    ui.add(egui::DragValue::new(&mut last).range(first..=pages));
+                }
+                ui.weak(format!("of {}", pages));
+                let first = first.clamp(1, pages);
+                entry.trim.pages = on.then_some((first, last.clamp(first, pages)));
+            });
+        }
+        let full = entry.content.as_deref().unwrap_or_default();
+        ui.weak(format!(
+            "{} of {} tokens included; select text below and press Keep selection to narrow it",
+            entry.tokens, entry.full_tokens
+        ));
+        egui::ScrollArea::vertical()
+            .id_salt(("remote_preview_scroll", index))
+            .max_height(300.0)
+            .show(ui, |ui| {
+                let output = egui::TextEdit::multiline(&mut &*full)
+                    .font(egui::TextStyle::Monospace)
+                    .desired_width(f32::INFINITY)
+                    .show(ui);
+                if let Some(range) = output.cursor_range.filter(|r| !r.is_empty()) {
+                    let chars = range.as_sorted_char_range();
+                    entry.marked = Some((chars.start, chars.end));
+                }
+            });
+    });
+    if entry.trim != before {
+        entry.recount(marker);
+    }
+}
+
+/// "≈ N tokens" for one terminal output or remote source, in the warning colour with its
+/// budget share when it's over `item_warn_percent`.
+fn item_tokens_label(ui: &mut egui::Ui, config: &ProjectConfig, tokens: usize) {
+    if !heavy_item_warning(ui, config, tokens) {
+        ui.weak(format!("≈ {} tokens", tokens))
+            .on_hover_text(format!(
+                "{:.1}% of the token budget",
+                tokens as f32 * 100.0 / config.token_budget().max(1) as f32
+            ));
+    }
+}
+
+/// The warning half of `item_tokens_label`, shown alone where the count is already on
+/// screen; returns whether it showed.
+fn heavy_item_warning(ui: &mut egui::Ui, config: &ProjectConfig, tokens: usize) -> bool {
+    if !config.is_heavy(tokens) {
+        return false;
+    }
+    ui.colored_label(
+        egui::Color32::from_rgb(230, 160, 60),
+        format!(
+            "⚠ ≈ {} tokens, {:.0}% of budget",
+            tokens,
+            tokens as f32 * 100.0 / config.token_budget().max(1) as f32
+        ),
+    )
+    .on_hover_text(format!(
+        "One item over {}% of the token budget; trim it or cap it",
+        config.item_warn_percent
+    ));
+    true
+}
+
+fn compute_and_copy_prompt(app: &mut MyApp, ctx: &egui::Context, kind: PromptKind) {
+    let xml = build_and_record(app, kind);
+    match app.copy_verified(&xml) {
+        Ok(()) => {
+            app.unsaved_prompt = None;
+            let repeats = app.save_to_history(&xml);
+            app.notify(if repeats > 0 {
+                format!("Prompt copied to clipboard! (copied again ×{})", repeats)
+            } else {
+                "Prompt copied to clipboard!".into()
+            });
+        }
+        Err(e) => {
+            // Still hand it to egui's clipboard path; it may succeed where the check could not
+            ctx.copy_text(xml.clone());
+            app.report_error(e);
+            app.unsaved_prompt = Some(xml);
+        }
+    }
+}
+
+/// Builds the prompt and records it as a conversation turn.
+fn build_and_record(app: &mut MyApp, kind: PromptKind) -> String {
+    let xml = build_prompt_as(app, kind);
+    app.cache_split = cache_split(app, &xml);
+    app.reconcile_file_tokens();
+
+    // Remember what the model has now seen; a fresh prompt starts a new conversation
+    if kind == PromptKind::Full {
+        app.conversation.turns.clear();
+    }
+    let sent: std::collections::HashMap<RelPath, u64> = app
+        .files
+        .iter()
+        .filter(|f| f.selected && !f.tree_only)
+        .filter_map(|f| Some((f.rel_path.clone(), content_hash(f.content.as_deref()?))))
+        .collect();
+    let output = app.terminal.terminal_output.clone();
+    app.conversation.record(
+        app.instruction(),
+        xml.clone(),
+        sent,
+        &output,
+        app.last_snapshot.clone(),
+    );
+
+    app.generated_prompt = xml.clone();
+    app.token_count = crate::token_count::count_tokens(&app.generated_prompt);
+    xml
+}
+
+/// With cache breakpoints in `xml`: the tokens up to the furthest breakpoint whose prefix
+/// the previous prompt shared (cache hits), and the rest.
+fn cache_split(app: &MyApp, xml: &str) -> Option<(usize, usize)> {
+    if app.cache_breakpoints.is_empty() {
+        return None;
+    }
+    let previous = app
+        .conversation
+        .turns
+        .last()
+        .map_or("", |t| t.prompt.as_str());
+    let hit = app
+        .cache_breakpoints
+        .iter()
+        .rev()
+        .find(|&&at| previous.as_bytes().starts_with(&xml.as_bytes()[..at]))
+        .copied()
+        .unwrap_or(0);
+    let cached = crate::token_count::count_tokens(&xml[..hit]);
+    Some((
+        cached,
+        crate::token_count::count_tokens(xml).saturating_sub(cached),
+    ))
+}
+
+/// Assemble the prompt from the current selection (no UI side effects). Produces a
+/// follow-up when the conversation pane is in follow-up mode, a full prompt otherwise.
+pub fn build_prompt(app: &mut MyApp) -> String {
+    let kind = if app.conversation.follow_up_active() {
+        PromptKind::FollowUp
+    } else {
+        PromptKind::Full
+    };
+    build_prompt_as(app, kind)
+}
+
+/// What the parallel read needs of one file: (index, path, archive entry, summarize as a
+/// table, rel path to send the git diff of).
+type ReadJob = (usize, PathBuf, Option<String>, bool, Option<String>);
+
+pub fn build_prompt_as(app: &mut MyApp, kind: PromptKind) -> String {
+    let esc = app.config.escaping;
+    app.cache_breakpoints.clear();
     // Refresh file list (paths, sizes, selections)
     app.refresh_files();
 
     // ---- load system prompt (with optional per-project addon) ----
-    let mut system_prompt: String = match find_system_prompt_path(app.current_folder.as_deref()) {
-        Ok(p) => match std::fs::read_to_string(&p) {
-            Ok(s) => s,
-            Err(e) => {
-                eprintln!("[prompt] ERROR reading system prompt {:?}: {}", p, e);
-                format!(
-                    "System prompt failed to load. Please warn the user about this. error: {:?}, path: {:?}",
-                    e, p
-                )
-            }
-        },
+    let profile = app
+        .system_profiles
+        .get(&app.config.system_profile)
+        .map(|p| p.text.clone());
+    let loaded = match profile {
+        Some(text) => Ok(text),
+        None => find_system_prompt_path(app.current_folder.as_deref()).and_then(|p| {
+            std::fs::read_to_string(&p).map_err(|e| PromptError::io("read system prompt", &p, e))
+        }),
+    };
+    let mut system_prompt: String = match loaded {
+        Ok(s) => s,
         Err(e) => {
-            eprintln!("[prompt] ERROR finding system prompt: {}", e);
-            format!(
-                "System prompt failed to load. Please warn the user about this. error: {:?}",
+            // Still emit a marker so the model can tell the user something is off
+            let text = format!(
+                "System prompt failed to load. Please warn the user about this. error: {}",
                 e
-            )
+            );
+            app.report_error(e);
+            text
         }
     };
-    if let Some(base) = app.current_folder.as_deref() {
+    if let Some(base) = app.current_folder.clone() {
         let addon = base.join(".prompt").join("system_prompt_addon.txt");
         if addon.is_file() {
             match std::fs::read_to_string(&addon) {
@@ -873,9 +6466,7 @@ fn compute_and_copy_prompt(app: &mut MyApp, ctx: &egui::Context) {
                     system_prompt.push_str("\n\n");
                     system_prompt.push_str(&extra);
                 }
-                Err(err) => {
-                    eprintln!("[prompt] WARN: failed reading addon {:?}: {}", addon, err);
-                }
+                Err(err) => app.report_error(PromptError::io("read addon", &addon, err)),
             }
         }
     }
@@ -885,136 +6476,1210 @@ fn compute_and_copy_prompt(app: &mut MyApp, ctx: &egui::Context) {
         .files
         .iter()
         .enumerate()
-        .filter(|(_, f)| f.selected)
+        .filter(|(_, f)| f.selected && !f.tree_only)
         .map(|(i, _)| i)
         .collect();
+    // A crate's manifest rides along whenever any of its files is selected, without
+    // becoming selected itself
+    if !app.workspace.is_empty() {
+        let mut wanted = std::collections::HashSet::new();
+        for &i in &sel_indices {
+            if let Some(c) = crate::workspace::crate_index(&app.workspace, &app.files[i].rel_path) {
+                wanted.insert(app.workspace[c].manifest());
+            }
+        }
+        sel_indices.extend(app.files.iter().enumerate().filter_map(|(i, f)| {
+            (!f.selected && !f.tree_only && wanted.contains(&*f.rel_path)).then_some(i)
+        }));
+    }
     sel_indices.sort_by_key(|&i| app.files[i].rel_path.clone()); // 🤖 stable output order
+    crate::file_order::arrange(
+        &app.files,
+        &mut sel_indices,
+        app.config.file_order,
+        &app.config.manual_order,
+    );
+
+    // The messages export attaches selected images as image parts rather than <code> text
+    let messages = app.config.output_format == OutputFormat::OpenAiMessages;
+    let mut images: Vec<(&str, PathBuf)> = Vec::new();
+    if messages {
+        sel_indices.retain(
+            |&i| match crate::json_prompt::image_mime(&app.files[i].rel_path) {
+                Some(mime) => {
+                    images.push((mime, app.files[i].path.clone()));
+                    false
+                }
+                None => true,
+            },
+        );
+    }
+
+    let labels = PromptLabels::resolve(&app.config.labels);
 
-    // Cap per-file bytes to keep prompts manageable
-    const MAX_PER_FILE_BYTES: usize = 512 * 1024; // 🤖 512 KiB head+tail total
+    // Stamped before reading, so a change made during the read still counts as stale
+    for &i in &sel_indices {
+        let file = &mut app.files[i];
+        file.content_modified = std::fs::metadata(&file.path)
+            .and_then(|m| m.modified())
+            .ok();
+        file.content_stale = false;
+    }
 
     // Read contents in parallel and store back into FileItem.content
     {
         use rayon::prelude::*; // 🤖 parallelism lives here
 
         // Prepare (index, path) pairs so the parallel job only needs owned data
-        let jobs: Vec<(usize, std::path::PathBuf)> = sel_indices
+        let jobs: Vec<ReadJob> = sel_indices
             .iter()
-            .map(|&i| (i, app.files[i].path.clone()))
+            .map(|&i| {
+                let f = &app.files[i];
+                let diff_of = (f.diff_only && f.archive_entry.is_none())
+                    .then(|| f.rel_path.replace('\\', "/"));
+                (
+                    i,
+                    f.path.clone(),
+                    f.archive_entry.clone(),
+                    f.summarize_table,
+                    diff_of,
+                )
+            })
             .collect();
 
         // Parallel read -> collect (index, text)
-        let results: Vec<(usize, String)> = jobs
+        let normalize = app.config.normalize_text;
+        let line_numbers = app.config.line_numbers;
+        let keywords = crate::truncation::keywords(&app.instruction());
+        let table_rows = app.config.table_rows;
+        let thumbnails = app.config.image_thumbnails;
+        let base = app.current_folder.clone().unwrap_or_default();
+        let diff_ref = app.config.diff_ref.clone();
+        let results: Vec<(usize, _)> = jobs
             .into_par_iter()
-            .map(|(i, path)| {
-                let text = read_text_capped(&path, MAX_PER_FILE_BYTES)
-                    .unwrap_or_else(|| String::from("[error reading file]\n"));
-                (i, text)
+            .map(|(i, path, entry, summarize_table, diff_of)| {
+                if let Some(rel) = diff_of {
+                    let diff = crate::languages::by_id("diff");
+                    match crate::file_diff::diff(&base, &rel, &diff_ref) {
+                        Ok(Some(text)) => return (i, Ok((text, TextIssues::default(), diff))),
+                        Ok(None) => {} // not in that revision: sent in full
+                        Err(e) => return (i, Err(e)),
+                    }
+                }
+                if summarize_table && entry.is_none() {
+                    let summary = crate::tables::summarize(&path, table_rows)
+                        .map(|text| (text, TextIssues::default(), None));
+                    return (i, summary);
+                }
+                if entry.is_none() && crate::image_info::is_image(&path.to_string_lossy()) {
+                    let description = crate::image_info::describe(&path, thumbnails)
+                        .map(|text| (text, TextIssues::default(), None));
+                    return (i, description);
+                }
+                let read = read_text_capped(
+                    &path,
+                    entry.as_deref(),
+                    MAX_PER_FILE_BYTES,
+                    &labels,
+                    normalize,
+                    line_numbers,
+                    &keywords,
+                );
+                let language = match &entry {
+                    Some(entry) => crate::languages::by_name(entry),
+                    None => crate::languages::detect_file(&path.to_string_lossy(), &path),
+                };
+                (i, read.map(|(text, issues)| (text, issues, language)))
             })
             .collect();
 
         // Single-threaded write-back to avoid &mut captures inside the parallel closure
-        for (i, text) in results {
-            app.files[i].content = Some(text);
+        let mut flagged = Vec::new();
+        for (i, result) in results {
+            let read_ok = result.is_ok();
+            let text = match result {
+                Ok((text, issues, language)) => {
+                    app.files[i].language = language;
+                    if issues.is_flagged() {
+                        flagged.push(format!("{}: {}", app.files[i].rel_path, issues.describe()));
+                    }
+                    app.files[i].text_issues = issues;
+                    text
+                }
+                Err(e) => {
+                    let marker = format!("{} {}\n", labels.read_error, e);
+                    app.report_error(e);
+                    marker
+                }
+            };
+            let read_len = text.len();
+            // Folds and comment stripping are for the file's own text, not its diff
+            let diffed =
+                app.files[i].diff_only && app.files[i].language.is_some_and(|l| l.id == "diff");
+            let folds = app.folds.get(&app.files[i].rel_path).filter(|_| !diffed);
+            let folded = folds.is_some();
+            let text = match folds {
+                Some(folds) => {
+                    let rel = app.files[i].rel_path.clone();
+                    let (folded, stale) =
+                        crate::folding::apply_folds(&rel, &text, folds, &labels.omitted);
+                    if !stale.is_empty() {
+                        app.notify(format!(
+                            "{}: {} collapsed region(s) no longer match the file; included in full",
+                            rel,
+                            stale.len()
+                        ));
+                    }
+                    folded
+                }
+                None => text,
+            };
+            // After folding: the fold notes are comments, so they're passed through as-is
+            let syntax = crate::comments::syntax_for(&app.files[i].rel_path)
+                .filter(|_| app.config.strip_comments && !diffed);
+            let stripped = syntax.is_some();
+            let text = match syntax {
+                Some(syntax) => crate::comments::strip(&text, syntax, &labels.omitted),
+                None => text,
+            };
+            let (text, redacted) = match app.redactions.apply(&text) {
+                std::borrow::Cow::Borrowed(_) => (text, false),
+                std::borrow::Cow::Owned(redacted) => (redacted, true),
+            };
+            // Only a file's own bytes, whole and unchanged, say how its size maps to tokens
+            let file = &mut app.files[i];
+            file.content_raw = !file.summarize_table
+                && !file.diff_only
+                && !crate::image_info::is_image(&file.rel_path)
+                && read_ok
+                && !folded
+                && !stripped
+                && !redacted
+                && read_len as u64 == file.size
+                && text.len() == read_len;
+            file.content = Some(text);
+        }
+        if !flagged.is_empty() {
+            app.notify(format!(
+                "{} included file(s) have a BOM or mixed line endings{}:\n{}",
+                flagged.len(),
+                if normalize {
+                    " (normalized in prompt)"
+                } else {
+                    ""
+                },
+                flagged.join("\n")
+            ));
         }
     }
 
-    // ---- build prompt (KEEPS two <instruction> blocks by design) ----
+    let before = app.duplicates.len();
+    app.duplicates = crate::duplicates::find(&app.files, &sel_indices);
+    if app.duplicates.len() > before {
+        app.notify(format!(
+            "{} selected file(s) repeat another selected file; see ⚠ duplicates in the bottom bar",
+            app.duplicates.len()
+        ));
+    }
+
+    let snapshot = capture_code_state(app, &sel_indices);
+    app.last_snapshot = Some(snapshot.clone());
+
+    if kind != PromptKind::Full && !app.conversation.turns.is_empty() {
+        let prompt = build_follow_up(app, &sel_indices, &labels, kind, &snapshot);
+        return if messages {
+            crate::json_prompt::openai_messages(None, &prompt, &read_images(app, &images))
+        } else {
+            prompt
+        };
+    }
+    let instruction = expanded_instruction(app, &labels);
+
+    // ---- build prompt in the configured section order (default repeats <instruction>) ----
     let base = app
         .current_folder
-        .as_deref()
-        .unwrap_or(std::path::Path::new("."));
-    let tree = generate_file_tree_string(&app.files, base);
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("."));
+    let tree = generate_file_tree_string(&app.file_tree, &app.files, &base);
+    if app.config.output_format == OutputFormat::Json {
+        let mut prompt = build_json(
+            app,
+            &sel_indices,
+            &labels,
+            &base,
+            tree,
+            system_prompt,
+            instruction,
+        );
+        prompt.snapshot = Some(snapshot.to_json());
+        return prompt.render();
+    }
+
+    // Anthropic caches prompt prefixes: stable content first, breakpoints after it
+    let cache_layout = app.config.cache_layout
+        && app.config.output_format == OutputFormat::Xml
+        && app
+            .config
+            .active_provider()
+            .is_some_and(|p| p.kind == crate::providers::ProviderKind::Anthropic);
+    let order = if cache_layout {
+        crate::sections::cache_order(&app.config.sections)
+    } else {
+        app.config.sections.clone()
+    };
+    let last_stable = order.iter().rposition(|s| s.is_stable());
+    let mut code_split = 0;
+    if cache_layout {
+        // Files the previous prompt already had in this form go first
+        let (unchanged, changed): (Vec<usize>, Vec<usize>) = sel_indices.iter().partition(|&&i| {
+            let f = &app.files[i];
+            !app.conversation
+                .changed_since_last(&f.rel_path, f.content.as_deref().unwrap_or(""))
+        });
+        sel_indices = unchanged.iter().chain(&changed).copied().collect();
+        code_split = unchanged.len();
+    }
+
+    let mut xml = String::new();
+    let sections = order.len();
+    for (position, section) in order.into_iter().enumerate() {
+        if cache_layout && position > 0 && Some(position - 1) == last_stable {
+            app.cache_breakpoints.push(xml.len());
+        }
+        match section {
+            // the messages export sends it as the system message
+            Section::SystemPrompt if messages => {}
+            Section::SystemPrompt => {
+                xml.push_str(&format!("<{}>\n", labels.system_prompt));
+                xml.push_str(&esc.wrap(&system_prompt));
+                xml.push_str(&format!("\n</{}>\n", labels.system_prompt));
+            }
+            Section::Instruction => {
+                xml.push_str(&format!("<{}>", labels.instruction));
+                xml.push_str(&esc.wrap(&instruction));
+                xml.push_str(&format!("</{}>\n", labels.instruction));
+            }
+            Section::FileTree => {
+                xml.push_str(&format!("<{}>\n", labels.file_tree));
+                xml.push_str(&esc.wrap(&tree));
+                xml.push_str(&format!("\n</{}>\n", labels.file_tree));
+            }
+            // manifest summary (opt-in)
+            Section::Metadata => {
+                if !app.config.include_metadata {
+                    continue;
+                }
+                if let Some(meta) = crate::project_metadata::summarize(&base) {
+                    xml.push_str(&format!("<{}>\n", labels.project_metadata));
+                    xml.push_str(&esc.wrap(&meta));
+                    xml.push_str(&format!("\n</{}>\n", labels.project_metadata));
+                }
+            }
+            Section::ProjectNotes => {
+                if let Some(notes) = project_notes(app) {
+                    xml.push_str(&format!("<{}>\n", labels.project_notes));
+                    xml.push_str(&esc.wrap(&notes));
+                    xml.push_str(&format!("\n</{}>\n", labels.project_notes));
+                }
+            }
+            Section::Code => {
+                let numbered_attr = line_numbers_attr(app);
+                xml.push_str(&format!("<{}>\n", labels.code));
+                for (n, &i) in sel_indices.iter().enumerate() {
+                    if cache_layout && n > 0 && n == code_split {
+                        app.cache_breakpoints.push(xml.len());
+                    }
+                    let f = &app.files[i];
+                    let rel = escape_xml_attr(&f.rel_path); // attribute still needs escaping
+                    let copy_of = same_as(app, i);
+                    let copy_attr = copy_of
+                        .map(|p| format!(" same_as=\"{}\"", escape_xml_attr(p)))
+                        .unwrap_or_default();
+                    let encoding_attr = f
+                        .text_issues
+                        .encoding
+                        .map(|e| format!(" encoding=\"{}\"", e.name()))
+                        .unwrap_or_default();
+                    let lang_attr = f
+                        .language
+                        .map(|l| format!(" lang=\"{}\"", l.id))
+                        .unwrap_or_default();
+                    match app.notes.get(&f.rel_path.replace('\\', "/")) {
+                        Some(note) => xml.push_str(&format!(
+                            "<{} path=\"{}\"{}{}{}{} note=\"{}\">",
+                            labels.file,
+                            rel,
+                            lang_attr,
+                            numbered_attr,
+                            copy_attr,
+                            encoding_attr,
+                            escape_xml_attr(&note.replace('\n', " "))
+                        )),
+                        None => xml.push_str(&format!(
+                            "<{} path=\"{}\"{}{}{}{}>",
+                            labels.file, rel, lang_attr, numbered_attr, copy_attr, encoding_attr
+                        )),
+                    }
+                    if copy_of.is_none() {
+                        xml.push_str(&esc.wrap(f.content.as_deref().unwrap_or("")));
+                    }
+                    xml.push_str(&format!("</{}>\n", labels.file));
+                }
+                xml.push_str(&format!("</{}>\n", labels.code));
+                xml.push_str(&snapshot_tag(&labels, &snapshot));
+                xml.push('\n');
+            }
+            Section::Attachments => {
+                xml.push_str(&attachments_xml(app, &labels));
+            }
+            // picked symbols (only when any were chosen)
+            Section::Snippets => {
+                if !app.symbol_picker.picked.is_empty() {
+                    xml.push_str(&format!("<{}>\n", labels.snippets));
+                    xml.push_str(&snippet_blocks(app, &labels));
+                    xml.push_str(&format!("</{}>\n\n", labels.snippets));
+                }
+            }
+            Section::Remote => {
+                xml.push_str(&remote_xml(app, &labels));
+            }
+            Section::TerminalCommand => {
+                xml.push_str(&terminal_command_xml(app, &labels));
+            }
+            Section::TerminalOutput => {
+                xml.push_str(&terminal_output_xml(app, &labels));
+            }
+        }
+    }
+    if cache_layout && last_stable == Some(sections - 1) {
+        app.cache_breakpoints.push(xml.len());
+    }
+
+    if messages {
+        let system = app
+            .config
+            .sections
+            .contains(&Section::SystemPrompt)
+            .then_some(system_prompt.as_str());
+        return crate::json_prompt::openai_messages(system, &xml, &read_images(app, &images));
+    }
+    xml
+}
+
+/// The selected images' bytes for the messages export; unreadable ones are reported and
+/// left out.
+fn read_images<'a>(app: &mut MyApp, images: &[(&'a str, PathBuf)]) -> Vec<(&'a str, Vec<u8>)> {
+    let mut out = Vec::new();
+    for (mime, path) in images {
+        match std::fs::read(path) {
+            Ok(bytes) => out.push((*mime, bytes)),
+            Err(e) => app.report_error(PromptError::io("read", path, e)),
+        }
+    }
+    out
+}
+/// The composed instruction with template variables filled in.
+fn expanded_instruction(app: &MyApp, labels: &PromptLabels) -> String {
+    let output = capped_terminal_output(app, labels);
+    let vars = crate::template_vars::Vars {
+        base: app.current_folder.as_deref(),
+        selected_file_count: app
+            .files
+            .iter()
+            .filter(|f| f.selected && !f.tree_only)
+            .count(),
+        last_command_output: &output,
+    };
+    crate::template_vars::expand(&app.instruction(), &vars)
+}
+
+/// `working_dir="..."` when the command ran outside the project folder, and with several
+/// runs in the prompt a `command="..."` label so outputs can be told apart.
+fn run_attrs(run: &RunView, labelled: bool) -> String {
+    let mut attrs = String::new();
+    if labelled {
+        attrs.push_str(&format!(
+            " command=\"{}\"",
+            escape_xml_attr(run.command.trim())
+        ));
+    }
+    let dir = run.working_dir.trim();
+    if !dir.is_empty() {
+        attrs.push_str(&format!(" working_dir=\"{}\"", escape_xml_attr(dir)));
+    }
+    attrs
+}
+
+/// `exit_code`, `duration_ms`, and `timed_out`/`truncated` when they apply; nothing for
+/// output that wasn't produced by a run. `capped` marks a cut by the token cap.
+fn run_status_attrs(run: &RunView, capped: bool) -> String {
+    let mut attrs = String::new();
+    if let Some(code) = run.exit_code {
+        attrs.push_str(&format!(" exit_code=\"{}\"", code));
+    }
+    if run.timed_out {
+        attrs.push_str(" timed_out=\"true\"");
+    }
+    if let Some(duration) = run.duration {
+        attrs.push_str(&format!(" duration_ms=\"{}\"", duration.as_millis()));
+    }
+    if run.truncated || capped {
+        attrs.push_str(" truncated=\"true\"");
+    }
+    attrs
+}
+
+/// JSON counterpart of `run_status_attrs`.
+fn json_run_status(run: &RunView, capped: bool) -> Option<JsonRunStatus> {
+    run.duration.map(|duration| JsonRunStatus {
+        exit_code: run.exit_code,
+        duration_ms: duration.as_millis() as u64,
+        timed_out: run.timed_out,
+        truncated: run.truncated || capped,
+    })
+}
+
+/// One <terminal_command> per run going into the prompt.
+fn terminal_command_xml(app: &MyApp, labels: &PromptLabels) -> String {
+    app.terminal
+        .prompt_runs()
+        .iter()
+        .map(|run| {
+            format!(
+                "<{}{}>{}</{}>\n",
+                labels.terminal_command,
+                run_attrs(run, false),
+                app.config.escaping.wrap(run.command),
+                labels.terminal_command
+            )
+        })
+        .collect()
+}
+
+/// JSON counterpart of `terminal_command_xml`; several runs' commands are newline-joined
+/// (each also appears in `terminal_runs`).
+fn set_json_terminal_command(app: &MyApp, prompt: &mut JsonPrompt) {
+    let runs = app.terminal.prompt_runs();
+    if runs.is_empty() {
+        return;
+    }
+    let commands: Vec<&str> = runs.iter().map(|r| r.command).collect();
+    prompt.terminal_command = Some(commands.join("\n"));
+    if let [run] = runs.as_slice() {
+        let dir = run.working_dir.trim();
+        prompt.terminal_working_dir = (!dir.is_empty()).then(|| dir.to_string());
+    }
+}
+
+/// With "Summarize errors" on, the diagnostics worth sending from `output` (and how many
+/// warnings were dropped); None when it holds none.
+fn error_summary(
+    app: &MyApp,
+    output: &str,
+) -> Option<(Vec<crate::build_errors::Diagnostic>, usize)> {
+    if !app.config.summarize_errors {
+        return None;
+    }
+    let output = app.redactions.apply(output);
+    let (diagnostics, omitted) = crate::build_errors::relevant(crate::build_errors::parse(&output));
+    (!diagnostics.is_empty()).then_some((diagnostics, omitted))
+}
 
+/// Per run, `<errors>` when its output summarizes, else the capped output in
+/// <terminal_output>; then any diagnostics ticked in the Lint window.
+fn terminal_output_xml(app: &MyApp, labels: &PromptLabels) -> String {
+    let esc = app.config.escaping;
+    let runs = app.terminal.prompt_runs();
+    let labelled = runs.len() > 1;
     let mut xml = String::new();
+    for run in &runs {
+        let attrs = run_attrs(run, labelled);
+        match error_summary(app, run.output) {
+            Some((diagnostics, omitted)) => xml.push_str(&crate::build_errors::render(
+                &labels.errors,
+                &format!("{}{}", attrs, run_status_attrs(run, false)),
+                &diagnostics,
+                omitted,
+                labels,
+                esc,
+            )),
+            None => {
+                let (output, capped) = capped_output(app, labels, run.output);
+                xml.push_str(&format!(
+                    "<{}{}{}>{}</{}>\n",
+                    labels.terminal_output,
+                    attrs,
+                    run_status_attrs(run, capped),
+                    esc.wrap(&output),
+                    labels.terminal_output
+                ));
+            }
+        }
+    }
+    let lints = app.lint.included();
+    if !lints.is_empty() {
+        xml.push_str(&crate::build_errors::render(
+            &labels.lints,
+            "",
+            &lints,
+            0,
+            labels,
+            esc,
+        ));
+    }
+    xml
+}
+
+/// Fills the JSON prompt's terminal output (or `errors` when summarizing); several runs go
+/// into `terminal_runs` instead.
+fn set_json_terminal_output(app: &MyApp, labels: &PromptLabels, prompt: &mut JsonPrompt) {
+    let runs = app.terminal.prompt_runs();
+    match runs.as_slice() {
+        [] => {}
+        [run] => match error_summary(app, run.output) {
+            Some((diagnostics, _)) => {
+                prompt.errors = diagnostics;
+                prompt.terminal_status = json_run_status(run, false);
+            }
+            None => {
+                let (output, capped) = capped_output(app, labels, run.output);
+                prompt.terminal_output = Some(output);
+                prompt.terminal_status = json_run_status(run, capped);
+            }
+        },
+        runs => {
+            prompt.terminal_runs = runs
+                .iter()
+                .map(|run| {
+                    let summary = error_summary(app, run.output);
+                    let (output, capped) = match summary {
+                        Some(_) => (None, false),
+                        None => {
+                            let (output, capped) = capped_output(app, labels, run.output);
+                            (Some(output), capped)
+                        }
+                    };
+                    JsonRun {
+                        command: run.command.to_string(),
+                        working_dir: run.working_dir.trim().to_string(),
+                        status: json_run_status(run, capped),
+                        output,
+                        errors: summary.map(|(d, _)| d).unwrap_or_default(),
+                    }
+                })
+                .collect()
+        }
+    }
+    prompt.lints = app.lint.included();
+}
+
+/// The active output, as `{{last_command_output}}` sees it.
+fn capped_terminal_output(app: &MyApp, labels: &PromptLabels) -> String {
+    capped_output(app, labels, &app.terminal.terminal_output).0
+}
+
+/// Redacted and token-capped output, and whether the cap cut anything.
+fn capped_output(app: &MyApp, labels: &PromptLabels, output: &str) -> (String, bool) {
+    let redacted = app.redactions.apply(output);
+    let capped = crate::command_runner::cap_output_tokens(
+        &redacted,
+        app.config.terminal_token_cap,
+        &labels.output_truncated,
+    );
+    let cut = capped.len() != redacted.len();
+    (capped, cut)
+}
+
+/// Delta prompt for an ongoing conversation: changed files (relative to every earlier turn
+/// for follow-ups, to the last prompt for deltas), a list of the unchanged ones, fresh
+/// terminal output, and the new instruction. System prompt and tree were already sent.
+fn build_follow_up(
+    app: &mut MyApp,
+    sel_indices: &[usize],
+    labels: &PromptLabels,
+    kind: PromptKind,
+    snapshot: &CodeState,
+) -> String {
+    let esc = app.config.escaping;
+    let instruction = expanded_instruction(app, labels);
+    let (changed, unchanged): (Vec<usize>, Vec<usize>) = sel_indices.iter().partition(|&&i| {
+        let f = &app.files[i];
+        let content = f.content.as_deref().unwrap_or("");
+        match kind {
+            PromptKind::Delta => app.conversation.changed_since_last(&f.rel_path, content),
+            _ => app.conversation.is_new_or_changed(&f.rel_path, content),
+        }
+    });
+    let output_seen = app
+        .conversation
+        .turns
+        .last()
+        .is_some_and(|t| t.terminal == content_hash(&app.terminal.terminal_output));
+    let send_output = !app.terminal.terminal_output.is_empty() && !output_seen;
+
+    if app.config.output_format == OutputFormat::Json {
+        let mut prompt = JsonPrompt {
+            instruction,
+            files: changed.iter().map(|&i| json_file(app, i)).collect(),
+            unchanged_files: unchanged
+                .iter()
+                .map(|&i| app.files[i].rel_path.replace('\\', "/"))
+                .collect(),
+            ..Default::default()
+        };
+        if send_output {
+            set_json_terminal_command(app, &mut prompt);
+            set_json_terminal_output(app, labels, &mut prompt);
+        }
+        prompt.snapshot = Some(snapshot.to_json());
+        return prompt.render();
+    }
 
-    // system prompt
-    xml.push_str("<system_prompt>\n");
-    xml.push_str(&cdata_wrap(&system_prompt));
-    xml.push_str("\n</system_prompt>\n");
+    let mut xml = String::new();
+    if !changed.is_empty() {
+        xml.push_str(&format!("<{}>\n", labels.code));
+        for i in changed {
+            let f = &app.files[i];
+            let rel = escape_xml_attr(&f.rel_path);
+            xml.push_str(&format!(
+                "<{} path=\"{}\"{}>",
+                labels.file,
+                rel,
+                line_numbers_attr(app)
+            ));
+            xml.push_str(&esc.wrap(f.content.as_deref().unwrap_or("")));
+            xml.push_str(&format!("</{}>\n", labels.file));
+        }
+        xml.push_str(&format!("</{}>\n\n", labels.code));
+    }
+    if !unchanged.is_empty() {
+        let mut note = String::from("Unchanged since the previous prompt (not repeated):\n");
+        for i in unchanged {
+            note.push_str(&app.files[i].rel_path);
+            note.push('\n');
+        }
+        xml.push_str(&format!("<{}>", labels.unchanged_files));
+        xml.push_str(&esc.wrap(&note));
+        xml.push_str(&format!("</{}>\n", labels.unchanged_files));
+    }
 
-    // FIRST instruction
-    xml.push_str("<instruction>");
-    xml.push_str(&cdata_wrap(&app.extra_text));
-    xml.push_str("</instruction>\n");
+    xml.push_str(&snapshot_tag(labels, snapshot));
+    if send_output {
+        xml.push_str(&terminal_command_xml(app, labels));
+        xml.push_str(&terminal_output_xml(app, labels));
+    }
 
-    // file tree
-    xml.push_str("<file_tree>\n");
-    xml.push_str(&cdata_wrap(&tree));
-    xml.push_str("\n</file_tree>\n");
+    xml.push_str(&format!("<{}>", labels.instruction));
+    xml.push_str(&esc.wrap(&instruction));
+    xml.push_str(&format!("</{}>\n", labels.instruction));
+    xml
+}
 
-    // selected code files
-    xml.push_str("<code>\n");
-    for i in sel_indices {
+/// Hash and git state of the files whose contents go into this prompt.
+fn capture_code_state(app: &MyApp, sel_indices: &[usize]) -> CodeState {
+    let base = app
+        .current_folder
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("."));
+    let files = sel_indices.iter().map(|&i| {
         let f = &app.files[i];
-        let rel = escape_xml_attr(&f.rel_path); // attribute still needs escaping
-        xml.push_str(&format!("<file path=\"{}\">", rel));
-        xml.push_str(&cdata_wrap(f.content.as_deref().unwrap_or("")));
-        xml.push_str("</file>\n");
+        (
+            f.rel_path.replace('\\', "/"),
+            f.content.as_deref().unwrap_or(""),
+        )
+    });
+    CodeState::capture(&base, files)
+}
+
+fn snapshot_tag(labels: &PromptLabels, snapshot: &CodeState) -> String {
+    let mut tag = format!(
+        "<{} hash=\"{}\" files=\"{}\"",
+        labels.snapshot, snapshot.hash, snapshot.files
+    );
+    if let Some(commit) = &snapshot.commit {
+        tag.push_str(&format!(" commit=\"{}\"", escape_xml_attr(commit)));
+        if snapshot.dirty {
+            tag.push_str(" dirty=\"true\"");
+        }
     }
-    xml.push_str("</code>\n\n");
+    tag.push_str("/>\n");
+    tag
+}
 
-    // terminal bits
-    xml.push_str("<terminal_command>");
-    xml.push_str(&cdata_wrap(&app.terminal.terminal_command));
-    xml.push_str("</terminal_command>\n");
+/// Tells the model the `NNN | ` prefixes aren't part of the file.
+/// The project notes when they're switched on and not blank.
+fn project_notes(app: &MyApp) -> Option<String> {
+    let text = app.project_notes.text.trim();
+    (app.config.include_project_notes && !text.is_empty())
+        .then(|| app.redactions.apply(text).into_owned())
+}
 
-    xml.push_str("<terminal_output>");
-    xml.push_str(&cdata_wrap(&app.terminal.terminal_output));
-    xml.push_str("</terminal_output>\n");
+fn line_numbers_attr(app: &MyApp) -> &'static str {
+    if app.config.line_numbers {
+        " line_numbers=\"true\""
+    } else {
+        ""
+    }
+}
 
-    // SECOND instruction
-    xml.push_str("<instruction>");
-    xml.push_str(&cdata_wrap(&app.extra_text));
-    xml.push_str("</instruction>\n");
+/// The earlier file `i` is sent as a copy of, when duplicates are collapsed.
+fn same_as(app: &MyApp, i: usize) -> Option<&str> {
+    if !app.config.dedupe_files {
+        return None;
+    }
+    app.duplicates
+        .iter()
+        .find(|d| d.index == i && d.exact)
+        .map(|d| &*app.files[d.original].rel_path)
+}
 
-    // ---- copy + (optional) accurate count ----
-    app.generated_prompt = xml.clone();
-    app.token_count = crate::token_count::count_tokens(&app.generated_prompt);
-    ctx.copy_text(xml);
-    app.notification = Some((
-        "Prompt copied to clipboard!".into(),
-        std::time::Instant::now(),
-    ));
+fn json_file(app: &MyApp, i: usize) -> JsonFile {
+    let f = &app.files[i];
+    let path = f.rel_path.replace('\\', "/");
+    let same_as = same_as(app, i).map(|p| p.replace('\\', "/"));
+    JsonFile {
+        note: app.notes.get(&path).map(str::to_string),
+        content: match same_as {
+            Some(_) => String::new(),
+            None => f.content.clone().unwrap_or_default(),
+        },
+        line_numbers: app.config.line_numbers,
+        same_as,
+        encoding: f.text_issues.encoding.map(|e| e.name().to_string()),
+        lang: f.language.map(|l| l.id.to_string()),
+        path,
+    }
+}
+
+/// The full prompt as one JSON object, with the same section selection as the XML layout
+/// (the instruction appears once however often the layout repeats it).
+fn build_json(
+    app: &mut MyApp,
+    sel_indices: &[usize],
+    labels: &PromptLabels,
+    base: &std::path::Path,
+    tree: String,
+    system_prompt: String,
+    instruction: String,
+) -> JsonPrompt {
+    let sections = app.config.sections.clone();
+    let has = |section: Section| sections.contains(&section);
+    let mut prompt = JsonPrompt {
+        instruction,
+        ..Default::default()
+    };
+    if has(Section::SystemPrompt) {
+        prompt.system = Some(system_prompt);
+    }
+    if has(Section::FileTree) {
+        prompt.file_tree = Some(tree);
+    }
+    if has(Section::Metadata) && app.config.include_metadata {
+        prompt.project_metadata = crate::project_metadata::summarize(base);
+    }
+    if has(Section::ProjectNotes) {
+        prompt.project_notes = project_notes(app);
+    }
+    if has(Section::Code) {
+        prompt.files = sel_indices.iter().map(|&i| json_file(app, i)).collect();
+    }
+    if has(Section::Attachments) {
+        let line_numbers = app.config.line_numbers;
+        prompt.attachments = attachment_texts(app, labels)
+            .into_iter()
+            .map(|(path, content)| JsonFile {
+                path,
+                note: None,
+                line_numbers,
+                same_as: None,
+                encoding: None,
+                lang: None,
+                content,
+            })
+            .collect();
+    }
+    if has(Section::Snippets) {
+        prompt.snippets = picked_snippets(app);
+    }
+    if has(Section::Remote) {
+        prompt.remote = remote_items(app, labels)
+            .into_iter()
+            .map(|(source, trimmed, content)| JsonRemote {
+                source,
+                trimmed,
+                content,
+            })
+            .collect();
+    }
+    if has(Section::TerminalCommand) {
+        set_json_terminal_command(app, &mut prompt);
+    }
+    if has(Section::TerminalOutput) {
+        set_json_terminal_output(app, labels, &mut prompt);
+    }
+    prompt
+}
+
+/// Source of every picked symbol, each followed by its callers listing when requested.
+fn picked_snippets(app: &mut MyApp) -> Vec<JsonSnippet> {
+    const MAX_CALLERS: usize = 30;
+    let mut out = Vec::new();
+    let mut missing = Vec::new();
+    let mut missing_blame = Vec::new();
+    for p in &app.symbol_picker.picked {
+        let text = app
+            .files
+            .iter()
+            .find(|f| f.rel_path == p.rel_path)
+            .and_then(|f| f.read_text().ok());
+        let span = text
+            .as_deref()
+            .and_then(|t| crate::symbols::locate(&p.rel_path, t, &p.name, p.kind));
+        let (Some(text), Some(span)) = (text, span) else {
+            missing.push(format!("{} in {}", p.name, p.rel_path));
+            continue;
+        };
+        let first = if p.include_doc {
+            span.doc_start
+        } else {
+            span.start
+        };
+        let body: Vec<&str> = text
+            .lines()
+            .skip(first - 1)
+            .take(span.end + 1 - first)
+            .collect();
+        out.push(JsonSnippet {
+            path: p.rel_path.to_string(),
+            symbol: p.name.clone(),
+            kind: p.kind.label().to_string(),
+            lines: Some((first, span.end)),
+            content: app.redactions.apply(&body.join("\n")).into_owned(),
+        });
+
+        if p.include_blame {
+            let base = app.current_folder.clone().unwrap_or_default();
+            let rel = p.rel_path.replace('\\', "/");
+            match crate::blame::blame(&base, &rel, first, span.end) {
+                Ok(hunks) => out.push(JsonSnippet {
+                    path: p.rel_path.to_string(),
+                    symbol: p.name.clone(),
+                    kind: "blame".to_string(),
+                    lines: Some((first, span.end)),
+                    content: app
+                        .redactions
+                        .apply(&crate::blame::render(&hunks))
+                        .into_owned(),
+                }),
+                Err(e) => missing_blame.push(format!("{} in {}: {}", p.name, p.rel_path, e)),
+            }
+        }
+
+        if p.include_callers {
+            let callers = crate::symbols::find_callers(&app.files, &p.name, MAX_CALLERS);
+            out.push(JsonSnippet {
+                path: p.rel_path.to_string(),
+                symbol: p.name.clone(),
+                kind: "callers".to_string(),
+                lines: None,
+                content: app.redactions.apply(&callers.join("\n")).into_owned(),
+            });
+        }
+    }
+    for m in missing {
+        app.notify(format!("Symbol {} not found; skipped", m));
+    }
+    for m in missing_blame {
+        app.notify(format!("No blame for {}", m));
+    }
+    out
+}
+
+/// Display path and text of each included attachment, read with the same cap and
+/// normalization as selected project files.
+fn attachment_texts(app: &mut MyApp, labels: &PromptLabels) -> Vec<(String, String)> {
+    let keywords = crate::truncation::keywords(&app.instruction());
+    let paths: Vec<(String, PathBuf)> = app
+        .attachments
+        .included()
+        .map(|a| (a.display_path(), a.path.clone()))
+        .collect();
+    let mut out = Vec::new();
+    for (display, path) in paths {
+        let text = match read_text_capped(
+            &path,
+            None,
+            MAX_PER_FILE_BYTES,
+            labels,
+            app.config.normalize_text,
+            app.config.line_numbers,
+            &keywords,
+        ) {
+            Ok((text, _)) => text,
+            Err(e) => {
+                let marker = format!("{} {}\n", labels.read_error, e);
+                app.report_error(e);
+                marker
+            }
+        };
+        out.push((display, app.redactions.apply(&text).into_owned()));
+    }
+    out
+}
+
+/// <attachments> with one <attachment> per included file; nothing when there are none.
+fn attachments_xml(app: &mut MyApp, labels: &PromptLabels) -> String {
+    let items = attachment_texts(app, labels);
+    if items.is_empty() {
+        return String::new();
+    }
+    let esc = app.config.escaping;
+    let numbered_attr = line_numbers_attr(app);
+    let mut xml = format!("<{}>\n", labels.attachments);
+    for (path, text) in items {
+        xml.push_str(&format!(
+            "<{} path=\"{}\"{}>",
+            labels.attachment,
+            escape_xml_attr(&path),
+            numbered_attr
+        ));
+        xml.push_str(&esc.wrap(&text));
+        xml.push_str(&format!("</{}>\n", labels.attachment));
+    }
+    xml.push_str(&format!("</{}>\n", labels.attachments));
+    xml
+}
+
+/// Label, whether trimmed, and text of each included source that has been fetched.
+fn remote_items(app: &MyApp, labels: &PromptLabels) -> Vec<(String, bool, String)> {
+    app.remote
+        .sources
+        .iter()
+        .filter(|s| s.include)
+        .filter_map(|s| {
+            let text = s.prompt_text(&labels.omitted)?;
+            Some((s.source.label(), s.trim.is_active(), text))
+        })
+        .collect()
+}
+
+/// <remote> with one <source> per included remote source; nothing when none are.
+fn remote_xml(app: &MyApp, labels: &PromptLabels) -> String {
+    let items = remote_items(app, labels);
+    if items.is_empty() {
+        return String::new();
+    }
+    let esc = app.config.escaping;
+    let mut xml = format!("<{}>\n", labels.remote);
+    for (source, trimmed, text) in items {
+        xml.push_str(&format!(
+            "<{} label=\"{}\"{}>",
+            labels.remote_source,
+            escape_xml_attr(&source),
+            if trimmed { " trimmed=\"true\"" } else { "" }
+        ));
+        xml.push_str(&esc.wrap(&text));
+        xml.push_str(&format!("</{}>\n", labels.remote_source));
+    }
+    xml.push_str(&format!("</{}>\n", labels.remote));
+    xml
 }
+
+/// One <snippet> per picked symbol, plus a callers listing when requested.
+fn snippet_blocks(app: &mut MyApp, labels: &PromptLabels) -> String {
+    let esc = app.config.escaping;
+    let mut out = String::new();
+    for snippet in picked_snippets(app) {
+        match snippet.lines {
+            Some((first, last)) => out.push_str(&format!(
+                "<{} path=\"{}\" symbol=\"{}\" kind=\"{}\" lines=\"{}-{}\">",
+                labels.snippet,
+                escape_xml_attr(&snippet.path),
+                escape_xml_attr(&snippet.symbol),
+                snippet.kind,
+                first,
+                last
+            )),
+            None => out.push_str(&format!(
+                "<{} symbol=\"{}\" kind=\"{}\">",
+                labels.snippet,
+                escape_xml_attr(&snippet.symbol),
+                snippet.kind
+            )),
+        }
+        out.push_str(&esc.wrap(&snippet.content));
+        out.push_str(&format!("</{}>\n", labels.snippet));
+    }
+    out
+}
+
 impl Default for MyApp {
     fn default() -> Self {
         let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
-        let ignore_set = crate::file_item::load_ignore_set_from(&cwd);
+        let (ignore_set, ignore_rules, _) = crate::file_item::load_ignore_set_from(&cwd, &[]);
 
         let mut app = Self {
             files: Vec::new(),
             extra_text: String::new(),
             ignore_set,
+            ignore_rules,
             generated_prompt: String::new(),
             token_count: 0,
+            cache_breakpoints: Vec::new(),
+            cache_split: None,
             current_folder: None,
             include_file_tree: true,
             notification: None,
+            notifications: NotificationLog::default(),
             remote: Remote::default(),
+            lint: Default::default(),
+            commit_message: Default::default(),
+            pr_description: Default::default(),
+            shell: Default::default(),
+            mcp: None,
+            http_api: None,
+            attachments: Default::default(),
+            jobs: Jobs::default(),
             terminal: Terminal::default(),
-            scanned_files: 0,
-            ignored_files: 0,
-            ignored_dirs: 0,
-            symlinks_skipped: 0,
+            scan_stats: ScanStats::default(),
+            scan_job: None,
+            deferred_dirs: BTreeSet::new(),
+            loading_dirs: BTreeMap::new(),
+            open_archives: BTreeSet::new(),
+            show_ignored_paths: false,
+            ignore_editor: IgnoreEditor::default(),
+            config: ProjectConfig::default(),
+            config_load_failed: false,
+            tree_view: TreeView::default(),
+            file_tree: FileTree::default(),
+            hide_token_threshold: 5_000,
+            symbol_picker: SymbolPicker::default(),
+            workspace: Vec::new(),
+            preview: None,
+            notes: Notes::default(),
+            token_calibration: Default::default(),
+            project_notes: ProjectNotes::default(),
+            conversation: Conversation::default(),
+            evaluator: Evaluator::default(),
+            response: ResponsePane::default(),
+            providers: Providers::default(),
+            code_import: CodeImport::default(),
+            show_histogram: false,
+            histogram_view: Default::default(),
+            treemap_whole_project: false,
+            treemap_dir: String::new(),
+            show_layout_editor: false,
+            show_file_order: false,
+            arranged_files: None,
+            show_prompt_viewer: false,
+            errors_as_snippets: false,
+            focus_tests_pending: false,
+            language_packs: Vec::new(),
+            last_snapshot: None,
+            duplicates: Vec::new(),
+            prompt_search: TextSearch::default(),
+            prompt_folds: Default::default(),
+            preview_search: TextSearch::default(),
+            preview_highlight: None,
+            preview_range: (1, 1),
+            instructions: InstructionLibrary::default(),
+            system_profiles: SystemProfiles::default(),
+            scaffold_offer: None,
+            note_editor: None,
+            comment_savings: Default::default(),
+            error_summary_tokens: Default::default(),
+            redactions: Redactions::default(),
+            env_presets: Default::default(),
+            folds: std::collections::HashMap::new(),
+            clipboard: None,
+            unsaved_prompt: None,
         };
         // Defer scanning until run() sets the folder
         app
     }
 }
 
-impl eframe::App for MyApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        while let Ok(update) = self.remote.remote_update_rx.try_recv() {
-            let RemoteUpdate::Fetched { index, content } = update;
-            if let Some(remote) = self.remote.remote_urls.get_mut(index) {
-                remote.content = Some(content);
-            }
+impl MyApp {
+    /// A new session on `folder`, scanning it.
+    pub fn for_folder(folder: PathBuf) -> Self {
+        let mut app = MyApp {
+            current_folder: Some(folder),
+            ..MyApp::default()
+        };
+        app.start_scan();
+        app.load_history();
+        app.offer_scaffold();
+        app
+    }
+
+    /// A new session on `folder` starting from this one's selection and instruction.
+    pub fn fork(&self, folder: PathBuf) -> Self {
+        // The scan keeps the selection of the files it finds again
+        let mut app = MyApp {
+            current_folder: Some(folder),
+            files: self.files.clone(),
+            extra_text: self.extra_text.clone(),
+            ..MyApp::default()
+        };
+        app.start_scan();
+        app.load_history();
+        app
+    }
+
+    /// Label for the session's tab: folder name, selected files and token meter.
+    pub fn tab_title(&self) -> String {
+        let folder = self
+            .current_folder
+            .as_deref()
+            .and_then(|f| f.file_name())
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "(no folder)".to_string());
+        let selected = self
+            .files
+            .iter()
+            .filter(|f| f.selected && !f.tree_only)
+            .count();
+        format!("{} · {} files · {} tok", folder, selected, self.token_count)
+    }
+
+    /// Everything but drawing: finished jobs, scheduled commands, stale contents and the
+    /// API servers. Runs for every session each frame; only the active one is drawn.
+    pub fn background(&mut self, ctx: &egui::Context) {
+        for output in self.jobs.poll() {
+            self.apply_job_output(output);
+        }
+        if self.response.streaming
+            && !self
+                .providers
+                .completion
+                .is_some_and(|id| self.jobs.is_running(id))
+        {
+            // Cancelled from the Jobs window
+            self.response.streaming = false;
+        }
+        // The file watcher runs for as long as it's needed and reports its own changes
+        let watching = self
+            .terminal
+            .watch
+            .is_some_and(|id| self.jobs.is_running(id));
+        if self.jobs.running_count() > usize::from(watching) {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        } else if watching {
+            ctx.request_repaint_after(crate::file_watch::POLL_INTERVAL);
         }
-        while let Ok(output) = self.terminal.terminal_update_rx.try_recv() {
-            self.terminal.terminal_output = output;
-            self.terminal.is_running = false;
+        self.run_scheduled_commands(ctx);
+        self.serve_mcp();
+        self.serve_http_api();
+        if self.response.watch_clipboard {
+            self.poll_clipboard_for_response();
+            ctx.request_repaint_after(crate::responses::POLL_INTERVAL);
         }
+    }
+}
+
+impl eframe::App for MyApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.background(ctx);
         self.remote_url_panel(ctx);
 
         self.file_panel(ctx);
@@ -1022,37 +7687,88 @@ impl eframe::App for MyApp {
         self.bottom_panel(ctx);
 
         self.central_panel(ctx);
+
+        self.ignore_editor_window(ctx);
+        self.ignored_paths_window(ctx);
+        self.notification_log_window(ctx);
+        self.symbol_picker_window(ctx);
+        self.unsaved_prompt_window(ctx);
+        self.jobs.window(ctx);
+        self.lint_window(ctx);
+        self.commit_message_window(ctx);
+        self.pr_description_window(ctx);
+        self.shell_window(ctx);
+        self.project_notes_window(ctx);
+        self.preview_window(ctx);
+        self.note_editor_window(ctx);
+        self.conversation_window(ctx);
+        self.response_window(ctx);
+        self.providers_window(ctx);
+        self.code_import_window(ctx);
+        self.histogram_window(ctx);
+        self.layout_editor_window(ctx);
+        self.file_order_window(ctx);
+        self.prompt_viewer_window(ctx);
+        self.instruction_library_window(ctx);
+        self.scaffold_window(ctx);
     }
 }
 
 pub fn run() {
-    let mut app = MyApp::default();
-    match env::args().nth(1) {
+    let args: Vec<String> = env::args().collect();
+    if crate::dev_bench::run_from_args(&args) || crate::batch::run_from_args(&args) {
+        return;
+    }
+    let mcp = args.iter().any(|a| a == "--mcp");
+    let serve = args
+        .iter()
+        .position(|a| a == "--serve")
+        .and_then(|i| args.get(i + 1))
+        .cloned();
+    let folder_arg = (1..args.len())
+        .find(|&i| !args[i].starts_with("--") && args[i - 1] != "--serve")
+        .map(|i| &args[i]);
+    let current_dir = || env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let folder = match folder_arg {
         Some(arg) => {
             let folder = PathBuf::from(arg);
             if folder.is_dir() {
-                app.current_folder = Some(folder);
+                folder
             } else {
                 eprintln!("Warning: Provided argument is not a valid directory.");
-                app.current_folder =
-                    Some(env::current_dir().unwrap_or_else(|_| PathBuf::from(".")));
+                current_dir()
             }
         }
-        None => {
-            app.current_folder = Some(env::current_dir().unwrap_or_else(|_| PathBuf::from(".")));
-        }
-    }
-    app.refresh_files();
-    app.load_history();
+        None => current_dir(),
+    };
+    let mut app = MyApp::for_folder(folder);
+    let ui = crate::ui_settings::UiSettings::load().unwrap_or_else(|e| {
+        app.report_error(e);
+        Default::default()
+    });
+    let viewport = match ui.window_size {
+        Some(size) => egui::ViewportBuilder::default().with_inner_size(size),
+        None => egui::ViewportBuilder::default().with_maximized(true),
+    };
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size([1920.0, 1080.0])
-            .with_transparent(true),
+        viewport: viewport.with_transparent(true),
         ..Default::default()
     };
     let _ = eframe::run_native(
         "Prompt Generator",
         options,
-        Box::new(|_cc| Ok(Box::new(app))),
+        Box::new(move |cc| {
+            if mcp {
+                app.mcp = Some(crate::mcp::McpServer::start(cc.egui_ctx.clone()));
+            }
+            if let Some(addr) = &serve {
+                match crate::http_api::ApiServer::start(addr, cc.egui_ctx.clone()) {
+                    Ok(server) => app.http_api = Some(server),
+                    Err(e) => app.report_error(e),
+                }
+            }
+            ui.apply(&cc.egui_ctx);
+            Ok(Box::new(crate::sessions::Sessions::new(app, ui)))
+        }),
     );
 }
@@ -0,0 +1,145 @@
+// Read-only look inside .zip and .tar(.gz) files in the project: opening one from the
+// tree lists its entries as files under `<archive path>/`, selectable like any other,
+// and their contents are read straight out of the archive when the prompt is built.
+// Nothing is extracted to disk.
+
+use crate::error::{PromptError, Result};
+use crate::file_item::ScannedFile;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, Path};
+
+#[derive(Clone, Copy)]
+enum Format {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+fn format(path: &str) -> Option<Format> {
+    let lower = path.to_ascii_lowercase();
+    if lower.ends_with(".zip") {
+        Some(Format::Zip)
+    } else if lower.ends_with(".tar") {
+        Some(Format::Tar)
+    } else if lower.ends_with(".tar.gz") || lower.ends_with(".tgz") {
+        Some(Format::TarGz)
+    } else {
+        None
+    }
+}
+
+pub fn is_archive(rel_path: &str) -> bool {
+    format(rel_path).is_some()
+}
+
+fn archive_error(path: &Path, message: impl ToString) -> PromptError {
+    PromptError::Archive {
+        path: path.to_path_buf(),
+        message: message.to_string(),
+    }
+}
+
+/// `name` as a '/'-separated relative path, or None if it would leave the archive
+/// (absolute, or climbing out with `..`).
+fn entry_path(name: &str) -> Option<String> {
+    let mut parts = Vec::new();
+    for component in Path::new(&name.replace('\\', "/")).components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+fn tar_entries(path: &Path, format: Format) -> Result<tar::Archive<Box<dyn Read>>> {
+    let file = File::open(path).map_err(|e| PromptError::io("read", path, e))?;
+    let reader: Box<dyn Read> = match format {
+        Format::TarGz => Box::new(flate2::read::GzDecoder::new(file)),
+        _ => Box::new(file),
+    };
+    Ok(tar::Archive::new(reader))
+}
+
+/// The files in the archive at `path` (`rel_path` in the project), at most `limit`.
+pub fn list(path: &Path, rel_path: &str, limit: usize) -> Result<Vec<ScannedFile>> {
+    let format = format(rel_path).ok_or_else(|| archive_error(path, "not an archive"))?;
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let mut found = Vec::new();
+    let mut add = |name: String, size: u64| {
+        if let Some(inner) = entry_path(&name) {
+            found.push(ScannedFile {
+                path: path.to_path_buf(),
+                rel_path: format!("{}/{}", rel_path, inner),
+                size,
+                modified,
+                archive_entry: Some(name),
+            });
+        }
+    };
+    match format {
+        Format::Zip => {
+            let file = File::open(path).map_err(|e| PromptError::io("read", path, e))?;
+            let mut zip = zip::ZipArchive::new(file).map_err(|e| archive_error(path, e))?;
+            for i in 0..zip.len().min(limit) {
+                let entry = zip.by_index_raw(i).map_err(|e| archive_error(path, e))?;
+                if !entry.is_dir() {
+                    add(entry.name().to_string(), entry.size());
+                }
+            }
+        }
+        Format::Tar | Format::TarGz => {
+            let mut tar = tar_entries(path, format)?;
+            let entries = tar.entries().map_err(|e| archive_error(path, e))?;
+            for entry in entries.take(limit) {
+                let entry = entry.map_err(|e| archive_error(path, e))?;
+                if entry.header().entry_type().is_file() {
+                    let name = entry.path().map_err(|e| archive_error(path, e))?;
+                    add(name.to_string_lossy().into_owned(), entry.size());
+                }
+            }
+        }
+    }
+    found.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+    Ok(found)
+}
+
+/// Up to `limit` bytes of entry `name`, and whether that was all of it.
+pub fn read_entry(path: &Path, name: &str, limit: usize) -> Result<(Vec<u8>, bool)> {
+    let format =
+        format(&path.to_string_lossy()).ok_or_else(|| archive_error(path, "not an archive"))?;
+    let read_capped = |reader: &mut dyn Read| {
+        let mut bytes = Vec::new();
+        reader
+            .take((limit as u64).saturating_add(1))
+            .read_to_end(&mut bytes)
+            .map_err(|e| archive_error(path, format!("{}: {}", name, e)))?;
+        let complete = bytes.len() <= limit;
+        bytes.truncate(limit);
+        Ok((bytes, complete))
+    };
+    match format {
+        Format::Zip => {
+            let file = File::open(path).map_err(|e| PromptError::io("read", path, e))?;
+            let mut zip = zip::ZipArchive::new(file).map_err(|e| archive_error(path, e))?;
+            let mut entry = zip
+                .by_name(name)
+                .map_err(|e| archive_error(path, format!("{}: {}", name, e)))?;
+            read_capped(&mut entry)
+        }
+        Format::Tar | Format::TarGz => {
+            let mut tar = tar_entries(path, format)?;
+            let entries = tar.entries().map_err(|e| archive_error(path, e))?;
+            for entry in entries {
+                let mut entry = entry.map_err(|e| archive_error(path, e))?;
+                let matches = entry.path().is_ok_and(|p| p.to_string_lossy() == name);
+                if matches {
+                    return read_capped(&mut entry);
+                }
+            }
+            Err(archive_error(path, format!("{}: no such entry", name)))
+        }
+    }
+}
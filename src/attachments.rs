@@ -0,0 +1,120 @@
+// Files from outside the project folder — a design doc in ~/Documents, a log in /tmp —
+// attached to the prompt. They're read like selected project files (per-file byte cap,
+// binary check, normalization) and emitted in their own <attachments> section. The list
+// is kept per project in `.prompt/attachments.json`.
+
+use crate::error::{PromptError, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub path: PathBuf,
+    #[serde(default = "included")]
+    pub include: bool,
+    /// Size-based estimate, like project files before an exact recount.
+    #[serde(skip)]
+    pub token_count: usize,
+    /// The file wasn't there when last checked.
+    #[serde(skip)]
+    pub missing: bool,
+}
+
+fn included() -> bool {
+    true
+}
+
+impl Attachment {
+    fn new(path: PathBuf) -> Self {
+        let mut attachment = Self {
+            path,
+            include: true,
+            token_count: 0,
+            missing: false,
+        };
+        attachment.refresh();
+        attachment
+    }
+
+    /// Re-reads the size for the token estimate.
+    pub fn refresh(&mut self) {
+        match std::fs::metadata(&self.path) {
+            Ok(meta) => {
+                self.token_count = ((meta.len() as f32) / 4.0).ceil() as usize;
+                self.missing = false;
+            }
+            Err(_) => {
+                self.token_count = 0;
+                self.missing = true;
+            }
+        }
+    }
+
+    /// The path with the home directory shortened to `~`, so prompts don't carry the
+    /// user name.
+    pub fn display_path(&self) -> String {
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        match home.as_deref().and_then(|h| self.path.strip_prefix(h).ok()) {
+            Some(rest) => format!("~/{}", rest.to_string_lossy().replace('\\', "/")),
+            None => self.path.to_string_lossy().replace('\\', "/"),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Attachments {
+    pub items: Vec<Attachment>,
+}
+
+impl Attachments {
+    pub fn path(base: &Path) -> PathBuf {
+        base.join(".prompt").join("attachments.json")
+    }
+
+    /// A missing file means no attachments; a malformed one is an error.
+    pub fn load(base: &Path) -> Result<Self> {
+        let path = Self::path(base);
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(PromptError::io("read", path, e)),
+        };
+        let mut items: Vec<Attachment> =
+            serde_json::from_str(&text).map_err(|e| PromptError::Config {
+                path,
+                message: e.to_string(),
+            })?;
+        for item in &mut items {
+            item.refresh();
+        }
+        Ok(Self { items })
+    }
+
+    pub fn save(&self, base: &Path) -> Result<()> {
+        let path = Self::path(base);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| PromptError::io("create", dir, e))?;
+        }
+        let json = serde_json::to_string_pretty(&self.items).unwrap();
+        std::fs::write(&path, json).map_err(|e| PromptError::io("write", &path, e))
+    }
+
+    /// Adds the paths not already attached; returns how many were new.
+    pub fn add(&mut self, paths: Vec<PathBuf>) -> usize {
+        let before = self.items.len();
+        for path in paths {
+            if !self.items.iter().any(|a| a.path == path) {
+                self.items.push(Attachment::new(path));
+            }
+        }
+        self.items.len() - before
+    }
+
+    pub fn included(&self) -> impl Iterator<Item = &Attachment> {
+        self.items.iter().filter(|a| a.include && !a.missing)
+    }
+
+    pub fn included_tokens(&self) -> usize {
+        self.included().map(|a| a.token_count).sum()
+    }
+}
@@ -0,0 +1,119 @@
+// Snapshots of files about to be overwritten by an import or patch, kept under
+// `.prompt/backups/<timestamp>/` so the last apply can be reverted even outside git.
+
+use crate::error::{PromptError, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Relative to the project root; the scanner skips it so backups never show in the tree.
+pub const BACKUPS_DIR: &str = ".prompt/backups";
+const MANIFEST: &str = "manifest.json";
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    path: String,
+    /// False if the apply created the file, so reverting deletes it.
+    existed: bool,
+}
+
+/// Paths a unified diff reads or writes, from its `---` / `+++` headers.
+pub fn patch_paths(patch: &str) -> Vec<String> {
+    let mut paths: Vec<String> = patch
+        .lines()
+        .filter_map(|l| l.strip_prefix("--- ").or_else(|| l.strip_prefix("+++ ")))
+        .map(|p| p.split('\t').next().unwrap_or(p).trim())
+        .filter(|p| *p != "/dev/null")
+        .map(|p| {
+            p.strip_prefix("a/")
+                .or_else(|| p.strip_prefix("b/"))
+                .unwrap_or(p)
+                .to_string()
+        })
+        .collect();
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// Copies the current contents of `rel_paths` into a fresh backup folder.
+pub fn snapshot(base: &Path, rel_paths: &[String]) -> Result<PathBuf> {
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S%.3f").to_string();
+    let dir = base.join(BACKUPS_DIR).join(stamp);
+    std::fs::create_dir_all(&dir).map_err(|e| PromptError::io("create", &dir, e))?;
+    let mut entries = Vec::new();
+    for rel in rel_paths {
+        // Paths outside the project are refused by the writers; nothing to back up
+        let Some(src) = crate::responses::project_path(base, rel) else {
+            continue;
+        };
+        let existed = src.is_file();
+        if existed {
+            let dst = dir.join(rel);
+            if let Some(parent) = dst.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| PromptError::io("create", parent, e))?;
+            }
+            std::fs::copy(&src, &dst).map_err(|e| PromptError::io("back up", &src, e))?;
+        }
+        entries.push(Entry {
+            path: rel.clone(),
+            existed,
+        });
+    }
+    let manifest = dir.join(MANIFEST);
+    let json = serde_json::to_string_pretty(&entries).unwrap_or_default();
+    std::fs::write(&manifest, json).map_err(|e| PromptError::io("write", &manifest, e))?;
+    Ok(dir)
+}
+
+/// Drops a snapshot whose apply failed, so "Revert" doesn't target a no-op.
+pub fn discard(dir: &Path) {
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+/// Most recent snapshot (folder names sort chronologically).
+pub fn latest(base: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(base.join(BACKUPS_DIR))
+        .ok()?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.join(MANIFEST).is_file())
+        .max()
+}
+
+/// Restores the latest snapshot and deletes it, so repeated reverts step further back.
+/// Returns the snapshot's folder name and how many files were restored or removed.
+pub fn revert_latest(base: &Path) -> Result<Option<(String, usize)>> {
+    let Some(dir) = latest(base) else {
+        return Ok(None);
+    };
+    let restored = restore(base, &dir)?;
+    let name = dir
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok(Some((name, restored)))
+}
+
+/// Puts back the files of the snapshot in `dir` and deletes it; returns how many files
+/// were restored or removed.
+pub fn restore(base: &Path, dir: &Path) -> Result<usize> {
+    let manifest = dir.join(MANIFEST);
+    let text =
+        std::fs::read_to_string(&manifest).map_err(|e| PromptError::io("read", &manifest, e))?;
+    let entries: Vec<Entry> = serde_json::from_str(&text).map_err(|e| PromptError::Config {
+        path: manifest.clone(),
+        message: e.to_string(),
+    })?;
+    for entry in &entries {
+        let target = base.join(&entry.path);
+        if entry.existed {
+            let src = dir.join(&entry.path);
+            std::fs::copy(&src, &target).map_err(|e| PromptError::io("restore", &target, e))?;
+        } else if target.exists() {
+            std::fs::remove_file(&target).map_err(|e| PromptError::io("remove", &target, e))?;
+        }
+    }
+    std::fs::remove_dir_all(dir).map_err(|e| PromptError::io("remove", dir, e))?;
+    Ok(entries.len())
+}
@@ -0,0 +1,181 @@
+// Non-interactive mode: `prompt batch --config jobs.toml`
+//
+// Each `[[job]]` selects files by glob, optionally runs a command for the terminal
+// section, and writes the finished prompt to a file. Relative paths in the jobs file
+// are resolved against the jobs file's directory.
+//
+// ```toml
+// [[job]]
+// name = "nightly-summary"
+// folder = "."                       # project root (default: jobs file dir)
+// select = ["src/**/*.rs", "Cargo.toml"]
+// exclude = ["src/generated/**"]
+// instruction = "Summarize today's changes."
+// # instruction_file = "prompts/summary.txt"   # alternative to `instruction`
+// terminal_command = "git diff HEAD~1"
+// output = "out/summary.xml"
+// ```
+
+use crate::app::{build_prompt, MyApp};
+use crate::error::{PromptError, Result};
+use crate::labels::PromptLabels;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Deserialize)]
+struct JobsFile {
+    #[serde(default, rename = "job")]
+    jobs: Vec<Job>,
+}
+
+#[derive(Deserialize)]
+struct Job {
+    name: String,
+    folder: Option<PathBuf>,
+    #[serde(default)]
+    select: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+    instruction: Option<String>,
+    instruction_file: Option<PathBuf>,
+    terminal_command: Option<String>,
+    output: PathBuf,
+}
+
+/// Handles `batch --config <file>`; returns false if the args don't ask for batch mode.
+pub fn run_from_args(args: &[String]) -> bool {
+    if args.get(1).map(String::as_str) != Some("batch") {
+        return false;
+    }
+    let config = args
+        .iter()
+        .position(|a| a == "--config")
+        .and_then(|i| args.get(i + 1))
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("jobs.toml"));
+    let failed = match run_jobs_file(&config) {
+        Ok(failed) => failed,
+        Err(e) => {
+            eprintln!("[batch] {}", e);
+            1
+        }
+    };
+    if failed > 0 {
+        std::process::exit(1);
+    }
+    true
+}
+
+/// Runs every job; returns how many failed. Only an unreadable jobs file aborts the batch.
+fn run_jobs_file(config: &Path) -> Result<usize> {
+    let text = std::fs::read_to_string(config).map_err(|e| PromptError::io("read", config, e))?;
+    let jobs: JobsFile = toml::from_str(&text).map_err(|e| PromptError::Config {
+        path: config.to_path_buf(),
+        message: e.to_string(),
+    })?;
+    let root = config
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    let mut failed = 0;
+    for job in &jobs.jobs {
+        match run_job(root, job) {
+            Ok((path, files, bytes)) => println!(
+                "[batch] {}: {} files, {} bytes -> {}",
+                job.name,
+                files,
+                bytes,
+                path.display()
+            ),
+            Err(e) => {
+                eprintln!("[batch] {}: FAILED: {}", job.name, e);
+                failed += 1;
+            }
+        }
+    }
+    println!(
+        "[batch] {} of {} jobs succeeded",
+        jobs.jobs.len() - failed,
+        jobs.jobs.len()
+    );
+    Ok(failed)
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for p in patterns {
+        let glob = Glob::new(p).map_err(|e| PromptError::Glob {
+            pattern: p.clone(),
+            message: e.to_string(),
+        })?;
+        builder.add(glob);
+    }
+    builder.build().map_err(|e| PromptError::Glob {
+        pattern: patterns.join(", "),
+        message: e.to_string(),
+    })
+}
+
+fn run_job(root: &Path, job: &Job) -> Result<(PathBuf, usize, usize)> {
+    let folder = root.join(job.folder.as_deref().unwrap_or(Path::new(".")));
+    let instruction = match (&job.instruction, &job.instruction_file) {
+        (_, Some(file)) => {
+            let path = root.join(file);
+            std::fs::read_to_string(&path).map_err(|e| PromptError::io("read", &path, e))?
+        }
+        (Some(text), None) => text.clone(),
+        (None, None) => String::new(),
+    };
+
+    let mut app = MyApp {
+        current_folder: Some(folder.clone()),
+        extra_text: instruction,
+        ..Default::default()
+    };
+    app.refresh_files();
+
+    let select = glob_set(&job.select)?;
+    let exclude = glob_set(&job.exclude)?;
+    for f in app.files.iter_mut() {
+        let rel = f.rel_path.replace('\\', "/");
+        f.selected = !f.tree_only && select.is_match(&rel) && !exclude.is_match(&rel);
+    }
+    let selected = app.files.iter().filter(|f| f.selected).count();
+
+    if let Some(command) = &job.terminal_command {
+        let tokens =
+            shell_words::split(command).map_err(|e| PromptError::CommandParse(e.to_string()))?;
+        let Some((cmd, args)) = tokens.split_first() else {
+            return Err(PromptError::CommandParse("empty terminal_command".into()));
+        };
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let marker = PromptLabels::resolve(&app.config.labels).output_truncated;
+        let t = &app.terminal;
+        let output = crate::command_runner::run_command(
+            &folder,
+            cmd,
+            &args,
+            t.head_lines,
+            t.tail_lines,
+            t.timeout_secs > 0,
+            Duration::from_secs(t.timeout_secs),
+            &[],
+            &marker,
+            None,
+            None,
+        )?;
+        app.terminal.terminal_command = command.clone();
+        app.terminal.terminal_output = output.text;
+    }
+
+    let prompt = build_prompt(&mut app);
+    let out = root.join(&job.output);
+    if let Some(dir) = out.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| PromptError::io("create", dir, e))?;
+    }
+    std::fs::write(&out, &prompt).map_err(|e| PromptError::io("write", &out, e))?;
+    Ok((out, selected, prompt.len()))
+}
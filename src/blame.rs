@@ -0,0 +1,128 @@
+// `git blame` for a snippet's lines, condensed to one line per run of lines from the same
+// commit: "12-18  a1b2c3d4  Jane Doe  2024-03-02  Handle empty input". Added after a
+// picked symbol on request, so a prompt asking why code is the way it is carries who
+// changed it, when, and the commit's summary.
+
+use crate::code_state::git;
+use crate::error::Result;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Lines `first..=last` last changed by one commit.
+pub struct Hunk {
+    pub first: usize,
+    pub last: usize,
+    /// Abbreviated; None for lines not committed yet.
+    pub commit: Option<String>,
+    pub author: String,
+    pub date: String,
+    pub summary: String,
+}
+
+#[derive(Default, Clone)]
+struct CommitInfo {
+    author: String,
+    date: String,
+    summary: String,
+}
+
+/// Blame of lines `first..=last` (1-based) of `rel_path`, run from `base`.
+pub fn blame(base: &Path, rel_path: &str, first: usize, last: usize) -> Result<Vec<Hunk>> {
+    let range = format!("{},{}", first, last);
+    let porcelain = git(
+        base,
+        &["blame", "--porcelain", "-L", &range, "--", rel_path],
+    )?;
+    Ok(parse(&porcelain))
+}
+
+/// Reads `--porcelain` output: a "<sha> <orig> <final> [<count>]" line per source line,
+/// followed by the commit's headers the first time it appears, then the tab-led line.
+fn parse(porcelain: &str) -> Vec<Hunk> {
+    let mut commits: HashMap<String, CommitInfo> = HashMap::new();
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    let mut current: Option<String> = None;
+    for line in porcelain.lines() {
+        if line.starts_with('\t') {
+            current = None;
+            continue;
+        }
+        if current.is_none() {
+            let mut parts = line.split_whitespace();
+            let (Some(sha), Some(_), Some(final_line)) = (parts.next(), parts.next(), parts.next())
+            else {
+                continue;
+            };
+            let Ok(final_line) = final_line.parse() else {
+                continue;
+            };
+            lines.push((final_line, sha.to_string()));
+            commits.entry(sha.to_string()).or_default();
+            current = Some(sha.to_string());
+            continue;
+        }
+        let Some(info) = current.as_ref().and_then(|sha| commits.get_mut(sha)) else {
+            continue;
+        };
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        match key {
+            "author" => info.author = value.to_string(),
+            "summary" => info.summary = value.to_string(),
+            "author-time" => {
+                info.date = value
+                    .parse()
+                    .ok()
+                    .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+                    .map(|t| t.format("%Y-%m-%d").to_string())
+                    .unwrap_or_default();
+            }
+            _ => {}
+        }
+    }
+
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let mut last_sha = String::new();
+    for (line, sha) in lines {
+        if let Some(hunk) = hunks
+            .last_mut()
+            .filter(|h| h.last + 1 == line && sha == last_sha)
+        {
+            hunk.last = line;
+            continue;
+        }
+        let info = commits.get(&sha).cloned().unwrap_or_default();
+        let uncommitted = sha.bytes().all(|b| b == b'0');
+        hunks.push(Hunk {
+            first: line,
+            last: line,
+            commit: (!uncommitted).then(|| sha[..sha.len().min(8)].to_string()),
+            author: info.author,
+            date: info.date,
+            summary: info.summary,
+        });
+        last_sha = sha;
+    }
+    hunks
+}
+
+/// One line per hunk.
+pub fn render(hunks: &[Hunk]) -> String {
+    let mut out = String::new();
+    for hunk in hunks {
+        let lines = if hunk.first == hunk.last {
+            hunk.first.to_string()
+        } else {
+            format!("{}-{}", hunk.first, hunk.last)
+        };
+        let _ = match &hunk.commit {
+            Some(commit) => writeln!(
+                out,
+                "{}  {}  {}  {}  {}",
+                lines, commit, hunk.author, hunk.date, hunk.summary
+            ),
+            None => writeln!(out, "{}  (not committed yet)", lines),
+        };
+    }
+    out
+}
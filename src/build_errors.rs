@@ -0,0 +1,293 @@
+// Compact summary of `cargo build` / `cargo test` output for fix-my-build prompts: each
+// rustc diagnostic (level, code, message, location and the source excerpt rustc printed)
+// and each panicking test, without the compile progress, backtrace hints and repeats
+// that make up most of the raw output.
+
+use crate::escaping::Escaping;
+use crate::labels::PromptLabels;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+
+/// Excerpt lines kept per diagnostic; rustc's own excerpts rarely run longer.
+const MAX_SNIPPET_LINES: usize = 16;
+
+static ANSI: Lazy<Regex> = Lazy::new(|| Regex::new(r"\x1b\[[0-9;]*m").unwrap());
+static HEADER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(error|warning)(?:\[([\w:]+)\])?: (.+)$").unwrap());
+static LOCATION: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*--> (.+?):(\d+):(\d+)\s*$").unwrap());
+/// Excerpt and note lines: `12 |     code`, `   |  ^^^`, `   = note: ...`, `help: ...`.
+static EXCERPT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\s*(?:(?:\d+\s*)?(?:\||=|\.\.\.)|help:|note:)").unwrap());
+/// `thread 'tests::x' panicked at src/lib.rs:10:5:` (message on the following lines);
+/// newer toolchains put the thread id after the name: `thread 'tests::x' (1234) panicked`.
+static PANIC: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^thread '([^']+)'(?: \(\d+\))? panicked at ([^'].*?):(\d+):(\d+):?$").unwrap()
+});
+/// Before Rust 1.73: `thread 'x' panicked at 'message', src/lib.rs:10:5`.
+static PANIC_OLD: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^thread '([^']+)'(?: \(\d+\))? panicked at '(.*)', (.+?):(\d+):(\d+)$").unwrap()
+});
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Level {
+    Error,
+    Warning,
+    /// A panicking test; `code` holds the test name.
+    TestFailure,
+}
+
+impl Level {
+    fn name(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warning => "warning",
+            Level::TestFailure => "test_failure",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub level: Level,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub snippet: String,
+}
+
+/// Cargo's own status lines, which restate rather than add to the diagnostics.
+fn is_summary(message: &str) -> bool {
+    message.starts_with("could not compile")
+        || message.starts_with("aborting due to")
+        || message.starts_with("test failed")
+        || message.starts_with("build failed")
+        || (message.contains(" generated ") && message.contains("warning"))
+}
+
+/// Diagnostics in order of appearance, without repeats (a lib and its test target often
+/// report the same error twice).
+pub fn parse(output: &str) -> Vec<Diagnostic> {
+    let output = ANSI.replace_all(output, "");
+    let lines: Vec<&str> = output.lines().collect();
+    let mut out: Vec<Diagnostic> = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        i += 1;
+        let diagnostic = if let Some(c) = HEADER.captures(line) {
+            let message = c[3].to_string();
+            if is_summary(&message) {
+                continue;
+            }
+            let mut d = Diagnostic {
+                level: if &c[1] == "error" {
+                    Level::Error
+                } else {
+                    Level::Warning
+                },
+                code: c.get(2).map(|m| m.as_str().to_string()),
+                message,
+                file: None,
+                line: None,
+                snippet: String::new(),
+            };
+            let mut excerpt = Vec::new();
+            while i < lines.len() && !lines[i].trim().is_empty() && !HEADER.is_match(lines[i]) {
+                if let Some(loc) = LOCATION.captures(lines[i]).filter(|_| d.file.is_none()) {
+                    d.file = Some(loc[1].replace('\\', "/"));
+                    d.line = loc[2].parse().ok();
+                } else if EXCERPT.is_match(lines[i]) && excerpt.len() < MAX_SNIPPET_LINES {
+                    excerpt.push(lines[i]);
+                }
+                i += 1;
+            }
+            // Drop the bare `|` gutter lines that only frame the excerpt
+            while excerpt.last().is_some_and(|l| l.trim() == "|") {
+                excerpt.pop();
+            }
+            let start = excerpt.iter().take_while(|l| l.trim() == "|").count();
+            d.snippet = excerpt[start..].join("\n");
+            d
+        } else if let Some(c) = PANIC.captures(line) {
+            let mut message = Vec::new();
+            while i < lines.len()
+                && !lines[i].trim().is_empty()
+                && !lines[i].starts_with("note: ")
+                && !lines[i].starts_with("stack backtrace:")
+            {
+                message.push(lines[i]);
+                i += 1;
+            }
+            Diagnostic {
+                level: Level::TestFailure,
+                code: Some(c[1].to_string()),
+                message: message.join("\n"),
+                file: Some(c[2].replace('\\', "/")),
+                line: c[3].parse().ok(),
+                snippet: String::new(),
+            }
+        } else if let Some(c) = PANIC_OLD.captures(line) {
+            Diagnostic {
+                level: Level::TestFailure,
+                code: Some(c[1].to_string()),
+                message: c[2].to_string(),
+                file: Some(c[3].replace('\\', "/")),
+                line: c[4].parse().ok(),
+                snippet: String::new(),
+            }
+        } else {
+            continue;
+        };
+        if !out.contains(&diagnostic) {
+            out.push(diagnostic);
+        }
+    }
+    out
+}
+
+/// What goes into the prompt: errors and test failures, or the warnings when there is
+/// nothing else; the second value counts warnings left out.
+pub fn relevant(diagnostics: Vec<Diagnostic>) -> (Vec<Diagnostic>, usize) {
+    let warnings = diagnostics
+        .iter()
+        .filter(|d| d.level == Level::Warning)
+        .count();
+    if warnings == diagnostics.len() {
+        return (diagnostics, 0);
+    }
+    let kept = diagnostics
+        .into_iter()
+        .filter(|d| d.level != Level::Warning)
+        .collect();
+    (kept, warnings)
+}
+
+/// `<errors>` (or `tag`, with extra `attrs`) holding one `<error>` per diagnostic;
+/// attributes carry level, code and location, the body the message and excerpt.
+pub fn render(
+    tag: &str,
+    attrs: &str,
+    diagnostics: &[Diagnostic],
+    warnings_omitted: usize,
+    labels: &PromptLabels,
+    esc: Escaping,
+) -> String {
+    let mut xml = format!("<{}{} count=\"{}\"", tag, attrs, diagnostics.len());
+    if warnings_omitted > 0 {
+        xml.push_str(&format!(" warnings_omitted=\"{}\"", warnings_omitted));
+    }
+    xml.push_str(">\n");
+    for d in diagnostics {
+        xml.push_str(&format!("<{} level=\"{}\"", labels.error, d.level.name()));
+        if let Some(code) = &d.code {
+            xml.push_str(&format!(" code=\"{}\"", crate::app::escape_xml_attr(code)));
+        }
+        if let Some(file) = &d.file {
+            xml.push_str(&format!(" path=\"{}\"", crate::app::escape_xml_attr(file)));
+        }
+        if let Some(line) = d.line {
+            xml.push_str(&format!(" line=\"{}\"", line));
+        }
+        xml.push('>');
+        let body = if d.snippet.is_empty() {
+            d.message.clone()
+        } else {
+            format!("{}\n{}", d.message, d.snippet)
+        };
+        xml.push_str(&esc.wrap(&body));
+        xml.push_str(&format!("</{}>\n", labels.error));
+    }
+    xml.push_str(&format!("</{}>\n", tag));
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUILD: &str = "\
+   Compiling demo v0.1.0 (/work/demo)
+\x1b[0m\x1b[1m\x1b[38;5;9merror[E0425]\x1b[0m: cannot find value `x` in this scope
+ --> src\\main.rs:2:13
+  |
+2 |     let y = x + 1;
+  |             ^ not found in this scope
+
+warning: unused variable: `y`
+ --> src/main.rs:2:9
+  |
+2 |     let y = x + 1;
+  |         ^ help: prefix it with an underscore: `_y`
+  |
+  = note: `#[warn(unused_variables)]` on by default
+
+error[E0425]: cannot find value `x` in this scope
+ --> src\\main.rs:2:13
+  |
+2 |     let y = x + 1;
+  |             ^ not found in this scope
+
+warning: `demo` (bin \"demo\") generated 1 warning
+error: could not compile `demo` (bin \"demo\") due to 1 previous error
+";
+
+    #[test]
+    fn rustc_errors_keep_code_location_and_excerpt_once() {
+        let diagnostics = parse(BUILD);
+        assert_eq!(diagnostics.len(), 2);
+        let error = &diagnostics[0];
+        assert!(error.level == Level::Error);
+        assert_eq!(error.code.as_deref(), Some("E0425"));
+        assert_eq!(error.message, "cannot find value `x` in this scope");
+        assert_eq!(error.file.as_deref(), Some("src/main.rs"));
+        assert_eq!(error.line, Some(2));
+        assert_eq!(
+            error.snippet,
+            "2 |     let y = x + 1;\n  |             ^ not found in this scope"
+        );
+        assert!(diagnostics[1]
+            .snippet
+            .ends_with("= note: `#[warn(unused_variables)]` on by default"));
+    }
+
+    #[test]
+    fn warnings_are_left_out_next_to_errors() {
+        let (kept, omitted) = relevant(parse(BUILD));
+        assert_eq!(kept.len(), 1);
+        assert_eq!(omitted, 1);
+        let (kept, omitted) = relevant(parse(&BUILD.replace("error[E0425]", "warning")));
+        assert_eq!((kept.len(), omitted), (2, 0));
+    }
+
+    #[test]
+    fn test_panics_in_both_formats() {
+        let output = "\
+---- tests::adds stdout ----
+thread 'tests::adds' (23793) panicked at src/lib.rs:10:5:
+assertion `left == right` failed
+  left: 3
+ right: 4
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+
+thread 'old' panicked at 'boom', src/old.rs:7:9
+";
+        let diagnostics = parse(output);
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics.iter().all(|d| d.level == Level::TestFailure));
+        assert_eq!(diagnostics[0].code.as_deref(), Some("tests::adds"));
+        assert_eq!(
+            diagnostics[0].message,
+            "assertion `left == right` failed\n  left: 3\n right: 4"
+        );
+        assert_eq!(diagnostics[0].line, Some(10));
+        assert_eq!(diagnostics[1].message, "boom");
+        assert_eq!(diagnostics[1].file.as_deref(), Some("src/old.rs"));
+    }
+}
@@ -0,0 +1,389 @@
+// "Import code from clipboard": path-annotated blocks (or a unified diff) from a model
+// reply, diffed against the files on disk and written only after the user confirms.
+
+use crate::diff_view::{FileDiff, Layout};
+use crate::responses::{parse_blocks, project_path};
+use std::path::Path;
+
+pub struct ImportEntry {
+    pub rel_path: String,
+    /// False when the change would create the file.
+    pub exists: bool,
+    pub diff: FileDiff,
+    pub selected: bool,
+}
+
+impl ImportEntry {
+    fn new(base: &Path, rel: &str, content: &str) -> Option<Self> {
+        let path = project_path(base, rel)?;
+        let existing = std::fs::read_to_string(path).ok();
+        let diff = FileDiff::new(existing.as_deref().unwrap_or(""), content);
+        Some(ImportEntry {
+            rel_path: rel.replace('\\', "/"),
+            exists: existing.is_some(),
+            selected: !diff.hunks.is_empty(),
+            diff,
+        })
+    }
+
+    pub fn is_unchanged(&self) -> bool {
+        self.diff.hunks.is_empty()
+    }
+
+    pub fn status(&self) -> String {
+        let (added, removed) = self.diff.added_removed();
+        if !self.exists {
+            format!("new file, {} lines", added)
+        } else if self.is_unchanged() {
+            "unchanged".to_string()
+        } else {
+            format!(
+                "+{} −{}, {} of {} hunks accepted",
+                added,
+                removed,
+                self.diff.accepted(),
+                self.diff.hunks.len()
+            )
+        }
+    }
+}
+
+pub struct CodeImport {
+    pub open: bool,
+    pub entries: Vec<ImportEntry>,
+    /// Blocks left out: no path annotation, a path outside the project, a hunk that
+    /// doesn't match the file, or superseded by a later block for the same file.
+    pub skipped: usize,
+    pub layout: Layout,
+}
+
+impl Default for CodeImport {
+    fn default() -> Self {
+        Self {
+            open: false,
+            entries: Vec::new(),
+            skipped: 0,
+            layout: Layout::Unified,
+        }
+    }
+}
+
+impl CodeImport {
+    fn push(&mut self, entry: Option<ImportEntry>) {
+        let Some(entry) = entry else {
+            self.skipped += 1;
+            return;
+        };
+        match self
+            .entries
+            .iter_mut()
+            .find(|e| e.rel_path == entry.rel_path)
+        {
+            Some(prev) => {
+                *prev = entry;
+                self.skipped += 1;
+            }
+            None => self.entries.push(entry),
+        }
+    }
+
+    /// One entry per path; when a reply repeats a file, its last block wins. Diff blocks
+    /// are applied to the files they name.
+    pub fn prepare(base: &Path, text: &str) -> Self {
+        let mut import = CodeImport {
+            open: true,
+            ..Default::default()
+        };
+        for block in parse_blocks(text) {
+            if block.is_diff() {
+                import.add_patch(base, &block.body);
+                continue;
+            }
+            let rel = block
+                .path_hint
+                .as_deref()
+                .map(|p| p.trim_start_matches("./"));
+            import.push(rel.and_then(|rel| ImportEntry::new(base, rel, &block.body)));
+        }
+        import
+    }
+
+    /// Entries for every file a unified diff touches. Deletions are left to `git apply`.
+    pub fn add_patch(&mut self, base: &Path, patch: &str) {
+        for file in parse_patch(patch) {
+            let Some(rel) = file.path else {
+                self.skipped += 1;
+                continue;
+            };
+            let old = match project_path(base, &rel) {
+                Some(path) if !file.created => std::fs::read_to_string(path).unwrap_or_default(),
+                _ => String::new(),
+            };
+            let entry =
+                apply_hunks(&old, &file.hunks).and_then(|new| ImportEntry::new(base, &rel, &new));
+            self.push(entry);
+        }
+    }
+}
+
+struct PatchHunk {
+    /// 1-based start line from the `@@` header, used to pick between repeated matches.
+    old_start: usize,
+    old: Vec<String>,
+    new: Vec<String>,
+    /// `\ No newline at end of file` after the hunk's last old or new line.
+    old_no_newline: bool,
+    new_no_newline: bool,
+}
+
+struct PatchFile {
+    /// None for deletions.
+    path: Option<String>,
+    created: bool,
+    hunks: Vec<PatchHunk>,
+}
+
+fn strip_prefix(path: &str) -> &str {
+    let path = path.split('\t').next().unwrap_or(path).trim();
+    path.strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path)
+}
+
+/// Old start and the old and new line counts of a hunk header (the part after `@@ -`);
+/// an omitted count is 1.
+fn hunk_header(header: &str) -> Option<(usize, usize, usize)> {
+    let (ranges, _) = header.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    let range = |r: &str| -> Option<(usize, usize)> {
+        match r.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((r.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_count) = range(old)?;
+    let (_, new_count) = range(new)?;
+    Some((old_start, old_count, new_count))
+}
+
+/// Splits a unified diff into files and hunks. A hunk's body is as many lines as its
+/// header counts, so a removed `-- comment` (`--- comment`) or an added `++ x` isn't taken
+/// for a file header. Models sometimes leave the numbers out (`@@ ... @@`); those hunks
+/// run until the next header line instead.
+fn parse_patch(patch: &str) -> Vec<PatchFile> {
+    let mut files: Vec<PatchFile> = Vec::new();
+    let mut created = false;
+    // Old and new lines still to come in the current hunk, when its header gave counts
+    let mut remaining: Option<(usize, usize)> = None;
+    let mut in_hunk = false;
+    let mut last = ' ';
+    for line in patch.lines() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let counting = remaining.is_some_and(|(old, new)| old + new > 0);
+        if !counting && !line.starts_with('\\') {
+            if let Some(old) = line.strip_prefix("--- ") {
+                created = old.trim() == "/dev/null";
+                in_hunk = false;
+                continue;
+            } else if let Some(new) = line.strip_prefix("+++ ") {
+                let path = (new.trim() != "/dev/null").then(|| strip_prefix(new).to_string());
+                files.push(PatchFile {
+                    path,
+                    created,
+                    hunks: Vec::new(),
+                });
+                in_hunk = false;
+                continue;
+            } else if let Some(header) = line.strip_prefix("@@") {
+                let header = header.trim_start().strip_prefix('-').unwrap_or("");
+                let counts = hunk_header(header);
+                let old_start = counts.map(|(start, _, _)| start).unwrap_or_else(|| {
+                    header
+                        .split([',', ' '])
+                        .next()
+                        .and_then(|n| n.parse().ok())
+                        .unwrap_or(1)
+                });
+                remaining = counts.map(|(_, old, new)| (old, new));
+                in_hunk = match files.last_mut() {
+                    Some(file) => {
+                        file.hunks.push(PatchHunk {
+                            old_start,
+                            old: Vec::new(),
+                            new: Vec::new(),
+                            old_no_newline: false,
+                            new_no_newline: false,
+                        });
+                        true
+                    }
+                    None => false,
+                };
+                continue;
+            } else if remaining.is_some() {
+                // A counted hunk is complete; anything else until the next header is noise
+                in_hunk = false;
+            }
+        }
+        if !in_hunk {
+            continue;
+        }
+        let Some(hunk) = files.last_mut().and_then(|f| f.hunks.last_mut()) else {
+            continue;
+        };
+        let kind = line.chars().next().unwrap_or(' ');
+        let (old_side, new_side) = match kind {
+            '-' => (true, false),
+            '+' => (false, true),
+            // Models often drop the leading space on blank context lines
+            ' ' => (true, true),
+            '\\' => {
+                hunk.old_no_newline |= last != '+';
+                hunk.new_no_newline |= last != '-';
+                continue;
+            }
+            _ => continue,
+        };
+        let body = line.get(1..).unwrap_or("");
+        if old_side {
+            hunk.old.push(body.to_string());
+        }
+        if new_side {
+            hunk.new.push(body.to_string());
+        }
+        if let Some((old_left, new_left)) = remaining.as_mut() {
+            *old_left = old_left.saturating_sub(old_side as usize);
+            *new_left = new_left.saturating_sub(new_side as usize);
+        }
+        last = kind;
+    }
+    files
+}
+
+/// Applies hunks by matching their old lines, nearest the stated position first, so
+/// slightly-off line numbers in model output still apply. The file keeps its line endings
+/// and its final newline (or lack of one) unless a hunk's `\ No newline` marker changes
+/// it. None if any hunk doesn't match.
+fn apply_hunks(old: &str, hunks: &[PatchHunk]) -> Option<String> {
+    let eol = if old.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut final_newline = old.is_empty() || old.ends_with('\n');
+    let mut lines: Vec<String> = old.lines().map(str::to_string).collect();
+    let mut floor = 0;
+    let mut shift: isize = 0;
+    for hunk in hunks {
+        let expected = (hunk.old_start.saturating_sub(1) as isize + shift).max(0) as usize;
+        let at = if hunk.old.is_empty() {
+            expected.clamp(floor, lines.len())
+        } else {
+            (floor..=lines.len().checked_sub(hunk.old.len())?)
+                .filter(|&i| lines[i..i + hunk.old.len()] == hunk.old[..])
+                .min_by_key(|&i| i.abs_diff(expected))?
+        };
+        lines.splice(at..at + hunk.old.len(), hunk.new.iter().cloned());
+        floor = at + hunk.new.len();
+        shift += hunk.new.len() as isize - hunk.old.len() as isize;
+        if hunk.new_no_newline {
+            final_newline = false;
+        } else if hunk.old_no_newline {
+            final_newline = true;
+        }
+    }
+    let mut out = lines.join(eol);
+    if !out.is_empty() && final_newline {
+        out.push_str(eol);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(old: &str, patch: &str) -> Option<String> {
+        let files = parse_patch(patch);
+        assert_eq!(files.len(), 1, "{} files in {:?}", files.len(), patch);
+        apply_hunks(old, &files[0].hunks)
+    }
+
+    #[test]
+    fn removed_and_added_lines_that_look_like_headers_stay_in_the_hunk() {
+        let patch = "\
+--- a/q.sql
++++ b/q.sql
+@@ -1,3 +1,3 @@
+ select 1;
+--- old comment
++++ new counter
+ select 2;
+";
+        let files = parse_patch(patch);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path.as_deref(), Some("q.sql"));
+        assert_eq!(
+            apply("select 1;\n-- old comment\nselect 2;\n", patch).as_deref(),
+            Some("select 1;\n++ new counter\nselect 2;\n")
+        );
+    }
+
+    #[test]
+    fn next_file_starts_after_the_counted_lines() {
+        let patch = "\
+--- a/one.txt
++++ b/one.txt
+@@ -1 +1 @@
+-a
++b
+--- a/two.txt
++++ b/two.txt
+@@ -1 +1 @@
+-c
++d
+";
+        let files = parse_patch(patch);
+        let paths: Vec<_> = files.iter().map(|f| f.path.as_deref()).collect();
+        assert_eq!(paths, [Some("one.txt"), Some("two.txt")]);
+        assert_eq!(files[1].hunks[0].new, ["d"]);
+    }
+
+    #[test]
+    fn hunks_without_counts_run_to_the_next_header() {
+        let patch = "\
+--- a/f.rs
++++ b/f.rs
+@@ ... @@
+ fn a() {}
+-fn b() {}
++fn c() {}
+";
+        assert_eq!(
+            apply("fn a() {}\nfn b() {}\n", patch).as_deref(),
+            Some("fn a() {}\nfn c() {}\n")
+        );
+    }
+
+    #[test]
+    fn crlf_files_keep_their_line_endings() {
+        let patch = "--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n one\n-two\n+2\n";
+        assert_eq!(
+            apply("one\r\ntwo\r\n", patch).as_deref(),
+            Some("one\r\n2\r\n")
+        );
+        // A patch copied with CRLF endings still matches
+        let patch = patch.replace('\n', "\r\n");
+        assert_eq!(apply("one\ntwo\n", &patch).as_deref(), Some("one\n2\n"));
+    }
+
+    #[test]
+    fn missing_final_newline_is_kept_unless_the_patch_changes_it() {
+        let patch = "--- a/f\n+++ b/f\n@@ -1 +1 @@\n-a\n+b\n";
+        assert_eq!(
+            apply("x\na", "--- a/f\n+++ b/f\n@@ -2 +2 @@\n-a\n+b\n").as_deref(),
+            Some("x\nb")
+        );
+        assert_eq!(apply("a\n", patch).as_deref(), Some("b\n"));
+
+        let adds_newline = "--- a/f\n+++ b/f\n@@ -1 +1 @@\n-a\n\\ No newline at end of file\n+a\n";
+        assert_eq!(apply("a", adds_newline).as_deref(), Some("a\n"));
+        let drops_newline = "--- a/f\n+++ b/f\n@@ -1 +1 @@\n-a\n+a\n\\ No newline at end of file\n";
+        assert_eq!(apply("a\n", drops_newline).as_deref(), Some("a"));
+    }
+}
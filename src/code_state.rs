@@ -0,0 +1,105 @@
+// Reproducibility metadata for a prompt: a hash over every included file's path and
+// content plus the git commit (and whether the tree had uncommitted changes), recorded in
+// the prompt and in history so a past answer can be tied to the exact code it saw. The
+// `git` runner here is shared by the other modes that ask git about the project.
+
+use crate::error::{PromptError, Result};
+use std::path::Path;
+use std::process::Command;
+
+/// Stdout of `git <args>` run in `base`; a failing git is an error with its stderr.
+pub fn git(base: &Path, args: &[&str]) -> Result<String> {
+    let out = Command::new("git")
+        .args(args)
+        .current_dir(base)
+        .output()
+        .map_err(|source| PromptError::Command {
+            command: format!("git {}", args.join(" ")),
+            source,
+        })?;
+    if !out.status.success() {
+        return Err(PromptError::Git(
+            String::from_utf8_lossy(&out.stderr).trim().to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+/// FNV-1a 64; unlike std's hashers its output is stable across builds and machines.
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl StableHasher {
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub fn finish_hex(&self) -> String {
+        format!("{:016x}", self.0)
+    }
+}
+
+#[derive(Clone)]
+pub struct CodeState {
+    pub hash: String,
+    pub files: usize,
+    /// None outside a git checkout.
+    pub commit: Option<String>,
+    pub dirty: bool,
+}
+
+impl CodeState {
+    /// `files` are ('/'-separated rel_path, content) pairs in any order.
+    pub fn capture<'a>(base: &Path, files: impl IntoIterator<Item = (String, &'a str)>) -> Self {
+        let mut files: Vec<(String, &str)> = files.into_iter().collect();
+        files.sort();
+        let mut hasher = StableHasher::default();
+        for (path, content) in &files {
+            // Length-prefixed so ("ab", "c") and ("a", "bc") differ
+            for part in [path.as_str(), content] {
+                hasher.write(&(part.len() as u64).to_le_bytes());
+                hasher.write(part.as_bytes());
+            }
+        }
+        let ask = |args: &[&str]| git(base, args).ok().map(|out| out.trim().to_string());
+        let commit = ask(&["rev-parse", "HEAD"]).filter(|c| !c.is_empty());
+        let dirty = commit.is_some()
+            && ask(&["status", "--porcelain", "--untracked-files=no"])
+                .is_some_and(|s| !s.is_empty());
+        Self {
+            hash: hasher.finish_hex(),
+            files: files.len(),
+            commit,
+            dirty,
+        }
+    }
+
+    /// "3f9c…e1 over 12 files at commit 1a2b3c4 (uncommitted changes)"
+    pub fn describe(&self) -> String {
+        let mut text = format!("{} over {} files", self.hash, self.files);
+        if let Some(commit) = &self.commit {
+            text.push_str(&format!(" at commit {}", &commit[..commit.len().min(12)]));
+            if self.dirty {
+                text.push_str(" (uncommitted changes)");
+            }
+        }
+        text
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "hash": self.hash,
+            "files": self.files,
+            "commit": self.commit,
+            "dirty": self.dirty,
+        })
+    }
+}
@@ -1,41 +1,361 @@
 // ... a couple lines above
+use crate::error::{PromptError, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
 use std::os::unix::process::ExitStatusExt;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often a running command checks for cancellation and its timeout.
+pub const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Runs kept as output tabs; the oldest is dropped first.
+const MAX_RUNS: usize = 8;
+
+/// What `run_command` hands back.
+#[derive(Default)]
+pub struct CommandOutput {
+    /// stdout then stderr, head/tail-truncated.
+    pub text: String,
+    /// None when the process was killed (timeout) or ended by a signal.
+    pub exit_code: Option<i32>,
+    pub duration: Duration,
+    /// Lines were cut between the head and the tail.
+    pub truncated: bool,
+    pub timed_out: bool,
+}
+
+/// One finished command, shown as an output tab.
+pub struct CommandRun {
+    /// Shown on the tab; stays stable as older runs are dropped.
+    pub number: usize,
+    pub command: String,
+    pub working_dir: String,
+    pub output: String,
+    /// Tokens in `output`, kept up to date with edits.
+    pub tokens: usize,
+    pub exit_code: Option<i32>,
+    pub duration: Duration,
+    pub truncated: bool,
+    pub timed_out: bool,
+    /// Scrollback captured from the interactive shell rather than a run; it has no status.
+    pub snapshot: bool,
+    /// Whether this run's output goes into the prompt.
+    pub include: bool,
+}
+
+impl CommandRun {
+    /// "exit 101", "timed out" or "killed".
+    pub fn status(&self) -> String {
+        if self.snapshot {
+            return "shell snapshot".into();
+        }
+        match self.exit_code {
+            Some(code) => format!("exit {}", code),
+            None if self.timed_out => "timed out".into(),
+            None => "killed".into(),
+        }
+    }
+}
+
+/// A command and its output as the prompt presents them; the status fields are unset for
+/// output that was typed or pasted rather than run.
+pub struct RunView<'a> {
+    pub command: &'a str,
+    pub working_dir: &'a str,
+    pub output: &'a str,
+    pub exit_code: Option<i32>,
+    pub duration: Option<Duration>,
+    pub truncated: bool,
+    pub timed_out: bool,
+}
+
+/// What a terminal command reads on stdin.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum StdinMode {
+    /// Nothing is piped in.
+    #[default]
+    None,
+    /// The text typed under the command.
+    Text,
+    /// The full generated prompt, built when the command starts.
+    Prompt,
+}
+
+impl StdinMode {
+    pub const ALL: [StdinMode; 3] = [StdinMode::None, StdinMode::Text, StdinMode::Prompt];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            StdinMode::None => "(none)",
+            StdinMode::Text => "Text",
+            StdinMode::Prompt => "Generated prompt",
+        }
+    }
+}
+
+/// A command as remembered in `.prompt/terminal_history.json`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub command: String,
+    /// See `Terminal::working_dir`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub working_dir: String,
+    /// Re-run automatically every this many minutes; 0 = off.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub every_minutes: u32,
+    /// Re-run automatically when a project file changes.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub on_change: bool,
+    /// Start of the latest run this session, manual or automatic.
+    #[serde(skip)]
+    pub last_run: Option<DateTime<Local>>,
+    /// Set when the schedule is set or resumed this session. A schedule read back from the
+    /// history file stays paused until then: anyone who can write to the project could
+    /// have put it there.
+    #[serde(skip)]
+    pub armed: bool,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+impl HistoryEntry {
+    pub fn new(command: String, working_dir: String) -> Self {
+        Self {
+            command,
+            working_dir,
+            every_minutes: 0,
+            on_change: false,
+            last_run: None,
+            armed: false,
+        }
+    }
+
+    pub fn same_command(&self, other: &HistoryEntry) -> bool {
+        self.command == other.command && self.working_dir == other.working_dir
+    }
+
+    pub fn is_scheduled(&self) -> bool {
+        self.every_minutes > 0 || self.on_change
+    }
+
+    /// Scheduled, and armed this session.
+    pub fn runs_automatically(&self) -> bool {
+        self.armed && self.is_scheduled()
+    }
+
+    /// A scheduled entry that hasn't run this session is due right away; after that when
+    /// its interval has passed or a project file changed since it started. Never while the
+    /// schedule isn't armed.
+    pub fn is_due(&self, now: DateTime<Local>, newest_change: Option<SystemTime>) -> bool {
+        if !self.armed {
+            return false;
+        }
+        let Some(last) = self.last_run else {
+            return self.is_scheduled();
+        };
+        let interval = self.every_minutes > 0
+            && now - last >= chrono::Duration::minutes(i64::from(self.every_minutes));
+        let changed = self.on_change && newest_change.is_some_and(|t| t > SystemTime::from(last));
+        interval || changed
+    }
+}
+
+/// `working_dir` (see `Terminal::working_dir`) resolved against the project folder.
+pub fn resolve_dir(base: &Path, working_dir: &str) -> PathBuf {
+    match working_dir.trim() {
+        "" => base.to_path_buf(),
+        dir => base.join(dir),
+    }
+}
 
 pub struct Terminal {
     pub terminal_command: String,
+    /// Where commands run: relative to the project folder or absolute; empty = the project
+    /// folder itself.
+    pub working_dir: String,
     pub head_lines: usize,
     pub tail_lines: usize,
     pub timeout_secs: u64,
+    pub stdin_mode: StdinMode,
+    /// Piped in when `stdin_mode` is `Text`.
+    pub stdin_text: String,
     pub terminal_output: String,
-    pub terminal_update_rx: mpsc::Receiver<String>,
-    pub terminal_update_tx: mpsc::Sender<String>,
-    pub history: Vec<String>,
+    pub history: Vec<HistoryEntry>,
     pub max_history: usize,
-    pub is_running: bool,
+    /// The command currently running as a job, if any.
+    pub job: Option<crate::jobs::JobId>,
+    /// Recent runs, oldest first; `terminal_output` shows (and edits) the active one.
+    pub runs: Vec<CommandRun>,
+    pub active_run: Option<usize>,
+    next_run: usize,
+    /// Newest modification time among the project files, for "on change" schedules.
+    pub newest_change: Option<SystemTime>,
+    /// The file_watch.rs job feeding `newest_change`, while an "on change" schedule runs.
+    pub watch: Option<crate::jobs::JobId>,
 }
 
 impl Default for Terminal {
     fn default() -> Self {
-        let (term_tx, term_rx) = mpsc::channel();
         Self {
             terminal_command: String::new(),
+            working_dir: String::new(),
             head_lines: 1000,
             tail_lines: 1000,
             timeout_secs: 25,
+            stdin_mode: StdinMode::None,
+            stdin_text: String::new(),
             terminal_output: String::new(),
-            terminal_update_rx: term_rx,
-            terminal_update_tx: term_tx,
             history: Vec::new(),
             max_history: 50,
-            is_running: false,
+            job: None,
+            runs: Vec::new(),
+            active_run: None,
+            next_run: 1,
+            newest_change: None,
+            watch: None,
         }
     }
 }
 
+impl Terminal {
+    /// Adds a finished run as the active tab. It goes into the prompt, replacing an earlier
+    /// run of the same command in the same directory.
+    pub fn push_run(&mut self, command: String, working_dir: String, output: CommandOutput) {
+        for run in &mut self.runs {
+            if run.command == command && run.working_dir == working_dir {
+                run.include = false;
+            }
+        }
+        self.runs.push(CommandRun {
+            number: self.next_run,
+            command,
+            working_dir,
+            tokens: crate::token_count::count_tokens(&output.text),
+            output: output.text,
+            exit_code: output.exit_code,
+            duration: output.duration,
+            truncated: output.truncated,
+            timed_out: output.timed_out,
+            snapshot: false,
+            include: true,
+        });
+        self.next_run += 1;
+        if self.runs.len() > MAX_RUNS {
+            self.runs.remove(0);
+        }
+        self.select_run(self.runs.len() - 1);
+    }
+
+    /// Adds scrollback from the interactive shell as the active tab, in place of any earlier
+    /// snapshot.
+    pub fn push_snapshot(&mut self, working_dir: String, text: String) {
+        let output = CommandOutput {
+            text,
+            ..Default::default()
+        };
+        self.push_run(
+            crate::pty_session::SNAPSHOT_COMMAND.into(),
+            working_dir,
+            output,
+        );
+        if let Some(run) = self.runs.last_mut() {
+            run.snapshot = true;
+        }
+    }
+
+    pub fn select_run(&mut self, index: usize) {
+        if let Some(run) = self.runs.get(index) {
+            self.active_run = Some(index);
+            self.terminal_output = run.output.clone();
+        }
+    }
+
+    pub fn close_run(&mut self, index: usize) {
+        if index >= self.runs.len() {
+            return;
+        }
+        self.runs.remove(index);
+        match self.active_run {
+            Some(active) if active == index => {
+                self.active_run = None;
+                if self.runs.is_empty() {
+                    self.terminal_output.clear();
+                } else {
+                    self.select_run(self.runs.len() - 1);
+                }
+            }
+            Some(active) if active > index => self.active_run = Some(active - 1),
+            _ => {}
+        }
+    }
+
+    /// Copies edits made in the output box back into the active run.
+    pub fn store_edit(&mut self) {
+        if let Some(run) = self.active_run.and_then(|i| self.runs.get_mut(i)) {
+            run.output = self.terminal_output.clone();
+            run.tokens = crate::token_count::count_tokens(&run.output);
+        }
+    }
+
+    /// The runs ticked for the prompt; before anything has run, the command and output
+    /// fields as typed or pasted.
+    pub fn prompt_runs(&self) -> Vec<RunView<'_>> {
+        if self.runs.is_empty() {
+            return vec![RunView {
+                command: &self.terminal_command,
+                working_dir: &self.working_dir,
+                output: &self.terminal_output,
+                exit_code: None,
+                duration: None,
+                truncated: false,
+                timed_out: false,
+            }];
+        }
+        self.runs
+            .iter()
+            .filter(|run| run.include)
+            .map(|run| RunView {
+                command: &run.command,
+                working_dir: &run.working_dir,
+                output: &run.output,
+                exit_code: run.exit_code,
+                duration: (!run.snapshot).then_some(run.duration),
+                truncated: run.truncated,
+                timed_out: run.timed_out,
+            })
+            .collect()
+    }
+
+    /// Indices of the runs in `prompt_runs`; `None` stands for the fields.
+    pub fn prompt_run_indices(&self) -> Vec<Option<usize>> {
+        if self.runs.is_empty() {
+            return vec![None];
+        }
+        (0..self.runs.len())
+            .filter(|&i| self.runs[i].include)
+            .map(Some)
+            .collect()
+    }
+
+    /// Distinct working directories from the history, most recent first.
+    pub fn recent_dirs(&self) -> Vec<String> {
+        let mut dirs: Vec<String> = Vec::new();
+        for entry in &self.history {
+            if !entry.working_dir.is_empty() && !dirs.contains(&entry.working_dir) {
+                dirs.push(entry.working_dir.clone());
+            }
+        }
+        dirs
+    }
+}
+
 // 🤖 Added `env_overrides` to pass leading KEY=VAL tokens into the child process
 pub fn run_command(
     working_dir: &Path,
@@ -46,7 +366,10 @@ pub fn run_command(
     do_timeout: bool,
     max_duration: Duration,
     env_overrides: &[(String, String)],
-) -> String {
+    truncation_marker: &str,
+    stdin: Option<String>,
+    cancel: Option<&AtomicBool>,
+) -> Result<CommandOutput> {
     let mut command = Command::new(cmd);
     command
         .args(args)
@@ -55,65 +378,114 @@ pub fn run_command(
         .stderr(Stdio::piped())
         .envs(env_overrides.iter().map(|(k, v)| (k.as_str(), v.as_str()))); // 🤖 apply env vars
 
-    let child = command.spawn().expect("Failed to spawn command");
+    // Never the app's own stdin, which is the client channel in MCP mode
+    command.stdin(if stdin.is_some() {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    });
+
+    let command_err = |source| PromptError::Command {
+        command: cmd.to_string(),
+        source,
+    };
+    let mut child = command.spawn().map_err(command_err)?;
+
+    // Written from its own thread so a command that produces output before reading all
+    // of its input can't deadlock against us; the pipe closes (EOF) when it's done
+    if let (Some(text), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        std::thread::spawn(move || {
+            let _ = pipe.write_all(text.as_bytes());
+        });
+    }
 
-    println!(
+    eprintln!(
         "Starting child command {} {:?} in {:?}",
         cmd, args, working_dir
     );
 
-    let output = if do_timeout {
-        let child_id = child.id();
-        let (tx, rx) = mpsc::channel();
+    let child_id = child.id();
+    let (tx, rx) = mpsc::channel();
 
-        // 🤖 wait for output in a helper thread
-        std::thread::spawn(move || {
-            let output = child
-                .wait_with_output()
-                .expect("Failed to wait on child process");
-            let _ = tx.send(output);
-        });
+    // 🤖 wait for output in a helper thread
+    std::thread::spawn(move || {
+        let _ = tx.send(child.wait_with_output());
+    });
 
-        match rx.recv_timeout(max_duration) {
-            Ok(output) => output,
+    let started = Instant::now();
+    let mut timed_out = false;
+    let output = loop {
+        match rx.recv_timeout(CANCEL_POLL_INTERVAL) {
+            Ok(output) => break output.map_err(command_err)?,
             Err(mpsc::RecvTimeoutError::Timeout) => {
-                println!("Timeout reached after {:?}", max_duration);
-                // 🤖 hard-kill on timeout to avoid zombie processes
-                #[cfg(unix)]
-                {
-                    let _ = Command::new("kill")
-                        .arg("-9")
-                        .arg(child_id.to_string())
-                        .status();
+                let cancelled = cancel.is_some_and(|c| c.load(Ordering::Relaxed));
+                timed_out = do_timeout && started.elapsed() >= max_duration;
+                if !cancelled && !timed_out {
+                    continue;
                 }
-                #[cfg(windows)]
-                {
-                    let _ = Command::new("taskkill")
-                        .arg("/PID")
-                        .arg(child_id.to_string())
-                        .arg("/F")
-                        .status();
+                if timed_out {
+                    eprintln!("Timeout reached after {:?}", max_duration);
                 }
-
-                rx.recv().unwrap_or_else(|_| Output {
-                    status: std::process::ExitStatus::from_raw(1),
-                    stdout: Vec::new(),
-                    stderr: Vec::new(),
-                })
+                // 🤖 hard-kill on timeout to avoid zombie processes
+                kill(child_id);
+                if cancelled {
+                    return Err(PromptError::Cancelled);
+                }
+                break rx
+                    .recv()
+                    .ok()
+                    .and_then(|r| r.ok())
+                    .unwrap_or_else(|| Output {
+                        status: std::process::ExitStatus::from_raw(1),
+                        stdout: Vec::new(),
+                        stderr: Vec::new(),
+                    });
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err(command_err(std::io::Error::other(
+                    "output reader thread exited unexpectedly",
+                )))
             }
-            Err(e) => panic!("Error waiting for command output: {:?}", e),
         }
-    } else {
-        child
-            .wait_with_output()
-            .expect("Failed to wait on child process")
     };
 
-    get_head_and_tail(first_n, last_n, output)
+    let exit_code = output.status.code();
+    let (text, truncated) = get_head_and_tail(first_n, last_n, output, truncation_marker);
+    Ok(CommandOutput {
+        text,
+        exit_code: if timed_out { None } else { exit_code },
+        duration: started.elapsed(),
+        truncated,
+        timed_out,
+    })
+}
+
+pub fn kill(child_id: u32) {
+    #[cfg(unix)]
+    {
+        let _ = Command::new("kill")
+            .arg("-9")
+            .arg(child_id.to_string())
+            .status();
+    }
+    #[cfg(windows)]
+    {
+        let _ = Command::new("taskkill")
+            .arg("/PID")
+            .arg(child_id.to_string())
+            .arg("/F")
+            .status();
+    }
 }
 
 // ... a couple lines below
-fn get_head_and_tail(first_n: usize, last_n: usize, output: Output) -> String {
+/// The combined output, and whether lines were cut from the middle.
+fn get_head_and_tail(
+    first_n: usize,
+    last_n: usize,
+    output: Output,
+    marker: &str,
+) -> (String, bool) {
     let mut combined = String::new();
     combined.push_str(&String::from_utf8_lossy(&output.stdout));
     combined.push_str(&String::from_utf8_lossy(&output.stderr));
@@ -130,11 +502,141 @@ fn get_head_and_tail(first_n: usize, last_n: usize, output: Output) -> String {
             result.push_str(line);
             result.push('\n');
         }
-        result.push_str("[... output truncated ...]\n");
+        result.push_str(marker);
+        result.push('\n');
         for line in &lines[total - last_n..] {
             result.push_str(line);
             result.push('\n');
         }
     }
-    result
+    (result, total > first_n + last_n)
+}
+
+/// Blank-line separated chunks; compiler diagnostics, test failures and tracebacks each
+/// come out as one block.
+fn output_blocks(output: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut current = String::new();
+    for line in output.lines() {
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                blocks.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        current.push_str(line);
+        current.push('\n');
+    }
+    if !current.is_empty() {
+        blocks.push(current);
+    }
+    blocks
+}
+
+fn is_important(block: &str) -> bool {
+    let lower = block.to_lowercase();
+    ["error", "panicked", "failed", "traceback", "exception"]
+        .iter()
+        .any(|k| lower.contains(k))
+}
+
+/// First and last lines within `max_tokens`, for output with no block structure to use.
+fn head_tail_tokens(output: &str, max_tokens: usize, marker: &str) -> String {
+    let lines: Vec<&str> = output.lines().collect();
+    let half = max_tokens / 2;
+    let (mut head, mut used) = (0, 0);
+    while head < lines.len() {
+        let cost = crate::token_count::count_tokens(lines[head]) + 1;
+        if used + cost > half {
+            break;
+        }
+        used += cost;
+        head += 1;
+    }
+    let (mut tail, mut used) = (lines.len(), 0);
+    while tail > head {
+        let cost = crate::token_count::count_tokens(lines[tail - 1]) + 1;
+        if used + cost > half {
+            break;
+        }
+        used += cost;
+        tail -= 1;
+    }
+    let mut out = lines[..head].join("\n");
+    out.push('\n');
+    out.push_str(marker);
+    out.push('\n');
+    out.push_str(&lines[tail..].join("\n"));
+    out.push('\n');
+    out
+}
+
+/// Caps output at roughly `max_tokens` (0 = no cap) while keeping what matters: the final
+/// block (summaries), the first (what ran), every block mentioning an error or failure,
+/// then as much of the tail as fits. Gaps are marked with `marker`.
+pub fn cap_output_tokens(output: &str, max_tokens: usize, marker: &str) -> String {
+    use crate::token_count::count_tokens;
+    if max_tokens == 0 || count_tokens(output) <= max_tokens {
+        return output.to_string();
+    }
+    let blocks = output_blocks(output);
+    let costs: Vec<usize> = blocks.iter().map(|b| count_tokens(b)).collect();
+    let budget = max_tokens.saturating_sub(count_tokens(marker) * 4);
+    let n = blocks.len();
+    let mut keep = vec![false; n];
+    let mut used = 0;
+    let order = [n.saturating_sub(1), 0]
+        .into_iter()
+        .chain((0..n).filter(|&i| is_important(&blocks[i])))
+        .chain((0..n).rev());
+    for i in order {
+        if used >= budget {
+            break;
+        }
+        if i < n && !keep[i] && used + costs[i] <= budget {
+            keep[i] = true;
+            used += costs[i];
+        }
+    }
+    if !keep.iter().any(|&k| k) {
+        return head_tail_tokens(output, budget, marker);
+    }
+    // A block too big to fit whole (often the one that matters) gets the leftover budget
+    // as head + tail instead of vanishing
+    let leftover = budget - used;
+    let partial = (leftover > budget / 4)
+        .then(|| {
+            (0..n)
+                .filter(|&i| !keep[i])
+                .max_by_key(|&i| (is_important(&blocks[i]), costs[i]))
+        })
+        .flatten()
+        .map(|i| (i, head_tail_tokens(&blocks[i], leftover, marker)));
+    let mut out = String::new();
+    let mut gap = false;
+    for (i, block) in blocks.iter().enumerate() {
+        let text = match &partial {
+            Some((p, trimmed)) if *p == i => trimmed.as_str(),
+            _ if keep[i] => block.as_str(),
+            _ => {
+                gap = true;
+                continue;
+            }
+        };
+        if gap {
+            out.push_str(marker);
+            out.push_str("\n\n");
+            gap = false;
+        }
+        out.push_str(text);
+        out.push('\n');
+    }
+    if gap {
+        out.push_str(marker);
+        out.push('\n');
+    }
+    let trimmed = out.trim_end().len();
+    out.truncate(trimmed);
+    out.push('\n');
+    out
 }
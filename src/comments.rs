@@ -0,0 +1,205 @@
+// Per-language comment and blank-line stripping, used as an optional transform on
+// included files (and for the clipboard button). A small lexer tracks strings and block
+// comments across lines so `"http://x"` or `'#'` survive; it isn't a parser, so exotic
+// literal syntax (heredocs, nested template literals) may occasionally be cut short.
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    /// `//`, nested `/* */`, raw strings, char literals vs lifetimes.
+    Rust,
+    /// C, C++, Java, Go, JS/TS, C#, shaders: `//`, `/* */`, backtick strings.
+    CLike,
+    /// Python, shell, TOML, YAML, Ruby: `#`, triple-quoted strings.
+    Hash,
+    /// Unknown origin (the clipboard): `//`, `/* */` and `#` are all comments.
+    Any,
+}
+
+pub fn syntax_for(rel_path: &str) -> Option<Syntax> {
+    let name = rel_path.rsplit(['/', '\\']).next()?;
+    if matches!(name, "Makefile" | "Dockerfile" | "Gemfile" | "Rakefile") {
+        return Some(Syntax::Hash);
+    }
+    match name.rsplit_once('.')?.1 {
+        "rs" => Some(Syntax::Rust),
+        "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "java" | "kt" | "kts" | "go" | "js"
+        | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "cs" | "swift" | "scala" | "dart" | "proto"
+        | "glsl" | "hlsl" | "wgsl" | "slang" => Some(Syntax::CLike),
+        "py" | "pyi" | "sh" | "bash" | "zsh" | "fish" | "toml" | "yaml" | "yml" | "rb" | "pl"
+        | "r" | "ps1" => Some(Syntax::Hash),
+        _ => None,
+    }
+}
+
+struct Quote {
+    close: String,
+    escapes: bool,
+    /// Single-line quotes are dropped at the end of the line, so one stray quote can't
+    /// swallow the rest of the file.
+    multiline: bool,
+}
+
+#[derive(Default)]
+struct Lexer {
+    block_depth: usize,
+    quote: Option<Quote>,
+}
+
+fn starts_with(chars: &[char], i: usize, pat: &str) -> bool {
+    pat.chars()
+        .enumerate()
+        .all(|(k, p)| chars.get(i + k) == Some(&p))
+}
+
+impl Lexer {
+    fn quote(close: &str, escapes: bool, multiline: bool) -> Option<Quote> {
+        Some(Quote {
+            close: close.to_string(),
+            escapes,
+            multiline,
+        })
+    }
+
+    /// Opening quote at `i`, if any, and its length.
+    fn open_quote(
+        &self,
+        chars: &[char],
+        i: usize,
+        syntax: Syntax,
+    ) -> Option<(Option<Quote>, usize)> {
+        let c = chars[i];
+        let prev_ident = i > 0 && (chars[i - 1].is_alphanumeric() || chars[i - 1] == '_');
+        match syntax {
+            Syntax::Rust => {
+                if c == 'r' && !prev_ident {
+                    let hashes = chars[i + 1..].iter().take_while(|&&h| h == '#').count();
+                    if chars.get(i + 1 + hashes) == Some(&'"') {
+                        let close = format!("\"{}", "#".repeat(hashes));
+                        return Some((Self::quote(&close, false, true), hashes + 2));
+                    }
+                }
+                match c {
+                    '"' => Some((Self::quote("\"", true, true), 1)),
+                    // 'a' or '\n' is a char literal; 'a without a close is a lifetime
+                    '\'' if chars.get(i + 1) == Some(&'\\') || chars.get(i + 2) == Some(&'\'') => {
+                        Some((Self::quote("'", true, false), 1))
+                    }
+                    _ => None,
+                }
+            }
+            Syntax::CLike => match c {
+                '"' | '\'' => Some((Self::quote(&c.to_string(), true, false), 1)),
+                '`' => Some((Self::quote("`", true, true), 1)),
+                _ => None,
+            },
+            Syntax::Hash => {
+                if starts_with(chars, i, "\"\"\"") || starts_with(chars, i, "'''") {
+                    let close: String = chars[i..i + 3].iter().collect();
+                    return Some((Self::quote(&close, true, true), 3));
+                }
+                match c {
+                    '"' | '\'' => Some((Self::quote(&c.to_string(), true, false), 1)),
+                    _ => None,
+                }
+            }
+            Syntax::Any => match c {
+                '"' | '\'' => Some((Self::quote(&c.to_string(), true, false), 1)),
+                _ => None,
+            },
+        }
+    }
+
+    fn strip_line(&mut self, line: &str, syntax: Syntax) -> String {
+        let chars: Vec<char> = line.chars().collect();
+        let slash_comments = syntax != Syntax::Hash;
+        let hash_comments = matches!(syntax, Syntax::Hash | Syntax::Any);
+        let mut out = String::with_capacity(line.len());
+        let mut i = 0;
+        while i < chars.len() {
+            if self.block_depth > 0 {
+                if syntax == Syntax::Rust && starts_with(&chars, i, "/*") {
+                    self.block_depth += 1;
+                    i += 2;
+                } else if starts_with(&chars, i, "*/") {
+                    self.block_depth -= 1;
+                    i += 2;
+                } else {
+                    i += 1;
+                }
+                continue;
+            }
+            if let Some(q) = &self.quote {
+                if q.escapes && chars[i] == '\\' {
+                    out.extend(chars[i..(i + 2).min(chars.len())].iter());
+                    i += 2;
+                    continue;
+                }
+                if starts_with(&chars, i, &q.close) {
+                    out.push_str(&q.close);
+                    i += q.close.chars().count();
+                    self.quote = None;
+                    continue;
+                }
+                out.push(chars[i]);
+                i += 1;
+                continue;
+            }
+            if (slash_comments && starts_with(&chars, i, "//"))
+                || (hash_comments && chars[i] == '#')
+            {
+                break;
+            }
+            if slash_comments && starts_with(&chars, i, "/*") {
+                self.block_depth = 1;
+                i += 2;
+                continue;
+            }
+            if let Some((quote, len)) = self.open_quote(&chars, i, syntax) {
+                out.extend(chars[i..i + len].iter());
+                self.quote = quote;
+                i += len;
+                continue;
+            }
+            out.push(chars[i]);
+            i += 1;
+        }
+        if self.quote.as_ref().is_some_and(|q| !q.multiline) {
+            self.quote = None;
+        }
+        out
+    }
+
+    fn in_code(&self) -> bool {
+        self.block_depth == 0 && self.quote.is_none()
+    }
+}
+
+/// Drops comments, trailing whitespace and blank lines. Lines containing `keep` (a
+/// marker such as the folded-region note, itself a comment) pass through untouched, as
+/// do `NNN | ` line-number prefixes and a leading shebang.
+pub fn strip(text: &str, syntax: Syntax, keep: &str) -> String {
+    let mut lexer = Lexer::default();
+    let mut out = String::with_capacity(text.len());
+    for (n, line) in text.lines().enumerate() {
+        if !keep.is_empty() && lexer.in_code() && line.contains(keep) {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        }
+        let (prefix, body) = crate::line_numbers::split(line);
+        if n == 0 && body.starts_with("#!") {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        }
+        let code = lexer.strip_line(body, syntax);
+        let code = code.trim_end();
+        if code.trim().is_empty() {
+            continue;
+        }
+        out.push_str(prefix);
+        out.push_str(code);
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,129 @@
+// Commit message mode: a prompt made of what's staged (`git diff --cached`), the last few
+// commit messages as examples of the project's style, and instructions for writing the
+// message. Nothing is staged or committed here; the prompt is copied, or sent to the
+// active provider and the reply written to the repository's COMMIT_EDITMSG, where
+// `git commit` (or `git commit -e -F .git/COMMIT_EDITMSG`) picks it up.
+
+use crate::code_state::git;
+use crate::error::{PromptError, Result};
+use crate::jobs::{JobId, JobKind, JobOutput, Jobs};
+use crate::providers::Provider;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_EXAMPLES: usize = 10;
+
+pub const SYSTEM_PROMPT: &str = "\
+You write git commit messages. Describe the staged change below in the style of the \
+recent commits: match their subject length, tense, capitalization and any prefixes or \
+ticket references they use. The subject says what the change does in plain words; add a \
+body only when the reason or the approach isn't obvious from the subject, wrapped at 72 \
+columns. Reply with the commit message only, without code fences or commentary.";
+
+pub struct CommitMessage {
+    pub open: bool,
+    /// Recent commit messages included as style examples.
+    pub examples: usize,
+    pub job: Option<JobId>,
+    /// The last generated message, editable before it's written again.
+    pub message: String,
+}
+
+impl Default for CommitMessage {
+    fn default() -> Self {
+        Self {
+            open: false,
+            examples: DEFAULT_EXAMPLES,
+            job: None,
+            message: String::new(),
+        }
+    }
+}
+
+/// The last `count` commit messages, newest first; none in a repository without commits.
+fn recent_messages(base: &Path, count: usize) -> Vec<String> {
+    if count == 0 {
+        return Vec::new();
+    }
+    let count = count.to_string();
+    // Record separator between messages, which can span several lines
+    git(
+        base,
+        &["log", "-n", &count, "--no-merges", "--format=%B%x1e"],
+    )
+    .map(|log| {
+        log.split('\x1e')
+            .map(str::trim)
+            .filter(|m| !m.is_empty())
+            .map(str::to_string)
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// The whole prompt, or an error when nothing is staged.
+pub fn build(base: &Path, examples: usize) -> Result<String> {
+    let diff = git(
+        base,
+        &[
+            "diff",
+            "--cached",
+            "--no-color",
+            "--no-ext-diff",
+            "--find-renames",
+        ],
+    )?;
+    if diff.trim().is_empty() {
+        return Err(PromptError::Git(
+            "nothing is staged; stage the change with git add first".into(),
+        ));
+    }
+    let stat = git(base, &["diff", "--cached", "--stat", "--no-color"])?;
+
+    let mut out = String::new();
+    let _ = writeln!(out, "{}\n", SYSTEM_PROMPT);
+    let messages = recent_messages(base, examples);
+    if !messages.is_empty() {
+        out.push_str("<recent_commits>\n");
+        for message in messages {
+            let _ = writeln!(out, "<commit>\n{}\n</commit>", message);
+        }
+        out.push_str("</recent_commits>\n\n");
+    }
+    let _ = writeln!(out, "<staged_files>\n{}</staged_files>\n", stat);
+    let _ = writeln!(out, "<staged_diff>\n{}</staged_diff>", diff);
+    Ok(out)
+}
+
+/// The reply without a code fence around it or blank lines at either end.
+pub fn clean(reply: &str) -> String {
+    let trimmed = reply.trim();
+    let unfenced = trimmed
+        .strip_prefix("```")
+        .and_then(|rest| rest.split_once('\n'))
+        .and_then(|(_, body)| body.trim_end().strip_suffix("```"))
+        .unwrap_or(trimmed);
+    format!("{}\n", unfenced.trim())
+}
+
+/// Writes `message` to the repository's COMMIT_EDITMSG (inside the git directory, so
+/// worktrees get their own) and returns its path.
+pub fn write(base: &Path, message: &str) -> Result<PathBuf> {
+    let path = git(base, &["rev-parse", "--git-path", "COMMIT_EDITMSG"])?;
+    let path = base.join(path.trim());
+    std::fs::write(&path, message).map_err(|e| PromptError::io("write", &path, e))?;
+    Ok(path)
+}
+
+/// Sends `prompt` to `provider`; the job's output is the cleaned-up message.
+pub fn spawn(provider: Provider, prompt: String, jobs: &mut Jobs) -> JobId {
+    let label = format!("Commit message ({})", provider.model);
+    jobs.spawn(JobKind::Model, label, move |ctx| {
+        let mut received = 0;
+        let result = provider.stream(&prompt, &[], ctx.cancel_flag(), |piece| {
+            received += piece.len();
+            ctx.progress(format!("{} bytes", received));
+        });
+        JobOutput::CommitMessage(result.map(|reply| clean(&reply)))
+    })
+}
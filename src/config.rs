@@ -0,0 +1,241 @@
+use crate::error::{PromptError, Result};
+use crate::escaping::Escaping;
+use crate::file_order::FileOrder;
+use crate::file_types::FileTypeRule;
+use crate::json_prompt::OutputFormat;
+use crate::labels::LabelConfig;
+use crate::pricing::ModelPrice;
+use crate::providers::Provider;
+use crate::sections::Section;
+use crate::token_meter::MeterSettings;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_TOKEN_BUDGET: usize = 200_000;
+
+/// Per-project settings persisted in `.prompt/config.toml`.
+/// Every field has a default so older/partial files keep loading.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectConfig {
+    /// Folders (relative, '/'-separated) whose files appear in <file_tree> but are never
+    /// selectable for content inclusion.
+    pub tree_only: Vec<String>,
+    /// Section tag names and markers (language preset + per-key overrides).
+    pub labels: LabelConfig,
+    /// How many import hops "Include dependencies" follows from the chosen file.
+    pub dependency_depth: usize,
+    /// Strip BOMs and convert CRLF/CR to LF in included file contents.
+    pub normalize_text: bool,
+    /// Apply the built-in language packs detected from root marker files (default ignores,
+    /// generated-file patterns, Smart select).
+    pub language_packs: bool,
+    /// Prefix included file lines with `NNN | ` (noted as an attribute on the file tag).
+    pub line_numbers: bool,
+    /// Drop comments and blank lines from included files in recognized languages.
+    pub strip_comments: bool,
+    /// Emit a <project_metadata> summary of Cargo.toml / package.json / pyproject.toml.
+    pub include_metadata: bool,
+    /// Emit `.prompt/notes.md` as a <project_notes> section.
+    pub include_project_notes: bool,
+    /// Open in editor command; see editor.rs for the placeholders.
+    pub editor_command: String,
+    /// Build/test command run by evaluation mode after a response's patch is applied.
+    pub verify_command: String,
+    /// XML (tagged sections) or a single JSON object.
+    pub output_format: OutputFormat,
+    /// How text is embedded inside tags (CDATA, entity-escaped, or sentinel-fenced).
+    pub escaping: Escaping,
+    /// Token cap for terminal output in the prompt (0 = unlimited); see cap_output_tokens.
+    pub terminal_token_cap: usize,
+    /// Name of the `.prompt/env.toml` set applied to terminal commands; empty = none.
+    pub env_preset: String,
+    /// Replace terminal output holding rustc diagnostics or test panics with a compact
+    /// <errors> summary; see build_errors.rs.
+    pub summarize_errors: bool,
+    /// Flag a single terminal output or remote source above this percent of the token
+    /// budget; 0 = never.
+    pub item_warn_percent: usize,
+    /// Top-level sections of the full prompt, in order; repeats allowed, omitted = excluded.
+    pub sections: Vec<Section>,
+    /// Instruction snippets (file stems in `.prompt/snippets/`) prepended to the instruction.
+    pub enabled_snippets: Vec<String>,
+    /// Model backends for Send to LLM; see providers.rs for the format.
+    pub providers: Vec<Provider>,
+    /// `name` of the provider Send to LLM uses.
+    pub active_provider: String,
+    /// Program given the prompt on stdin by Send to command, e.g. `llm -m gpt-4`; its
+    /// stdout becomes the reply.
+    pub send_command: String,
+    /// With an Anthropic provider active, put stable sections first and mark prompt-cache
+    /// breakpoints; see sections::cache_order.
+    pub cache_layout: bool,
+    /// USD per million tokens, keyed by model id (or id prefix).
+    pub prices: BTreeMap<String, ModelPrice>,
+    /// Reply length assumed for the output cost estimate; 0 hides it.
+    pub expected_output_tokens: usize,
+    /// Order of the included files; see file_order.rs.
+    pub file_order: FileOrder,
+    /// Relative paths in the order arranged by hand, for `FileOrder::Manual`.
+    pub manual_order: Vec<String>,
+    /// Send selected files that repeat another one (up to whitespace) as empty `same_as`
+    /// entries; see duplicates.rs.
+    pub dedupe_files: bool,
+    /// Walk symlinked directories and include symlinked files instead of skipping them.
+    pub follow_symlinks: bool,
+    /// Links followed through at most this many symlinks (a link inside a linked folder
+    /// is 2 deep).
+    pub symlink_depth: usize,
+    /// Data files (relative, '/'-separated) sent as a schema and row sample instead of
+    /// their contents; see tables.rs.
+    pub summarize_tables: Vec<String>,
+    /// Files (relative, '/'-separated) sent as their git diff against `diff_ref` instead
+    /// of their contents; see file_diff.rs.
+    pub diff_only: Vec<String>,
+    /// Revision diff-only files are compared with; empty = HEAD.
+    pub diff_ref: String,
+    /// Rows shown at each end of a summarized table.
+    pub table_rows: usize,
+    /// Add a base64 PNG thumbnail to the description of included images; see image_info.rs.
+    pub image_thumbnails: bool,
+    /// Named system prompt used instead of system_prompt.txt; empty = the default lookup.
+    /// See system_prompts.rs.
+    pub system_profile: String,
+    /// `[[file_types]]`: colours, icons and categories for the file tree on top of the
+    /// built-in ones; see file_types.rs.
+    pub file_types: Vec<FileTypeRule>,
+    /// Token limit when the active provider doesn't know its model's context window.
+    pub context_limit: usize,
+    /// Number locale and warning/critical thresholds of the token meter, unless the active
+    /// provider has its own; see token_meter.rs.
+    pub meter: MeterSettings,
+}
+
+impl Default for ProjectConfig {
+    fn default() -> Self {
+        Self {
+            tree_only: Vec::new(),
+            labels: LabelConfig::default(),
+            dependency_depth: 2,
+            normalize_text: true,
+            language_packs: true,
+            line_numbers: false,
+            strip_comments: false,
+            include_metadata: false,
+            include_project_notes: false,
+            editor_command: crate::editor::DEFAULT_COMMAND.to_string(),
+            verify_command: String::new(),
+            output_format: OutputFormat::default(),
+            escaping: Escaping::default(),
+            terminal_token_cap: 10_000,
+            env_preset: String::new(),
+            summarize_errors: false,
+            item_warn_percent: 20,
+            sections: crate::sections::default_order(),
+            enabled_snippets: Vec::new(),
+            providers: Vec::new(),
+            active_provider: String::new(),
+            send_command: String::new(),
+            cache_layout: true,
+            prices: crate::pricing::default_prices(),
+            expected_output_tokens: 0,
+            file_order: FileOrder::default(),
+            manual_order: Vec::new(),
+            dedupe_files: false,
+            follow_symlinks: false,
+            symlink_depth: 2,
+            summarize_tables: Vec::new(),
+            diff_only: Vec::new(),
+            diff_ref: String::new(),
+            table_rows: 5,
+            image_thumbnails: false,
+            system_profile: String::new(),
+            file_types: Vec::new(),
+            context_limit: DEFAULT_TOKEN_BUDGET,
+            meter: MeterSettings::default(),
+        }
+    }
+}
+
+/// Settings shared by every project: `$XDG_CONFIG_HOME/prompt`, else `~/.config/prompt`.
+pub fn global_dir() -> Option<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+    Some(config.join("prompt"))
+}
+
+impl ProjectConfig {
+    pub fn path(base: &Path) -> PathBuf {
+        base.join(".prompt").join("config.toml")
+    }
+
+    /// A missing file yields the defaults; an unreadable or malformed one is an error.
+    pub fn load(base: &Path) -> Result<Self> {
+        let path = Self::path(base);
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(PromptError::io("read", path, e)),
+        };
+        toml::from_str(&text).map_err(|e| PromptError::Config {
+            path,
+            message: e.to_string(),
+        })
+    }
+
+    pub fn save(&self, base: &Path) -> Result<()> {
+        let path = Self::path(base);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| PromptError::io("create", dir, e))?;
+        }
+        let text = toml::to_string_pretty(self).map_err(|e| PromptError::Config {
+            path: path.clone(),
+            message: e.to_string(),
+        })?;
+        std::fs::write(&path, text).map_err(|e| PromptError::io("write", &path, e))
+    }
+
+    /// The active provider's context window (probed or entered), else `context_limit`.
+    pub fn token_budget(&self) -> usize {
+        self.active_provider()
+            .and_then(|p| p.context_length)
+            .unwrap_or(self.context_limit)
+            .max(1)
+    }
+
+    /// The active provider's meter settings, else the project's.
+    pub fn meter(&self) -> &MeterSettings {
+        self.active_provider()
+            .and_then(|p| p.meter.as_ref())
+            .unwrap_or(&self.meter)
+    }
+
+    /// Whether one item of `tokens` is over `item_warn_percent` of the budget.
+    pub fn is_heavy(&self, tokens: usize) -> bool {
+        self.item_warn_percent > 0 && tokens * 100 > self.token_budget() * self.item_warn_percent
+    }
+
+    pub fn active_provider(&self) -> Option<&Provider> {
+        self.providers
+            .iter()
+            .find(|p| p.name == self.active_provider)
+    }
+
+    /// The marked folder covering `rel_path` (itself or an ancestor), if any.
+    pub fn tree_only_root(&self, rel_path: &str) -> Option<&str> {
+        self.tree_only
+            .iter()
+            .map(|d| d.trim_end_matches('/'))
+            .find(|d| {
+                !d.is_empty()
+                    && (rel_path == *d
+                        || (rel_path.starts_with(d) && rel_path[d.len()..].starts_with('/')))
+            })
+    }
+
+    pub fn is_tree_only(&self, rel_path: &str) -> bool {
+        self.tree_only_root(rel_path).is_some()
+    }
+}
@@ -0,0 +1,113 @@
+// Multi-turn state: each "Copy Prompt" becomes a turn, the model's reply is pasted back
+// into the response pane, and follow-up prompts only carry what changed since.
+
+use crate::code_state::CodeState;
+use crate::eval::EvalResult;
+use crate::intern::RelPath;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PromptMode {
+    /// Full prompt: system prompt, tree, every selected file.
+    Fresh,
+    /// Only newly selected or changed files plus the new instruction.
+    FollowUp,
+}
+
+/// What a single "Copy" produces.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PromptKind {
+    Full,
+    /// Files the model hasn't seen in their current form in any turn.
+    FollowUp,
+    /// Files changed since the last copied prompt only.
+    Delta,
+}
+
+pub struct Turn {
+    pub instruction: String,
+    pub prompt: String,
+    pub response: String,
+    /// Content hash of every file sent in this turn.
+    pub files: HashMap<RelPath, u64>,
+    /// Hash of the terminal output at the time, so unchanged output isn't resent.
+    pub terminal: u64,
+    /// Included files' hash and git commit when the prompt was built.
+    pub snapshot: Option<CodeState>,
+    /// Outcome of applying this turn's response and running the verify command.
+    pub eval: Option<EvalResult>,
+    /// Times the identical prompt was copied again after this turn was recorded.
+    pub copied_again: usize,
+}
+
+pub struct Conversation {
+    pub turns: Vec<Turn>,
+    pub mode: PromptMode,
+    pub open: bool,
+}
+
+impl Default for Conversation {
+    fn default() -> Self {
+        Self {
+            turns: Vec::new(),
+            mode: PromptMode::Fresh,
+            open: false,
+        }
+    }
+}
+
+pub fn content_hash(content: &str) -> u64 {
+    let mut h = std::collections::hash_map::DefaultHasher::new();
+    content.hash(&mut h);
+    h.finish()
+}
+
+impl Conversation {
+    /// A follow-up needs a previous turn to be relative to.
+    pub fn follow_up_active(&self) -> bool {
+        self.mode == PromptMode::FollowUp && !self.turns.is_empty()
+    }
+
+    /// True if the model has not seen this exact content in any earlier turn.
+    pub fn is_new_or_changed(&self, rel_path: &RelPath, content: &str) -> bool {
+        let hash = content_hash(content);
+        self.turns.iter().rev().find_map(|t| t.files.get(rel_path)) != Some(&hash)
+    }
+
+    /// True if the file differs from (or was absent in) the most recent turn.
+    pub fn changed_since_last(&self, rel_path: &RelPath, content: &str) -> bool {
+        let last = self.turns.last().and_then(|t| t.files.get(rel_path));
+        last != Some(&content_hash(content))
+    }
+
+    pub fn record(
+        &mut self,
+        instruction: String,
+        prompt: String,
+        files: HashMap<RelPath, u64>,
+        terminal_output: &str,
+        snapshot: Option<CodeState>,
+    ) {
+        // Copying the same prompt twice is one turn, not two
+        if let Some(last) = self.turns.last_mut().filter(|t| t.prompt == prompt) {
+            last.copied_again += 1;
+            return;
+        }
+        self.turns.push(Turn {
+            instruction,
+            prompt,
+            response: String::new(),
+            files,
+            terminal: content_hash(terminal_output),
+            snapshot,
+            eval: None,
+            copied_again: 0,
+        });
+    }
+
+    pub fn reset(&mut self) {
+        self.turns.clear();
+        self.mode = PromptMode::Fresh;
+    }
+}
@@ -0,0 +1,466 @@
+// Import/mod-declaration scanning used by "Include dependencies".
+//
+// Regex-based on purpose: it only has to find *local* files a source file pulls in, so
+// external crates/packages that don't resolve to a loaded FileItem are simply dropped.
+
+use crate::error::{PromptError, Result};
+use crate::file_item::FileItem;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    Rust,
+    Script,
+    Python,
+}
+
+pub fn lang_of(rel_path: &str) -> Option<Lang> {
+    let ext = rel_path.rsplit_once('.')?.1;
+    match ext {
+        "rs" => Some(Lang::Rust),
+        "ts" | "tsx" | "js" | "jsx" | "mjs" | "cjs" => Some(Lang::Script),
+        "py" => Some(Lang::Python),
+        _ => None,
+    }
+}
+
+pub fn supports(rel_path: &str) -> bool {
+    lang_of(rel_path).is_some()
+}
+
+static RUST_MOD: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?mod\s+([A-Za-z_][A-Za-z0-9_]*)\s*;").unwrap()
+});
+static RUST_USE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?use\s+((?:crate|super|self)\b(?:::[A-Za-z_][A-Za-z0-9_]*)*)(?:::\{([^;]*)\})?").unwrap()
+});
+static SCRIPT_IMPORT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?:\bfrom\s+|\bimport\s+|\brequire\(\s*|\bimport\(\s*)['"](\.{1,2}/[^'"]+)['"]"#)
+        .unwrap()
+});
+static PY_FROM: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?m)^\s*from\s+(\.*)([A-Za-z_][\w.]*)?\s+import\s+([^#\n]+)").unwrap()
+});
+static PY_IMPORT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?m)^\s*import\s+([A-Za-z_][\w.]*(?:\s*,\s*[A-Za-z_][\w.]*)*)").unwrap()
+});
+
+fn parent_dir(rel_path: &str) -> &str {
+    rel_path.rsplit_once('/').map(|(d, _)| d).unwrap_or("")
+}
+
+fn join(dir: &str, rest: &str) -> String {
+    if dir.is_empty() {
+        rest.to_string()
+    } else {
+        format!("{}/{}", dir, rest)
+    }
+}
+
+/// Collapses `.` and `..` segments; returns None if the path escapes the project root.
+fn normalize(path: &str) -> Option<String> {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            p => parts.push(p),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// Directory holding the child modules of a Rust file (`foo.rs` -> `foo/`, `mod.rs` -> its dir).
+fn rust_module_dir(rel_path: &str) -> String {
+    let dir = parent_dir(rel_path);
+    let file = rel_path.rsplit('/').next().unwrap_or(rel_path);
+    match file {
+        "mod.rs" | "lib.rs" | "main.rs" => dir.to_string(),
+        _ => join(dir, file.trim_end_matches(".rs")),
+    }
+}
+
+/// Nearest `src` directory above the file, falling back to the file's own directory.
+fn rust_crate_root(rel_path: &str) -> String {
+    let dir = parent_dir(rel_path);
+    let components: Vec<&str> = dir.split('/').collect();
+    match components.iter().rposition(|c| *c == "src") {
+        Some(i) => components[..=i].join("/"),
+        None => dir.to_string(),
+    }
+}
+
+fn rust_module_files(dir: &str, module: &str) -> [String; 2] {
+    [
+        join(dir, &format!("{}.rs", module)),
+        join(dir, &format!("{}/mod.rs", module)),
+    ]
+}
+
+fn rust_candidates(rel_path: &str, source: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let module_dir = rust_module_dir(rel_path);
+    for cap in RUST_MOD.captures_iter(source) {
+        out.extend(rust_module_files(&module_dir, &cap[1]));
+    }
+    for cap in RUST_USE.captures_iter(source) {
+        let mut paths = vec![cap[1].to_string()];
+        if let Some(group) = cap.get(2) {
+            expand_use_group(&cap[1], group.as_str(), &mut paths);
+        }
+        for path in paths {
+            let mut segments = path.split("::");
+            let mut dir = match segments.next() {
+                Some("crate") => rust_crate_root(rel_path),
+                Some("self") => module_dir.clone(),
+                // super:: names the parent module, whose children live one dir up
+                _ => parent_dir(&module_dir).to_string(),
+            };
+            let mut rest: Vec<&str> = segments.collect();
+            while rest.first() == Some(&"super") {
+                dir = parent_dir(&dir).to_string();
+                rest.remove(0);
+            }
+            // `use crate::a::b::Item` may name a module (a/b.rs) or an item inside a.rs;
+            // offer every prefix and let resolution keep whatever exists.
+            for segment in rest {
+                out.extend(rust_module_files(&dir, segment));
+                dir = join(&dir, segment);
+            }
+        }
+    }
+    out
+}
+
+/// The paths of a `use prefix::{...}` group, nested groups included: `{a, b::{c, d}}`
+/// gives `prefix::a`, `prefix::b::c` and `prefix::b::d`.
+fn expand_use_group(prefix: &str, group: &str, out: &mut Vec<String>) {
+    let mut depth = 0;
+    let mut start = 0;
+    let mut items = Vec::new();
+    for (i, c) in group.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&group[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&group[start..]);
+    for item in items {
+        let item = item.split(" as ").next().unwrap_or("").trim();
+        if let Some((head, inner)) = item.split_once("::{") {
+            let nested = format!("{}::{}", prefix, head.trim());
+            out.push(nested.clone());
+            expand_use_group(&nested, inner.trim_end().trim_end_matches('}'), out);
+        } else if !item.is_empty() && item != "self" && item != "*" {
+            out.push(format!("{}::{}", prefix, item));
+        }
+    }
+}
+
+const SCRIPT_EXTS: [&str; 6] = ["ts", "tsx", "js", "jsx", "mjs", "cjs"];
+
+fn script_candidates(rel_path: &str, source: &str) -> Vec<String> {
+    let dir = parent_dir(rel_path);
+    let mut out = Vec::new();
+    for cap in SCRIPT_IMPORT.captures_iter(source) {
+        let Some(target) = normalize(&join(dir, &cap[1])) else {
+            continue;
+        };
+        out.push(target.clone());
+        for ext in SCRIPT_EXTS {
+            out.push(format!("{}.{}", target, ext));
+            out.push(format!("{}/index.{}", target, ext));
+        }
+    }
+    out
+}
+
+fn python_module_files(dir: &str, dotted: &str) -> [String; 2] {
+    let path = join(dir, &dotted.replace('.', "/"));
+    [format!("{}.py", path), format!("{}/__init__.py", path)]
+}
+
+fn python_candidates(rel_path: &str, source: &str) -> Vec<String> {
+    let dir = parent_dir(rel_path);
+    let mut out = Vec::new();
+    for cap in PY_FROM.captures_iter(source) {
+        let dots = cap.get(1).map_or(0, |m| m.as_str().len());
+        let module = cap.get(2).map_or("", |m| m.as_str());
+        // Relative imports climb from the file's package; absolute ones are tried both
+        // from the project root and next to the file (common for script-style layouts).
+        let bases: Vec<String> = if dots > 0 {
+            let mut base = dir.to_string();
+            for _ in 1..dots {
+                base = parent_dir(&base).to_string();
+            }
+            vec![base]
+        } else {
+            vec![String::new(), dir.to_string()]
+        };
+        for base in &bases {
+            let pkg = if module.is_empty() {
+                base.clone()
+            } else {
+                out.extend(python_module_files(base, module));
+                join(base, &module.replace('.', "/"))
+            };
+            // `from pkg import a, b` may import submodules a and b
+            for name in cap[3].trim_matches(|c| c == '(' || c == ')').split(',') {
+                let name = name.split_whitespace().next().unwrap_or("");
+                if !name.is_empty() && name != "*" {
+                    out.extend(python_module_files(&pkg, name));
+                }
+            }
+        }
+    }
+    for cap in PY_IMPORT.captures_iter(source) {
+        for module in cap[1].split(',').map(str::trim) {
+            out.extend(python_module_files("", module));
+            out.extend(python_module_files(dir, module));
+        }
+    }
+    out
+}
+
+/// Relative paths this file might import. Candidates are unverified; callers keep the
+/// ones that match a loaded file.
+pub fn import_candidates(rel_path: &str, source: &str) -> Vec<String> {
+    match lang_of(rel_path) {
+        Some(Lang::Rust) => rust_candidates(rel_path, source),
+        Some(Lang::Script) => script_candidates(rel_path, source),
+        Some(Lang::Python) => python_candidates(rel_path, source),
+        None => Vec::new(),
+    }
+}
+
+fn index_by_path(files: &[FileItem]) -> HashMap<&str, usize> {
+    files
+        .iter()
+        .enumerate()
+        .map(|(i, f)| (&*f.rel_path, i))
+        .collect()
+}
+
+/// Loaded files that `index` imports directly (unfiltered, may contain duplicates).
+fn direct_imports(
+    files: &[FileItem],
+    by_path: &HashMap<&str, usize>,
+    index: usize,
+) -> Result<Vec<usize>> {
+    let file = &files[index];
+    let source = file.read_text()?;
+    Ok(import_candidates(&file.rel_path, &source)
+        .iter()
+        .filter_map(|c| by_path.get(c.as_str()).copied())
+        .filter(|&dep| dep != index)
+        .collect())
+}
+
+/// Files reachable from `start` through local imports, up to `max_depth` hops, and the
+/// files that couldn't be read along the way (their imports aren't followed).
+/// Tree-only files are skipped since their contents are never included.
+pub fn collect_dependencies(
+    files: &[FileItem],
+    start: usize,
+    max_depth: usize,
+) -> (Vec<usize>, Vec<PromptError>) {
+    let by_path = index_by_path(files);
+    let mut errors = Vec::new();
+    let found = walk(start, max_depth, |index| {
+        match direct_imports(files, &by_path, index) {
+            Ok(deps) => deps
+                .into_iter()
+                .filter(|&dep| !files[dep].tree_only)
+                .collect(),
+            Err(e) => {
+                errors.push(e);
+                Vec::new()
+            }
+        }
+    });
+    (found, errors)
+}
+
+/// Files that import `target`, directly or (up to `max_depth` hops) through other
+/// dependents, and the files that couldn't be read. Every supported file is scanned once
+/// to build the reverse graph.
+pub fn collect_dependents(
+    files: &[FileItem],
+    target: usize,
+    max_depth: usize,
+) -> (Vec<usize>, Vec<PromptError>) {
+    use rayon::prelude::*;
+
+    let by_path = index_by_path(files);
+    let edges: Vec<(usize, Result<Vec<usize>>)> = (0..files.len())
+        .into_par_iter()
+        .filter(|&i| !files[i].tree_only && supports(&files[i].rel_path))
+        .map(|i| (i, direct_imports(files, &by_path, i)))
+        .collect();
+    let mut importers: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut errors = Vec::new();
+    for (from, deps) in edges {
+        match deps {
+            Ok(deps) => {
+                for dep in deps {
+                    importers.entry(dep).or_default().push(from);
+                }
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    let found = walk(target, max_depth, |index| {
+        importers.get(&index).cloned().unwrap_or_default()
+    });
+    (found, errors)
+}
+
+/// The symbol mode of `collect_dependents`: files that use `symbol`, defined in `target`
+/// (one hop; see `symbols::files_using`), and the files that couldn't be read.
+pub fn collect_symbol_dependents(
+    files: &[FileItem],
+    target: usize,
+    symbol: &crate::symbols::Symbol,
+) -> (Vec<usize>, Vec<PromptError>) {
+    let (mut found, errors) = crate::symbols::files_using(files, symbol);
+    found.retain(|&i| i != target);
+    (found, errors)
+}
+
+/// `indices` reordered so every file comes after the ones among them it imports. Ready
+/// files are taken in their input order; in a cycle, the earliest remaining file goes
+/// first.
+pub fn dependency_order(files: &[FileItem], indices: &[usize]) -> Vec<usize> {
+    use rayon::prelude::*;
+    use std::collections::BTreeSet;
+
+    let by_path = index_by_path(files);
+    let position: HashMap<usize, usize> =
+        indices.iter().enumerate().map(|(n, &i)| (i, n)).collect();
+    // Edges as input positions: deps[n] are what file n imports
+    let deps: Vec<HashSet<usize>> = indices
+        .par_iter()
+        .map(|&i| {
+            if !supports(&files[i].rel_path) {
+                return HashSet::new();
+            }
+            // An unreadable file is reported when the prompt reads it; here it just has no
+            // known imports
+            direct_imports(files, &by_path, i)
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|dep| position.get(&dep).copied())
+                .collect()
+        })
+        .collect();
+    let mut importers: Vec<Vec<usize>> = vec![Vec::new(); indices.len()];
+    for (n, file_deps) in deps.iter().enumerate() {
+        for &dep in file_deps {
+            importers[dep].push(n);
+        }
+    }
+    let mut waiting: Vec<usize> = deps.iter().map(HashSet::len).collect();
+    let mut pending: BTreeSet<usize> = (0..indices.len()).collect();
+    let mut ready: BTreeSet<usize> = pending
+        .iter()
+        .copied()
+        .filter(|&n| waiting[n] == 0)
+        .collect();
+    let mut order = Vec::with_capacity(indices.len());
+    while let Some(&first) = pending.first() {
+        let n = ready.pop_first().unwrap_or(first);
+        pending.remove(&n);
+        order.push(indices[n]);
+        for &user in &importers[n] {
+            waiting[user] = waiting[user].saturating_sub(1);
+            if waiting[user] == 0 && pending.contains(&user) {
+                ready.insert(user);
+            }
+        }
+    }
+    order
+}
+
+/// Breadth-first walk from `start`, returning newly reached nodes in discovery order.
+fn walk(start: usize, max_depth: usize, mut next: impl FnMut(usize) -> Vec<usize>) -> Vec<usize> {
+    let mut seen: HashSet<usize> = HashSet::from([start]);
+    let mut found = Vec::new();
+    let mut queue = VecDeque::from([(start, 0usize)]);
+    while let Some((index, depth)) = queue.pop_front() {
+        if depth >= max_depth {
+            continue;
+        }
+        for neighbour in next(index) {
+            if seen.insert(neighbour) {
+                found.push(neighbour);
+                queue.push_back((neighbour, depth + 1));
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rust_grouped_use_offers_each_module() {
+        let source = "use crate::{app::MyApp, jobs::{JobId, JobKind}, error};\nmod helpers;\n";
+        let found = import_candidates("src/main.rs", source);
+        for wanted in [
+            "src/app.rs",
+            "src/jobs.rs",
+            "src/error.rs",
+            "src/error/mod.rs",
+            "src/helpers.rs",
+            "src/helpers/mod.rs",
+        ] {
+            assert!(found.iter().any(|c| c == wanted), "missing {}", wanted);
+        }
+    }
+
+    #[test]
+    fn rust_crate_root_is_a_whole_src_component() {
+        let found = import_candidates("src/src_util/x.rs", "use crate::foo::Bar;\n");
+        assert!(found.iter().any(|c| c == "src/foo.rs"));
+        assert!(!found.iter().any(|c| c == "src/src_util/foo.rs"));
+    }
+
+    #[test]
+    fn rust_super_climbs_from_the_module_dir() {
+        let found = import_candidates("src/ui/panel.rs", "use super::theme::Colors;\n");
+        assert!(found.iter().any(|c| c == "src/ui/theme.rs"));
+        assert!(!found.iter().any(|c| c == "src/theme.rs"));
+    }
+
+    #[test]
+    fn rust_external_and_similar_names_are_ignored() {
+        let source = "use std::fs;\nuse self_update::Update;\nuse serde::{Deserialize};\n";
+        assert!(import_candidates("src/main.rs", source).is_empty());
+    }
+
+    #[test]
+    fn python_relative_and_absolute_imports() {
+        let source = "from .models import User, Group\nfrom .. import utils\nimport pkg.config\n";
+        let found = import_candidates("pkg/api/views.py", source);
+        for wanted in [
+            "pkg/api/models.py",
+            "pkg/api/models/__init__.py",
+            "pkg/api/models/User.py",
+            "pkg/utils.py",
+            "pkg/config.py",
+        ] {
+            assert!(found.iter().any(|c| c == wanted), "missing {}", wanted);
+        }
+    }
+}
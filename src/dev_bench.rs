@@ -0,0 +1,145 @@
+// Hidden developer mode: `prompt --dev-bench <N> [--keep]`
+//
+// Generates a deterministic synthetic project with N files under the temp dir, then
+// drives the same code paths the UI uses (scan, tree build, select all, build prompt,
+// token count) and prints wall-clock timings. Not shown in the UI on purpose.
+
+use crate::app::{build_prompt, MyApp};
+use crate::file_tree::{build_file_tree, sort_file_tree};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const FILES_PER_DIR: usize = 12;
+const DIRS_PER_LEVEL: usize = 8;
+
+pub fn run_from_args(args: &[String]) -> bool {
+    let Some(pos) = args.iter().position(|a| a == "--dev-bench") else {
+        return false;
+    };
+    let count = args
+        .get(pos + 1)
+        .and_then(|n| n.parse::<usize>().ok())
+        .unwrap_or(5_000);
+    let keep = args.iter().any(|a| a == "--keep");
+    run_bench(count, keep);
+    true
+}
+
+fn run_bench(count: usize, keep: bool) {
+    if count > crate::file_item::MAX_FILES {
+        eprintln!(
+            "[bench] note: {} files exceeds MAX_FILES ({}); the scan will defer folders past it",
+            count,
+            crate::file_item::MAX_FILES
+        );
+    }
+    let root = std::env::temp_dir().join(format!("prompt-bench-{}", count));
+    let mut timings: Vec<(&str, Duration)> = Vec::new();
+
+    let t = Instant::now();
+    if let Err(e) = generate_fixture(&root, count) {
+        eprintln!(
+            "[bench] failed to generate fixture at {}: {}",
+            root.display(),
+            e
+        );
+        return;
+    }
+    timings.push(("generate fixture", t.elapsed()));
+
+    let mut app = MyApp {
+        current_folder: Some(root.clone()),
+        extra_text: "Benchmark instruction: summarize the project.".to_string(),
+        ..Default::default()
+    };
+
+    let t = Instant::now();
+    app.refresh_files();
+    timings.push(("scan (refresh_files)", t.elapsed()));
+
+    let t = Instant::now();
+    let mut tree = build_file_tree(&app.files);
+    sort_file_tree(&mut tree, &app.files);
+    timings.push(("build + sort tree", t.elapsed()));
+
+    let t = Instant::now();
+    for f in app.files.iter_mut() {
+        f.selected = true;
+    }
+    timings.push(("select all", t.elapsed()));
+
+    let t = Instant::now();
+    let prompt = build_prompt(&mut app);
+    timings.push(("build prompt", t.elapsed()));
+
+    let t = Instant::now();
+    let tokens = crate::token_count::count_tokens(&prompt);
+    timings.push(("count tokens", t.elapsed()));
+
+    println!(
+        "[bench] {} files loaded, prompt {} bytes, ~{} tokens",
+        app.files.len(),
+        prompt.len(),
+        tokens
+    );
+    for (label, d) in &timings {
+        println!(
+            "[bench] {:<22} {:>10.2} ms",
+            label,
+            d.as_secs_f64() * 1000.0
+        );
+    }
+
+    if keep {
+        println!("[bench] fixture kept at {}", root.display());
+    } else {
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
+
+/// Deterministic layout: a tree DIRS_PER_LEVEL wide holding FILES_PER_DIR files per
+/// directory, with file sizes varying by index so token counts are not uniform.
+fn generate_fixture(root: &Path, count: usize) -> std::io::Result<()> {
+    if root.exists() {
+        std::fs::remove_dir_all(root)?;
+    }
+    std::fs::create_dir_all(root)?;
+
+    let dir_count = count.div_ceil(FILES_PER_DIR).max(1);
+    let dirs: Vec<PathBuf> = (0..dir_count).map(|d| root.join(dir_path(d))).collect();
+
+    for i in 0..count {
+        let dir = &dirs[i / FILES_PER_DIR];
+        std::fs::create_dir_all(dir)?;
+        let ext = match i % 4 {
+            0 => "rs",
+            1 => "py",
+            2 => "md",
+            _ => "toml",
+        };
+        std::fs::write(dir.join(format!("file_{}.{}", i, ext)), file_body(i))?;
+    }
+    Ok(())
+}
+
+fn dir_path(mut d: usize) -> PathBuf {
+    let mut parts = Vec::new();
+    loop {
+        parts.push(format!("mod_{}", d % DIRS_PER_LEVEL));
+        d /= DIRS_PER_LEVEL;
+        if d == 0 {
+            break;
+        }
+    }
+    parts.iter().rev().collect()
+}
+
+fn file_body(i: usize) -> String {
+    let lines = 10 + (i * 7919) % 200;
+    let mut s = String::with_capacity(lines * 40);
+    for l in 0..lines {
+        let _ = writeln!(s, "// synthetic line {} of file {}", l, i);
+    }
+    s
+}
@@ -0,0 +1,190 @@
+// Colored per-file diff with per-hunk accept, shown before proposed changes are written.
+
+use eframe::egui;
+use similar::{DiffOp, DiffTag, TextDiff};
+
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    Unified,
+    SideBySide,
+}
+
+pub struct Hunk {
+    pub ops: Vec<DiffOp>,
+    pub accepted: bool,
+}
+
+impl Hunk {
+    fn old_range(&self) -> std::ops::Range<usize> {
+        let first = self.ops.first().map_or(0, |op| op.old_range().start);
+        let last = self.ops.last().map_or(0, |op| op.old_range().end);
+        first..last
+    }
+
+    fn new_range(&self) -> std::ops::Range<usize> {
+        let first = self.ops.first().map_or(0, |op| op.new_range().start);
+        let last = self.ops.last().map_or(0, |op| op.new_range().end);
+        first..last
+    }
+
+    fn header(&self) -> String {
+        let (old, new) = (self.old_range(), self.new_range());
+        format!(
+            "@@ -{},{} +{},{} @@",
+            old.start + 1,
+            old.len(),
+            new.start + 1,
+            new.len()
+        )
+    }
+}
+
+pub struct FileDiff {
+    old_lines: Vec<String>,
+    new_lines: Vec<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+fn lines(text: &str) -> Vec<String> {
+    text.split_inclusive('\n').map(str::to_string).collect()
+}
+
+impl FileDiff {
+    /// Every hunk starts accepted.
+    pub fn new(old: &str, new: &str) -> Self {
+        let hunks = TextDiff::from_lines(old, new)
+            .grouped_ops(CONTEXT_LINES)
+            .into_iter()
+            .map(|ops| Hunk {
+                ops,
+                accepted: true,
+            })
+            .collect();
+        Self {
+            old_lines: lines(old),
+            new_lines: lines(new),
+            hunks,
+        }
+    }
+
+    pub fn added_removed(&self) -> (usize, usize) {
+        let (mut added, mut removed) = (0, 0);
+        for op in self.hunks.iter().flat_map(|h| &h.ops) {
+            if op.tag() != DiffTag::Equal {
+                added += op.new_range().len();
+                removed += op.old_range().len();
+            }
+        }
+        (added, removed)
+    }
+
+    pub fn accepted(&self) -> usize {
+        self.hunks.iter().filter(|h| h.accepted).count()
+    }
+
+    /// The old text with only the accepted hunks applied.
+    pub fn merged(&self) -> String {
+        let mut out = String::new();
+        let mut pos = 0;
+        for hunk in &self.hunks {
+            let old = hunk.old_range();
+            out.extend(self.old_lines[pos..old.start].iter().map(String::as_str));
+            if hunk.accepted {
+                out.extend(self.new_lines[hunk.new_range()].iter().map(String::as_str));
+            } else {
+                out.extend(self.old_lines[old.clone()].iter().map(String::as_str));
+            }
+            pos = old.end;
+        }
+        out.extend(self.old_lines[pos..].iter().map(String::as_str));
+        out
+    }
+}
+
+fn line_text(line: &str) -> &str {
+    line.trim_end_matches(['\n', '\r'])
+}
+
+fn row(ui: &mut egui::Ui, prefix: char, line: &str, color: Option<egui::Color32>) {
+    let mut text = egui::RichText::new(format!("{}{}", prefix, line_text(line))).monospace();
+    if let Some(color) = color {
+        text = text.color(color);
+    }
+    ui.label(text);
+}
+
+fn show_unified(ui: &mut egui::Ui, diff: &FileDiff, hunk: &Hunk) {
+    for op in &hunk.ops {
+        let (tag, old, new) = op.as_tag_tuple();
+        if matches!(tag, DiffTag::Equal) {
+            for line in &diff.old_lines[old] {
+                row(ui, ' ', line, None);
+            }
+            continue;
+        }
+        for line in &diff.old_lines[old] {
+            row(ui, '-', line, Some(egui::Color32::LIGHT_RED));
+        }
+        for line in &diff.new_lines[new] {
+            row(ui, '+', line, Some(egui::Color32::LIGHT_GREEN));
+        }
+    }
+}
+
+fn show_side_by_side(ui: &mut egui::Ui, diff: &FileDiff, hunk: &Hunk, id: egui::Id) {
+    egui::Grid::new(id)
+        .num_columns(2)
+        .min_col_width(ui.available_width() / 2.0 - 8.0)
+        .show(ui, |ui| {
+            for op in &hunk.ops {
+                let (tag, old, new) = op.as_tag_tuple();
+                let rows = old.len().max(new.len());
+                for i in 0..rows {
+                    let left = diff.old_lines.get(old.start + i).filter(|_| i < old.len());
+                    let right = diff.new_lines.get(new.start + i).filter(|_| i < new.len());
+                    let (lc, rc) = match tag {
+                        DiffTag::Equal => (None, None),
+                        _ => (
+                            Some(egui::Color32::LIGHT_RED),
+                            Some(egui::Color32::LIGHT_GREEN),
+                        ),
+                    };
+                    match left {
+                        Some(line) => row(ui, ' ', line, lc),
+                        None => {
+                            ui.label("");
+                        }
+                    }
+                    match right {
+                        Some(line) => row(ui, ' ', line, rc),
+                        None => {
+                            ui.label("");
+                        }
+                    }
+                    ui.end_row();
+                }
+            }
+        });
+}
+
+/// Hunks with an accept checkbox each; `id` keeps widget state apart between files.
+pub fn show(ui: &mut egui::Ui, diff: &mut FileDiff, layout: Layout, id: egui::Id) {
+    for i in 0..diff.hunks.len() {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut diff.hunks[i].accepted, "Accept");
+            ui.label(
+                egui::RichText::new(diff.hunks[i].header())
+                    .monospace()
+                    .color(egui::Color32::LIGHT_BLUE),
+            );
+        });
+        let hunk = &diff.hunks[i];
+        match layout {
+            Layout::Unified => show_unified(ui, diff, hunk),
+            Layout::SideBySide => show_side_by_side(ui, diff, hunk, id.with(i)),
+        }
+        ui.add_space(4.0);
+    }
+}
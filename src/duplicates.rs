@@ -0,0 +1,90 @@
+// Selected files whose contents repeat another selected file: vendored copies, generated
+// duplicates, the same config in several packages. Files equal up to whitespace and line
+// endings are exact duplicates and can be sent once (the copies become empty `same_as`
+// entries); files sharing most of their lines are only reported, since dropping them
+// would lose the lines that differ.
+
+use crate::file_item::FileItem;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+/// Files shorter than this are ignored: empty `__init__.py`s and one-line stubs repeat
+/// everywhere without costing anything.
+const MIN_LINES: usize = 5;
+/// Share of distinct lines two files must have in common to count as near-identical.
+const NEAR_SIMILARITY: f32 = 0.9;
+
+pub struct Duplicate {
+    pub index: usize,
+    /// The earlier selected file it repeats.
+    pub original: usize,
+    /// Equal up to whitespace; otherwise near-identical.
+    pub exact: bool,
+}
+
+fn hash_of(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Trimmed, non-blank lines.
+fn lines(text: &str) -> Vec<&str> {
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect()
+}
+
+/// Duplicates among `indices` (in prompt order), from their read `content`. Each copy
+/// points at the first file with that content.
+pub fn find(files: &[FileItem], indices: &[usize]) -> Vec<Duplicate> {
+    let mut found = Vec::new();
+    let mut by_hash: HashMap<u64, usize> = HashMap::new();
+    // Distinct line hashes of the files kept so far, for the near-identical check
+    let mut kept: Vec<(usize, HashSet<u64>)> = Vec::new();
+    for &i in indices {
+        let Some(content) = files[i].content.as_deref() else {
+            continue;
+        };
+        let lines = lines(content);
+        if lines.len() < MIN_LINES {
+            continue;
+        }
+        let hash = hash_of(&lines);
+        if let Some(&original) = by_hash.get(&hash) {
+            found.push(Duplicate {
+                index: i,
+                original,
+                exact: true,
+            });
+            continue;
+        }
+        by_hash.insert(hash, i);
+        let set: HashSet<u64> = lines.iter().map(hash_of).collect();
+        let similar = kept.iter().find(|(_, other)| {
+            let (small, large) = if set.len() <= other.len() {
+                (&set, other)
+            } else {
+                (other, &set)
+            };
+            // Jaccard similarity can't reach the threshold past this size ratio
+            if (small.len() as f32) < large.len() as f32 * NEAR_SIMILARITY {
+                return false;
+            }
+            let common = small.iter().filter(|h| large.contains(h)).count();
+            let union = small.len() + large.len() - common;
+            common as f32 >= union as f32 * NEAR_SIMILARITY
+        });
+        match similar {
+            Some(&(original, _)) => found.push(Duplicate {
+                index: i,
+                original,
+                exact: false,
+            }),
+            None => kept.push((i, set)),
+        }
+    }
+    found
+}
@@ -0,0 +1,45 @@
+// "Open in editor": hands a project file, at a line, to the user's editor through a
+// configurable command. `{file}` and `{line}` are filled in per argument, so
+// `code -g {file}:{line}` and `nvim --server /tmp/nvim.sock --remote-silent +{line} {file}`
+// both work; a terminal editor needs its terminal in the command (`kitty nvim +{line} {file}`).
+
+use crate::error::{PromptError, Result};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+pub const DEFAULT_COMMAND: &str = "code -g {file}:{line}";
+
+/// Starts the editor without waiting for it; `line` defaults to the first.
+pub fn open(template: &str, path: &Path, line: Option<usize>) -> Result<()> {
+    let template = match template.trim() {
+        "" => DEFAULT_COMMAND,
+        t => t,
+    };
+    let words =
+        shell_words::split(template).map_err(|e| PromptError::CommandParse(e.to_string()))?;
+    let file = path.to_string_lossy();
+    let line = line.unwrap_or(1).to_string();
+    let mut words = words
+        .into_iter()
+        .map(|w| w.replace("{file}", &file).replace("{line}", &line));
+    let Some(program) = words.next() else {
+        return Err(PromptError::CommandParse(
+            "the editor command is empty".into(),
+        ));
+    };
+    let mut child = Command::new(&program)
+        .args(words)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|source| PromptError::Command {
+            command: program,
+            source,
+        })?;
+    // Reap it when it exits (editors that hand off to a running instance exit at once)
+    std::thread::spawn(move || {
+        let _ = child.wait();
+    });
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use std::path::PathBuf;
+
+/// Crate-wide error type. Variants carry enough context (paths, URLs, commands) that the
+/// `Display` text can be shown to the user as-is.
+#[derive(Debug, thiserror::Error)]
+pub enum PromptError {
+    #[error("failed to {action} {}: {source}", path.display())]
+    Io {
+        action: &'static str,
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("system prompt not found. Tried: {tried}")]
+    SystemPromptNotFound { tried: String },
+
+    #[error("invalid ignore pattern `{pattern}`: {message}")]
+    IgnorePattern { pattern: String, message: String },
+
+    #[error("invalid glob `{pattern}`: {message}")]
+    Glob { pattern: String, message: String },
+
+    #[error("invalid config {}: {message}", path.display())]
+    Config { path: PathBuf, message: String },
+
+    #[error("error parsing command: {0}")]
+    CommandParse(String),
+
+    #[error("failed to run `{command}`: {source}")]
+    Command {
+        command: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("interactive shell: {0}")]
+    Pty(String),
+
+    #[error("error fetching {url}: {message}")]
+    Fetch { url: String, message: String },
+
+    #[error("clipboard unavailable: {0}")]
+    Clipboard(String),
+
+    #[error("failed to read archive {}: {message}", path.display())]
+    Archive { path: PathBuf, message: String },
+
+    #[error("failed to read image {}: {message}", path.display())]
+    Image { path: PathBuf, message: String },
+
+    #[error("failed to read table {}: {message}", path.display())]
+    Table { path: PathBuf, message: String },
+
+    #[error("git: {0}")]
+    Git(String),
+
+    #[error("not a valid selection share: {0}")]
+    Share(String),
+
+    #[error("cancelled")]
+    Cancelled,
+}
+
+impl PromptError {
+    pub fn io(action: &'static str, path: impl Into<PathBuf>, source: std::io::Error) -> Self {
+        PromptError::Io {
+            action,
+            path: path.into(),
+            source,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, PromptError>;
@@ -0,0 +1,92 @@
+// `file:line` references in command output — rustc/cargo (`--> src/x.rs:3:5`), pytest
+// and gcc-style (`path:12:`), Python tracebacks, tsc (`a.ts(3,5)` and `a.ts:3:5`) and
+// eslint's grouped format — resolved against the project's file list.
+
+use crate::file_item::FileItem;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::path::Path;
+
+static RUSTC: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*--> ([^\s:]+):(\d+):\d+").unwrap());
+static TRACEBACK: Lazy<Regex> = Lazy::new(|| Regex::new(r#"File "([^"]+)", line (\d+)"#).unwrap());
+static TSC_PARENS: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^([^\s():]+\.\w+)\((\d+),\d+\)").unwrap());
+static COLON: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?:^|\s)([\w./\\-]+\.\w+):(\d+)(?::\d+)?(?:[:\s]|$)").unwrap());
+/// eslint prints the file on its own line, then indented `line:col  error  message` rows.
+static ESLINT_ROW: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\s+(\d+):\d+\s+(?:error|warning)\s").unwrap());
+static BARE_PATH: Lazy<Regex> = Lazy::new(|| Regex::new(r"^([^\s]+\.\w+)\s*$").unwrap());
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct ErrorRef {
+    /// As printed; see `resolve`.
+    pub path: String,
+    /// 1-based.
+    pub line: usize,
+}
+
+/// References in order of first appearance, without duplicates.
+pub fn parse(output: &str) -> Vec<ErrorRef> {
+    let mut refs: Vec<ErrorRef> = Vec::new();
+    let mut eslint_file: Option<&str> = None;
+    for line in output.lines() {
+        let found = if let Some(c) = RUSTC.captures(line) {
+            Some((c.get(1).unwrap().as_str(), c.get(2).unwrap().as_str()))
+        } else if let Some(c) = TRACEBACK.captures(line) {
+            Some((c.get(1).unwrap().as_str(), c.get(2).unwrap().as_str()))
+        } else if let Some(c) = TSC_PARENS.captures(line) {
+            Some((c.get(1).unwrap().as_str(), c.get(2).unwrap().as_str()))
+        } else if let (Some(file), Some(c)) = (eslint_file, ESLINT_ROW.captures(line)) {
+            Some((file, c.get(1).unwrap().as_str()))
+        } else {
+            COLON
+                .captures(line)
+                .map(|c| (c.get(1).unwrap().as_str(), c.get(2).unwrap().as_str()))
+        };
+        if let Some(c) = BARE_PATH.captures(line) {
+            eslint_file = Some(c.get(1).unwrap().as_str());
+        } else if line.trim().is_empty() {
+            eslint_file = None;
+        }
+        let Some((path, number)) = found else {
+            continue;
+        };
+        let Ok(number) = number.parse() else {
+            continue;
+        };
+        let r = ErrorRef {
+            path: path.replace('\\', "/"),
+            line: number,
+        };
+        if !refs.contains(&r) {
+            refs.push(r);
+        }
+    }
+    refs
+}
+
+/// Index of the project file a printed path refers to: absolute paths under `base`,
+/// paths relative to the project root, or relative to a subfolder (a workspace crate,
+/// a package dir) when exactly one file ends with it.
+pub fn resolve(files: &[FileItem], base: &Path, path: &str) -> Option<usize> {
+    let base = base.to_string_lossy().replace('\\', "/");
+    let rel = path
+        .strip_prefix(base.trim_end_matches('/'))
+        .map(|p| p.trim_start_matches('/'))
+        .unwrap_or(path)
+        .trim_start_matches("./");
+    let normalized = |f: &FileItem| f.rel_path.replace('\\', "/");
+    if let Some(i) = files.iter().position(|f| normalized(f) == rel) {
+        return Some(i);
+    }
+    let suffix = format!("/{}", rel);
+    let mut matches = files
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| normalized(f).ends_with(&suffix));
+    match (matches.next(), matches.next()) {
+        (Some((i, _)), None) => Some(i),
+        _ => None,
+    }
+}
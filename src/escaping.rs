@@ -0,0 +1,161 @@
+// How text content is embedded inside the prompt's XML tags. CDATA is the default; some
+// chat UIs strip or mangle CDATA sections, so content can instead be entity-escaped or
+// fenced between sentinel lines that never occur in the content.
+
+use serde::{Deserialize, Serialize};
+
+const SENTINEL: &str = "CONTENT";
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Escaping {
+    #[default]
+    Cdata,
+    Entities,
+    Sentinels,
+}
+
+impl Escaping {
+    pub const ALL: [Escaping; 3] = [Escaping::Cdata, Escaping::Entities, Escaping::Sentinels];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Escaping::Cdata => "CDATA",
+            Escaping::Entities => "Entities",
+            Escaping::Sentinels => "Sentinels",
+        }
+    }
+
+    pub fn wrap(self, s: &str) -> String {
+        match self {
+            Escaping::Cdata => {
+                let safe = s.replace("]]>", "]]]]><![CDATA[>");
+                format!("<![CDATA[{}]]>", safe)
+            }
+            Escaping::Entities => s
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;"),
+            Escaping::Sentinels => {
+                let tag = sentinel_for(s);
+                format!("<<<{tag}\n{s}\n{tag}>>>")
+            }
+        }
+    }
+
+    /// Inverse of `wrap`; None if `s` isn't in this strategy's form.
+    pub fn unwrap(self, s: &str) -> Option<String> {
+        match self {
+            Escaping::Cdata => {
+                // Concatenate consecutive sections; `wrap` splits around every "]]>"
+                let mut out = String::new();
+                let mut rest = s;
+                while !rest.is_empty() {
+                    let body = rest.strip_prefix("<![CDATA[")?;
+                    let end = body.find("]]>")?;
+                    out.push_str(&body[..end]);
+                    rest = &body[end + 3..];
+                }
+                Some(out)
+            }
+            Escaping::Entities => Some(
+                s.replace("&lt;", "<")
+                    .replace("&gt;", ">")
+                    .replace("&amp;", "&"),
+            ),
+            Escaping::Sentinels => {
+                let (tag, rest) = s.strip_prefix("<<<")?.split_once('\n')?;
+                rest.strip_suffix(&format!("\n{tag}>>>"))
+                    .map(str::to_string)
+            }
+        }
+    }
+}
+
+/// `CONTENT`, or `CONTENT_2`, `CONTENT_3`, ... if the content already contains a marker.
+fn sentinel_for(s: &str) -> String {
+    let mut tag = SENTINEL.to_string();
+    let mut n = 1;
+    while s.contains(&format!("<<<{tag}")) || s.contains(&format!("{tag}>>>")) {
+        n += 1;
+        tag = format!("{SENTINEL}_{n}");
+    }
+    tag
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: &[&str] = &[
+        "",
+        "plain text",
+        "fn main() {\n    println!(\"<hi> & bye\");\n}\n",
+        "]]>",
+        "a]]>b]]>c",
+        "]]]]><![CDATA[>",
+        "<![CDATA[nested]]>",
+        "&lt; already escaped &amp;gt; &amp;amp;",
+        "<<<CONTENT\ninner\nCONTENT>>>",
+        "CONTENT>>> and <<<CONTENT_2",
+        "\n\nleading and trailing newlines\n\n",
+        "tabs\tand\r\nCRLF\r\n",
+        "unicode: héllo — 日本語 🚀",
+    ];
+
+    #[test]
+    fn every_strategy_round_trips() {
+        for strategy in Escaping::ALL {
+            for &sample in SAMPLES {
+                let wrapped = strategy.wrap(sample);
+                assert_eq!(
+                    strategy.unwrap(&wrapped).as_deref(),
+                    Some(sample),
+                    "{} failed on {:?} (wrapped: {:?})",
+                    strategy.label(),
+                    sample,
+                    wrapped
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cdata_output_has_no_premature_terminator() {
+        for &sample in SAMPLES {
+            let wrapped = Escaping::Cdata.wrap(sample);
+            // Every "]]>" must be immediately followed by a new section or be the end
+            let mut rest = wrapped.as_str();
+            while let Some(i) = rest.find("]]>") {
+                rest = &rest[i + 3..];
+                assert!(
+                    rest.is_empty() || rest.starts_with("<![CDATA["),
+                    "{:?}",
+                    wrapped
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn entities_output_has_no_markup() {
+        for &sample in SAMPLES {
+            let wrapped = Escaping::Entities.wrap(sample);
+            assert!(
+                !wrapped.contains('<') && !wrapped.contains('>'),
+                "{:?}",
+                wrapped
+            );
+        }
+    }
+
+    #[test]
+    fn sentinel_never_appears_in_content() {
+        for &sample in SAMPLES {
+            let wrapped = Escaping::Sentinels.wrap(sample);
+            let tag = wrapped[3..].split('\n').next().unwrap();
+            assert!(!sample.contains(&format!("{tag}>>>")), "{:?}", wrapped);
+            assert!(!sample.contains(&format!("<<<{tag}")), "{:?}", wrapped);
+        }
+    }
+}
@@ -0,0 +1,231 @@
+// Evaluation mode: apply the patch from a pasted response, run the project's verify
+// command, and record pass/fail next to the prompt in `.prompt/eval_history.jsonl`. The
+// patch is only applied for the run: the files go back to how they were afterwards, so
+// every turn is judged against the same code.
+
+use crate::code_state::CodeState;
+use crate::error::{PromptError, Result};
+use chrono::{DateTime, Local};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+const OUTPUT_TAIL_LINES: usize = 40;
+
+/// Numbers the patch files of this process, so concurrent evals never share one.
+static PATCH_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone)]
+pub struct EvalResult {
+    pub at: DateTime<Local>,
+    pub passed: bool,
+    /// Why it failed (or what passed): last lines of the failing step's output.
+    pub summary: String,
+}
+
+pub struct EvalUpdate {
+    pub turn: usize,
+    pub result: EvalResult,
+}
+
+#[derive(Default)]
+pub struct Evaluator {
+    pub enabled: bool,
+}
+
+/// Unified diff(s) in a response: fenced ```diff / ```patch blocks, or the whole text if
+/// it already is a bare diff.
+pub fn extract_patch(response: &str) -> Option<String> {
+    let mut patch = String::new();
+    let mut in_block = false;
+    for line in response.lines() {
+        let trimmed = line.trim_start();
+        if !in_block && (trimmed.starts_with("```diff") || trimmed.starts_with("```patch")) {
+            in_block = true;
+        } else if in_block && trimmed.starts_with("```") {
+            in_block = false;
+        } else if in_block {
+            patch.push_str(line);
+            patch.push('\n');
+        }
+    }
+    if patch.is_empty() {
+        let bare = response.trim_start();
+        if bare.starts_with("diff --git") || bare.starts_with("--- ") {
+            patch = response.to_string();
+            if !patch.ends_with('\n') {
+                patch.push('\n');
+            }
+        }
+    }
+    (!patch.is_empty()).then_some(patch)
+}
+
+fn tail(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    lines[lines.len().saturating_sub(OUTPUT_TAIL_LINES)..].join("\n")
+}
+
+/// Success and output (stdout, then stderr) of `program`; setting `cancel` kills it.
+fn run(
+    base: &Path,
+    program: &str,
+    args: &[&str],
+    cancel: Option<&AtomicBool>,
+) -> Result<(bool, String)> {
+    // No head/tail cut here; `tail` keeps the end
+    let output = crate::command_runner::run_command(
+        base,
+        program,
+        args,
+        usize::MAX / 2,
+        0,
+        false,
+        Duration::ZERO,
+        &[],
+        "",
+        None,
+        cancel,
+    )?;
+    Ok((output.exit_code == Some(0), output.text))
+}
+
+/// `git apply` a unified diff in `base`; returns success and git's output. The files it
+/// touches are snapshotted first so the apply can be reverted.
+pub fn apply_patch(base: &Path, patch: &str) -> Result<(bool, String)> {
+    apply(base, patch).map(|(ok, out, _)| (ok, out))
+}
+
+/// `apply_patch`, also returning the snapshot when the patch applied.
+fn apply(base: &Path, patch: &str) -> Result<(bool, String, Option<PathBuf>)> {
+    let backup = crate::backups::snapshot(base, &crate::backups::patch_paths(patch))?;
+    let file = std::env::temp_dir().join(format!(
+        "prompt-{}-{}.patch",
+        std::process::id(),
+        PATCH_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    // create_new: never write through a file (or link) someone else left at that name
+    let written = match std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&file)
+    {
+        Ok(mut f) => f.write_all(patch.as_bytes()).inspect_err(|_| {
+            let _ = std::fs::remove_file(&file);
+        }),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = written {
+        crate::backups::discard(&backup);
+        return Err(PromptError::io("write", &file, e));
+    }
+    let result = run(
+        base,
+        "git",
+        &["apply", "--whitespace=nowarn", &file.to_string_lossy()],
+        None,
+    );
+    let _ = std::fs::remove_file(&file);
+    match result {
+        Ok((true, out)) => Ok((true, out, Some(backup))),
+        Ok((false, out)) => {
+            crate::backups::discard(&backup);
+            Ok((false, out, None))
+        }
+        Err(e) => {
+            crate::backups::discard(&backup);
+            Err(e)
+        }
+    }
+}
+
+fn evaluate(
+    base: &Path,
+    patch: Option<&str>,
+    verify: &str,
+    cancel: &AtomicBool,
+) -> Result<EvalResult> {
+    let failed = |summary: String| EvalResult {
+        at: Local::now(),
+        passed: false,
+        summary,
+    };
+    let tokens =
+        shell_words::split(verify).map_err(|e| PromptError::CommandParse(e.to_string()))?;
+    let Some((program, args)) = tokens.split_first() else {
+        return Err(PromptError::CommandParse(
+            "no verify command configured".into(),
+        ));
+    };
+    let mut backup = None;
+    if let Some(patch) = patch {
+        let (ok, out, applied) = apply(base, patch)?;
+        if !ok {
+            return Ok(failed(format!("patch did not apply:\n{}", tail(&out))));
+        }
+        backup = applied;
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let verified = run(base, program, &args, Some(cancel));
+    // Also after a cancel or a failed run: the patch never stays applied
+    if let Some(backup) = backup {
+        crate::backups::restore(base, &backup)?;
+    }
+    let (passed, out) = verified?;
+    Ok(EvalResult {
+        at: Local::now(),
+        passed,
+        summary: tail(&out),
+    })
+}
+
+/// Runs as a job; errors become failed results so the UI always hears back.
+pub fn evaluate_turn(
+    turn: usize,
+    base: &Path,
+    patch: Option<&str>,
+    verify: &str,
+    cancel: &AtomicBool,
+) -> EvalUpdate {
+    let result = evaluate(base, patch, verify, cancel).unwrap_or_else(|e| EvalResult {
+        at: Local::now(),
+        passed: false,
+        summary: e.to_string(),
+    });
+    EvalUpdate { turn, result }
+}
+
+pub fn history_path(base: &Path) -> PathBuf {
+    base.join(".prompt").join("eval_history.jsonl")
+}
+
+/// Appends one JSON line per evaluation, so runs can be compared across prompt revisions.
+pub fn append_history(
+    base: &Path,
+    instruction: &str,
+    prompt: &str,
+    verify: &str,
+    snapshot: Option<&CodeState>,
+    result: &EvalResult,
+) -> Result<()> {
+    let path = history_path(base);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| PromptError::io("create", dir, e))?;
+    }
+    let line = serde_json::json!({
+        "at": result.at.to_rfc3339(),
+        "passed": result.passed,
+        "verify_command": verify,
+        "instruction": instruction,
+        "prompt": prompt,
+        "snapshot": snapshot.map(CodeState::to_json),
+        "summary": result.summary,
+    });
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| PromptError::io("open", &path, e))?;
+    writeln!(file, "{}", line).map_err(|e| PromptError::io("write", &path, e))
+}
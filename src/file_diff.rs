@@ -0,0 +1,43 @@
+// "Include only changes": a selected file can be sent as its `git diff` against HEAD, or
+// the ref in `ProjectConfig::diff_ref`, instead of its full contents, to ask about a
+// change without paying for the untouched parts of the file. A file that isn't in that
+// revision (untracked, newly added, or outside a git checkout) has no useful diff and is
+// sent in full. Chosen per file from its context menu (`ProjectConfig::diff_only`).
+
+use crate::code_state::git;
+use crate::error::{PromptError, Result};
+use std::path::Path;
+
+pub const DEFAULT_REF: &str = "HEAD";
+
+/// The diff of `rel_path` (relative to `base`) against `reference`; None when that
+/// revision doesn't have the file, so it should be sent in full.
+pub fn diff(base: &Path, rel_path: &str, reference: &str) -> Result<Option<String>> {
+    let reference = match reference.trim() {
+        "" => DEFAULT_REF,
+        reference => reference,
+    };
+    // `<rev>:./<path>` resolves against the working directory rather than the repo root
+    let object = format!("{}:./{}", reference, rel_path);
+    match git(base, &["cat-file", "-e", &object]) {
+        Ok(_) => {}
+        Err(PromptError::Git(_)) => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let diff = git(
+        base,
+        &[
+            "diff",
+            "--no-color",
+            "--no-ext-diff",
+            reference,
+            "--",
+            rel_path,
+        ],
+    )?;
+    Ok(Some(if diff.trim().is_empty() {
+        format!("[no changes against {}]\n", reference)
+    } else {
+        diff
+    }))
+}
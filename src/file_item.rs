@@ -1,16 +1,59 @@
+use crate::error::{PromptError, Result};
+use crate::intern::RelPath;
+use crate::language_packs::LanguagePack;
+use crate::languages::Language;
+use crate::text_hygiene::TextIssues;
 use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::collections::{BTreeSet, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
 
+/// Files loaded up front. Directories past this many (breadth-first, in path order) are
+/// deferred: listed in the tree unwalked, and loaded when expanded.
 pub const MAX_FILES: usize = 10_000;
+// Cap on how many ignored paths are remembered for the "ignored paths" debug list
+const MAX_IGNORED_PATHS: usize = 5_000;
+/// How often (in files found) a scan reports progress.
+const PROGRESS_EVERY: usize = 500;
 
 #[derive(Clone)]
 pub struct FileItem {
     pub path: PathBuf,
-    pub rel_path: String,
+    pub rel_path: RelPath,
     pub selected: bool,
     pub content: Option<String>,
+    pub content_modified: Option<std::time::SystemTime>, // on-disk time when content was read
+    pub content_stale: bool, // changed on disk since content was read, which was dropped
+    pub content_raw: bool,   // content is the file as read: not truncated, numbered or rewritten
     pub token_count: usize,
+    pub exact_tokens: bool, // token_count came from the tokenizer rather than the file size
+    pub tree_only: bool,    // listed in <file_tree> but never included as content
+    pub summarize_table: bool, // sent as a table summary; see tables.rs
+    pub diff_only: bool,    // sent as its git diff; see file_diff.rs
+    pub archive_entry: Option<String>, // entry name inside the archive at `path`; see archives.rs
+    pub size: u64,
+    pub modified: Option<std::time::SystemTime>,
+    pub text_issues: TextIssues, // filled in when contents are read for a prompt
+    pub language: Option<&'static Language>, // likewise; sent as the file tag's `lang`
+    pub note: Option<String>,    // from .prompt/notes.json
+}
+
+impl FileItem {
+    /// The whole text of the file, or of its entry when it's inside an archive; text
+    /// that isn't UTF-8 is an error, as with `fs::read_to_string`.
+    pub fn read_text(&self) -> Result<String> {
+        let Some(entry) = &self.archive_entry else {
+            return fs::read_to_string(&self.path)
+                .map_err(|e| PromptError::io("read", &self.path, e));
+        };
+        let (bytes, _) = crate::archives::read_entry(&self.path, entry, usize::MAX)?;
+        String::from_utf8(bytes).map_err(|e| {
+            let e = std::io::Error::new(std::io::ErrorKind::InvalidData, e);
+            PromptError::io("read", self.path.join(entry), e)
+        })
+    }
 }
 
 pub fn find_ignore_file(start: &Path) -> Option<PathBuf> {
@@ -38,137 +81,479 @@ pub fn find_ignore_file(start: &Path) -> Option<PathBuf> {
     None
 }
 
-pub fn load_ignore_set_from(base: &Path) -> GlobSet {
+/// Used when the project has no `.promptignore`, ahead of the language packs' rules.
+const FALLBACK_IGNORES: &str = "**/target/**\n**/.git/**\n**/node_modules/**\n*.tmp\n";
+
+/// The rules a scan applies given the `.promptignore` contents (None when there is no
+/// file): the file plus the packs' generated-file patterns, or the fallback plus the
+/// packs' ignores. Shared with the ignore editor so its preview matches the scan.
+pub fn ignore_rules(contents: Option<&str>, packs: &[&LanguagePack]) -> String {
+    match contents {
+        Some(contents) => format!(
+            "{}\n{}",
+            contents,
+            crate::language_packs::generated_text(packs)
+        ),
+        None => format!(
+            "{}{}",
+            FALLBACK_IGNORES,
+            crate::language_packs::ignore_text(packs)
+        ),
+    }
+}
+
+/// Rule lines in ignore file text: neither blank nor comments. One line can compile to
+/// several globs, so this is what the user counts as patterns.
+pub fn rule_count(contents: &str) -> usize {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .count()
+}
+
+/// The project's `.promptignore` (or the fallback plus the packs' ignores when there is
+/// none); the packs' generated-file patterns apply either way. Also returns how many
+/// rule lines the set was built from, and the invalid patterns, which are skipped.
+pub fn load_ignore_set_from(
+    base: &Path,
+    packs: &[&LanguagePack],
+) -> (GlobSet, usize, Vec<PromptError>) {
     let ignore_path =
         find_ignore_file(base).unwrap_or_else(|| base.join(".prompt").join(".promptignore"));
     eprintln!("Loading ignore patterns from {:?}", ignore_path);
+    let contents = fs::read_to_string(ignore_path).ok();
+    let rules = ignore_rules(contents.as_deref(), packs);
+    let (gs, errors) = build_ignore_set(&rules);
+    eprintln!("Loaded {} ignore patterns.", gs.len());
+    (gs, rule_count(&rules), errors)
+}
+
+/// Build a glob set from `.promptignore` contents (used for both the file on disk
+/// and the unsaved text in the editor). Invalid patterns are skipped and returned
+/// alongside the set so callers can surface them.
+pub fn build_ignore_set(contents: &str) -> (GlobSet, Vec<PromptError>) {
     let mut builder = GlobSetBuilder::new();
-    if let Ok(contents) = fs::read_to_string(ignore_path) {
-        for line in contents.lines() {
-            let trimmed = line.trim();
-            if trimmed.is_empty() || trimmed.starts_with('#') {
-                continue;
-            }
+    let mut errors = Vec::new();
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
 
-            let mut patterns: Vec<String> = Vec::new();
+        let mut patterns: Vec<String> = Vec::new();
 
-            if trimmed.ends_with('/') {
-                // Directory pattern: ignore the dir itself and all its contents at any depth
-                let d = trimmed.trim_end_matches('/');
-                if !d.is_empty() {
-                    patterns.push(format!("**/{}", d));
-                    patterns.push(format!("**/{}/**", d));
-                }
-            } else if trimmed.contains('/') {
-                // Path pattern (may include globs): match at any depth
+        if trimmed.ends_with('/') {
+            // Directory pattern: ignore the dir itself and all its contents at any depth
+            let d = trimmed.trim_end_matches('/');
+            if !d.is_empty() {
+                patterns.push(format!("**/{}", d));
+                patterns.push(format!("**/{}/**", d));
+            }
+        } else if trimmed.contains('/') {
+            // Path pattern (may include globs): match at any depth
+            patterns.push(format!("**/{}", trimmed));
+        } else {
+            // Basename pattern
+            let has_glob = trimmed.chars().any(|c| matches!(c, '*' | '?' | '['));
+            if has_glob {
+                // e.g., *.tmp -> **/*.tmp
                 patterns.push(format!("**/{}", trimmed));
             } else {
-                // Basename pattern
-                let has_glob = trimmed.chars().any(|c| matches!(c, '*' | '?' | '['));
-                if has_glob {
-                    // e.g., *.tmp -> **/*.tmp
-                    patterns.push(format!("**/{}", trimmed));
-                } else {
-                    // Name without globs: ignore file or directory with this name anywhere
-                    patterns.push(format!("**/{}", trimmed));
-                    patterns.push(format!("**/{}/**", trimmed));
-                }
+                // Name without globs: ignore file or directory with this name anywhere
+                patterns.push(format!("**/{}", trimmed));
+                patterns.push(format!("**/{}/**", trimmed));
             }
+        }
 
-            for pat in patterns {
-                if let Ok(glob) = Glob::new(&pat) {
+        for pat in patterns {
+            match Glob::new(&pat) {
+                Ok(glob) => {
                     builder.add(glob);
                 }
+                Err(e) => {
+                    errors.push(PromptError::IgnorePattern {
+                        pattern: trimmed.to_string(),
+                        message: e.kind().to_string(),
+                    });
+                    break; // one report per line is enough
+                }
             }
         }
-    } else {
-        builder.add(Glob::new("**/target/**").unwrap());
-        builder.add(Glob::new("**/.git/**").unwrap());
-        builder.add(Glob::new("**/node_modules/**").unwrap());
-        builder.add(Glob::new("**/*.tmp").unwrap());
     }
-    let gs = builder.build().unwrap();
-    eprintln!("Loaded {} ignore patterns.", gs.len());
-    gs
+    match builder.build() {
+        Ok(set) => (set, errors),
+        Err(e) => {
+            errors.push(PromptError::IgnorePattern {
+                pattern: String::from("<all>"),
+                message: e.to_string(),
+            });
+            (GlobSet::empty(), errors)
+        }
+    }
 }
 
-pub fn get_all_files_limited(
-    base: &Path,
-    limit: usize,
-    ignore_set: &GlobSet,
-) -> (Vec<PathBuf>, usize, usize, usize, usize) {
-    let mut files = Vec::new();
-    let mut scanned_files: usize = 0; // file entries visited (not counting pruned subtrees)
-    let mut ignored_files: usize = 0; // files ignored by patterns
-    let mut ignored_dirs: usize = 0; // directories ignored (each counts recursively skipped subtree)
-    let mut symlinks_skipped: usize = 0; // symlink files/dirs skipped
-    let mut dirs = vec![base.to_path_buf()];
-    while let Some(current_dir) = dirs.pop() {
-        let rel_dir = current_dir.strip_prefix(base).unwrap_or(&current_dir);
-        if ignore_set.is_match(rel_dir.to_string_lossy().as_ref()) {
-            // this whole subtree is pruned; count files inside so Ignored reflects them
-            ignored_dirs += 1;
-            ignored_files += count_files_under(&current_dir);
+/// True if `rel_path` or any of its parent directories matches the set,
+/// mirroring how the scanner prunes ignored directories.
+pub fn is_path_ignored(ignore_set: &GlobSet, rel_path: &str) -> bool {
+    if ignore_set.is_match(rel_path) {
+        return true;
+    }
+    let mut dir = rel_path;
+    while let Some(pos) = dir.rfind('/') {
+        dir = &dir[..pos];
+        if !dir.is_empty() && ignore_set.is_match(dir) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Every file under `base` (ignore rules NOT applied), as '/'-separated relative paths.
+/// Used by the .promptignore editor to preview what a rule change would do.
+pub fn list_all_rel_paths(base: &Path, limit: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut stack: Vec<PathBuf> = vec![base.to_path_buf()];
+    while let Some(d) = stack.pop() {
+        let Ok(entries) = fs::read_dir(&d) else {
             continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(ft) = entry.file_type() else { continue };
+            if ft.is_symlink() {
+                continue;
+            }
+            let p = entry.path();
+            if ft.is_dir() {
+                stack.push(p);
+            } else if ft.is_file() {
+                if let Ok(rel) = p.strip_prefix(base) {
+                    out.push(rel.to_string_lossy().replace('\\', "/"));
+                }
+                if out.len() >= limit {
+                    return out;
+                }
+            }
         }
-        if let Ok(entries) = fs::read_dir(&current_dir) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                let rel_path = match path.strip_prefix(base) {
-                    Ok(r) => r,
-                    Err(_) => continue,
-                };
-                let rel_path_str = rel_path.to_string_lossy();
+    }
+    out
+}
 
-                // Use DirEntry::file_type to avoid following symlinks
-                let ft = match entry.file_type() {
-                    Ok(t) => t,
-                    Err(_) => continue,
-                };
+#[derive(Clone, Default)]
+pub struct ScanStats {
+    pub scanned_files: usize, // file entries visited (not counting pruned subtrees)
+    pub ignored_files: usize, // files ignored by patterns
+    pub ignored_dirs: usize,  // directories ignored (each counts recursively skipped subtree)
+    pub symlinks_skipped: usize, // symlink files/dirs skipped
+    pub pattern_count: usize, // rule lines behind the active ignore set
+    pub ignored_paths: Vec<String>, // pruned dirs (with trailing '/') and ignored files, capped
+    pub deferred_dirs: Vec<String>, // left unwalked past the file limit, '/'-separated
+}
 
-                // Skip symlinks entirely to avoid cycles/explosions
-                if ft.is_symlink() {
-                    symlinks_skipped += 1;
-                    continue;
+impl ScanStats {
+    pub fn ignored_paths_truncated(&self) -> bool {
+        self.ignored_paths.len() >= MAX_IGNORED_PATHS
+    }
+
+    fn record_ignored(&mut self, rel: String) {
+        if self.ignored_paths.len() < MAX_IGNORED_PATHS {
+            self.ignored_paths.push(rel);
+        }
+    }
+}
+
+/// A file found by a scan, with the metadata the size estimate and tree sorting use.
+pub struct ScannedFile {
+    pub path: PathBuf,
+    /// '/'-separated, relative to the project; under a followed symlink this is the path
+    /// through the link, while `path` is the file's real location.
+    pub rel_path: String,
+    pub size: u64,
+    pub modified: Option<std::time::SystemTime>,
+    /// Set for a file listed from inside the archive at `path`.
+    pub archive_entry: Option<String>,
+}
+
+/// What a scan walks and where it stops.
+#[derive(Clone)]
+pub struct ScanOptions {
+    pub ignore_set: GlobSet,
+    /// Rule lines `ignore_set` was built from, reported as `ScanStats::pattern_count`.
+    pub ignore_rules: usize,
+    /// Files to list before deferring the remaining directories; see `plan_deferred`.
+    pub limit: usize,
+    /// Directories (relative, '/'-separated) deferred by an earlier scan.
+    pub deferred: BTreeSet<String>,
+    /// Walk symlinked directories and include symlinked files instead of skipping them.
+    pub follow_symlinks: bool,
+    /// How many symlinks deep to follow (a link inside a followed link is depth 2).
+    pub symlink_depth: usize,
+}
+
+/// A symlinked directory found by a walk, followed after it.
+struct Link {
+    path: PathBuf,
+    rel: String,
+    depth: usize,
+}
+
+/// Shared state of one scan's walks.
+struct Walk<'a> {
+    options: &'a ScanOptions,
+    progress: &'a (dyn Fn(usize) + Sync),
+    cancel: &'a AtomicBool,
+    files: Mutex<Vec<ScannedFile>>,
+    stats: Mutex<ScanStats>,
+    links: Mutex<Vec<Link>>,
+    found: AtomicUsize,
+}
+
+/// Walks `dir` (`base` or a folder under it) on the `ignore` crate's parallel walker (its
+/// own gitignore handling is off; the ignore set decides), pruning ignored directories.
+/// Directories in `options.deferred`, and those `plan_deferred` leaves out once
+/// `options.limit` files are counted, are not descended but reported in
+/// `ScanStats::deferred_dirs`; a directory that is walked is always listed whole.
+/// Followed symlinked directories are walked after the tree holding them, in path order,
+/// skipping any whose target was already walked or contains the link (a cycle). `progress` gets the running file count every `PROGRESS_EVERY` files;
+/// setting `cancel` stops the walk early. The result is sorted by path, so a scan is
+/// stable however the threads interleave.
+pub fn get_all_files_limited(
+    base: &Path,
+    dir: &Path,
+    options: &ScanOptions,
+    progress: &(dyn Fn(usize) + Sync),
+    cancel: &AtomicBool,
+) -> (Vec<ScannedFile>, ScanStats) {
+    let walk = Walk {
+        options,
+        progress,
+        cancel,
+        files: Mutex::new(Vec::new()),
+        stats: Mutex::new(ScanStats {
+            pattern_count: options.ignore_rules,
+            ..Default::default()
+        }),
+        links: Mutex::new(Vec::new()),
+        found: AtomicUsize::new(0),
+    };
+    let rel_root = dir
+        .strip_prefix(base)
+        .map(|r| r.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_default();
+    walk.run(dir, &rel_root, 0);
+
+    // Canonical targets already walked; the project itself first
+    let mut visited: HashSet<PathBuf> = base.canonicalize().into_iter().collect();
+    loop {
+        let mut links = std::mem::take(&mut *walk.links.lock().unwrap_or_else(|e| e.into_inner()));
+        if links.is_empty() || cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        // Each link's walk gets what's left of the limit, so the order decides what's deferred
+        links.sort_by(|a, b| a.rel.cmp(&b.rel));
+        for link in links {
+            let target = link.path.canonicalize().ok();
+            let inside = link
+                .path
+                .parent()
+                .and_then(|p| p.canonicalize().ok())
+                .zip(target.as_ref())
+                .is_some_and(|(parent, target)| parent.starts_with(target));
+            match target {
+                Some(target) if !inside && visited.insert(target.clone()) => {
+                    walk.run(&target, &link.rel, link.depth)
                 }
+                _ => walk.lock_stats().symlinks_skipped += 1,
+            }
+        }
+    }
 
-                if ft.is_file() {
-                    scanned_files += 1;
-                    if ignore_set.is_match(rel_path_str.as_ref()) {
-                        ignored_files += 1;
-                        continue;
-                    }
-                    files.push(path);
-                    if files.len() >= limit {
-                        break;
-                    }
-                } else if ft.is_dir() {
-                    if ignore_set.is_match(rel_path_str.as_ref()) {
-                        // prune this subtree and count files within it
-                        ignored_dirs += 1;
-                        ignored_files += count_files_under(&path);
-                        continue;
+    let mut files = walk.files.into_inner().unwrap_or_else(|e| e.into_inner());
+    let mut stats = walk.stats.into_inner().unwrap_or_else(|e| e.into_inner());
+    files.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+    stats.ignored_paths.sort();
+    stats.deferred_dirs.sort();
+    (files, stats)
+}
+
+impl Walk<'_> {
+    fn lock_stats(&self) -> std::sync::MutexGuard<'_, ScanStats> {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn add_file(&self, path: &Path, rel_path: String, meta: Option<fs::Metadata>) {
+        let file = ScannedFile {
+            path: path.to_path_buf(),
+            rel_path,
+            size: meta.as_ref().map(|m| m.len()).unwrap_or(0),
+            modified: meta.and_then(|m| m.modified().ok()),
+            archive_entry: None,
+        };
+        self.files
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(file);
+        let n = self.found.fetch_add(1, Ordering::Relaxed) + 1;
+        if n.is_multiple_of(PROGRESS_EVERY) {
+            (self.progress)(n);
+        }
+    }
+
+    /// The directories under `root` to defer so that about `options.limit` files (less
+    /// those already found) get listed: directories are taken breadth-first in path order,
+    /// each whole, until the count is reached, and the rest are deferred. Decided before
+    /// the parallel walk so that the same tree always defers the same folders.
+    fn plan_deferred(&self, root: &Path, rel_root: &str) -> HashSet<String> {
+        let options = self.options;
+        let mut deferred = HashSet::new();
+        if options.limit == usize::MAX {
+            return deferred;
+        }
+        let mut budget = options
+            .limit
+            .saturating_sub(self.found.load(Ordering::Relaxed));
+        let join = |rel: &str, name: &str| {
+            if rel.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}/{}", rel, name)
+            }
+        };
+        let mut queue =
+            std::collections::VecDeque::from([(root.to_path_buf(), rel_root.to_string())]);
+        let mut first = true;
+        while let Some((dir, key)) = queue.pop_front() {
+            if !std::mem::take(&mut first) && budget == 0 {
+                deferred.insert(key);
+                continue;
+            }
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            let mut entries: Vec<fs::DirEntry> = entries.flatten().collect();
+            entries.sort_by_key(|e| e.file_name());
+            for entry in entries {
+                let Ok(ft) = entry.file_type() else {
+                    continue;
+                };
+                let child = join(&key, &entry.file_name().to_string_lossy());
+                if ft.is_dir() {
+                    if child != crate::backups::BACKUPS_DIR
+                        && !options.ignore_set.is_match(&child)
+                        && !options.deferred.contains(&child)
+                    {
+                        queue.push_back((entry.path(), child));
                     }
-                    dirs.push(path);
+                } else if (ft.is_file() || (ft.is_symlink() && options.follow_symlinks))
+                    && !options.ignore_set.is_match(&child)
+                {
+                    budget = budget.saturating_sub(1);
                 }
             }
         }
-        if files.len() >= limit {
-            break;
-        }
+        deferred
     }
-    if files.len() >= limit {
-        rfd::MessageDialog::new()
-            .set_title("Warning")
-            .set_description(&format!(
-                "More than {} files detected. Only the first {} files will be loaded.",
-                limit, limit
-            ))
-            .set_level(rfd::MessageLevel::Warning)
-            .show();
+
+    /// Walks `root`, whose files are reported under `rel_root`; `link_depth` counts the
+    /// symlinks followed to get here.
+    fn run(&self, root: &Path, rel_root: &str, link_depth: usize) {
+        use ignore::WalkState;
+
+        let options = self.options;
+        let ignore_set = &options.ignore_set;
+        let planned = self.plan_deferred(root, rel_root);
+        ignore::WalkBuilder::new(root)
+            .standard_filters(false)
+            .follow_links(false)
+            .build_parallel()
+            .run(|| {
+                Box::new(|entry| {
+                    if self.cancel.load(Ordering::Relaxed) {
+                        return WalkState::Quit;
+                    }
+                    let Ok(entry) = entry else {
+                        return WalkState::Continue;
+                    };
+                    let path = entry.path();
+                    let Ok(rel_path) = path.strip_prefix(root) else {
+                        return WalkState::Continue;
+                    };
+                    let rel_path = rel_path.to_string_lossy().replace('\\', "/");
+                    let key = match (rel_root.is_empty(), rel_path.is_empty()) {
+                        (true, _) => rel_path,
+                        (false, true) => rel_root.to_string(),
+                        (false, false) => format!("{}/{}", rel_root, rel_path),
+                    };
+                    let Some(mut ft) = entry.file_type() else {
+                        return WalkState::Continue;
+                    };
+                    let mut meta = None;
+                    let mut stats = self.lock_stats();
+
+                    if ft.is_symlink() {
+                        // Skipped unless following; then dangling links still are
+                        let target = options
+                            .follow_symlinks
+                            .then(|| fs::metadata(path).ok())
+                            .flatten();
+                        let Some(target) = target else {
+                            stats.symlinks_skipped += 1;
+                            return WalkState::Continue;
+                        };
+                        if target.is_dir() {
+                            if ignore_set.is_match(&key) {
+                                stats.ignored_dirs += 1;
+                                stats.record_ignored(format!("{}/", key));
+                            } else if link_depth >= options.symlink_depth {
+                                stats.symlinks_skipped += 1;
+                            } else {
+                                self.links
+                                    .lock()
+                                    .unwrap_or_else(|e| e.into_inner())
+                                    .push(Link {
+                                        path: path.to_path_buf(),
+                                        rel: key,
+                                        depth: link_depth + 1,
+                                    });
+                            }
+                            return WalkState::Continue;
+                        }
+                        ft = target.file_type();
+                        meta = Some(target);
+                    }
+
+                    if ft.is_dir() {
+                        if key == crate::backups::BACKUPS_DIR {
+                            return WalkState::Skip;
+                        }
+                        if ignore_set.is_match(&key) {
+                            // prune this subtree and count files within it
+                            stats.ignored_dirs += 1;
+                            stats.ignored_files += count_files_under(path);
+                            stats.record_ignored(format!("{}/", key));
+                            return WalkState::Skip;
+                        }
+                        if entry.depth() > 0
+                            && (options.deferred.contains(&key) || planned.contains(&key))
+                        {
+                            stats.deferred_dirs.push(key);
+                            return WalkState::Skip;
+                        }
+                    } else if ft.is_file() {
+                        stats.scanned_files += 1;
+                        if ignore_set.is_match(&key) {
+                            stats.ignored_files += 1;
+                            stats.record_ignored(key);
+                            return WalkState::Continue;
+                        }
+                        drop(stats);
+                        let meta = meta.or_else(|| entry.metadata().ok());
+                        self.add_file(path, key, meta);
+                    }
+                    WalkState::Continue
+                })
+            });
     }
-    files.truncate(limit);
-    (files, scanned_files, ignored_files, ignored_dirs, symlinks_skipped)
 }
 
 fn count_files_under(dir: &Path) -> usize {
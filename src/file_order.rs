@@ -0,0 +1,84 @@
+// Order of the included files in the prompt. Where a file sits in the context window
+// affects how much attention it gets, so besides the path sort the files can be grouped
+// by directory, put in dependency order (a file after the files it imports), or arranged
+// by hand in the File order window. Every order is deterministic: ties go by path.
+
+use crate::file_item::FileItem;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileOrder {
+    #[default]
+    Path,
+    Directory,
+    Dependencies,
+    Manual,
+}
+
+impl FileOrder {
+    pub const ALL: [FileOrder; 4] = [
+        FileOrder::Path,
+        FileOrder::Directory,
+        FileOrder::Dependencies,
+        FileOrder::Manual,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FileOrder::Path => "Path",
+            FileOrder::Directory => "Directory",
+            FileOrder::Dependencies => "Dependencies first",
+            FileOrder::Manual => "Manual",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            FileOrder::Path => "Sorted by relative path.",
+            FileOrder::Directory => {
+                "Each directory's own files together, before its subdirectories."
+            }
+            FileOrder::Dependencies => {
+                "Imported files before the files that import them (Rust, JS/TS, Python); cycles are broken by path."
+            }
+            FileOrder::Manual => {
+                "As arranged below; newly selected files follow, by path."
+            }
+        }
+    }
+}
+
+fn parent_dir(rel_path: &str) -> &str {
+    rel_path.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+/// Rearranges `indices`, which arrive sorted by path. `manual` holds relative paths in
+/// the user's order for `FileOrder::Manual`.
+pub fn arrange(files: &[FileItem], indices: &mut Vec<usize>, order: FileOrder, manual: &[String]) {
+    match order {
+        FileOrder::Path => {}
+        FileOrder::Directory => {
+            indices.sort_by(|&a, &b| {
+                let (a, b) = (&*files[a].rel_path, &*files[b].rel_path);
+                parent_dir(a).cmp(parent_dir(b)).then_with(|| a.cmp(b))
+            });
+        }
+        FileOrder::Dependencies => *indices = crate::deps::dependency_order(files, indices),
+        FileOrder::Manual => {
+            let position: HashMap<&str, usize> = manual
+                .iter()
+                .enumerate()
+                .map(|(n, path)| (path.as_str(), n))
+                .collect();
+            // Stable, so unlisted files keep their path order at the end
+            indices.sort_by_key(|&i| {
+                position
+                    .get(&*files[i].rel_path)
+                    .copied()
+                    .unwrap_or(usize::MAX)
+            });
+        }
+    }
+}
@@ -1,32 +1,47 @@
 use crate::file_item::FileItem;
+use crate::file_types::FileTypes;
+use crate::intern::RelPath;
 use std::collections::BTreeMap;
 
 #[derive(Default)]
 pub struct FileTree {
-    pub folders: BTreeMap<String, FileTree>,
+    pub folders: BTreeMap<RelPath, FileTree>,
     pub files: Vec<usize>,
+    /// Not walked yet (the scan stopped descending at `MAX_FILES`); loaded when expanded.
+    pub deferred: bool,
 }
 
 pub fn build_file_tree(files: &[FileItem]) -> FileTree {
-    let mut root = FileTree {
-        folders: BTreeMap::new(),
-        files: Vec::new(),
-    };
+    let mut root = FileTree::default();
     for (i, file) in files.iter().enumerate() {
-        let path = file.rel_path.replace('\\', "/");
-        let parts: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let parts: Vec<&str> = file
+            .rel_path
+            .split(['/', '\\'])
+            .filter(|s| !s.is_empty())
+            .collect();
         let mut current = &mut root;
         for (j, part) in parts.iter().enumerate() {
             if j == parts.len() - 1 {
                 current.files.push(i);
             } else {
-                current = current.folders.entry(part.to_string()).or_default();
+                current = current.folders.entry(RelPath::from(*part)).or_default();
             }
         }
     }
     root
 }
 
+/// Adds the folders a scan left unwalked, as empty deferred nodes.
+pub fn add_deferred_dirs<'a>(tree: &mut FileTree, dirs: impl IntoIterator<Item = &'a String>) {
+    for dir in dirs {
+        let mut current = &mut *tree;
+        for part in dir.split('/').filter(|s| !s.is_empty()) {
+            current = current.folders.entry(RelPath::from(part)).or_default();
+        }
+        current.deferred = true;
+    }
+}
+
 pub fn sort_file_tree(tree: &mut FileTree, files: &[FileItem]) {
     tree.files.sort_by(|&a, &b| {
         let name_a = files[a].rel_path.rsplit('/').next().unwrap_or("");
@@ -38,15 +53,47 @@ pub fn sort_file_tree(tree: &mut FileTree, files: &[FileItem]) {
     }
 }
 
-pub fn set_folder_selection(tree: &FileTree, files: &mut [FileItem], value: bool) {
+/// Whether "Hide files over N tokens" leaves the file out of the tree.
+fn hidden(file: &FileItem, hide_over_tokens: Option<usize>) -> bool {
+    hide_over_tokens.is_some_and(|max| file.token_count > max)
+}
+
+/// Selects or clears the folder's files, leaving those the tree hides alone.
+pub fn set_folder_selection(
+    tree: &FileTree,
+    files: &mut [FileItem],
+    value: bool,
+    hide_over_tokens: Option<usize>,
+) {
     for &i in &tree.files {
-        files[i].selected = value;
+        if !files[i].tree_only && !hidden(&files[i], hide_over_tokens) {
+            files[i].selected = value;
+        }
     }
     for sub_tree in tree.folders.values() {
-        set_folder_selection(sub_tree, files, value);
+        set_folder_selection(sub_tree, files, value, hide_over_tokens);
     }
 }
 
+fn subtree_size(tree: &FileTree, files: &[FileItem]) -> u64 {
+    tree.files.iter().map(|&i| files[i].size).sum::<u64>()
+        + tree
+            .folders
+            .values()
+            .map(|sub| subtree_size(sub, files))
+            .sum::<u64>()
+}
+
+fn subtree_latest(tree: &FileTree, files: &[FileItem]) -> Option<std::time::SystemTime> {
+    let own = tree.files.iter().filter_map(|&i| files[i].modified).max();
+    let nested = tree
+        .folders
+        .values()
+        .filter_map(|sub| subtree_latest(sub, files))
+        .max();
+    own.max(nested)
+}
+
 pub fn subtree_tokens(tree: &FileTree, files: &[FileItem]) -> usize {
     let mut sum = 0;
     for &i in &tree.files {
@@ -58,125 +105,781 @@ pub fn subtree_tokens(tree: &FileTree, files: &[FileItem]) -> usize {
     sum
 }
 
-use egui::{CollapsingHeader, Color32, RichText};
+fn all_tree_only(tree: &FileTree, files: &[FileItem]) -> bool {
+    tree.files.iter().all(|&i| files[i].tree_only)
+        && tree.folders.values().all(|sub| all_tree_only(sub, files))
+}
 
-pub fn show_file_tree(ui: &mut egui::Ui, tree: &FileTree, files: &mut [FileItem]) {
-    for (folder_name, subtree) in &tree.folders {
-        ui.horizontal(|ui| {
-            let old_spacing = ui.spacing().item_spacing;
-            ui.spacing_mut().item_spacing.x = 0.0;
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TreeSort {
+    Alphabetical,
+    TokensDesc,
+    SizeDesc,
+    RecentFirst,
+}
 
-            let (total, selected) = get_folder_selection_counts(subtree, files);
-            let mut folder_selected = selected == total;
-            let indeterminate = selected > 0 && selected < total;
-            let cb = egui::Checkbox::new(&mut folder_selected, "").indeterminate(indeterminate);
-            if ui.add(cb).changed() {
-                set_folder_selection(subtree, files, folder_selected);
-            }
+impl TreeSort {
+    pub const ALL: [TreeSort; 4] = [
+        TreeSort::Alphabetical,
+        TreeSort::TokensDesc,
+        TreeSort::SizeDesc,
+        TreeSort::RecentFirst,
+    ];
 
-            let total_tok = subtree_tokens(subtree, files);
-            CollapsingHeader::new(
-                RichText::new(format!("{} ({})", folder_name, total_tok))
-                    .color(Color32::from_rgb(230, 200, 120)),
-            )
-            .id_salt(folder_name)
-            .show(ui, |ui| {
-                show_file_tree(ui, subtree, files);
+    pub fn label(self) -> &'static str {
+        match self {
+            TreeSort::Alphabetical => "Name",
+            TreeSort::TokensDesc => "Tokens (largest first)",
+            TreeSort::SizeDesc => "Size (largest first)",
+            TreeSort::RecentFirst => "Recently modified",
+        }
+    }
+}
+
+/// Share of the budget at which the heat map is fully red; anything bigger is as bad.
+const HEAT_FULL_SHARE: f32 = 0.10;
+
+/// Display-only ordering/filtering for the tree panel. The prompt's <file_tree>
+/// is always alphabetical regardless of these settings.
+pub struct TreeView {
+    pub sort: TreeSort,
+    pub hide_over_tokens: Option<usize>,
+    /// Color labels by share of the token budget instead of by file type.
+    pub heat_map: bool,
+    /// Token budget the heat map and tooltips are relative to; set by the app each frame.
+    pub budget: usize,
+    /// Colours, icons and categories by file type; rebuilt when the config is loaded.
+    pub file_types: FileTypes,
+}
+
+impl Default for TreeView {
+    fn default() -> Self {
+        Self {
+            sort: TreeSort::Alphabetical,
+            hide_over_tokens: None,
+            heat_map: false,
+            budget: crate::config::DEFAULT_TOKEN_BUDGET,
+            file_types: FileTypes::default(),
+        }
+    }
+}
+
+impl TreeView {
+    fn share(&self, tokens: usize) -> f32 {
+        tokens as f32 / self.budget.max(1) as f32
+    }
+
+    /// Green → yellow → red. The square root spreads out the many small files.
+    pub fn heat_color(&self, tokens: usize) -> Color32 {
+        let t = (self.share(tokens) / HEAT_FULL_SHARE).sqrt().min(1.0);
+        let lerp = |a: u8, b: u8, t: f32| (a as f32 + (b as f32 - a as f32) * t) as u8;
+        let (from, to, t) = if t < 0.5 {
+            ((100, 210, 100), (230, 210, 80), t * 2.0)
+        } else {
+            ((230, 210, 80), (240, 90, 80), t * 2.0 - 1.0)
+        };
+        Color32::from_rgb(
+            lerp(from.0, to.0, t),
+            lerp(from.1, to.1, t),
+            lerp(from.2, to.2, t),
+        )
+    }
+
+    pub fn budget_tooltip(&self, tokens: usize) -> String {
+        format!(
+            "{} tokens, {:.2}% of the {} token budget",
+            tokens,
+            self.share(tokens) * 100.0,
+            self.budget
+        )
+    }
+}
+
+/// Requests raised by tree rows that need app-level state (config, notifications).
+pub enum TreeAction {
+    ToggleTreeOnly(String),
+    IncludeDependencies(usize),
+    SelectDependents(usize),
+    /// Select the files using a symbol the file defines.
+    SelectSymbolUsers(usize, crate::symbols::Symbol),
+    Preview(usize),
+    OpenInEditor(usize),
+    EditNote(usize),
+    RecountTokens(usize),
+    ToggleTableSummary(usize),
+    ToggleDiffOnly(usize),
+    /// Walk a deferred folder.
+    LoadFolder(String),
+    /// Select everything under a workspace crate's `src/`.
+    SelectCrateSrc(usize),
+    OpenArchive(usize),
+    /// Drop an open archive's entries from the list.
+    CloseArchive(String),
+}
+
+use crate::workspace::WorkspaceCrate;
+use egui::{Color32, RichText};
+
+/// One line of the tree panel. Only rows under expanded folders are listed, so the panel
+/// can lay out just the ones scrolled into view.
+pub enum TreeRow<'a> {
+    /// A workspace crate, grouping the folders and files it owns.
+    Crate {
+        crates: &'a [WorkspaceCrate],
+        index: usize,
+        tokens: usize,
+    },
+    Folder {
+        name: &'a str,
+        path: String,
+        depth: usize,
+        tree: &'a FileTree,
+    },
+    File {
+        index: usize,
+        depth: usize,
+    },
+}
+
+/// Height of every tree row, without item spacing; `ScrollArea::show_rows` needs them equal.
+pub fn tree_row_height(ui: &egui::Ui) -> f32 {
+    ui.spacing().interact_size.y
+}
+
+fn folder_state_id(path: &str) -> egui::Id {
+    egui::Id::new(("file_tree_folder", path))
+}
+
+fn folder_open(ctx: &egui::Context, path: &str) -> bool {
+    egui::collapsing_header::CollapsingState::load_with_default_open(
+        ctx,
+        folder_state_id(path),
+        false,
+    )
+    .is_open()
+}
+
+fn set_folder_open(ui: &egui::Ui, path: &str, open: bool) {
+    let mut state = egui::collapsing_header::CollapsingState::load_with_default_open(
+        ui.ctx(),
+        folder_state_id(path),
+        false,
+    );
+    state.set_open(open);
+    state.store(ui.ctx());
+    ui.ctx().request_repaint();
+}
+
+fn crate_state_id(dir: &str) -> egui::Id {
+    egui::Id::new(("file_tree_crate", dir))
+}
+
+fn crate_open(ctx: &egui::Context, dir: &str) -> bool {
+    egui::collapsing_header::CollapsingState::load_with_default_open(ctx, crate_state_id(dir), true)
+        .is_open()
+}
+
+fn set_crate_open(ui: &egui::Ui, dir: &str, open: bool) {
+    let mut state = egui::collapsing_header::CollapsingState::load_with_default_open(
+        ui.ctx(),
+        crate_state_id(dir),
+        true,
+    );
+    state.set_open(open);
+    state.store(ui.ctx());
+    ui.ctx().request_repaint();
+}
+
+fn subtree_at<'a>(tree: &'a FileTree, dir: &str) -> Option<&'a FileTree> {
+    dir.split('/')
+        .filter(|s| !s.is_empty())
+        .try_fold(tree, |node, part| node.folders.get(part))
+}
+
+/// Whether the folder at `path` belongs to a member crate, or holds nothing but member
+/// crates; those are listed under their crate's group instead.
+fn member_owned(
+    path: &str,
+    tree: &FileTree,
+    files: &[FileItem],
+    crates: &[WorkspaceCrate],
+) -> bool {
+    let member = |rel: &str| {
+        crate::workspace::crate_index(crates, rel).is_some_and(|i| !crates[i].dir.is_empty())
+    };
+    fn all_files(tree: &FileTree, f: &mut impl FnMut(usize) -> bool) -> bool {
+        tree.files.iter().all(|&i| f(i)) && tree.folders.values().all(|sub| all_files(sub, f))
+    }
+    let nested = format!("{}/", path);
+    crates.iter().any(|c| c.dir == path)
+        || (crates.iter().any(|c| c.dir.starts_with(&nested))
+            && all_files(tree, &mut |i| member(&files[i].rel_path)))
+}
+
+/// The rows currently visible in `tree`, in display order. With workspace `crates`, each
+/// crate is a top-level group and whatever no crate owns follows them.
+pub fn visible_rows<'a>(
+    ctx: &egui::Context,
+    tree: &'a FileTree,
+    files: &[FileItem],
+    view: &TreeView,
+    crates: &'a [WorkspaceCrate],
+) -> Vec<TreeRow<'a>> {
+    let mut rows = Vec::new();
+    let walk = Walk {
+        ctx,
+        files,
+        view,
+        crates,
+    };
+    if crates.is_empty() {
+        walk.push_rows(tree, "", 0, &mut rows);
+        return rows;
+    }
+    let totals = crate::workspace::crate_tokens(crates, files);
+    for (index, krate) in crates.iter().enumerate() {
+        let Some(subtree) = subtree_at(tree, &krate.dir) else {
+            continue;
+        };
+        rows.push(TreeRow::Crate {
+            crates,
+            index,
+            tokens: totals[index],
+        });
+        if !crate_open(ctx, &krate.dir) {
+            continue;
+        }
+        if subtree.deferred {
+            rows.push(TreeRow::Folder {
+                name: krate.dir.rsplit('/').next().unwrap_or(&krate.dir),
+                path: krate.dir.clone(),
+                depth: 1,
+                tree: subtree,
             });
+        } else {
+            walk.push_rows(subtree, &krate.dir, 1, &mut rows);
+        }
+    }
+    // Without a root package the root's own files and non-member folders are left over
+    if crates.iter().all(|c| !c.dir.is_empty()) {
+        walk.push_rows(tree, "", 0, &mut rows);
+    }
+    rows
+}
+
+/// What every level of the row walk shares.
+struct Walk<'w> {
+    ctx: &'w egui::Context,
+    files: &'w [FileItem],
+    view: &'w TreeView,
+    crates: &'w [WorkspaceCrate],
+}
 
-            ui.spacing_mut().item_spacing = old_spacing;
+impl Walk<'_> {
+    fn push_rows<'a>(
+        &self,
+        tree: &'a FileTree,
+        prefix: &str,
+        depth: usize,
+        rows: &mut Vec<TreeRow<'a>>,
+    ) {
+        use std::cmp::Reverse;
+        let Walk {
+            ctx,
+            files,
+            view,
+            crates,
+        } = *self;
+
+        let mut folders: Vec<(&RelPath, &FileTree)> = tree.folders.iter().collect();
+        let mut file_order: Vec<usize> = tree.files.clone();
+        match view.sort {
+            TreeSort::Alphabetical => {}
+            TreeSort::TokensDesc => {
+                folders.sort_by_cached_key(|(_, t)| Reverse(subtree_tokens(t, files)));
+                file_order.sort_by_key(|&i| Reverse(files[i].token_count));
+            }
+            TreeSort::SizeDesc => {
+                folders.sort_by_cached_key(|(_, t)| Reverse(subtree_size(t, files)));
+                file_order.sort_by_key(|&i| Reverse(files[i].size));
+            }
+            TreeSort::RecentFirst => {
+                folders.sort_by_cached_key(|(_, t)| Reverse(subtree_latest(t, files)));
+                file_order.sort_by_key(|&i| Reverse(files[i].modified));
+            }
+        }
+        file_order.retain(|&i| !hidden(&files[i], view.hide_over_tokens));
+        // An open archive is shown by the folder of its entries instead
+        file_order.retain(|&i| {
+            let name = files[i].rel_path.rsplit('/').next().unwrap_or("");
+            !tree.folders.contains_key(name)
         });
+
+        for (folder_name, subtree) in folders {
+            let path = if prefix.is_empty() {
+                folder_name.to_string()
+            } else {
+                format!("{}/{}", prefix, folder_name)
+            };
+            if !crates.is_empty() && member_owned(&path, subtree, files, crates) {
+                continue;
+            }
+            let open = folder_open(ctx, &path) && !subtree.deferred;
+            rows.push(TreeRow::Folder {
+                name: folder_name,
+                path: path.clone(),
+                depth,
+                tree: subtree,
+            });
+            if open {
+                self.push_rows(subtree, &path, depth + 1, rows);
+            }
+        }
+        rows.extend(
+            file_order
+                .into_iter()
+                .map(|index| TreeRow::File { index, depth }),
+        );
     }
+}
 
-    for &i in &tree.files {
-        let file = &mut files[i];
-        let name = file.rel_path.rsplit('/').next().unwrap_or(&file.rel_path);
-        let color = if name.ends_with(".rs") {
-            Color32::from_rgb(250, 150, 150) // Rust
-        } else if name.ends_with(".md") || name.ends_with(".txt") {
-            Color32::from_rgb(100, 250, 100) // Markdown/Text
-        } else if name.ends_with(".cu") || name.ends_with(".cuda") {
-            Color32::from_rgb(100, 150, 250) // CUDA
-        } else if name.ends_with(".o") {
-            Color32::from_rgb(150, 150, 150) // Object files
-        } else if name.ends_with(".py") {
-            Color32::from_rgb(50, 100, 250) // Python
-        } else if name.ends_with(".html") {
-            Color32::from_rgb(250, 100, 50) // HTML
-        } else if name.ends_with(".css") {
-            Color32::from_rgb(150, 100, 250) // CSS
-        } else if name.ends_with(".csv") {
-            Color32::from_rgb(100, 250, 150) // CSV
-        } else if name.ends_with(".slang") {
-            Color32::from_rgb(250, 150, 50) // Slang
-        } else if name.ends_with(".wgsl") {
-            Color32::from_rgb(250, 100, 250) // WGSL
-        } else if name.ends_with(".png")
-            || name.ends_with(".exr")
-            || name.ends_with(".hdr")
-            || name.ends_with(".jpg")
-            || name.ends_with(".jpeg")
+/// Draws one row in a strip exactly `tree_row_height` tall.
+pub fn show_tree_row(
+    ui: &mut egui::Ui,
+    row: &TreeRow,
+    files: &mut [FileItem],
+    view: &TreeView,
+    actions: &mut Vec<TreeAction>,
+) {
+    let size = egui::vec2(ui.available_width(), tree_row_height(ui));
+    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let mut ui = ui.new_child(
+        egui::UiBuilder::new()
+            .max_rect(rect)
+            .layout(egui::Layout::left_to_right(egui::Align::Center)),
+    );
+    let depth = match row {
+        TreeRow::Crate { .. } => 0,
+        TreeRow::Folder { depth, .. } | TreeRow::File { depth, .. } => *depth,
+    };
+    ui.add_space(depth as f32 * ui.spacing().indent);
+    match row {
+        TreeRow::Crate {
+            crates,
+            index,
+            tokens,
+        } => show_crate_row(&mut ui, crates, *index, *tokens, files, view, actions),
+        TreeRow::Folder {
+            name, path, tree, ..
+        } => show_folder_row(&mut ui, name, path, tree, files, view, actions),
+        TreeRow::File { index, .. } => show_file_row(&mut ui, *index, files, view, actions),
+    }
+}
+
+fn show_crate_row(
+    ui: &mut egui::Ui,
+    crates: &[WorkspaceCrate],
+    index: usize,
+    tokens: usize,
+    files: &mut [FileItem],
+    view: &TreeView,
+    actions: &mut Vec<TreeAction>,
+) {
+    ui.spacing_mut().item_spacing.x = 0.0;
+    let krate = &crates[index];
+
+    let open = crate_open(ui.ctx(), &krate.dir);
+    let arrow = if open { "⏷" } else { "⏵" };
+    if ui
+        .add(egui::Label::new(arrow).sense(egui::Sense::click()))
+        .clicked()
+    {
+        set_crate_open(ui, &krate.dir, !open);
+    }
+
+    // The crate's own files: a nested member's are left to that member's row
+    let owned: Vec<usize> = (0..files.len())
+        .filter(|&i| crate::workspace::crate_index(crates, &files[i].rel_path) == Some(index))
+        .filter(|&i| !files[i].tree_only && !hidden(&files[i], view.hide_over_tokens))
+        .collect();
+    let selected = owned.iter().filter(|&&i| files[i].selected).count();
+    let mut crate_selected = !owned.is_empty() && selected == owned.len();
+    let cb = egui::Checkbox::new(&mut crate_selected, "")
+        .indeterminate(selected > 0 && selected < owned.len());
+    if ui.add_enabled(!owned.is_empty(), cb).changed() {
+        for &i in &owned {
+            files[i].selected = crate_selected;
+        }
+    }
+
+    let color = if view.heat_map {
+        view.heat_color(tokens)
+    } else {
+        Color32::from_rgb(230, 200, 120)
+    };
+    let dir = if krate.dir.is_empty() {
+        "."
+    } else {
+        &krate.dir
+    };
+    let label = ui
+        .add(
+            egui::Label::new(
+                RichText::new(format!("{} ({})", krate.name, tokens))
+                    .color(color)
+                    .strong(),
+            )
+            .sense(egui::Sense::click()),
+        )
+        .on_hover_text(format!("Workspace crate in {}", dir));
+    if label.clicked() {
+        set_crate_open(ui, &krate.dir, !open);
+    }
+    ui.add_space(6.0);
+    if ui
+        .small_button("Select src/")
+        .on_hover_text(format!("Select every file under {}", krate.src_dir()))
+        .clicked()
+    {
+        actions.push(TreeAction::SelectCrateSrc(index));
+    }
+}
+
+fn show_folder_row(
+    ui: &mut egui::Ui,
+    name: &str,
+    path: &str,
+    subtree: &FileTree,
+    files: &mut [FileItem],
+    view: &TreeView,
+    actions: &mut Vec<TreeAction>,
+) {
+    ui.spacing_mut().item_spacing.x = 0.0;
+
+    if subtree.deferred {
+        let arrow = ui.add(egui::Label::new("⏵").sense(egui::Sense::click()));
+        ui.add_enabled(false, egui::Checkbox::new(&mut false, ""));
+        let title = RichText::new(format!("{} (not loaded)", name))
+            .color(Color32::from_rgb(230, 200, 120))
+            .italics();
+        let label = ui
+            .add(egui::Label::new(title).sense(egui::Sense::click()))
+            .on_hover_text("Skipped by the initial scan of this large project; expand to load");
+        if arrow.clicked() || label.clicked() {
+            // Opened, so the files show as soon as they arrive
+            set_folder_open(ui, path, true);
+            actions.push(TreeAction::LoadFolder(path.to_string()));
+        }
+        return;
+    }
+
+    let open = folder_open(ui.ctx(), path);
+    let arrow = if open { "⏷" } else { "⏵" };
+    if ui
+        .add(egui::Label::new(arrow).sense(egui::Sense::click()))
+        .clicked()
+    {
+        set_folder_open(ui, path, !open);
+    }
+
+    let tree_only = all_tree_only(subtree, files);
+    let (total, selected) = get_folder_selection_counts(subtree, files, view.hide_over_tokens);
+    let mut folder_selected = selected == total;
+    let indeterminate = selected > 0 && selected < total;
+    let cb = egui::Checkbox::new(&mut folder_selected, "").indeterminate(indeterminate);
+    if ui.add_enabled(!tree_only, cb).changed() {
+        set_folder_selection(subtree, files, folder_selected, view.hide_over_tokens);
+    }
+
+    let total_tok = subtree_tokens(subtree, files);
+    let title = if tree_only {
+        format!("{} ({}) [tree only]", name, total_tok)
+    } else {
+        format!("{} ({})", name, total_tok)
+    };
+    let folder_color = if view.heat_map {
+        view.heat_color(total_tok)
+    } else {
+        Color32::from_rgb(230, 200, 120)
+    };
+    let header_response = ui
+        .add(egui::Label::new(RichText::new(title).color(folder_color)).sense(egui::Sense::click()))
+        .on_hover_text(view.budget_tooltip(total_tok));
+    if header_response.clicked() {
+        set_folder_open(ui, path, !open);
+    }
+    header_response.context_menu(|ui| {
+        if crate::archives::is_archive(name)
+            && ui
+                .button("Close archive")
+                .on_hover_text(
+                    "Drop the archive's entries from the list; open it again to re-read it",
+                )
+                .clicked()
         {
-            Color32::from_rgb(250, 250, 100) // Images
-        } else if name.ends_with(".gltf") || name.ends_with(".glb") {
-            Color32::from_rgb(100, 250, 250) // GLTF/GLB
-        } else if name.ends_with(".spv") || name.ends_with(".spvasm") {
-            Color32::from_rgb(150, 100, 150) // SPIR-V
-        } else if name.ends_with(".glsl") || name.ends_with(".comp") {
-            Color32::from_rgb(100, 150, 250) // GLSL/Compute Shaders
-        } else if name.ends_with(".sh") {
-            Color32::from_rgb(150, 250, 100) // Shell scripts
-        } else if name.ends_with(".lock") {
-            Color32::from_rgb(250, 100, 100) // Lock files (same as Rust for consistency)
-        } else if name.ends_with(".toml") {
-            Color32::from_rgb(250, 150, 150) // TOML
+            actions.push(TreeAction::CloseArchive(path.to_string()));
+            ui.close_menu();
+        }
+        let label = if tree_only {
+            "Include contents again"
         } else {
-            ui.visuals().text_color()
+            "Include as tree only"
         };
-        let label = RichText::new(format!("{} ({})", name, file.token_count)).color(color);
-        ui.checkbox(&mut file.selected, label);
+        if ui.button(label).clicked() {
+            actions.push(TreeAction::ToggleTreeOnly(path.to_string()));
+            ui.close_menu();
+        }
+    });
+}
+
+fn show_file_row(
+    ui: &mut egui::Ui,
+    i: usize,
+    files: &mut [FileItem],
+    view: &TreeView,
+    actions: &mut Vec<TreeAction>,
+) {
+    let file = &mut files[i];
+    let name = file.rel_path.rsplit('/').next().unwrap_or(&file.rel_path);
+    let style = view.file_types.style(&file.rel_path);
+    let color = if view.heat_map {
+        view.heat_color(file.token_count)
+    } else {
+        style.color.unwrap_or(ui.visuals().text_color())
+    };
+    let flagged = file.text_issues.is_flagged();
+    // Archives nested inside an open archive aren't browsable
+    let archive = file.archive_entry.is_none() && crate::archives::is_archive(&file.rel_path);
+    let mut text = match style.icon {
+        Some(icon) => format!("{} {} ({})", icon, name, file.token_count),
+        None => format!("{} ({})", name, file.token_count),
+    };
+    if archive {
+        text.push_str(" 📦");
+    }
+    if file.note.is_some() {
+        text.push_str(" 📝");
+    }
+    if file.content_stale {
+        text.push_str(" ✏");
+    }
+    if file.diff_only {
+        text.push_str(" ±");
+    }
+    if flagged {
+        text.push_str(" ⚠");
+    }
+    let label = RichText::new(text).color(color);
+    if file.tree_only {
+        ui.add_enabled(false, egui::Checkbox::new(&mut file.selected, label))
+            .on_disabled_hover_text("Folder is marked tree only");
+    } else {
+        let mut response = ui
+            .horizontal(|ui| {
+                // The name opens the preview; the box alone toggles selection
+                let checkbox = ui.checkbox(&mut file.selected, "");
+                let name = ui
+                    .add(egui::Label::new(label).sense(egui::Sense::click()))
+                    .on_hover_cursor(egui::CursorIcon::PointingHand);
+                if name.clicked() {
+                    actions.push(if archive {
+                        TreeAction::OpenArchive(i)
+                    } else {
+                        TreeAction::Preview(i)
+                    });
+                }
+                let response = checkbox.union(name);
+                if file.selected
+                    && !file.exact_tokens
+                    && ui
+                        .small_button("↻")
+                        .on_hover_text("Recount exactly with the tokenizer")
+                        .clicked()
+                {
+                    actions.push(TreeAction::RecountTokens(i));
+                }
+                response
+            })
+            .inner;
+        response = response.on_hover_text(view.budget_tooltip(file.token_count));
+        if let Some(category) = style.category {
+            response = response.on_hover_text(match file.language {
+                Some(lang) => format!("{} ({})", lang.id, category),
+                None => category.to_string(),
+            });
+        }
+        if file.exact_tokens {
+            response = response.on_hover_text("Token count is exact");
+        }
+        if flagged {
+            response = response.on_hover_text(file.text_issues.describe());
+        }
+        if file.content_stale {
+            response = response
+                .on_hover_text("Changed on disk since the last prompt; the next one re-reads it");
+        }
+        if let Some(note) = &file.note {
+            response = response.on_hover_text(note);
+        }
+        // Imports and definitions inside an archive don't resolve against the project
+        let supported = file.archive_entry.is_none() && crate::deps::supports(&file.rel_path);
+        response.context_menu(|ui| {
+            if archive
+                && ui
+                    .button("Browse archive")
+                    .on_hover_text("List the files inside, to select them without extracting")
+                    .clicked()
+            {
+                actions.push(TreeAction::OpenArchive(i));
+                ui.close_menu();
+            }
+            if ui
+                .button("Preview…")
+                .on_hover_text("Show the file; collapse regions to leave them out")
+                .clicked()
+            {
+                actions.push(TreeAction::Preview(i));
+                ui.close_menu();
+            }
+            if file.archive_entry.is_none() && ui.button("Open in editor").clicked() {
+                actions.push(TreeAction::OpenInEditor(i));
+                ui.close_menu();
+            }
+            let note_label = if file.note.is_some() {
+                "Edit note…"
+            } else {
+                "Add note…"
+            };
+            if ui
+                .button(note_label)
+                .on_hover_text("Attach a note that is sent with this file")
+                .clicked()
+            {
+                actions.push(TreeAction::EditNote(i));
+                ui.close_menu();
+            }
+            if ui
+                .button("Recount tokens exactly")
+                .on_hover_text(
+                    "Read the file and run the tokenizer instead of estimating from size",
+                )
+                .clicked()
+            {
+                actions.push(TreeAction::RecountTokens(i));
+                ui.close_menu();
+            }
+            if file.archive_entry.is_none() && crate::tables::is_table(&file.rel_path) {
+                let mut summarize = file.summarize_table;
+                if ui
+                    .checkbox(&mut summarize, "Summarize table")
+                    .on_hover_text(
+                        "Send the row count, column types and the first and last rows instead of the contents",
+                    )
+                    .changed()
+                {
+                    actions.push(TreeAction::ToggleTableSummary(i));
+                    ui.close_menu();
+                }
+            }
+            if file.archive_entry.is_none() {
+                let mut diff_only = file.diff_only;
+                if ui
+                    .checkbox(&mut diff_only, "Include only changes")
+                    .on_hover_text(
+                        "Send the file's git diff against HEAD (or the diff ref in config.toml); files not in that revision are sent in full",
+                    )
+                    .changed()
+                {
+                    actions.push(TreeAction::ToggleDiffOnly(i));
+                    ui.close_menu();
+                }
+            }
+            if supported {
+                if ui
+                    .button("Include dependencies")
+                    .on_hover_text("Select the local files this file imports")
+                    .clicked()
+                {
+                    actions.push(TreeAction::IncludeDependencies(i));
+                    ui.close_menu();
+                }
+                if ui
+                    .button("Select dependents")
+                    .on_hover_text("Select the project files that import this file")
+                    .clicked()
+                {
+                    actions.push(TreeAction::SelectDependents(i));
+                    ui.close_menu();
+                }
+                ui.menu_button("Select users of", |ui| {
+                    let symbols = file
+                        .read_text()
+                        .map(|text| crate::symbols::symbols_in(&file.rel_path, &text))
+                        .unwrap_or_default();
+                    if symbols.is_empty() {
+                        ui.weak("No definitions found");
+                    }
+                    egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                        for symbol in symbols {
+                            let label = format!("{} {}", symbol.kind.label(), symbol.name);
+                            if ui
+                                .button(label)
+                                .on_hover_text("Select the project files that use this definition")
+                                .clicked()
+                            {
+                                actions.push(TreeAction::SelectSymbolUsers(i, symbol));
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                });
+            }
+        });
     }
 }
 
-pub fn generate_file_tree_string(files: &[FileItem], base: &std::path::Path) -> String {
-    let mut tree = build_file_tree(files);
-    sort_file_tree(&mut tree, files);
+/// `tree` is the app's scanned tree (built and sorted); deferred folders end in "…".
+pub fn generate_file_tree_string(
+    tree: &FileTree,
+    files: &[FileItem],
+    base: &std::path::Path,
+) -> String {
     let base_name = base
         .file_name()
         .unwrap_or_else(|| std::ffi::OsStr::new("root"))
         .to_string_lossy()
         .to_string();
     let mut output = format!("{}/\n", base_name);
-    output.push_str(&generate_tree_string(&tree, files, "".to_string()));
+    output.push_str(&generate_tree_string(tree, files, "".to_string()));
     output
 }
 
 pub fn generate_tree_string(tree: &FileTree, files: &[FileItem], prefix: String) -> String {
     let mut output = String::new();
-    let mut entries: Vec<(String, bool, Option<&FileTree>)> = Vec::new();
+    let mut entries: Vec<(&str, bool, Option<&FileTree>)> = Vec::new();
     for (folder, sub_tree) in &tree.folders {
-        entries.push((folder.clone(), true, Some(sub_tree)));
+        entries.push((folder, true, Some(sub_tree)));
     }
     for &file_index in &tree.files {
-        let file_name = files[file_index]
-            .rel_path
-            .rsplit('/')
-            .next()
-            .unwrap_or("")
-            .to_string();
+        let file_name = files[file_index].rel_path.rsplit('/').next().unwrap_or("");
         entries.push((file_name, false, None));
     }
     use std::cmp::Ordering;
     entries.sort_by(|a, b| match (a.1, b.1) {
         (true, false) => Ordering::Less,
         (false, true) => Ordering::Greater,
-        _ => a.0.cmp(&b.0),
+        _ => a.0.cmp(b.0),
     });
     let total = entries.len();
     for (i, entry) in entries.into_iter().enumerate() {
         let is_last = i == total - 1;
         let connector = if is_last { "└─ " } else { "├─ " };
-        output.push_str(&format!("{}{}{}\n", prefix, connector, entry.0));
+        let more = if entry.2.is_some_and(|t| t.deferred) {
+            " …"
+        } else {
+            ""
+        };
+        output.push_str(&format!("{}{}{}{}\n", prefix, connector, entry.0, more));
         if entry.1 {
             if let Some(sub_tree) = entry.2 {
                 let new_prefix = if is_last {
@@ -191,11 +894,23 @@ pub fn generate_tree_string(tree: &FileTree, files: &[FileItem], prefix: String)
     output
 }
 
-pub fn get_folder_selection_counts(tree: &FileTree, files: &[FileItem]) -> (usize, usize) {
-    let mut total = tree.files.len();
-    let mut selected = tree.files.iter().filter(|&&i| files[i].selected).count();
+/// (files, selected files) under the folder, counting only those the tree shows and
+/// `set_folder_selection` can select (not tree-only ones).
+pub fn get_folder_selection_counts(
+    tree: &FileTree,
+    files: &[FileItem],
+    hide_over_tokens: Option<usize>,
+) -> (usize, usize) {
+    let shown = || {
+        tree.files
+            .iter()
+            .filter(|&&i| !files[i].tree_only && !hidden(&files[i], hide_over_tokens))
+    };
+    let mut total = shown().count();
+    let mut selected = shown().filter(|&&i| files[i].selected).count();
     for sub_tree in tree.folders.values() {
-        let (sub_total, sub_selected) = get_folder_selection_counts(sub_tree, files);
+        let (sub_total, sub_selected) =
+            get_folder_selection_counts(sub_tree, files, hide_over_tokens);
         total += sub_total;
         selected += sub_selected;
     }
@@ -0,0 +1,171 @@
+// How each file is drawn in the tree: label colour, an optional icon and a category for
+// the tooltip, looked up by file name or extension. The built-in styles come from the
+// language table (languages.rs) and the asset kinds below. `[[file_types]]` entries in
+// `.prompt/config.toml` add types or override parts of a built-in one, so a new language
+// doesn't need a code change:
+//
+// ```toml
+// [[file_types]]
+// extensions = ["prisma"]
+// color = "#5a67d8"
+// icon = "◆"
+// category = "schema"
+//
+// [[file_types]]
+// names = ["Justfile"]
+// category = "build"
+// ```
+
+use crate::error::PromptError;
+use crate::languages::{self, Category, Rgb};
+use egui::Color32;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// One `[[file_types]]` entry. Unset fields keep the built-in value.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileTypeRule {
+    /// Without the dot; matched case-insensitively.
+    pub extensions: Vec<String>,
+    /// Exact file names, e.g. `Justfile`.
+    pub names: Vec<String>,
+    /// `#rrggbb`.
+    pub color: Option<String>,
+    pub icon: Option<String>,
+    pub category: Option<String>,
+}
+
+/// Built-in styles for files that aren't source text.
+static ASSETS: &[(&[&str], Rgb, Category)] = &[
+    (
+        &[
+            "png", "jpg", "jpeg", "gif", "webp", "bmp", "ico", "tif", "tiff", "exr", "hdr",
+        ],
+        (250, 250, 100),
+        Category::Image,
+    ),
+    (&["gltf", "glb", "fbx"], (100, 250, 250), Category::Model),
+    (&["spv"], (150, 100, 150), Category::Binary),
+    (
+        &["o", "a", "so", "dll", "obj", "exe", "wasm"],
+        (150, 150, 150),
+        Category::Binary,
+    ),
+    (&["parquet"], (100, 250, 150), Category::Data),
+    (&["lock"], (250, 100, 100), Category::Lock),
+];
+
+fn default_icon(category: Category) -> Option<&'static str> {
+    match category {
+        Category::Docs => Some("📄"),
+        Category::Data => Some("📊"),
+        Category::Image => Some("🖼"),
+        Category::Lock => Some("🔒"),
+        _ => None,
+    }
+}
+
+/// A file's style in the tree; None fields fall back to the plain label.
+#[derive(Clone, Copy, Default)]
+pub struct FileStyle<'a> {
+    pub color: Option<Color32>,
+    pub icon: Option<&'a str>,
+    pub category: Option<&'a str>,
+}
+
+/// A config rule with its colour parsed and its extensions lowercased.
+struct Rule {
+    extensions: Vec<String>,
+    names: Vec<String>,
+    color: Option<Color32>,
+    icon: Option<String>,
+    category: Option<String>,
+}
+
+#[derive(Default)]
+pub struct FileTypes {
+    rules: Vec<Rule>,
+}
+
+impl FileTypes {
+    /// The built-in styles with `rules` on top, later rules winning. A rule with an
+    /// unreadable colour keeps its other fields and is reported.
+    pub fn new(rules: &[FileTypeRule], config_path: &Path) -> (Self, Vec<PromptError>) {
+        let mut errors = Vec::new();
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let color = rule.color.as_deref().and_then(|hex| {
+                    let hex = hex.trim();
+                    let hex = hex.strip_prefix('#').unwrap_or(hex);
+                    match Color32::from_hex(&format!("#{}", hex)) {
+                        Ok(color) => Some(color),
+                        Err(_) => {
+                            errors.push(PromptError::Config {
+                                path: config_path.to_path_buf(),
+                                message: format!(
+                                    "file_types: colour {:?} is not #rrggbb",
+                                    rule.color.as_deref().unwrap_or_default()
+                                ),
+                            });
+                            None
+                        }
+                    }
+                });
+                Rule {
+                    extensions: rule
+                        .extensions
+                        .iter()
+                        .map(|e| e.trim_start_matches('.').to_ascii_lowercase())
+                        .collect(),
+                    names: rule.names.clone(),
+                    color,
+                    icon: rule.icon.clone().filter(|i| !i.is_empty()),
+                    category: rule.category.clone().filter(|c| !c.is_empty()),
+                }
+            })
+            .collect();
+        (Self { rules }, errors)
+    }
+
+    pub fn style(&self, rel_path: &str) -> FileStyle<'_> {
+        let name = languages::file_name(rel_path);
+        let ext = languages::extension(name);
+        let builtin = match languages::by_name(rel_path) {
+            Some(lang) => Some((lang.color, lang.category)),
+            None => ext.as_deref().and_then(|ext| {
+                ASSETS
+                    .iter()
+                    .find(|(exts, ..)| exts.contains(&ext))
+                    .map(|&(_, color, category)| (color, category))
+            }),
+        };
+        let mut style = match builtin {
+            Some(((r, g, b), category)) => FileStyle {
+                color: Some(Color32::from_rgb(r, g, b)),
+                icon: default_icon(category),
+                category: Some(category.label()),
+            },
+            None => FileStyle::default(),
+        };
+        let matching = self.rules.iter().filter(|rule| {
+            rule.names.iter().any(|n| n == name)
+                || ext
+                    .as_ref()
+                    .is_some_and(|ext| rule.extensions.contains(ext))
+        });
+        for rule in matching {
+            if rule.color.is_some() {
+                style.color = rule.color;
+            }
+            if let Some(icon) = &rule.icon {
+                style.icon = Some(icon);
+            }
+            if let Some(category) = &rule.category {
+                style.category = Some(category);
+            }
+        }
+        style
+    }
+}
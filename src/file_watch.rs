@@ -0,0 +1,89 @@
+// Polls the project files' modification times on a worker thread, so "on change" command
+// schedules and the contents read into a prompt notice edits without the UI thread
+// stat-ing every file. One job runs while something needs it; it reports the files that
+// changed and is restarted whenever the file list does.
+
+use crate::file_item::FileItem;
+use crate::jobs::{JobId, JobKind, JobOutput, Jobs};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the files are checked.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How often a sleeping watcher looks at its cancel flag.
+const CANCEL_POLL: Duration = Duration::from_millis(100);
+
+/// A path whose modification time differs from the previous check (or the starting one).
+pub struct FileChange {
+    pub path: PathBuf,
+    /// Indices into the file list of the files at `path`: an archive and its entries.
+    pub files: Vec<usize>,
+    /// None when the file is gone.
+    pub modified: Option<SystemTime>,
+    pub size: Option<u64>,
+}
+
+struct Watched {
+    path: PathBuf,
+    files: Vec<usize>,
+    modified: Option<SystemTime>,
+}
+
+/// Watches `files` of `folder`, each path once. A file's starting time is when its
+/// contents were read, if they were, or else when the scan saw it.
+pub fn spawn(folder: PathBuf, files: &[FileItem], jobs: &mut Jobs) -> JobId {
+    let mut watched: Vec<Watched> = Vec::new();
+    let mut by_path: HashMap<&PathBuf, usize> = HashMap::new();
+    for (index, file) in files.iter().enumerate() {
+        let slot = *by_path.entry(&file.path).or_insert_with(|| {
+            watched.push(Watched {
+                path: file.path.clone(),
+                files: Vec::new(),
+                modified: file.modified,
+            });
+            watched.len() - 1
+        });
+        let entry = &mut watched[slot];
+        entry.files.push(index);
+        if file.content.is_some() {
+            entry.modified = file.content_modified;
+        }
+    }
+    jobs.spawn(JobKind::Scan, "Watch project files", move |ctx| {
+        let cancel = ctx.cancel_flag();
+        loop {
+            let slept = Instant::now();
+            while slept.elapsed() < POLL_INTERVAL {
+                if cancel.load(Ordering::Relaxed) {
+                    return JobOutput::FileChanges {
+                        folder,
+                        changes: Vec::new(),
+                    };
+                }
+                std::thread::sleep(CANCEL_POLL);
+            }
+            let mut changes = Vec::new();
+            for file in watched.iter_mut() {
+                let meta = std::fs::metadata(&file.path).ok();
+                let modified = meta.as_ref().and_then(|m| m.modified().ok());
+                if modified != file.modified {
+                    file.modified = modified;
+                    changes.push(FileChange {
+                        path: file.path.clone(),
+                        files: file.files.clone(),
+                        modified,
+                        size: meta.map(|m| m.len()),
+                    });
+                }
+            }
+            if !changes.is_empty() {
+                ctx.send(JobOutput::FileChanges {
+                    folder: folder.clone(),
+                    changes,
+                });
+            }
+        }
+    })
+}
@@ -0,0 +1,183 @@
+// Foldable regions for partial file selection. Collapsing a region in the preview marks it
+// excluded; the prompt builder then replaces those lines with an "omitted" marker.
+
+use crate::deps::{lang_of, Lang};
+use crate::intern::RelPath;
+use crate::symbols::{block_end, definition_on};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::path::PathBuf;
+
+static RUST_BLOCK: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:unsafe\s+)?(impl\b.*|mod\s+[A-Za-z_]\w*\s*\{.*)")
+        .unwrap()
+});
+
+/// A foldable block, 1-based inclusive lines.
+#[derive(Clone)]
+pub struct Region {
+    pub start: usize,
+    pub end: usize,
+    pub label: String,
+}
+
+/// An excluded region. `header` is the trimmed first line, re-checked at build time so a
+/// fold doesn't cut the wrong lines after the file was edited.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Fold {
+    pub start: usize,
+    pub end: usize,
+    pub header: String,
+}
+
+/// Functions, types, classes and (Rust) impl/mod blocks spanning more than one line.
+pub fn regions(rel_path: &str, text: &str) -> Vec<Region> {
+    let Some(lang) = lang_of(rel_path) else {
+        return Vec::new();
+    };
+    let lines: Vec<&str> = text.lines().collect();
+    let mut out = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let label = match definition_on(lang, line) {
+            Some((kind, name)) => format!("{} {}", kind.label(), name),
+            None if lang == Lang::Rust => match RUST_BLOCK.captures(line) {
+                Some(c) => c[1].trim_end_matches('{').trim().to_string(),
+                None => continue,
+            },
+            None => continue,
+        };
+        let end = block_end(lang, &lines, i);
+        if end > i {
+            out.push(Region {
+                start: i + 1,
+                end: end + 1,
+                label,
+            });
+        }
+    }
+    out
+}
+
+fn omitted_line(rel_path: &str, marker: &str, fold: &Fold, indent: &str) -> String {
+    let note = format!("{} (lines {}-{})", marker, fold.start, fold.end);
+    match lang_of(rel_path) {
+        Some(Lang::Python) => format!("{}# {}", indent, note),
+        _ => format!("{}/* {} */", indent, note),
+    }
+}
+
+/// Replaces folded line ranges with a marker. Folds whose header line no longer matches
+/// are skipped and returned so the caller can warn about them.
+pub fn apply_folds(
+    rel_path: &str,
+    text: &str,
+    folds: &[Fold],
+    marker: &str,
+) -> (String, Vec<Fold>) {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut stale = Vec::new();
+    let mut active: Vec<&Fold> = Vec::new();
+    for fold in folds {
+        let ok = fold.start >= 1
+            && fold.end <= lines.len()
+            && crate::line_numbers::strip(lines[fold.start - 1]).trim() == fold.header;
+        if ok {
+            active.push(fold);
+        } else {
+            stale.push(fold.clone());
+        }
+    }
+    active.sort_by_key(|f| f.start);
+
+    let mut out = String::with_capacity(text.len());
+    let mut line = 1;
+    for fold in active {
+        if fold.start < line {
+            continue; // nested inside an outer fold already omitted
+        }
+        for l in &lines[line - 1..fold.start - 1] {
+            out.push_str(l);
+            out.push('\n');
+        }
+        let header = crate::line_numbers::strip(lines[fold.start - 1]);
+        let indent = &header[..header.len() - header.trim_start().len()];
+        out.push_str(&omitted_line(rel_path, marker, fold, indent));
+        out.push('\n');
+        line = fold.end + 1;
+    }
+    for l in lines.iter().skip(line - 1) {
+        out.push_str(l);
+        out.push('\n');
+    }
+    (out, stale)
+}
+
+/// The file shown in the preview pane.
+pub struct Preview {
+    pub rel_path: RelPath,
+    pub path: PathBuf,
+    pub text: String,
+    pub regions: Vec<Region>,
+}
+
+impl Preview {
+    pub fn load(rel_path: RelPath, path: PathBuf) -> std::io::Result<Self> {
+        let text = std::fs::read_to_string(&path)?;
+        Ok(Self::from_text(rel_path, path, text))
+    }
+
+    /// A preview of text read some other way, e.g. from inside an archive.
+    pub fn from_text(rel_path: RelPath, path: PathBuf, text: String) -> Self {
+        let regions = regions(&rel_path, &text);
+        Self {
+            rel_path,
+            path,
+            text,
+            regions,
+        }
+    }
+
+    pub fn fold_for(&self, region: &Region) -> Fold {
+        self.fold_lines(region.start, region.end)
+    }
+
+    fn fold_lines(&self, start: usize, end: usize) -> Fold {
+        let header = self
+            .text
+            .lines()
+            .nth(start - 1)
+            .unwrap_or("")
+            .trim()
+            .to_string();
+        Fold { start, end, header }
+    }
+
+    /// Folds hiding the bodies of the innermost regions, keeping their first and last
+    /// lines (signature and closing brace) and everything around them.
+    pub fn outline_folds(&self) -> Vec<Fold> {
+        self.regions
+            .iter()
+            .filter(|r| {
+                !self
+                    .regions
+                    .iter()
+                    .any(|q| q.start > r.start && q.end <= r.end)
+            })
+            .filter(|r| r.end >= r.start + 3)
+            .map(|r| self.fold_lines(r.start + 1, r.end - 1))
+            .collect()
+    }
+
+    /// Folds hiding everything outside lines `first..=last`.
+    pub fn range_folds(&self, first: usize, last: usize) -> Vec<Fold> {
+        let total = self.text.lines().count();
+        let mut folds = Vec::new();
+        if first > 1 {
+            folds.push(self.fold_lines(1, first - 1));
+        }
+        if last < total {
+            folds.push(self.fold_lines(last + 1, total));
+        }
+        folds
+    }
+}
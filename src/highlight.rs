@@ -0,0 +1,69 @@
+// Syntax colours for the file preview, from syntect's bundled grammars and themes. Lines
+// are highlighted in order so multi-line strings and comments come out right; the result
+// is kept per line as byte ranges so the viewer can layer search hits on top.
+
+use eframe::egui::Color32;
+use once_cell::sync::Lazy;
+use std::ops::Range;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+/// Past this size the preview stays plain; the regex-based grammars get slow.
+const MAX_BYTES: usize = 512 * 1024;
+
+static SYNTAXES: Lazy<SyntaxSet> = Lazy::new(SyntaxSet::load_defaults_newlines);
+static THEMES: Lazy<ThemeSet> = Lazy::new(ThemeSet::load_defaults);
+
+/// A coloured span of one line, in bytes from the line start.
+pub type Span = (Color32, Range<usize>);
+
+pub struct Highlighted {
+    /// Which theme the colours came from, so a visuals switch re-highlights.
+    pub dark: bool,
+    /// Empty when the file isn't highlighted.
+    pub lines: Vec<Vec<Span>>,
+}
+
+impl Highlighted {
+    pub fn line(&self, index: usize) -> &[Span] {
+        self.lines.get(index).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Colours for `text` by the file name's extension; none for unknown languages and
+/// oversized files.
+pub fn highlight(rel_path: &str, text: &str, dark: bool) -> Highlighted {
+    Highlighted {
+        dark,
+        lines: highlight_lines(rel_path, text, dark).unwrap_or_default(),
+    }
+}
+
+fn highlight_lines(rel_path: &str, text: &str, dark: bool) -> Option<Vec<Vec<Span>>> {
+    if text.len() > MAX_BYTES {
+        return None;
+    }
+    let ext = rel_path.rsplit('.').next()?;
+    let syntax = SYNTAXES.find_syntax_by_extension(ext)?;
+    let theme = &THEMES.themes[if dark {
+        "base16-ocean.dark"
+    } else {
+        "InspiredGitHub"
+    }];
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let mut lines = Vec::new();
+    for line in LinesWithEndings::from(text) {
+        let ranges = highlighter.highlight_line(line, &SYNTAXES).ok()?;
+        let mut spans = Vec::with_capacity(ranges.len());
+        let mut pos = 0;
+        for (style, piece) in ranges {
+            let c = style.foreground;
+            spans.push((Color32::from_rgb(c.r, c.g, c.b), pos..pos + piece.len()));
+            pos += piece.len();
+        }
+        lines.push(spans);
+    }
+    Some(lines)
+}
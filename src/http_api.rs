@@ -0,0 +1,188 @@
+// Localhost HTTP API for editor plugins and scripts: `prompt --serve 127.0.0.1:7777`
+// drives the open GUI session. Like the MCP server, requests are handed to the GUI thread
+// and answered from there.
+//
+// Every request needs `Authorization: Bearer <token>`, with the token printed to stderr at
+// startup, and a `Host` of 127.0.0.1 or localhost: any web page can reach a loopback port,
+// and a DNS-rebound name would otherwise get through too.
+//
+//   POST /instruction   body: the free-form instruction text
+//   POST /select        {"glob": "src/**/*.rs", "selected": true}  -> {"matched": n}
+//   POST /generate      builds (and records) the prompt            -> {"tokens": n}
+//   GET  /prompt        the last generated prompt, as text
+
+use crate::error::{PromptError, Result};
+use eframe::egui;
+use serde_json::{json, Value};
+use std::hash::{BuildHasher, Hasher};
+use std::net::SocketAddr;
+use std::sync::mpsc;
+
+/// Response body, or the message of a failed call.
+pub type Reply = mpsc::Sender<std::result::Result<String, String>>;
+
+pub enum ApiCall {
+    SetInstruction(String),
+    Select { glob: String, selected: bool },
+    Generate,
+    Prompt,
+}
+
+pub struct ApiRequest {
+    pub call: ApiCall,
+    pub reply: Reply,
+}
+
+pub struct ApiServer {
+    requests: mpsc::Receiver<ApiRequest>,
+}
+
+impl ApiServer {
+    /// Binds `addr`, which must be a loopback address: the API hands out project code.
+    pub fn start(addr: &str, ctx: egui::Context) -> Result<Self> {
+        let fail = |message: String| PromptError::Config {
+            path: "--serve".into(),
+            message,
+        };
+        let addr: SocketAddr = addr.parse().map_err(|e| fail(format!("{}: {}", addr, e)))?;
+        if !addr.ip().is_loopback() {
+            return Err(fail(format!("{} is not a loopback address", addr)));
+        }
+        let server = tiny_http::Server::http(addr).map_err(|e| fail(e.to_string()))?;
+        let token = new_token();
+        eprintln!("[serve] listening on http://{}", addr);
+        eprintln!(
+            "[serve] send \"Authorization: Bearer {}\" with every request",
+            token
+        );
+        let (tx, requests) = mpsc::channel();
+        std::thread::spawn(move || {
+            for request in server.incoming_requests() {
+                if !is_local_host(&request) {
+                    respond_error(request, 403, "Host must be 127.0.0.1 or localhost");
+                } else if !has_token(&request, &token) {
+                    respond_error(request, 401, "missing or wrong bearer token");
+                } else {
+                    serve(request, &tx, &ctx);
+                }
+            }
+        });
+        Ok(Self { requests })
+    }
+
+    pub fn next(&self) -> Option<ApiRequest> {
+        self.requests.try_recv().ok()
+    }
+}
+
+/// 128 random bits as hex. The standard library's hasher keys come from the OS's random
+/// source, which is all this needs without another dependency.
+fn new_token() -> String {
+    let state = std::collections::hash_map::RandomState::new();
+    (0..2u8)
+        .map(|i| {
+            let mut hasher = state.build_hasher();
+            hasher.write_u8(i);
+            hasher.write_u128(
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |d| d.as_nanos()),
+            );
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
+
+fn header<'a>(request: &'a tiny_http::Request, name: &'static str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.as_str())
+}
+
+fn is_local_host(request: &tiny_http::Request) -> bool {
+    let Some(host) = header(request, "Host") else {
+        return false;
+    };
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if port.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => host,
+    };
+    name.eq_ignore_ascii_case("localhost") || name == "127.0.0.1" || name == "[::1]"
+}
+
+fn has_token(request: &tiny_http::Request, token: &str) -> bool {
+    let Some(given) = header(request, "Authorization").and_then(|v| v.strip_prefix("Bearer "))
+    else {
+        return false;
+    };
+    // Compared in full whatever differs, so the time taken says nothing about the token
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn respond(request: tiny_http::Request, status: u16, content_type: &str, body: String) {
+    let header = tiny_http::Header::from_bytes("Content-Type", content_type)
+        .expect("static header is valid");
+    let response = tiny_http::Response::from_string(body)
+        .with_status_code(status)
+        .with_header(header);
+    let _ = request.respond(response);
+}
+
+fn respond_error(request: tiny_http::Request, status: u16, message: &str) {
+    respond(
+        request,
+        status,
+        "application/json",
+        json!({ "error": message }).to_string(),
+    );
+}
+
+fn serve(mut request: tiny_http::Request, tx: &mpsc::Sender<ApiRequest>, ctx: &egui::Context) {
+    let mut body = String::new();
+    if request.as_reader().read_to_string(&mut body).is_err() {
+        return respond_error(request, 400, "body is not UTF-8");
+    }
+    let path = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    let call = match (request.method(), path.as_str()) {
+        (tiny_http::Method::Post, "/instruction") => ApiCall::SetInstruction(body),
+        (tiny_http::Method::Post, "/select") => {
+            let Ok(args) = serde_json::from_str::<Value>(&body) else {
+                return respond_error(request, 400, "expected a JSON body");
+            };
+            let Some(glob) = args["glob"].as_str() else {
+                return respond_error(request, 400, "missing \"glob\"");
+            };
+            ApiCall::Select {
+                glob: glob.to_string(),
+                selected: args["selected"].as_bool().unwrap_or(true),
+            }
+        }
+        (tiny_http::Method::Post, "/generate") => ApiCall::Generate,
+        (tiny_http::Method::Get, "/prompt") => ApiCall::Prompt,
+        _ => return respond_error(request, 404, "no such endpoint"),
+    };
+    let plain = matches!(call, ApiCall::Prompt);
+    let (reply, answer) = mpsc::channel();
+    if tx.send(ApiRequest { call, reply }).is_err() {
+        return respond_error(request, 503, "the app is shutting down");
+    }
+    ctx.request_repaint();
+    match answer.recv() {
+        Ok(Ok(text)) if plain => respond(request, 200, "text/plain; charset=utf-8", text),
+        Ok(Ok(text)) => respond(request, 200, "application/json", text),
+        Ok(Err(message)) => respond_error(request, 400, &message),
+        Err(_) => respond_error(request, 503, "the app closed before answering"),
+    }
+}
@@ -0,0 +1,123 @@
+use crate::error::{PromptError, Result};
+use crate::file_item::{build_ignore_set, ignore_rules, is_path_ignored, list_all_rel_paths};
+use crate::language_packs::LanguagePack;
+use std::path::{Path, PathBuf};
+
+// Upper bound on the unfiltered walk done when the editor opens (target/ can be huge)
+const PREVIEW_SCAN_LIMIT: usize = 100_000;
+const SAMPLE_PATHS: usize = 20;
+
+#[derive(Default)]
+pub struct IgnoreEditor {
+    pub open: bool,
+    pub path: Option<PathBuf>,
+    pub text: String,
+    pub saved_text: String,
+    all_paths: Vec<String>,
+    saved_ignored: Vec<bool>,
+    preview_for: Option<String>,
+    pub newly_ignored: usize,
+    pub newly_unignored: usize,
+    pub ignored_total: usize,
+    pub sample_ignored: Vec<String>,
+    pub sample_unignored: Vec<String>,
+    pub pattern_errors: Vec<String>,
+    /// The project's language packs, whose patterns the scan adds to the file's.
+    packs: Vec<&'static LanguagePack>,
+}
+
+impl IgnoreEditor {
+    /// Load the active ignore file and snapshot every file under `base` so previews don't
+    /// touch the disk while typing. Without a file the buffer starts with the fallback
+    /// rules the scan uses then, unsaved.
+    pub fn open_for(&mut self, base: &Path, packs: &[&'static LanguagePack]) {
+        let path = crate::file_item::find_ignore_file(base)
+            .unwrap_or_else(|| base.join(".prompt").join(".promptignore"));
+        let saved = std::fs::read_to_string(&path).ok();
+        self.packs = packs.to_vec();
+
+        self.all_paths = list_all_rel_paths(base, PREVIEW_SCAN_LIMIT);
+        self.all_paths.sort();
+        let (saved_set, _) = build_ignore_set(&ignore_rules(saved.as_deref(), &self.packs));
+        self.saved_ignored = self
+            .all_paths
+            .iter()
+            .map(|p| is_path_ignored(&saved_set, p))
+            .collect();
+
+        self.path = Some(path);
+        self.text = saved
+            .clone()
+            .unwrap_or_else(|| ignore_rules(None, &self.packs));
+        self.saved_text = saved.unwrap_or_default();
+        self.preview_for = None;
+        self.open = true;
+        self.update_preview();
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.text != self.saved_text
+    }
+
+    /// Recompute the diff against the saved rules; no-op if the text hasn't changed.
+    pub fn update_preview(&mut self) {
+        if self.preview_for.as_deref() == Some(self.text.as_str()) {
+            return;
+        }
+        self.preview_for = Some(self.text.clone());
+
+        let (set, errors) = build_ignore_set(&ignore_rules(Some(&self.text), &self.packs));
+        self.pattern_errors = errors.iter().map(|e| e.to_string()).collect();
+        self.newly_ignored = 0;
+        self.newly_unignored = 0;
+        self.ignored_total = 0;
+        self.sample_ignored.clear();
+        self.sample_unignored.clear();
+        for (path, &was) in self.all_paths.iter().zip(&self.saved_ignored) {
+            let now = is_path_ignored(&set, path);
+            if now {
+                self.ignored_total += 1;
+            }
+            if now && !was {
+                self.newly_ignored += 1;
+                if self.sample_ignored.len() < SAMPLE_PATHS {
+                    self.sample_ignored.push(path.clone());
+                }
+            } else if was && !now {
+                self.newly_unignored += 1;
+                if self.sample_unignored.len() < SAMPLE_PATHS {
+                    self.sample_unignored.push(path.clone());
+                }
+            }
+        }
+    }
+
+    pub fn total_files(&self) -> usize {
+        self.all_paths.len()
+    }
+
+    /// Write the buffer to disk; the caller is expected to `refresh_files` afterwards.
+    pub fn save(&mut self) -> Result<PathBuf> {
+        let Some(path) = self.path.clone() else {
+            return Err(PromptError::io(
+                "write",
+                ".promptignore",
+                std::io::ErrorKind::NotFound.into(),
+            ));
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| PromptError::io("create", dir, e))?;
+        }
+        std::fs::write(&path, &self.text).map_err(|e| PromptError::io("write", &path, e))?;
+        self.saved_text = self.text.clone();
+        let (set, _) = build_ignore_set(&ignore_rules(Some(&self.text), &self.packs));
+        self.saved_ignored = self
+            .all_paths
+            .iter()
+            .map(|p| is_path_ignored(&set, p))
+            .collect();
+        self.preview_for = None;
+        self.update_preview();
+        Ok(path)
+    }
+}
@@ -0,0 +1,79 @@
+// Image assets in the selection are described instead of dropped as "[binary file
+// omitted]": format, dimensions and byte size, read from the header alone. With
+// `ProjectConfig::image_thumbnails` a small PNG thumbnail is added as a data URL, for
+// multimodal models that read inline images.
+
+use crate::error::{PromptError, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::path::Path;
+
+/// Longest side of an embedded thumbnail, in pixels.
+const THUMBNAIL_SIZE: u32 = 128;
+/// Token estimate for an image's description line before it is read.
+pub const DESCRIPTION_TOKENS: usize = 20;
+
+pub fn is_image(rel_path: &str) -> bool {
+    let Some((_, ext)) = rel_path.rsplit_once('.') else {
+        return false;
+    };
+    matches!(
+        ext.to_ascii_lowercase().as_str(),
+        "png" | "jpg" | "jpeg" | "gif" | "webp" | "bmp" | "ico" | "tif" | "tiff"
+    )
+}
+
+fn image_error(path: &Path, message: impl ToString) -> PromptError {
+    PromptError::Image {
+        path: path.to_path_buf(),
+        message: message.to_string(),
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
+
+/// One-line description of the image at `path`, plus a thumbnail line when asked for.
+pub fn describe(path: &Path, thumbnail: bool) -> Result<String> {
+    let size = std::fs::metadata(path)
+        .map_err(|e| PromptError::io("read", path, e))?
+        .len();
+    let open = || {
+        image::ImageReader::open(path)
+            .map_err(|e| PromptError::io("read", path, e))?
+            .with_guessed_format()
+            .map_err(|e| PromptError::io("read", path, e))
+    };
+    let reader = open()?;
+    let format = reader
+        .format()
+        .map(|f| format!("{:?}", f).to_uppercase())
+        .unwrap_or_else(|| "unknown format".to_string());
+    let (width, height) = reader.into_dimensions().map_err(|e| image_error(path, e))?;
+    let mut out = format!(
+        "[image: {}, {}×{} px, {}]\n",
+        format,
+        width,
+        height,
+        format_bytes(size)
+    );
+    if thumbnail {
+        let image = open()?.decode().map_err(|e| image_error(path, e))?;
+        let mut png = std::io::Cursor::new(Vec::new());
+        image
+            .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+            .write_to(&mut png, image::ImageFormat::Png)
+            .map_err(|e| image_error(path, e))?;
+        out.push_str(&format!(
+            "thumbnail: data:image/png;base64,{}\n",
+            STANDARD.encode(png.into_inner())
+        ));
+    }
+    Ok(out)
+}
@@ -0,0 +1,136 @@
+// Reusable instruction snippets ("write tests", "respond with unified diff only"), one
+// text file each in `.prompt/snippets/`, toggled on and combined with the free-form
+// instruction. Which ones are on is remembered in config.toml.
+
+use crate::error::{PromptError, Result};
+use std::path::{Path, PathBuf};
+
+const STARTERS: &[(&str, &str)] = &[
+    (
+        "write-tests",
+        "Add or update tests that cover the change, following the project's existing test layout.",
+    ),
+    (
+        "explain-tradeoffs",
+        "Before the code, briefly explain the design options you considered and why you chose this one.",
+    ),
+    (
+        "unified-diff-only",
+        "Respond with a unified diff only (git apply compatible), no prose and no full files.",
+    ),
+];
+
+pub struct Snippet {
+    /// File stem; also the key stored in config.
+    pub name: String,
+    pub text: String,
+}
+
+#[derive(Default)]
+pub struct InstructionLibrary {
+    pub snippets: Vec<Snippet>,
+    pub editor_open: bool,
+    /// Snippet being edited (or created, when the name is new).
+    pub draft_name: String,
+    pub draft_text: String,
+}
+
+pub fn dir(base: &Path) -> PathBuf {
+    base.join(".prompt").join("snippets")
+}
+
+/// Keeps names usable as file stems on every platform.
+pub fn sanitize_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+impl InstructionLibrary {
+    /// `.txt` and `.md` files, sorted by name. A missing folder is an empty library.
+    pub fn load(&mut self, base: &Path) -> Result<()> {
+        let dir = dir(base);
+        self.snippets.clear();
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(PromptError::io("read", dir, e)),
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_text = matches!(
+                path.extension().and_then(|e| e.to_str()),
+                Some("txt") | Some("md")
+            );
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            if !is_text {
+                continue;
+            }
+            let text =
+                std::fs::read_to_string(&path).map_err(|e| PromptError::io("read", &path, e))?;
+            self.snippets.push(Snippet {
+                name: name.to_string(),
+                text: text.trim().to_string(),
+            });
+        }
+        self.snippets.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Snippet> {
+        self.snippets.iter().find(|s| s.name == name)
+    }
+
+    /// Writes `<name>.txt`, replacing an existing `.md` of the same name.
+    pub fn save(&mut self, base: &Path, name: &str, text: &str) -> Result<()> {
+        let dir = dir(base);
+        std::fs::create_dir_all(&dir).map_err(|e| PromptError::io("create", &dir, e))?;
+        let _ = std::fs::remove_file(dir.join(format!("{}.md", name)));
+        let path = dir.join(format!("{}.txt", name));
+        std::fs::write(&path, text.trim()).map_err(|e| PromptError::io("write", &path, e))?;
+        self.load(base)
+    }
+
+    pub fn delete(&mut self, base: &Path, name: &str) -> Result<()> {
+        for ext in ["txt", "md"] {
+            let path = dir(base).join(format!("{}.{}", name, ext));
+            if path.exists() {
+                std::fs::remove_file(&path).map_err(|e| PromptError::io("remove", &path, e))?;
+            }
+        }
+        self.load(base)
+    }
+
+    /// Writes the starter snippets that don't exist yet.
+    pub fn create_starters(&mut self, base: &Path) -> Result<()> {
+        for (name, text) in STARTERS {
+            if self.get(name).is_none() {
+                self.save(base, name, text)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Enabled snippets (in `enabled` order) followed by the free-form text.
+    pub fn compose(&self, enabled: &[String], free_text: &str) -> String {
+        let mut parts: Vec<&str> = enabled
+            .iter()
+            .filter_map(|n| self.get(n))
+            .map(|s| s.text.as_str())
+            .filter(|t| !t.is_empty())
+            .collect();
+        if !free_text.trim().is_empty() {
+            parts.push(free_text);
+        }
+        parts.join("\n\n")
+    }
+}
@@ -0,0 +1,6 @@
+use std::sync::Arc;
+
+/// Shared, cheaply clonable path string. A scan builds each file's once; the file list,
+/// tree, conversation and jobs clone it with a refcount bump, and it's freed with the last
+/// of them (a folder switch or a rescan that no longer finds the file).
+pub type RelPath = Arc<str>;
@@ -0,0 +1,386 @@
+// Background work (remote fetches, exact token counts, terminal commands, model requests,
+// evaluations) goes through one `Jobs` list: each job runs on a worker thread and reports
+// over a single channel, and the Jobs window shows what is running, lets it be cancelled,
+// and keeps the errors of failed jobs until they're cleared.
+
+use crate::error::Result;
+use crate::eval::EvalUpdate;
+use crate::intern::RelPath;
+use crate::providers::ProviderUpdate;
+use crate::remote::RemoteUpdate;
+use eframe::egui;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+/// Finished jobs kept for the window; older ones are dropped first.
+const MAX_FINISHED: usize = 50;
+
+pub type JobId = u64;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum JobKind {
+    Fetch,
+    Tokenize,
+    Command,
+    Model,
+    Eval,
+    Scan,
+}
+
+impl JobKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            JobKind::Fetch => "Fetch",
+            JobKind::Tokenize => "Tokenize",
+            JobKind::Command => "Command",
+            JobKind::Model => "Model",
+            JobKind::Eval => "Evaluate",
+            JobKind::Scan => "Scan",
+        }
+    }
+}
+
+/// One file's exact count; `raw` when it was counted over the file as read, which also
+/// calibrates the size estimates.
+pub struct FileTokens {
+    pub index: usize,
+    pub rel_path: RelPath,
+    pub tokens: usize,
+    pub raw: bool,
+}
+
+/// What a job hands back to the app. Streaming jobs may send several before finishing.
+pub enum JobOutput {
+    Remote(RemoteUpdate),
+    Terminal {
+        command: String,
+        working_dir: String,
+        result: Result<crate::command_runner::CommandOutput>,
+    },
+    Lint(Result<Vec<crate::build_errors::Diagnostic>>),
+    CommitMessage(Result<String>),
+    PrDescription(crate::pr_description::PrUpdate),
+    Eval(EvalUpdate),
+    Provider(ProviderUpdate),
+    Tokens {
+        index: usize,
+        rel_path: RelPath,
+        result: Result<usize>,
+    },
+    /// Exact counts of the files just built into a prompt.
+    PromptTokens(Vec<FileTokens>),
+    Scan {
+        folder: std::path::PathBuf,
+        /// The deferred folder walked, or None for the whole project.
+        under: Option<String>,
+        files: Vec<crate::file_item::ScannedFile>,
+        stats: crate::file_item::ScanStats,
+    },
+    Archive {
+        folder: std::path::PathBuf,
+        /// The archive's relative path, under which its entries are listed.
+        rel_path: String,
+        result: Result<Vec<crate::file_item::ScannedFile>>,
+    },
+    /// Files of `folder` changed on disk; see file_watch.rs.
+    FileChanges {
+        folder: std::path::PathBuf,
+        changes: Vec<crate::file_watch::FileChange>,
+    },
+}
+
+impl JobOutput {
+    /// The failure this output reports, if any; it marks the job as failed.
+    fn error(&self) -> Option<String> {
+        match self {
+            JobOutput::Remote(RemoteUpdate::Failed { error, .. }) => Some(error.to_string()),
+            JobOutput::Provider(ProviderUpdate::Failed(e)) => Some(e.to_string()),
+            JobOutput::PrDescription(crate::pr_description::PrUpdate::Failed(e)) => {
+                Some(e.to_string())
+            }
+            JobOutput::Terminal { result: Err(e), .. }
+            | JobOutput::Lint(Err(e))
+            | JobOutput::CommitMessage(Err(e))
+            | JobOutput::Tokens { result: Err(e), .. }
+            | JobOutput::Archive { result: Err(e), .. } => Some(e.to_string()),
+            _ => None,
+        }
+    }
+}
+
+enum Event {
+    Progress(JobId, String),
+    Output(JobId, JobOutput),
+    Done(JobId),
+}
+
+/// Handed to the worker: cancellation flag, progress text and intermediate output.
+pub struct JobContext {
+    id: JobId,
+    cancel: Arc<AtomicBool>,
+    tx: mpsc::Sender<Event>,
+}
+
+impl JobContext {
+    pub fn cancel_flag(&self) -> &AtomicBool {
+        &self.cancel
+    }
+
+    pub fn progress(&self, text: impl Into<String>) {
+        let _ = self.tx.send(Event::Progress(self.id, text.into()));
+    }
+
+    pub fn send(&self, output: JobOutput) {
+        let _ = self.tx.send(Event::Output(self.id, output));
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub enum JobState {
+    Running,
+    Finished,
+    Failed(String),
+    Cancelled,
+}
+
+pub struct Job {
+    pub id: JobId,
+    pub kind: JobKind,
+    pub label: String,
+    pub started: Instant,
+    pub elapsed: Option<Duration>,
+    pub progress: String,
+    pub state: JobState,
+    cancel: Arc<AtomicBool>,
+}
+
+pub struct Jobs {
+    pub jobs: Vec<Job>,
+    pub open: bool,
+    next_id: JobId,
+    tx: mpsc::Sender<Event>,
+    rx: mpsc::Receiver<Event>,
+}
+
+impl Default for Jobs {
+    fn default() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            jobs: Vec::new(),
+            open: false,
+            next_id: 1,
+            tx,
+            rx,
+        }
+    }
+}
+
+impl Jobs {
+    /// Runs `work` on a new thread. Its return value is delivered like any other output.
+    pub fn spawn<F>(&mut self, kind: JobKind, label: impl Into<String>, work: F) -> JobId
+    where
+        F: FnOnce(&JobContext) -> JobOutput + Send + 'static,
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.jobs.push(Job {
+            id,
+            kind,
+            label: label.into(),
+            started: Instant::now(),
+            elapsed: None,
+            progress: String::new(),
+            state: JobState::Running,
+            cancel: cancel.clone(),
+        });
+        let ctx = JobContext {
+            id,
+            cancel,
+            tx: self.tx.clone(),
+        };
+        std::thread::spawn(move || {
+            let output = work(&ctx);
+            ctx.send(output);
+            let _ = ctx.tx.send(Event::Done(id));
+        });
+        id
+    }
+
+    /// Applies progress and state changes; returns the outputs of jobs that weren't
+    /// cancelled, in arrival order.
+    pub fn poll(&mut self) -> Vec<JobOutput> {
+        let mut outputs = Vec::new();
+        while let Ok(event) = self.rx.try_recv() {
+            let id = match &event {
+                Event::Progress(id, _) | Event::Output(id, _) | Event::Done(id) => *id,
+            };
+            let Some(job) = self.jobs.iter_mut().find(|j| j.id == id) else {
+                continue;
+            };
+            if job.state == JobState::Cancelled {
+                continue;
+            }
+            match event {
+                Event::Progress(_, text) => job.progress = text,
+                Event::Output(_, output) => {
+                    if let Some(error) = output.error() {
+                        job.state = JobState::Failed(error);
+                    }
+                    outputs.push(output);
+                }
+                Event::Done(_) => {
+                    job.elapsed = Some(job.started.elapsed());
+                    if job.state == JobState::Running {
+                        job.state = JobState::Finished;
+                    }
+                }
+            }
+        }
+        self.prune();
+        outputs
+    }
+
+    /// Asks the worker to stop (commands are killed) and drops whatever it still sends.
+    pub fn cancel(&mut self, id: JobId) {
+        if let Some(job) = self.jobs.iter_mut().find(|j| j.id == id) {
+            if job.state == JobState::Running {
+                job.cancel.store(true, Ordering::Relaxed);
+                job.state = JobState::Cancelled;
+                job.elapsed = Some(job.started.elapsed());
+            }
+        }
+    }
+
+    /// Cancels every running job, as when the tab they belong to is closed.
+    pub fn cancel_all(&mut self) {
+        let running: Vec<JobId> = self
+            .jobs
+            .iter()
+            .filter(|j| j.state == JobState::Running)
+            .map(|j| j.id)
+            .collect();
+        for id in running {
+            self.cancel(id);
+        }
+    }
+
+    /// The progress text of a running job.
+    pub fn progress(&self, id: JobId) -> Option<&str> {
+        self.jobs
+            .iter()
+            .find(|j| j.id == id && j.state == JobState::Running)
+            .map(|j| j.progress.as_str())
+    }
+
+    pub fn is_running(&self, id: JobId) -> bool {
+        self.jobs
+            .iter()
+            .any(|j| j.id == id && j.state == JobState::Running)
+    }
+
+    pub fn any_running(&self, kind: JobKind) -> bool {
+        self.jobs
+            .iter()
+            .any(|j| j.kind == kind && j.state == JobState::Running)
+    }
+
+    pub fn running_count(&self) -> usize {
+        self.jobs
+            .iter()
+            .filter(|j| j.state == JobState::Running)
+            .count()
+    }
+
+    pub fn failed_count(&self) -> usize {
+        self.jobs
+            .iter()
+            .filter(|j| matches!(j.state, JobState::Failed(_)))
+            .count()
+    }
+
+    fn prune(&mut self) {
+        let finished = self.jobs.len() - self.running_count();
+        let mut excess = finished.saturating_sub(MAX_FINISHED);
+        self.jobs.retain(|j| {
+            if excess > 0 && j.state != JobState::Running {
+                excess -= 1;
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    pub fn window(&mut self, ctx: &egui::Context) {
+        let mut open = self.open;
+        let mut cancel = None;
+        let mut clear = false;
+        egui::Window::new("Jobs")
+            .open(&mut open)
+            .default_width(520.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "{} running, {} failed",
+                        self.running_count(),
+                        self.failed_count()
+                    ));
+                    if ui.button("Clear finished").clicked() {
+                        clear = true;
+                    }
+                });
+                ui.separator();
+                if self.jobs.is_empty() {
+                    ui.label("Nothing has run yet.");
+                    return;
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(360.0)
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        for job in self.jobs.iter().rev() {
+                            ui.horizontal(|ui| {
+                                let elapsed = job.elapsed.unwrap_or_else(|| job.started.elapsed());
+                                match &job.state {
+                                    JobState::Running => {
+                                        ui.spinner();
+                                    }
+                                    JobState::Finished => {
+                                        ui.colored_label(egui::Color32::LIGHT_GREEN, "✔");
+                                    }
+                                    JobState::Failed(_) => {
+                                        ui.colored_label(egui::Color32::LIGHT_RED, "✘");
+                                    }
+                                    JobState::Cancelled => {
+                                        ui.weak("⊘");
+                                    }
+                                }
+                                ui.strong(job.kind.label());
+                                ui.label(&job.label);
+                                ui.weak(format!("{:.1}s", elapsed.as_secs_f32()));
+                                if !job.progress.is_empty() {
+                                    ui.weak(&job.progress);
+                                }
+                                if job.state == JobState::Running
+                                    && ui.small_button("Cancel").clicked()
+                                {
+                                    cancel = Some(job.id);
+                                }
+                            });
+                            if let JobState::Failed(error) = &job.state {
+                                ui.colored_label(egui::Color32::LIGHT_RED, error);
+                            }
+                        }
+                    });
+            });
+        self.open = open;
+        if let Some(id) = cancel {
+            self.cancel(id);
+        }
+        if clear {
+            self.jobs.retain(|j| j.state == JobState::Running);
+        }
+    }
+}
@@ -0,0 +1,197 @@
+// JSON rendering of the prompt for pipelines that pass structured context to an API
+// instead of pasting text into a chat. Escaping comes from serde_json, so the content
+// escaping strategy doesn't apply; section inclusion still follows the layout.
+//
+// The OpenAI messages export wraps the XML prompt instead: the system prompt as the
+// `system` message, everything else as the `user` message, with selected images attached
+// as `image_url` parts.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    #[default]
+    Xml,
+    Json,
+    /// A chat completions `messages` array around the XML prompt.
+    OpenAiMessages,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 3] = [
+        OutputFormat::Xml,
+        OutputFormat::Json,
+        OutputFormat::OpenAiMessages,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            OutputFormat::Xml => "XML",
+            OutputFormat::Json => "JSON",
+            OutputFormat::OpenAiMessages => "OpenAI messages",
+        }
+    }
+
+    /// File extension for saved prompts.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Xml => "xml",
+            OutputFormat::Json | OutputFormat::OpenAiMessages => "json",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct JsonFile {
+    pub path: String,
+    /// Markdown fence name of the file's language.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Content lines carry `NNN | ` prefixes.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub line_numbers: bool,
+    /// Path of the file this one repeats; its content is then left empty.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub same_as: Option<String>,
+    /// Original encoding of a file that was transcoded from something other than UTF-8.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    pub content: String,
+}
+
+#[derive(Serialize)]
+pub struct JsonSnippet {
+    pub path: String,
+    pub symbol: String,
+    /// Symbol kind, or "callers" for a call-site listing.
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lines: Option<(usize, usize)>,
+    pub content: String,
+}
+
+#[derive(Serialize)]
+pub struct JsonRemote {
+    pub source: String,
+    /// Only part of the fetched text is included.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub trimmed: bool,
+    pub content: String,
+}
+
+/// Exit status of the command behind a terminal output.
+#[derive(Serialize)]
+pub struct JsonRunStatus {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub timed_out: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+#[derive(Serialize)]
+pub struct JsonRun {
+    pub command: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub working_dir: String,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub status: Option<JsonRunStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<crate::build_errors::Diagnostic>,
+}
+
+/// Optional sections are omitted rather than emitted as null.
+#[derive(Serialize, Default)]
+pub struct JsonPrompt {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    pub instruction: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_tree: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_metadata: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_notes: Option<String>,
+    pub files: Vec<JsonFile>,
+    /// Files from outside the project folder; `path` is absolute or `~/`-relative.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<JsonFile>,
+    /// Follow-ups only: selected files the model has already seen unchanged.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unchanged_files: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub snippets: Vec<JsonSnippet>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub remote: Vec<JsonRemote>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub terminal_command: Option<String>,
+    /// Set when the command ran outside the project folder.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub terminal_working_dir: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub terminal_output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub terminal_status: Option<JsonRunStatus>,
+    /// Several runs' outputs, in place of `terminal_output`/`errors`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub terminal_runs: Vec<JsonRun>,
+    /// Stands in for `terminal_output` when errors are summarized.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<crate::build_errors::Diagnostic>,
+    /// Diagnostics ticked in the Lint window.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub lints: Vec<crate::build_errors::Diagnostic>,
+    /// Hash of the included files and the git commit; see code_state.rs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<serde_json::Value>,
+}
+
+impl JsonPrompt {
+    pub fn render(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+/// MIME type of the image formats chat APIs accept, by extension.
+pub fn image_mime(path: &str) -> Option<&'static str> {
+    let ext = path.rsplit('.').next()?.to_ascii_lowercase();
+    match ext.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// `{"messages": [...]}` for the chat completions API. The user message is plain text
+/// unless `images` (MIME type, bytes) are attached, then a list of content parts.
+pub fn openai_messages(system: Option<&str>, user: &str, images: &[(&str, Vec<u8>)]) -> String {
+    let mut messages = Vec::new();
+    if let Some(system) = system {
+        messages.push(json!({ "role": "system", "content": system }));
+    }
+    let content = if images.is_empty() {
+        Value::String(user.to_string())
+    } else {
+        let mut parts = vec![json!({ "type": "text", "text": user })];
+        for (mime, bytes) in images {
+            parts.push(json!({
+                "type": "image_url",
+                "image_url": { "url": format!("data:{};base64,{}", mime, STANDARD.encode(bytes)) },
+            }));
+        }
+        Value::Array(parts)
+    };
+    messages.push(json!({ "role": "user", "content": content }));
+    serde_json::to_string_pretty(&json!({ "messages": messages })).unwrap_or_default()
+}
@@ -0,0 +1,223 @@
+use crate::error::PromptError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// `[labels]` table in `.prompt/config.toml`:
+///
+/// ```toml
+/// [labels]
+/// language = "de"                 # built-in preset: en, de, ja
+/// [labels.overrides]
+/// instruction = "aufgabe"         # any key from PromptLabels
+/// truncated = "[... gekürzt ...]"
+/// ```
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LabelConfig {
+    pub language: String,
+    pub overrides: BTreeMap<String, String>,
+}
+
+impl Default for LabelConfig {
+    fn default() -> Self {
+        Self {
+            language: "en".to_string(),
+            overrides: BTreeMap::new(),
+        }
+    }
+}
+
+/// Section tag names and boilerplate markers emitted into the prompt.
+#[derive(Clone)]
+pub struct PromptLabels {
+    // tag names
+    pub system_prompt: String,
+    pub instruction: String,
+    pub file_tree: String,
+    pub project_metadata: String,
+    pub project_notes: String,
+    pub code: String,
+    pub file: String,
+    pub attachments: String,
+    pub attachment: String,
+    pub terminal_command: String,
+    pub terminal_output: String,
+    pub errors: String,
+    pub error: String,
+    pub lints: String,
+    pub snippets: String,
+    pub snippet: String,
+    pub remote: String,
+    pub remote_source: String,
+    pub unchanged_files: String,
+    pub snapshot: String,
+    // markers
+    pub truncated: String,
+    pub output_truncated: String,
+    pub binary_omitted: String,
+    pub read_error: String,
+    pub omitted: String,
+}
+
+pub const LANGUAGES: &[&str] = &["en", "de", "ja"];
+
+impl PromptLabels {
+    fn english() -> Self {
+        Self {
+            system_prompt: "system_prompt".into(),
+            instruction: "instruction".into(),
+            file_tree: "file_tree".into(),
+            project_metadata: "project_metadata".into(),
+            project_notes: "project_notes".into(),
+            code: "code".into(),
+            file: "file".into(),
+            attachments: "attachments".into(),
+            attachment: "attachment".into(),
+            terminal_command: "terminal_command".into(),
+            terminal_output: "terminal_output".into(),
+            errors: "errors".into(),
+            error: "error".into(),
+            lints: "lints".into(),
+            snippets: "snippets".into(),
+            snippet: "snippet".into(),
+            remote: "remote".into(),
+            remote_source: "source".into(),
+            unchanged_files: "unchanged_files".into(),
+            snapshot: "snapshot".into(),
+            truncated: "[... truncated ...]".into(),
+            output_truncated: "[... output truncated ...]".into(),
+            binary_omitted: "[binary file omitted]".into(),
+            read_error: "[error reading file]".into(),
+            omitted: "… omitted …".into(),
+        }
+    }
+
+    /// Presets translate the markers only; tag names stay English unless overridden,
+    /// since most models are tuned on English tag vocabularies.
+    fn preset(language: &str) -> Self {
+        let mut l = Self::english();
+        match language {
+            "de" => {
+                l.truncated = "[... gekürzt ...]".into();
+                l.output_truncated = "[... Ausgabe gekürzt ...]".into();
+                l.binary_omitted = "[Binärdatei ausgelassen]".into();
+                l.read_error = "[Fehler beim Lesen der Datei]".into();
+                l.omitted = "… ausgelassen …".into();
+            }
+            "ja" => {
+                l.truncated = "[... 省略 ...]".into();
+                l.output_truncated = "[... 出力省略 ...]".into();
+                l.binary_omitted = "[バイナリファイルは省略]".into();
+                l.read_error = "[ファイル読み込みエラー]".into();
+                l.omitted = "… 省略 …".into();
+            }
+            _ => {}
+        }
+        l
+    }
+
+    pub fn resolve(cfg: &LabelConfig) -> Self {
+        Self::resolve_with(cfg, &mut Vec::new())
+    }
+
+    /// What `resolve` leaves out of `cfg`: unknown override keys and tag names that
+    /// aren't valid XML names, as errors against the config file at `config_path`.
+    pub fn problems(cfg: &LabelConfig, config_path: &Path) -> Vec<PromptError> {
+        let mut problems = Vec::new();
+        Self::resolve_with(cfg, &mut problems);
+        problems
+            .into_iter()
+            .map(|message| PromptError::Config {
+                path: config_path.to_path_buf(),
+                message,
+            })
+            .collect()
+    }
+
+    fn resolve_with(cfg: &LabelConfig, problems: &mut Vec<String>) -> Self {
+        let mut l = Self::preset(&cfg.language);
+        for (key, value) in &cfg.overrides {
+            let slot = match key.as_str() {
+                "system_prompt" => &mut l.system_prompt,
+                "instruction" => &mut l.instruction,
+                "file_tree" => &mut l.file_tree,
+                "project_metadata" => &mut l.project_metadata,
+                "project_notes" => &mut l.project_notes,
+                "code" => &mut l.code,
+                "file" => &mut l.file,
+                "attachments" => &mut l.attachments,
+                "attachment" => &mut l.attachment,
+                "terminal_command" => &mut l.terminal_command,
+                "terminal_output" => &mut l.terminal_output,
+                "errors" => &mut l.errors,
+                "error" => &mut l.error,
+                "lints" => &mut l.lints,
+                "snippets" => &mut l.snippets,
+                "snippet" => &mut l.snippet,
+                "remote" => &mut l.remote,
+                "remote_source" => &mut l.remote_source,
+                "unchanged_files" => &mut l.unchanged_files,
+                "snapshot" => &mut l.snapshot,
+                "truncated" => &mut l.truncated,
+                "output_truncated" => &mut l.output_truncated,
+                "binary_omitted" => &mut l.binary_omitted,
+                "read_error" => &mut l.read_error,
+                "omitted" => &mut l.omitted,
+                other => {
+                    problems.push(format!("labels.overrides: unknown label key {:?}", other));
+                    continue;
+                }
+            };
+            *slot = value.clone();
+        }
+        // Tag names must stay valid XML names or the whole prompt becomes malformed
+        let english = Self::english();
+        for (tag, fallback) in [
+            (&mut l.system_prompt, english.system_prompt),
+            (&mut l.instruction, english.instruction),
+            (&mut l.file_tree, english.file_tree),
+            (&mut l.project_metadata, english.project_metadata),
+            (&mut l.project_notes, english.project_notes),
+            (&mut l.code, english.code),
+            (&mut l.file, english.file),
+            (&mut l.attachments, english.attachments),
+            (&mut l.attachment, english.attachment),
+            (&mut l.terminal_command, english.terminal_command),
+            (&mut l.terminal_output, english.terminal_output),
+            (&mut l.errors, english.errors),
+            (&mut l.error, english.error),
+            (&mut l.lints, english.lints),
+            (&mut l.snippets, english.snippets),
+            (&mut l.snippet, english.snippet),
+            (&mut l.remote, english.remote),
+            (&mut l.remote_source, english.remote_source),
+            (&mut l.unchanged_files, english.unchanged_files),
+            (&mut l.snapshot, english.snapshot),
+        ] {
+            if !is_xml_name(tag) {
+                problems.push(format!(
+                    "labels.overrides: {:?} is not a valid tag name; using {:?}",
+                    tag, fallback
+                ));
+                *tag = fallback;
+            }
+        }
+        l
+    }
+}
+
+impl Default for PromptLabels {
+    fn default() -> Self {
+        Self::english()
+    }
+}
+
+fn is_xml_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
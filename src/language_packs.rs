@@ -0,0 +1,148 @@
+// Built-in per-language defaults, switched on by marker files in the project root: ignore
+// rules used when the project has no .promptignore, generated-file patterns that are
+// always skipped, and the core files "Smart select" picks as a starting selection.
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::path::Path;
+
+pub struct LanguagePack {
+    pub name: &'static str,
+    /// Any of these in the project root activates the pack.
+    markers: &'static [&'static str],
+    /// `.promptignore` syntax.
+    ignores: &'static [&'static str],
+    /// `.promptignore` syntax; code generators' output, never worth prompt tokens.
+    generated: &'static [&'static str],
+    /// Globs relative to the root (`*` stops at `/`).
+    core: &'static [&'static str],
+}
+
+pub const PACKS: &[LanguagePack] = &[
+    LanguagePack {
+        name: "Rust",
+        markers: &["Cargo.toml"],
+        ignores: &["**/target/**", "Cargo.lock"],
+        generated: &["*.pb.rs", "*_generated.rs"],
+        core: &[
+            "Cargo.toml",
+            "*/Cargo.toml",
+            "build.rs",
+            "src/main.rs",
+            "src/lib.rs",
+            "*/src/lib.rs",
+            "crates/*/src/lib.rs",
+        ],
+    },
+    LanguagePack {
+        name: "JS/TS",
+        markers: &["package.json", "tsconfig.json"],
+        ignores: &[
+            "**/node_modules/**",
+            "**/dist/**",
+            "**/build/**",
+            "**/coverage/**",
+            "**/.next/**",
+            "**/.turbo/**",
+            "package-lock.json",
+            "yarn.lock",
+            "pnpm-lock.yaml",
+        ],
+        generated: &["*.min.js", "*.min.css", "*.map", "**/__snapshots__/**"],
+        core: &[
+            "package.json",
+            "tsconfig.json",
+            "index.{js,ts}",
+            "src/index.{js,jsx,ts,tsx}",
+            "src/main.{js,jsx,ts,tsx}",
+            "src/App.{jsx,tsx}",
+            "{vite,next,webpack}.config.{js,mjs,ts}",
+        ],
+    },
+    LanguagePack {
+        name: "Python",
+        markers: &[
+            "pyproject.toml",
+            "setup.py",
+            "setup.cfg",
+            "requirements.txt",
+        ],
+        ignores: &[
+            "**/__pycache__/**",
+            "*.pyc",
+            "**/.venv/**",
+            "**/venv/**",
+            "**/*.egg-info/**",
+            "**/.pytest_cache/**",
+            "**/.mypy_cache/**",
+            "**/.tox/**",
+            "poetry.lock",
+        ],
+        generated: &["*_pb2.py", "*_pb2_grpc.py"],
+        core: &[
+            "pyproject.toml",
+            "setup.py",
+            "requirements.txt",
+            "{main,app,manage,__main__}.py",
+            "*/__init__.py",
+            "*/__main__.py",
+            "src/*/__init__.py",
+        ],
+    },
+    LanguagePack {
+        name: "Go",
+        markers: &["go.mod"],
+        ignores: &["**/vendor/**", "go.sum"],
+        generated: &["*.pb.go", "*_gen.go", "zz_generated*.go"],
+        core: &["go.mod", "main.go", "cmd/*/main.go", "internal/*/doc.go"],
+    },
+];
+
+/// Every pack whose marker is present; polyglot repos get several.
+pub fn detect(base: &Path) -> Vec<&'static LanguagePack> {
+    PACKS
+        .iter()
+        .filter(|p| p.markers.iter().any(|m| base.join(m).is_file()))
+        .collect()
+}
+
+pub fn names(packs: &[&LanguagePack]) -> String {
+    packs.iter().map(|p| p.name).collect::<Vec<_>>().join(", ")
+}
+
+/// `.promptignore` sections for the packs, ignores and generated files together.
+pub fn ignore_text(packs: &[&LanguagePack]) -> String {
+    let mut text = String::new();
+    for pack in packs {
+        text.push_str(&format!("# {}\n", pack.name));
+        for pattern in pack.ignores {
+            text.push_str(pattern);
+            text.push('\n');
+        }
+        text.push_str(&format!("# {} (generated)\n", pack.name));
+        for pattern in pack.generated {
+            text.push_str(pattern);
+            text.push('\n');
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Generated-file patterns only, in `.promptignore` syntax.
+pub fn generated_text(packs: &[&LanguagePack]) -> String {
+    packs
+        .iter()
+        .flat_map(|p| p.generated.iter())
+        .map(|p| format!("{}\n", p))
+        .collect()
+}
+
+pub fn core_set(packs: &[&LanguagePack]) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in packs.iter().flat_map(|p| p.core.iter()) {
+        if let Ok(glob) = GlobBuilder::new(pattern).literal_separator(true).build() {
+            builder.add(glob);
+        }
+    }
+    builder.build().unwrap_or_else(|_| GlobSet::empty())
+}
@@ -0,0 +1,317 @@
+// Language of a project file, from its name (extension or well-known file name) or, for
+// extensionless scripts, its shebang or an editor modeline. The language's Markdown
+// fence name is sent as the `lang` attribute of included files, and its colour and
+// category are the file tree's built-in style for it (see file_types.rs).
+
+use std::path::Path;
+
+pub type Rgb = (u8, u8, u8);
+
+/// Broad kind of file, shown in the tree's tooltips and used to pick a default icon.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Code,
+    Shell,
+    Build,
+    Markup,
+    Docs,
+    Config,
+    Data,
+    Image,
+    Model,
+    Binary,
+    Lock,
+}
+
+impl Category {
+    pub fn label(self) -> &'static str {
+        match self {
+            Category::Code => "code",
+            Category::Shell => "shell",
+            Category::Build => "build",
+            Category::Markup => "markup",
+            Category::Docs => "docs",
+            Category::Config => "config",
+            Category::Data => "data",
+            Category::Image => "image",
+            Category::Model => "3D model",
+            Category::Binary => "binary",
+            Category::Lock => "lock file",
+        }
+    }
+}
+
+pub struct Language {
+    /// Markdown fence name; emitted as `lang="..."`.
+    pub id: &'static str,
+    extensions: &'static [&'static str],
+    file_names: &'static [&'static str],
+    /// Interpreter names a shebang or modeline may use for it.
+    interpreters: &'static [&'static str],
+    /// Label colour in the file tree.
+    pub color: Rgb,
+    pub category: Category,
+}
+
+const fn lang(
+    id: &'static str,
+    extensions: &'static [&'static str],
+    file_names: &'static [&'static str],
+    interpreters: &'static [&'static str],
+    color: Rgb,
+    category: Category,
+) -> Language {
+    Language {
+        id,
+        extensions,
+        file_names,
+        interpreters,
+        color,
+        category,
+    }
+}
+
+use Category::*;
+
+const NONE: &[&str] = &[];
+const RUST: Rgb = (250, 150, 150);
+const C_FAMILY: Rgb = (100, 150, 250);
+const SCRIPT: Rgb = (240, 220, 110);
+const TEXT: Rgb = (100, 250, 100);
+const SHELL: Rgb = (150, 250, 100);
+const CONFIG: Rgb = (250, 150, 150);
+const DEFAULT: Rgb = (200, 200, 200);
+
+static LANGUAGES: &[Language] = &[
+    lang("rust", &["rs"], NONE, NONE, RUST, Code),
+    lang("c", &["c", "h"], NONE, NONE, C_FAMILY, Code),
+    lang(
+        "cpp",
+        &["cc", "cpp", "cxx", "hpp", "hh", "hxx"],
+        NONE,
+        NONE,
+        C_FAMILY,
+        Code,
+    ),
+    lang("cuda", &["cu", "cuh", "cuda"], NONE, NONE, C_FAMILY, Code),
+    lang(
+        "glsl",
+        &["glsl", "vert", "frag", "comp", "geom"],
+        NONE,
+        NONE,
+        C_FAMILY,
+        Code,
+    ),
+    lang("hlsl", &["hlsl"], NONE, NONE, C_FAMILY, Code),
+    lang("wgsl", &["wgsl"], NONE, NONE, (250, 100, 250), Code),
+    lang("slang", &["slang"], NONE, NONE, (250, 150, 50), Code),
+    lang("spirv", &["spvasm"], NONE, NONE, (150, 100, 150), Code),
+    lang("go", &["go"], NONE, NONE, C_FAMILY, Code),
+    lang("java", &["java"], NONE, NONE, C_FAMILY, Code),
+    lang("kotlin", &["kt", "kts"], NONE, NONE, C_FAMILY, Code),
+    lang("csharp", &["cs"], NONE, NONE, C_FAMILY, Code),
+    lang("swift", &["swift"], NONE, NONE, C_FAMILY, Code),
+    lang("scala", &["scala"], NONE, NONE, C_FAMILY, Code),
+    lang("dart", &["dart"], NONE, NONE, C_FAMILY, Code),
+    lang("zig", &["zig"], NONE, NONE, C_FAMILY, Code),
+    lang(
+        "javascript",
+        &["js", "mjs", "cjs", "jsx"],
+        NONE,
+        &["node", "nodejs"],
+        SCRIPT,
+        Code,
+    ),
+    lang(
+        "typescript",
+        &["ts", "tsx", "mts", "cts"],
+        NONE,
+        &["deno", "ts-node"],
+        SCRIPT,
+        Code,
+    ),
+    lang(
+        "python",
+        &["py", "pyi", "pyw"],
+        NONE,
+        &["python", "python3", "python2"],
+        (50, 100, 250),
+        Code,
+    ),
+    lang(
+        "ruby",
+        &["rb"],
+        &["Gemfile", "Rakefile"],
+        &["ruby"],
+        (250, 100, 100),
+        Code,
+    ),
+    lang("perl", &["pl", "pm"], NONE, &["perl"], SCRIPT, Code),
+    lang("php", &["php"], NONE, &["php"], SCRIPT, Code),
+    lang("lua", &["lua"], NONE, &["lua", "luajit"], SCRIPT, Code),
+    lang("r", &["r"], NONE, &["rscript"], SCRIPT, Code),
+    lang(
+        "bash",
+        &["sh", "bash", "zsh"],
+        &[".bashrc", ".zshrc", ".profile"],
+        &["sh", "bash", "zsh", "dash"],
+        SHELL,
+        Shell,
+    ),
+    lang("fish", &["fish"], NONE, &["fish"], SHELL, Shell),
+    lang(
+        "powershell",
+        &["ps1", "psm1"],
+        NONE,
+        &["pwsh", "powershell"],
+        SHELL,
+        Shell,
+    ),
+    lang(
+        "makefile",
+        &["mk"],
+        &["Makefile", "GNUmakefile", "makefile"],
+        &["make"],
+        SHELL,
+        Build,
+    ),
+    lang(
+        "dockerfile",
+        &["dockerfile"],
+        &["Dockerfile", "Containerfile"],
+        NONE,
+        SHELL,
+        Build,
+    ),
+    lang("cmake", &["cmake"], &["CMakeLists.txt"], NONE, SHELL, Build),
+    lang("html", &["html", "htm"], NONE, NONE, (250, 100, 50), Markup),
+    lang(
+        "css",
+        &["css", "scss", "sass", "less"],
+        NONE,
+        NONE,
+        (150, 100, 250),
+        Markup,
+    ),
+    lang("vue", &["vue"], NONE, NONE, SCRIPT, Markup),
+    lang("svelte", &["svelte"], NONE, NONE, SCRIPT, Markup),
+    lang("sql", &["sql"], NONE, NONE, C_FAMILY, Data),
+    lang("graphql", &["graphql", "gql"], NONE, NONE, C_FAMILY, Data),
+    lang("proto", &["proto"], NONE, NONE, C_FAMILY, Data),
+    lang("markdown", &["md", "markdown"], NONE, NONE, TEXT, Docs),
+    lang("text", &["txt"], &["LICENSE", "README"], NONE, TEXT, Docs),
+    lang("rst", &["rst"], NONE, NONE, TEXT, Docs),
+    lang("toml", &["toml"], NONE, NONE, CONFIG, Config),
+    lang("yaml", &["yaml", "yml"], NONE, NONE, CONFIG, Config),
+    lang("json", &["json", "jsonc"], NONE, NONE, CONFIG, Config),
+    lang("xml", &["xml", "svg", "xsd"], NONE, NONE, CONFIG, Config),
+    lang(
+        "ini",
+        &["ini", "cfg", "conf"],
+        &[".editorconfig", ".gitconfig"],
+        NONE,
+        CONFIG,
+        Config,
+    ),
+    lang("csv", &["csv", "tsv"], NONE, NONE, (100, 250, 150), Data),
+    lang("diff", &["diff", "patch"], NONE, NONE, DEFAULT, Docs),
+];
+
+pub fn file_name(rel_path: &str) -> &str {
+    rel_path.rsplit(['/', '\\']).next().unwrap_or(rel_path)
+}
+
+/// Lowercased extension of a file name; None for dotfiles like `.bashrc`.
+pub fn extension(name: &str) -> Option<String> {
+    name.rsplit_once('.')
+        .filter(|(stem, _)| !stem.is_empty())
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+}
+
+pub fn by_id(id: &str) -> Option<&'static Language> {
+    LANGUAGES.iter().find(|l| l.id == id)
+}
+
+/// Language by file name alone.
+pub fn by_name(rel_path: &str) -> Option<&'static Language> {
+    let name = file_name(rel_path);
+    if let Some(lang) = LANGUAGES.iter().find(|l| l.file_names.contains(&name)) {
+        return Some(lang);
+    }
+    let ext = extension(name)?;
+    LANGUAGES
+        .iter()
+        .find(|l| l.extensions.contains(&ext.as_str()))
+}
+
+fn by_interpreter(interpreter: &str) -> Option<&'static Language> {
+    // python3.11 -> python3
+    let interpreter = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    LANGUAGES.iter().find(|l| {
+        l.interpreters
+            .iter()
+            .any(|i| i.trim_end_matches(|c: char| c.is_ascii_digit()) == interpreter)
+    })
+}
+
+/// `#!/usr/bin/env python3` or `#!/bin/bash -e` on the first line.
+fn by_shebang(first_line: &str) -> Option<&'static Language> {
+    let command = first_line.strip_prefix("#!")?.trim();
+    let mut words = command.split_whitespace();
+    let mut program = file_name(words.next()?);
+    if program == "env" {
+        program = words.find(|w| !w.starts_with('-'))?;
+    }
+    by_interpreter(program)
+}
+
+/// A vim (`vim: set ft=ruby:`, `vi: filetype=sh`) or Emacs (`-*- mode: python -*-`)
+/// modeline.
+fn by_modeline(line: &str) -> Option<&'static Language> {
+    let lower = line.to_ascii_lowercase();
+    for key in ["filetype=", "ft=", "mode:"] {
+        if let Some(at) = lower.find(key) {
+            let value = lower[at + key.len()..]
+                .trim_start()
+                .split(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '+'))
+                .next()
+                .unwrap_or("");
+            let found = LANGUAGES
+                .iter()
+                .find(|l| l.id == value || l.extensions.contains(&value))
+                .or_else(|| by_interpreter(value));
+            if found.is_some() {
+                return found;
+            }
+        }
+    }
+    None
+}
+
+/// Language by file name, else by the shebang or a modeline in the first lines of `head`.
+pub fn detect(rel_path: &str, head: &str) -> Option<&'static Language> {
+    if let Some(lang) = by_name(rel_path) {
+        return Some(lang);
+    }
+    if let Some(lang) = head.lines().next().and_then(by_shebang) {
+        return Some(lang);
+    }
+    head.lines()
+        .take(5)
+        .filter(|l| l.contains("vim:") || l.contains("vi:") || l.contains("-*-"))
+        .find_map(by_modeline)
+}
+
+/// `detect` for a file on disk, reading its first kilobyte only when the name says nothing.
+pub fn detect_file(rel_path: &str, path: &Path) -> Option<&'static Language> {
+    use std::io::Read;
+
+    if let Some(lang) = by_name(rel_path) {
+        return Some(lang);
+    }
+    let mut head = [0u8; 1024];
+    let n = std::fs::File::open(path)
+        .and_then(|mut f| f.read(&mut head))
+        .ok()?;
+    detect(rel_path, &String::from_utf8_lossy(&head[..n]))
+}
@@ -0,0 +1,42 @@
+// Optional `NNN | ` prefixes on included file lines, so the model can cite exact
+// locations. Numbering happens before truncation and folding, which look through the
+// prefix via `strip`, so numbers always refer to the file on disk.
+
+const SEPARATOR: &str = " | ";
+
+/// Numbers every line, right-aligned to the widest number. Numbering stops at `stop`,
+/// the marker where a huge file's unread middle was cut and line counts are unknown.
+pub fn number(text: &str, stop: &str) -> String {
+    let width = text.lines().count().max(1).to_string().len();
+    let mut out = String::with_capacity(text.len() + text.len() / 8);
+    let mut numbering = true;
+    for (i, line) in text.lines().enumerate() {
+        if line == stop {
+            numbering = false;
+        }
+        if numbering {
+            out.push_str(&format!("{:>width$}{}", i + 1, SEPARATOR));
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+/// (`NNN | ` prefix, rest of the line); the prefix is empty for unnumbered lines.
+pub fn split(line: &str) -> (&str, &str) {
+    let body = strip(line);
+    (&line[..line.len() - body.len()], body)
+}
+
+/// The line without its number prefix (unchanged if it has none).
+pub fn strip(line: &str) -> &str {
+    let digits = line.trim_start();
+    let rest = digits.trim_start_matches(|c: char| c.is_ascii_digit());
+    if rest.len() == digits.len() {
+        return line;
+    }
+    rest.strip_prefix(SEPARATOR)
+        .or_else(|| rest.strip_prefix(SEPARATOR.trim_end()))
+        .unwrap_or(line)
+}
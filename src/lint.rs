@@ -0,0 +1,250 @@
+// Lint: runs `cargo clippy --message-format=json`, lists its diagnostics in a sortable
+// table, and lets the user tick the ones to send. Ticked diagnostics go into the prompt
+// as a <lints> block shaped like build_errors' <errors>.
+
+use crate::build_errors::{Diagnostic, Level};
+use crate::error::Result;
+use crate::jobs::{JobContext, JobId, JobKind, JobOutput, Jobs};
+use eframe::egui;
+use std::path::Path;
+use std::time::Duration;
+
+pub const COMMAND: &str = "cargo clippy --all-targets --message-format=json";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LintSort {
+    Level,
+    Lint,
+    Location,
+    Message,
+}
+
+impl LintSort {
+    const COLUMNS: [(LintSort, &'static str); 4] = [
+        (LintSort::Level, "Level"),
+        (LintSort::Lint, "Lint"),
+        (LintSort::Location, "Location"),
+        (LintSort::Message, "Message"),
+    ];
+}
+
+pub struct LintItem {
+    pub diagnostic: Diagnostic,
+    pub include: bool,
+}
+
+pub struct Lint {
+    pub open: bool,
+    pub items: Vec<LintItem>,
+    pub job: Option<JobId>,
+    pub sort: LintSort,
+    pub descending: bool,
+}
+
+impl Default for Lint {
+    fn default() -> Self {
+        Self {
+            open: false,
+            items: Vec::new(),
+            job: None,
+            sort: LintSort::Location,
+            descending: false,
+        }
+    }
+}
+
+impl Lint {
+    pub fn run(&mut self, base: &Path, jobs: &mut Jobs) {
+        let base = base.to_path_buf();
+        self.job = Some(jobs.spawn(JobKind::Command, "cargo clippy", move |ctx| {
+            JobOutput::Lint(run_clippy(&base, ctx))
+        }));
+    }
+
+    pub fn included(&self) -> Vec<Diagnostic> {
+        self.items
+            .iter()
+            .filter(|item| item.include)
+            .map(|item| item.diagnostic.clone())
+            .collect()
+    }
+
+    fn sort_items(&mut self) {
+        self.items.sort_by(|a, b| {
+            let (a, b) = (&a.diagnostic, &b.diagnostic);
+            let order = match self.sort {
+                LintSort::Level => (a.level != Level::Error).cmp(&(b.level != Level::Error)),
+                LintSort::Lint => a.code.cmp(&b.code),
+                LintSort::Location => (&a.file, a.line).cmp(&(&b.file, b.line)),
+                LintSort::Message => a.message.cmp(&b.message),
+            };
+            if self.descending {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+    }
+
+    /// The diagnostics table; returns the items ticked this frame so the app can select
+    /// their files. A clicked location is put in `open` for Open in editor.
+    pub fn table(
+        &mut self,
+        ui: &mut egui::Ui,
+        open: &mut Option<(String, Option<usize>)>,
+    ) -> Vec<usize> {
+        let mut ticked = Vec::new();
+        let mut resort = None;
+        egui::ScrollArea::vertical()
+            .max_height(420.0)
+            .auto_shrink([false, true])
+            .show(ui, |ui| {
+                egui::Grid::new("lint_grid")
+                    .num_columns(5)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("");
+                        for (sort, name) in LintSort::COLUMNS {
+                            let arrow = match (self.sort == sort, self.descending) {
+                                (false, _) => "",
+                                (true, false) => " ⏶",
+                                (true, true) => " ⏷",
+                            };
+                            if ui
+                                .selectable_label(self.sort == sort, format!("{}{}", name, arrow))
+                                .clicked()
+                            {
+                                resort = Some(sort);
+                            }
+                        }
+                        ui.end_row();
+                        for (i, item) in self.items.iter_mut().enumerate() {
+                            let d = &item.diagnostic;
+                            let hover = if d.snippet.is_empty() {
+                                d.message.clone()
+                            } else {
+                                format!("{}\n{}", d.message, d.snippet)
+                            };
+                            if ui.checkbox(&mut item.include, "").changed() && item.include {
+                                ticked.push(i);
+                            }
+                            match d.level {
+                                Level::Error => {
+                                    ui.colored_label(egui::Color32::LIGHT_RED, "error");
+                                }
+                                _ => {
+                                    ui.colored_label(
+                                        egui::Color32::from_rgb(230, 160, 60),
+                                        "warning",
+                                    );
+                                }
+                            }
+                            ui.monospace(d.code.as_deref().unwrap_or("—"));
+                            match (&d.file, d.line) {
+                                (Some(file), line) => {
+                                    let text = match line {
+                                        Some(line) => format!("{}:{}", file, line),
+                                        None => file.clone(),
+                                    };
+                                    if ui
+                                        .link(egui::RichText::new(text).monospace())
+                                        .on_hover_text("Open in editor")
+                                        .clicked()
+                                    {
+                                        *open = Some((file.clone(), line));
+                                    }
+                                }
+                                _ => {
+                                    ui.label("");
+                                }
+                            }
+                            ui.label(&d.message).on_hover_text(hover);
+                            ui.end_row();
+                        }
+                    });
+            });
+        if let Some(sort) = resort {
+            self.descending = self.sort == sort && !self.descending;
+            self.sort = sort;
+            self.sort_items();
+        }
+        ticked
+    }
+
+    /// Replaces the list with a finished run's diagnostics, keeping ticks on any that
+    /// are still reported.
+    pub fn set_results(&mut self, diagnostics: Vec<Diagnostic>) {
+        let previous: Vec<Diagnostic> = self.included();
+        self.items = diagnostics
+            .into_iter()
+            .map(|diagnostic| LintItem {
+                include: previous.contains(&diagnostic),
+                diagnostic,
+            })
+            .collect();
+        self.sort_items();
+    }
+}
+
+fn run_clippy(base: &Path, ctx: &JobContext) -> Result<Vec<Diagnostic>> {
+    let words: Vec<&str> = COMMAND.split_whitespace().collect();
+    // No head/tail cut: every line is a JSON record
+    let output = crate::command_runner::run_command(
+        base,
+        words[0],
+        &words[1..],
+        usize::MAX / 2,
+        0,
+        false,
+        Duration::ZERO,
+        &[],
+        "",
+        None,
+        Some(ctx.cancel_flag()),
+    )?;
+    Ok(parse(&output.text))
+}
+
+/// `compiler-message` records with a primary span, in order and without repeats (a crate's
+/// lib and test targets report shared code twice).
+pub fn parse(output: &str) -> Vec<Diagnostic> {
+    let mut out: Vec<Diagnostic> = Vec::new();
+    for line in output.lines().filter(|l| l.starts_with('{')) {
+        let Ok(record) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        if record["reason"] != "compiler-message" {
+            continue;
+        }
+        let message = &record["message"];
+        let level = match message["level"].as_str() {
+            Some("error") => Level::Error,
+            Some("warning") => Level::Warning,
+            _ => continue,
+        };
+        let Some(span) = message["spans"]
+            .as_array()
+            .and_then(|spans| spans.iter().find(|s| s["is_primary"] == true))
+        else {
+            continue;
+        };
+        // rustc's human rendering carries the excerpt and the help/note lines
+        let snippet = message["rendered"]
+            .as_str()
+            .and_then(|r| crate::build_errors::parse(r).into_iter().next())
+            .map(|d| d.snippet)
+            .unwrap_or_default();
+        let diagnostic = Diagnostic {
+            level,
+            code: message["code"]["code"].as_str().map(str::to_string),
+            message: message["message"].as_str().unwrap_or_default().to_string(),
+            file: span["file_name"].as_str().map(|f| f.replace('\\', "/")),
+            line: span["line_start"].as_u64().map(|l| l as usize),
+            snippet,
+        };
+        if !out.contains(&diagnostic) {
+            out.push(diagnostic);
+        }
+    }
+    out
+}
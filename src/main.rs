@@ -1,10 +1,79 @@
 mod app;
+mod archives;
+mod attachments;
+mod backups;
+mod batch;
+mod blame;
+mod build_errors;
+mod code_import;
+mod code_state;
 mod command_runner;
+mod comments;
+mod commit_message;
+mod config;
+mod conversation;
+mod deps;
+mod dev_bench;
+mod diff_view;
+mod duplicates;
+mod editor;
+mod env_presets;
+mod error;
+mod error_refs;
+mod escaping;
+mod eval;
+mod file_diff;
 mod file_item;
+mod file_order;
 mod file_tree;
+mod file_types;
+mod file_watch;
+mod folding;
+mod highlight;
+mod http_api;
+mod ignore_editor;
+mod image_info;
+mod instructions;
+mod intern;
+mod jobs;
+mod json_prompt;
+mod labels;
+mod language_packs;
+mod languages;
+mod line_numbers;
+mod lint;
+mod mcp;
+mod notes;
+mod notifications;
+mod pr_description;
+mod pricing;
+mod project_metadata;
 mod prompt_builder;
+mod prompt_history;
+mod prompt_outline;
+mod providers;
+mod pty_session;
+mod redactions;
 mod remote;
+mod responses;
+mod scaffold;
+mod sections;
+mod sessions;
+mod share;
+mod symbols;
+mod system_prompts;
+mod tables;
+mod template_vars;
+mod test_focus;
+mod text_hygiene;
+mod text_search;
+mod token_calibration;
 mod token_count; // 🤖 NEW: tokenizer-backed counting
+mod token_histogram;
+mod token_meter;
+mod truncation;
+mod ui_settings;
+mod workspace;
 
 fn main() {
     app::run();
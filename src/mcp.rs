@@ -0,0 +1,164 @@
+// MCP (Model Context Protocol) server over stdio: `prompt --mcp [folder]` opens the window
+// as usual and also answers a desktop AI client on stdin/stdout, so the client can list and
+// read project files, run commands, and fetch the prompt built from the selection being
+// curated in the GUI. Messages are newline-delimited JSON-RPC; each tool call is handed
+// to the GUI thread, which owns the app state, and answered from there.
+
+use eframe::egui;
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+use std::sync::mpsc;
+
+const PROTOCOL_VERSION: &str = "2024-11-05";
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+/// Tool result text, or the message of a failed call.
+pub type Reply = mpsc::Sender<std::result::Result<String, String>>;
+
+pub enum McpCall {
+    ListFiles,
+    ReadFile(String),
+    RunCommand(String),
+    BuildPrompt,
+}
+
+pub struct McpRequest {
+    pub call: McpCall,
+    pub reply: Reply,
+}
+
+pub struct McpServer {
+    requests: mpsc::Receiver<McpRequest>,
+}
+
+impl McpServer {
+    /// Serves stdin on a thread; requests wake `ctx` so the GUI answers them promptly.
+    pub fn start(ctx: egui::Context) -> Self {
+        let (tx, requests) = mpsc::channel();
+        std::thread::spawn(move || serve(&tx, &ctx));
+        Self { requests }
+    }
+
+    pub fn next(&self) -> Option<McpRequest> {
+        self.requests.try_recv().ok()
+    }
+}
+
+fn serve(tx: &mpsc::Sender<McpRequest>, ctx: &egui::Context) {
+    let mut stdout = std::io::stdout();
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => handle(&message, tx, ctx),
+            Err(e) => Some(error(Value::Null, PARSE_ERROR, e.to_string())),
+        };
+        if let Some(response) = response {
+            if writeln!(stdout, "{}", response)
+                .and_then(|_| stdout.flush())
+                .is_err()
+            {
+                break;
+            }
+        }
+    }
+    eprintln!("[mcp] client disconnected");
+}
+
+fn error(id: Value, code: i64, message: String) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// The response to `message`; None for notifications, which carry no id.
+fn handle(message: &Value, tx: &mpsc::Sender<McpRequest>, ctx: &egui::Context) -> Option<Value> {
+    let id = message.get("id")?.clone();
+    let method = message["method"].as_str().unwrap_or_default();
+    let result = match method {
+        "initialize" => Ok(json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": "prompt", "version": env!("CARGO_PKG_VERSION") },
+        })),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({ "tools": tools() })),
+        "tools/call" => call_tool(&message["params"], tx, ctx),
+        _ => Err((METHOD_NOT_FOUND, format!("unknown method {:?}", method))),
+    };
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => error(id, code, message),
+    })
+}
+
+fn tools() -> Value {
+    let no_args = json!({ "type": "object", "properties": {} });
+    json!([
+        {
+            "name": "list_files",
+            "description": "Project files with their estimated token counts; files selected in the GUI are marked.",
+            "inputSchema": no_args,
+        },
+        {
+            "name": "read_file",
+            "description": "Contents of a project file, by path relative to the project folder.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "path": { "type": "string" } },
+                "required": ["path"],
+            },
+        },
+        {
+            "name": "run_command",
+            "description": "Run a command in the project folder (no shell; head/tail-truncated output, the GUI's timeout and env preset). The run also appears in the GUI's terminal tabs.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "command": { "type": "string" } },
+                "required": ["command"],
+            },
+        },
+        {
+            "name": "build_prompt",
+            "description": "The prompt for the files, instruction and sections currently chosen in the GUI.",
+            "inputSchema": no_args,
+        },
+    ])
+}
+
+fn call_tool(
+    params: &Value,
+    tx: &mpsc::Sender<McpRequest>,
+    ctx: &egui::Context,
+) -> std::result::Result<Value, (i64, String)> {
+    let args = &params["arguments"];
+    let text_arg = |name: &str| {
+        args[name].as_str().map(str::to_string).ok_or((
+            INVALID_PARAMS,
+            format!("missing string argument {:?}", name),
+        ))
+    };
+    let call = match params["name"].as_str().unwrap_or_default() {
+        "list_files" => McpCall::ListFiles,
+        "read_file" => McpCall::ReadFile(text_arg("path")?),
+        "run_command" => McpCall::RunCommand(text_arg("command")?),
+        "build_prompt" => McpCall::BuildPrompt,
+        other => return Err((INVALID_PARAMS, format!("unknown tool {:?}", other))),
+    };
+    let (reply, answer) = mpsc::channel();
+    if tx.send(McpRequest { call, reply }).is_err() {
+        return Err((INTERNAL_ERROR, "the app is shutting down".into()));
+    }
+    ctx.request_repaint();
+    let (text, is_error) = match answer.recv() {
+        Ok(Ok(text)) => (text, false),
+        Ok(Err(message)) => (message, true),
+        Err(_) => ("the app closed before answering".to_string(), true),
+    };
+    Ok(json!({ "content": [{ "type": "text", "text": text }], "isError": is_error }))
+}
@@ -0,0 +1,117 @@
+use crate::error::{PromptError, Result};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Short per-file notes ("legacy path, don't modify") kept in `.prompt/notes.json`
+/// as a flat `{ "rel/path": "note" }` object.
+#[derive(Default)]
+pub struct Notes {
+    pub by_path: BTreeMap<String, String>,
+}
+
+impl Notes {
+    pub fn path(base: &Path) -> PathBuf {
+        base.join(".prompt").join("notes.json")
+    }
+
+    /// A missing file means no notes; a malformed one is an error.
+    pub fn load(base: &Path) -> Result<Self> {
+        let path = Self::path(base);
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(PromptError::io("read", path, e)),
+        };
+        let by_path = serde_json::from_str(&text).map_err(|e| PromptError::Config {
+            path,
+            message: e.to_string(),
+        })?;
+        Ok(Self { by_path })
+    }
+
+    pub fn save(&self, base: &Path) -> Result<()> {
+        let path = Self::path(base);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| PromptError::io("create", dir, e))?;
+        }
+        let json = serde_json::to_string_pretty(&self.by_path).unwrap();
+        std::fs::write(&path, json).map_err(|e| PromptError::io("write", &path, e))
+    }
+
+    pub fn get(&self, rel_path: &str) -> Option<&str> {
+        self.by_path.get(rel_path).map(String::as_str)
+    }
+
+    /// An empty/blank note removes the entry.
+    pub fn set(&mut self, rel_path: &str, note: &str) {
+        let note = note.trim();
+        if note.is_empty() {
+            self.by_path.remove(rel_path);
+        } else {
+            self.by_path.insert(rel_path.to_string(), note.to_string());
+        }
+    }
+}
+
+/// Free-form project context ("the API crate is being split, don't touch v1") kept in
+/// `.prompt/notes.md`, edited in the Project notes window.
+#[derive(Default)]
+pub struct ProjectNotes {
+    pub open: bool,
+    pub text: String,
+    /// What's on disk, to tell whether `text` needs saving.
+    saved: String,
+    /// Project folder the notes were read from.
+    base: Option<PathBuf>,
+}
+
+impl ProjectNotes {
+    pub fn path(base: &Path) -> PathBuf {
+        base.join(".prompt").join("notes.md")
+    }
+
+    /// Reads `base`'s notes (a missing file means empty ones). Unsaved edits are kept on
+    /// a rescan of the same project and saved first when switching to another.
+    pub fn load(&mut self, base: &Path) -> Result<()> {
+        if self.is_dirty() {
+            if self.base.as_deref() == Some(base) {
+                return Ok(());
+            }
+            self.save()?;
+        }
+        let path = Self::path(base);
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(PromptError::io("read", path, e)),
+        };
+        self.base = Some(base.to_path_buf());
+        self.saved = text.clone();
+        self.text = text;
+        Ok(())
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.text != self.saved
+    }
+
+    /// Writes to the project the notes were loaded from.
+    pub fn save(&mut self) -> Result<()> {
+        let Some(base) = &self.base else {
+            return Ok(());
+        };
+        let path = Self::path(base);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| PromptError::io("create", dir, e))?;
+        }
+        std::fs::write(&path, &self.text).map_err(|e| PromptError::io("write", &path, e))?;
+        self.saved = self.text.clone();
+        Ok(())
+    }
+}
+
+/// Open note editor: the file being annotated and the draft text.
+pub struct NoteEditor {
+    pub rel_path: String,
+    pub text: String,
+}
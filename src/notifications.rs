@@ -0,0 +1,45 @@
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+
+const MAX_ENTRIES: usize = 200;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Error,
+}
+
+pub struct LogEntry {
+    pub at: DateTime<Local>,
+    pub level: Level,
+    pub message: String,
+}
+
+/// Reviewable history of notifications; the fading status label only shows the newest one.
+#[derive(Default)]
+pub struct NotificationLog {
+    pub entries: VecDeque<LogEntry>,
+    pub open: bool,
+    pub unread: usize,
+}
+
+impl NotificationLog {
+    pub fn push(&mut self, level: Level, message: String) {
+        if self.entries.len() >= MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(LogEntry {
+            at: Local::now(),
+            level,
+            message,
+        });
+        if !self.open {
+            self.unread += 1;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.unread = 0;
+    }
+}
@@ -0,0 +1,267 @@
+// PR description mode: a prompt made of the branch's diff against a base ref, its commits,
+// and the GitHub issues they mention (`#12`, `owner/repo#12` or an issue URL, in commit
+// messages or typed in), fetched like the GitHub issue remote source. Bare `#12` refers
+// to the repository `origin` points at. The prompt is copied, or sent to the active
+// provider and the reply shown for a one-click copy as GitHub Markdown.
+
+use crate::code_state::git;
+use crate::error::{PromptError, Result};
+use crate::jobs::{JobId, JobKind, JobOutput, Jobs};
+use crate::providers::Provider;
+use crate::redactions::Redactions;
+use crate::remote::{GithubIssueSource, Source};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub const DEFAULT_BASE_REF: &str = "main";
+/// Issues fetched at most; the rest are listed by reference only.
+const MAX_ISSUES: usize = 10;
+
+pub const TEMPLATE: &str = "\
+Write the description of a GitHub pull request for the branch below, in GitHub-flavored \
+Markdown. Start with a short summary of what the change does and why, then a `## Changes` \
+list grouped by area, then `## Testing` with how it can be verified. End with a \
+`Closes #N` line for each linked issue the branch resolves, and `Refs #N` for ones it only \
+touches. Use the linked issues for the motivation, the commits for the story of the \
+change, and the diff for the details. Reply with the description only.";
+
+static ISSUE_REF: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"https://github\.com/([\w.-]+)/([\w.-]+)/(?:issues|pull)/(\d+)|(?:\b([\w.-]+)/([\w.-]+))?#(\d+)\b",
+    )
+    .expect("valid regex")
+});
+
+pub struct PrDescription {
+    pub open: bool,
+    /// Ref the branch is compared against (`main`, `origin/main`, a tag).
+    pub base_ref: String,
+    /// Issues to include besides those the commits mention.
+    pub issues: String,
+    pub job: Option<JobId>,
+    /// The model's description, editable before it's copied.
+    pub description: String,
+}
+
+impl Default for PrDescription {
+    fn default() -> Self {
+        Self {
+            open: false,
+            base_ref: DEFAULT_BASE_REF.to_string(),
+            issues: String::new(),
+            job: None,
+            description: String::new(),
+        }
+    }
+}
+
+pub enum PrUpdate {
+    /// The assembled prompt, when it was only to be copied.
+    Prompt(String),
+    Description(String),
+    Failed(PromptError),
+}
+
+/// `owner/repo` of a GitHub `origin` remote, over SSH or HTTPS.
+fn origin_repo(base: &Path) -> Option<(String, String)> {
+    let url = git(base, &["remote", "get-url", "origin"]).ok()?;
+    let url = url.trim();
+    let path = url
+        .strip_prefix("git@github.com:")
+        .or_else(|| url.strip_prefix("ssh://git@github.com/"))
+        .or_else(|| url.strip_prefix("https://github.com/"))?;
+    let (owner, repo) = path.trim_end_matches('/').split_once('/')?;
+    Some((owner.to_string(), repo.trim_end_matches(".git").to_string()))
+}
+
+/// Issues referenced in `text`, in order of first mention; bare `#N` needs `origin`.
+fn issue_refs(text: &str, origin: Option<&(String, String)>) -> Vec<GithubIssueSource> {
+    let mut seen = std::collections::HashSet::new();
+    let mut issues = Vec::new();
+    for caps in ISSUE_REF.captures_iter(text) {
+        let (owner, repo, number) = match (caps.get(1), caps.get(4)) {
+            (Some(owner), _) => (owner.as_str(), &caps[2], &caps[3]),
+            (None, Some(owner)) => (owner.as_str(), &caps[5], &caps[6]),
+            (None, None) => match origin {
+                Some((owner, repo)) => (owner.as_str(), repo.as_str(), &caps[6]),
+                None => continue,
+            },
+        };
+        let Ok(number) = number.parse() else {
+            continue;
+        };
+        let issue = GithubIssueSource {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            number,
+        };
+        if seen.insert(issue.label().to_lowercase()) {
+            issues.push(issue);
+        }
+    }
+    issues
+}
+
+/// The whole prompt for the branch at `base` against `base_ref`, plus the issues in
+/// `extra_issues`. Fetches issues from GitHub, so it runs as a job.
+pub fn build(
+    base: &Path,
+    base_ref: &str,
+    extra_issues: &str,
+    cancel: &AtomicBool,
+) -> Result<String> {
+    let base_ref = match base_ref.trim() {
+        "" => DEFAULT_BASE_REF,
+        base_ref => base_ref,
+    };
+    let range = format!("{}..HEAD", base_ref);
+    let commits = git(
+        base,
+        &[
+            "log",
+            "--reverse",
+            "--no-merges",
+            "--format=%h %s%n%n%b%x1e",
+            &range,
+        ],
+    )?;
+    let commits: Vec<&str> = commits
+        .split('\x1e')
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .collect();
+    if commits.is_empty() {
+        return Err(PromptError::Git(format!(
+            "no commits on this branch since {}",
+            base_ref
+        )));
+    }
+    // Three dots: what the branch changed since it left the base, not what the base did since
+    let merge_range = format!("{}...HEAD", base_ref);
+    let diff = git(
+        base,
+        &[
+            "diff",
+            "--no-color",
+            "--no-ext-diff",
+            "--find-renames",
+            &merge_range,
+        ],
+    )?;
+    let stat = git(base, &["diff", "--stat", "--no-color", &merge_range])?;
+
+    let origin = origin_repo(base);
+    let mut issues = issue_refs(extra_issues, origin.as_ref());
+    let mut seen: std::collections::HashSet<String> =
+        issues.iter().map(|i| i.label().to_lowercase()).collect();
+    for issue in issue_refs(&commits.join("\n"), origin.as_ref()) {
+        if seen.insert(issue.label().to_lowercase()) {
+            issues.push(issue);
+        }
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(out, "{}\n", TEMPLATE);
+    if !issues.is_empty() {
+        out.push_str("<linked_issues>\n");
+        for (i, issue) in issues.iter().enumerate() {
+            let body = if i >= MAX_ISSUES || cancel.load(Ordering::Relaxed) {
+                "[not fetched]".to_string()
+            } else {
+                issue
+                    .fetch(cancel)
+                    .unwrap_or_else(|e| format!("[could not fetch: {}]", e))
+            };
+            let _ = writeln!(
+                out,
+                "<issue ref=\"{}\">\n{}\n</issue>",
+                issue.label(),
+                body.trim()
+            );
+        }
+        out.push_str("</linked_issues>\n\n");
+    }
+    out.push_str("<commits>\n");
+    for commit in &commits {
+        let _ = writeln!(out, "<commit>\n{}\n</commit>", commit);
+    }
+    out.push_str("</commits>\n\n");
+    let _ = writeln!(
+        out,
+        "<changed_files base=\"{}\">\n{}</changed_files>\n",
+        base_ref, stat
+    );
+    let _ = writeln!(out, "<diff base=\"{}\">\n{}</diff>", base_ref, diff);
+    Ok(out)
+}
+
+/// Builds the prompt, with the project's redactions applied, and, with a provider, sends
+/// it; without one the prompt itself is the output, for copying.
+pub fn spawn(
+    base: PathBuf,
+    base_ref: String,
+    extra_issues: String,
+    provider: Option<Provider>,
+    redactions: Redactions,
+    jobs: &mut Jobs,
+) -> JobId {
+    let label = match &provider {
+        Some(p) => format!("PR description ({})", p.model),
+        None => "PR description prompt".to_string(),
+    };
+    jobs.spawn(JobKind::Model, label, move |ctx| {
+        ctx.progress("gathering commits and issues");
+        let built = build(&base, &base_ref, &extra_issues, ctx.cancel_flag())
+            .map(|prompt| redactions.apply(&prompt).into_owned());
+        let update = match built {
+            Err(e) => PrUpdate::Failed(e),
+            Ok(prompt) => match provider {
+                None => PrUpdate::Prompt(prompt),
+                Some(provider) => {
+                    let mut received = 0;
+                    match provider.stream(&prompt, &[], ctx.cancel_flag(), |piece| {
+                        received += piece.len();
+                        ctx.progress(format!("{} bytes", received));
+                    }) {
+                        Ok(reply) => PrUpdate::Description(crate::commit_message::clean(&reply)),
+                        Err(e) => PrUpdate::Failed(e),
+                    }
+                }
+            },
+        };
+        JobOutput::PrDescription(update)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(text: &str, origin: Option<&(String, String)>) -> Vec<String> {
+        issue_refs(text, origin).iter().map(|i| i.label()).collect()
+    }
+
+    #[test]
+    fn qualified_refs_name_their_own_repo() {
+        let origin = ("me".to_string(), "app".to_string());
+        assert_eq!(
+            labels("Fixes #12, see rust-lang/rust#4021", Some(&origin)),
+            ["me/app#12", "rust-lang/rust#4021"]
+        );
+    }
+
+    #[test]
+    fn bare_refs_need_origin() {
+        assert_eq!(labels("Fixes #12 and other/lib#3", None), ["other/lib#3"]);
+    }
+
+    #[test]
+    fn urls_and_repeats() {
+        let origin = ("Me".to_string(), "App".to_string());
+        let text = "https://github.com/me/app/pull/7#issuecomment-1\nRefs #7, me/app#7, #8";
+        assert_eq!(labels(text, Some(&origin)), ["me/app#7", "Me/App#8"]);
+    }
+}
@@ -0,0 +1,82 @@
+// Rough API cost of a prompt from a per-model price table in `.prompt/config.toml`:
+//
+// ```toml
+// expected_output_tokens = 2000
+//
+// [prices."gpt-4o"]
+// input_per_mtok = 2.5
+// output_per_mtok = 10.0
+// ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// USD per million tokens.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+/// Starting table; list prices change, so it's written to config and edited there.
+pub fn default_prices() -> BTreeMap<String, ModelPrice> {
+    [
+        ("gpt-4o", 2.5, 10.0),
+        ("gpt-4o-mini", 0.15, 0.6),
+        ("o3-mini", 1.1, 4.4),
+        ("claude-3-5-sonnet", 3.0, 15.0),
+        ("claude-3-5-haiku", 0.8, 4.0),
+        ("claude-3-opus", 15.0, 75.0),
+    ]
+    .into_iter()
+    .map(|(name, input, output)| {
+        (
+            name.to_string(),
+            ModelPrice {
+                input_per_mtok: input,
+                output_per_mtok: output,
+            },
+        )
+    })
+    .collect()
+}
+
+/// Exact match, else the longest key the model id starts with, so "claude-3-5-sonnet"
+/// also prices "claude-3-5-sonnet-20241022".
+pub fn lookup<'a>(
+    prices: &'a BTreeMap<String, ModelPrice>,
+    model: &str,
+) -> Option<(&'a str, &'a ModelPrice)> {
+    if let Some((k, p)) = prices.get_key_value(model) {
+        return Some((k, p));
+    }
+    prices
+        .iter()
+        .filter(|(k, _)| !k.is_empty() && model.starts_with(k.as_str()))
+        .max_by_key(|(k, _)| k.len())
+        .map(|(k, p)| (k.as_str(), p))
+}
+
+pub struct Estimate {
+    pub input: f64,
+    /// None when no output guess is configured.
+    pub output: Option<f64>,
+}
+
+pub fn estimate(price: &ModelPrice, input_tokens: usize, output_tokens: usize) -> Estimate {
+    Estimate {
+        input: input_tokens as f64 * price.input_per_mtok / 1e6,
+        output: (output_tokens > 0).then(|| output_tokens as f64 * price.output_per_mtok / 1e6),
+    }
+}
+
+/// Cents matter for small prompts; whole dollars are enough past $10.
+pub fn format_usd(amount: f64) -> String {
+    if amount < 0.01 {
+        format!("${:.4}", amount)
+    } else if amount < 10.0 {
+        format!("${:.2}", amount)
+    } else {
+        format!("${:.0}", amount)
+    }
+}
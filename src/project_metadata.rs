@@ -0,0 +1,181 @@
+// Compact manifest summary for the <project_metadata> section: package identity,
+// dependency versions and features, which models need far more often than whole lockfiles.
+
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// Summaries of every recognised manifest at `base`, or None if there are none.
+/// Unparsable manifests are noted inline rather than failing the whole prompt.
+pub fn summarize(base: &Path) -> Option<String> {
+    let mut out = String::new();
+    for (file, summarize) in [
+        ("Cargo.toml", cargo as fn(&str) -> Result<String, String>),
+        ("package.json", package_json),
+        ("pyproject.toml", pyproject),
+    ] {
+        let Ok(text) = std::fs::read_to_string(base.join(file)) else {
+            continue;
+        };
+        let _ = writeln!(out, "{}:", file);
+        match summarize(&text) {
+            Ok(summary) => out.push_str(&summary),
+            Err(e) => {
+                let _ = writeln!(out, "  (could not parse: {})", e);
+            }
+        }
+    }
+    (!out.is_empty()).then_some(out)
+}
+
+fn toml_version(spec: &toml::Value) -> String {
+    match spec {
+        toml::Value::String(v) => v.clone(),
+        toml::Value::Table(t) => {
+            let mut s = match (t.get("version"), t.get("path"), t.get("git")) {
+                (Some(v), _, _) => v.as_str().unwrap_or("?").to_string(),
+                (None, Some(p), _) => format!("path {}", p.as_str().unwrap_or("?")),
+                (None, None, Some(g)) => format!("git {}", g.as_str().unwrap_or("?")),
+                _ if t.get("workspace").and_then(|w| w.as_bool()) == Some(true) => {
+                    "workspace".to_string()
+                }
+                _ => "*".to_string(),
+            };
+            if let Some(features) = t.get("features").and_then(|f| f.as_array()) {
+                let names: Vec<&str> = features.iter().filter_map(|f| f.as_str()).collect();
+                let _ = write!(s, " [{}]", names.join(", "));
+            }
+            if t.get("optional").and_then(|o| o.as_bool()) == Some(true) {
+                s.push_str(" (optional)");
+            }
+            s
+        }
+        other => other.to_string(),
+    }
+}
+
+fn toml_deps(out: &mut String, title: &str, table: Option<&toml::Value>) {
+    let Some(deps) = table.and_then(|t| t.as_table()) else {
+        return;
+    };
+    if deps.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "  {}:", title);
+    for (name, spec) in deps {
+        let _ = writeln!(out, "    {} = {}", name, toml_version(spec));
+    }
+}
+
+fn cargo(text: &str) -> Result<String, String> {
+    let doc: toml::Value = toml::from_str(text).map_err(|e| e.to_string())?;
+    let mut out = String::new();
+    if let Some(pkg) = doc.get("package") {
+        let field = |k: &str| pkg.get(k).and_then(|v| v.as_str()).unwrap_or("?");
+        let _ = writeln!(
+            out,
+            "  package: {} {} (edition {})",
+            field("name"),
+            field("version"),
+            field("edition")
+        );
+    }
+    if let Some(features) = doc.get("features").and_then(|f| f.as_table()) {
+        let _ = writeln!(out, "  features:");
+        for (name, enables) in features {
+            let list: Vec<&str> = enables
+                .as_array()
+                .map(|a| a.iter().filter_map(|v| v.as_str()).collect())
+                .unwrap_or_default();
+            let _ = writeln!(out, "    {} = [{}]", name, list.join(", "));
+        }
+    }
+    toml_deps(&mut out, "dependencies", doc.get("dependencies"));
+    toml_deps(&mut out, "dev-dependencies", doc.get("dev-dependencies"));
+    toml_deps(
+        &mut out,
+        "build-dependencies",
+        doc.get("build-dependencies"),
+    );
+    if let Some(ws) = doc.get("workspace") {
+        if let Some(members) = ws.get("members").and_then(|m| m.as_array()) {
+            let names: Vec<&str> = members.iter().filter_map(|m| m.as_str()).collect();
+            let _ = writeln!(out, "  workspace members: {}", names.join(", "));
+        }
+        toml_deps(&mut out, "workspace dependencies", ws.get("dependencies"));
+    }
+    Ok(out)
+}
+
+fn package_json(text: &str) -> Result<String, String> {
+    let doc: serde_json::Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let mut out = String::new();
+    let field = |k: &str| doc.get(k).and_then(|v| v.as_str()).unwrap_or("?");
+    let _ = writeln!(out, "  package: {}@{}", field("name"), field("version"));
+    for key in [
+        "dependencies",
+        "devDependencies",
+        "peerDependencies",
+        "optionalDependencies",
+    ] {
+        let Some(deps) = doc.get(key).and_then(|d| d.as_object()) else {
+            continue;
+        };
+        if deps.is_empty() {
+            continue;
+        }
+        let _ = writeln!(out, "  {}:", key);
+        for (name, version) in deps {
+            let _ = writeln!(out, "    {} = {}", name, version.as_str().unwrap_or("?"));
+        }
+    }
+    Ok(out)
+}
+
+fn pyproject(text: &str) -> Result<String, String> {
+    let doc: toml::Value = toml::from_str(text).map_err(|e| e.to_string())?;
+    let mut out = String::new();
+    let str_list = |v: Option<&toml::Value>| -> Vec<String> {
+        v.and_then(|v| v.as_array())
+            .map(|a| {
+                a.iter()
+                    .filter_map(|s| s.as_str())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    if let Some(project) = doc.get("project") {
+        let field = |k: &str| project.get(k).and_then(|v| v.as_str()).unwrap_or("?");
+        let _ = writeln!(
+            out,
+            "  project: {} {} (requires-python {})",
+            field("name"),
+            field("version"),
+            field("requires-python")
+        );
+        let deps = str_list(project.get("dependencies"));
+        if !deps.is_empty() {
+            let _ = writeln!(out, "  dependencies:");
+            for d in deps {
+                let _ = writeln!(out, "    {}", d);
+            }
+        }
+        if let Some(extras) = project
+            .get("optional-dependencies")
+            .and_then(|o| o.as_table())
+        {
+            for (extra, deps) in extras {
+                let _ = writeln!(
+                    out,
+                    "  extra {}: {}",
+                    extra,
+                    str_list(Some(deps)).join(", ")
+                );
+            }
+        }
+    }
+    if let Some(poetry) = doc.get("tool").and_then(|t| t.get("poetry")) {
+        toml_deps(&mut out, "poetry dependencies", poetry.get("dependencies"));
+    }
+    Ok(out)
+}
@@ -1,4 +1,35 @@
-pub fn extract_text(html: &str) -> String {
+use crate::error::{PromptError, Result};
+
+/// Separates pages in text extracted from a PDF, so a page range can be picked later.
+pub const PAGE_BREAK: char = '\u{c}';
+
+/// Fetched bytes that are a PDF, going by the content type or the `%PDF-` magic.
+pub fn is_pdf(content_type: &str, bytes: &[u8]) -> bool {
+    content_type.starts_with("application/pdf") || bytes.starts_with(b"%PDF-")
+}
+
+/// Text of each page, headed `--- page N ---` and joined with `PAGE_BREAK`.
+pub fn extract_pdf_text(url: &str, bytes: &[u8]) -> Result<String> {
+    let error = |message: String| PromptError::Fetch {
+        url: url.to_string(),
+        message: format!("could not extract text from PDF: {}", message),
+    };
+    // The extractor panics on some malformed documents
+    let pages = std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem_by_pages(bytes))
+        .map_err(|_| error("unsupported document".into()))?
+        .map_err(|e| error(e.to_string()))?;
+    Ok(pages
+        .iter()
+        .enumerate()
+        .map(|(i, page)| format!("--- page {} ---\n{}", i + 1, page.trim()))
+        .collect::<Vec<_>>()
+        .join(&format!("\n{}", PAGE_BREAK)))
+}
+
+pub fn extract_text(url: &str, html: &str) -> Result<String> {
     // 🤖 Keep wrapping modest to preserve code blocks
-    html2text::from_read(html.as_bytes(), 80).unwrap()
+    html2text::from_read(html.as_bytes(), 80).map_err(|e| PromptError::Fetch {
+        url: url.to_string(),
+        message: format!("could not convert HTML to text: {}", e),
+    })
 }
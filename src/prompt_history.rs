@@ -0,0 +1,134 @@
+// Every copied prompt is kept in `.prompt/history/`, one file per prompt plus an
+// `index.json` listing them. Copying a prompt identical to the previous one doesn't store
+// another copy; the last entry's counter goes up instead ("copied again ×3").
+// Prompts saved on purpose ("Save Prompt…") go to `.prompt/prompts/` under a dated name.
+
+use crate::code_state::StableHasher;
+use crate::error::{PromptError, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Instruction preview length kept in the index.
+const INSTRUCTION_CHARS: usize = 200;
+/// Words of the instruction used in a saved prompt's file name.
+const SLUG_WORDS: usize = 4;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// RFC 3339, first copy.
+    pub at: String,
+    /// RFC 3339, most recent copy of the same content.
+    pub last_copied: String,
+    /// Stable hash of the full prompt text.
+    pub hash: String,
+    /// File name inside the history folder.
+    pub file: String,
+    pub instruction: String,
+    pub bytes: usize,
+    /// Copies after the first.
+    #[serde(default)]
+    pub copied_again: usize,
+}
+
+pub fn dir(base: &Path) -> PathBuf {
+    base.join(".prompt").join("history")
+}
+
+pub fn saved_dir(base: &Path) -> PathBuf {
+    base.join(".prompt").join("prompts")
+}
+
+/// `2024-06-01T12-33_refactor-the-parser.xml`: local time plus the instruction's first
+/// few words, so a folder listing sorts by date and still says what each prompt was for.
+pub fn saved_name(instruction: &str, extension: &str) -> String {
+    let slug = instruction
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .take(SLUG_WORDS)
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-");
+    let slug = if slug.is_empty() {
+        "prompt".to_string()
+    } else {
+        slug
+    };
+    format!(
+        "{}_{}.{}",
+        chrono::Local::now().format("%Y-%m-%dT%H-%M"),
+        slug,
+        extension
+    )
+}
+
+fn index_path(base: &Path) -> PathBuf {
+    dir(base).join("index.json")
+}
+
+pub fn prompt_hash(prompt: &str) -> String {
+    let mut hasher = StableHasher::default();
+    hasher.write(prompt.as_bytes());
+    hasher.finish_hex()
+}
+
+/// Oldest first. A missing index is an empty history.
+pub fn load(base: &Path) -> Result<Vec<HistoryEntry>> {
+    let path = index_path(base);
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(PromptError::io("read", path, e)),
+    };
+    serde_json::from_str(&text).map_err(|e| PromptError::Config {
+        path,
+        message: e.to_string(),
+    })
+}
+
+fn save(base: &Path, entries: &[HistoryEntry]) -> Result<()> {
+    let path = index_path(base);
+    let json = serde_json::to_string_pretty(entries).unwrap();
+    std::fs::write(&path, json).map_err(|e| PromptError::io("write", &path, e))
+}
+
+/// Stores `prompt` unless it repeats the latest entry, and returns that entry.
+/// `extension` names the stored file's format ("xml", "json").
+pub fn record(
+    base: &Path,
+    prompt: &str,
+    instruction: &str,
+    extension: &str,
+) -> Result<HistoryEntry> {
+    let dir = dir(base);
+    std::fs::create_dir_all(&dir).map_err(|e| PromptError::io("create", &dir, e))?;
+    let mut entries = load(base)?;
+    let now = chrono::Local::now();
+    let hash = prompt_hash(prompt);
+    if let Some(last) = entries.last_mut().filter(|e| e.hash == hash) {
+        last.copied_again += 1;
+        last.last_copied = now.to_rfc3339();
+        let entry = last.clone();
+        save(base, &entries)?;
+        return Ok(entry);
+    }
+    let file = format!(
+        "{}_{}.{}",
+        now.format("%Y-%m-%dT%H-%M-%S"),
+        &hash[..8],
+        extension
+    );
+    let path = dir.join(&file);
+    std::fs::write(&path, prompt).map_err(|e| PromptError::io("write", &path, e))?;
+    let entry = HistoryEntry {
+        at: now.to_rfc3339(),
+        last_copied: now.to_rfc3339(),
+        hash,
+        file,
+        instruction: instruction.chars().take(INSTRUCTION_CHARS).collect(),
+        bytes: prompt.len(),
+        copied_again: 0,
+    };
+    entries.push(entry.clone());
+    save(base, &entries)?;
+    Ok(entry)
+}
@@ -0,0 +1,83 @@
+// Top-level sections of a built prompt, for folding in the prompt viewer: XML tags that
+// open at the start of a line and close on a later one, or the keys of the JSON object.
+
+pub struct Region {
+    /// Tag or key name plus occurrence ("instruction#2"), stable across rebuilds.
+    pub key: String,
+    pub name: String,
+    /// First and last line (zero-based, inclusive).
+    pub start: usize,
+    pub end: usize,
+}
+
+fn xml_open(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix('<')?;
+    let end = rest
+        .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+        .unwrap_or(rest.len());
+    (end > 0).then(|| &rest[..end])
+}
+
+fn json_key(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix("  \"")?;
+    if rest.starts_with(' ') {
+        return None;
+    }
+    Some(&rest[..rest.find('"')?])
+}
+
+pub fn regions(lines: &[&str]) -> Vec<Region> {
+    let json = lines.first().is_some_and(|l| l.trim() == "{");
+    let mut out: Vec<Region> = Vec::new();
+    let mut seen = std::collections::HashMap::new();
+    let mut push = |out: &mut Vec<Region>, name: &str, start: usize, end: usize| {
+        let n = seen.entry(name.to_string()).or_insert(0);
+        *n += 1;
+        out.push(Region {
+            key: format!("{}#{}", name, n),
+            name: name.to_string(),
+            start,
+            end,
+        });
+    };
+    if json {
+        let starts: Vec<(usize, &str)> = lines
+            .iter()
+            .enumerate()
+            .filter_map(|(i, l)| json_key(l).map(|k| (i, k)))
+            .collect();
+        for (n, &(start, name)) in starts.iter().enumerate() {
+            let end = starts
+                .get(n + 1)
+                .map_or(lines.len().saturating_sub(2), |&(next, _)| next - 1);
+            if end > start {
+                push(&mut out, name, start, end);
+            }
+        }
+        return out;
+    }
+    let mut i = 0;
+    while i < lines.len() {
+        let Some(name) = xml_open(lines[i]) else {
+            i += 1;
+            continue;
+        };
+        let close = format!("</{}>", name);
+        if lines[i].contains(&close) {
+            i += 1;
+            continue;
+        }
+        let end = (i + 1..lines.len()).find(|&j| {
+            let l = lines[j];
+            l.starts_with(&close) || l.trim_end().ends_with(&close)
+        });
+        match end {
+            Some(end) => {
+                push(&mut out, name, i, end);
+                i = end + 1;
+            }
+            None => i += 1,
+        }
+    }
+    out
+}
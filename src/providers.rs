@@ -0,0 +1,617 @@
+// Model providers (OpenAI, Anthropic, Ollama, llama.cpp server) configured per project in
+// `.prompt/config.toml`. Requests run on worker threads and report back over a channel,
+// like remote fetches.
+//
+// ```toml
+// active_provider = "local"
+//
+// [[providers]]
+// name = "local"
+// kind = "ollama"
+// base_url = "http://localhost:11434"
+// model = "qwen2.5-coder:14b"
+// [providers.options]
+// temperature = 0.2
+// ```
+//
+// The project file is shared with whoever clones the repository, so it holds no keys, and a
+// key is only sent where the user put it: the `api_key_env` variable goes to the kind's own
+// API (its default base URL) and nowhere else, and keys for other servers are kept per user
+// in `~/.config/prompt/keys.toml`, keyed by base URL:
+//
+// ```toml
+// "https://llm.example.com/v1" = "sk-..."
+// ```
+//
+// `send_command` is the escape hatch for everything else (`llm`, `aichat`, a company CLI):
+// the prompt goes to the program's stdin and its stdout is the reply.
+
+use crate::error::{PromptError, Result};
+use crate::jobs::{JobId, JobKind, JobOutput, Jobs};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(600);
+/// cache_control marks allowed per Anthropic request.
+const MAX_CACHE_BREAKPOINTS: usize = 4;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    OpenAi,
+    Anthropic,
+    Ollama,
+    /// llama.cpp's `server`, which speaks the OpenAI API.
+    LlamaCpp,
+}
+
+impl ProviderKind {
+    pub const ALL: [ProviderKind; 4] = [
+        ProviderKind::OpenAi,
+        ProviderKind::Anthropic,
+        ProviderKind::Ollama,
+        ProviderKind::LlamaCpp,
+    ];
+
+    /// Local servers report the model's context window and need no API key.
+    pub fn is_local(self) -> bool {
+        matches!(self, ProviderKind::Ollama | ProviderKind::LlamaCpp)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ProviderKind::OpenAi => "OpenAI",
+            ProviderKind::Anthropic => "Anthropic",
+            ProviderKind::Ollama => "Ollama",
+            ProviderKind::LlamaCpp => "llama.cpp",
+        }
+    }
+
+    pub fn default_base_url(self) -> &'static str {
+        match self {
+            ProviderKind::OpenAi => "https://api.openai.com/v1",
+            ProviderKind::Anthropic => "https://api.anthropic.com",
+            ProviderKind::Ollama => "http://localhost:11434",
+            ProviderKind::LlamaCpp => "http://localhost:8080/v1",
+        }
+    }
+
+    pub fn default_key_env(self) -> &'static str {
+        match self {
+            ProviderKind::OpenAi => "OPENAI_API_KEY",
+            ProviderKind::Anthropic => "ANTHROPIC_API_KEY",
+            ProviderKind::Ollama | ProviderKind::LlamaCpp => "",
+        }
+    }
+}
+
+/// The user's key for `base_url` from `keys.toml` in the global config folder.
+fn stored_key(base_url: &str) -> Option<String> {
+    let path = crate::config::global_dir()?.join("keys.toml");
+    let text = std::fs::read_to_string(path).ok()?;
+    let keys: BTreeMap<String, String> = toml::from_str(&text).ok()?;
+    keys.into_iter()
+        .find(|(url, _)| url.trim_end_matches('/') == base_url)
+        .map(|(_, key)| key)
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Provider {
+    pub name: String,
+    pub kind: ProviderKind,
+    pub base_url: String,
+    /// Environment variable holding the API key; only read while `base_url` is the kind's
+    /// default, so a cloned project can't point it at another server.
+    #[serde(default)]
+    pub api_key_env: String,
+    #[serde(default)]
+    pub model: String,
+    /// Last list fetched from the server; kept so the picker works offline.
+    #[serde(default)]
+    pub models: Vec<String>,
+    /// Context window probed from a local server for `model`; sets the token budget.
+    #[serde(default)]
+    pub context_length: Option<usize>,
+    /// Token meter locale and thresholds for this model instead of the project's.
+    #[serde(default)]
+    pub meter: Option<crate::token_meter::MeterSettings>,
+    /// Extra request body fields (temperature, max_tokens, num_ctx, ...), merged as-is.
+    #[serde(default)]
+    pub options: BTreeMap<String, Value>,
+}
+
+impl Provider {
+    pub fn new(kind: ProviderKind) -> Self {
+        Self {
+            name: kind.label().to_lowercase(),
+            kind,
+            base_url: kind.default_base_url().to_string(),
+            api_key_env: kind.default_key_env().to_string(),
+            model: String::new(),
+            models: Vec::new(),
+            context_length: None,
+            meter: None,
+            options: BTreeMap::new(),
+        }
+    }
+
+    fn key(&self) -> Option<String> {
+        let base_url = self.base_url.trim_end_matches('/');
+        if !self.api_key_env.is_empty()
+            && base_url == self.kind.default_base_url().trim_end_matches('/')
+        {
+            if let Ok(key) = std::env::var(&self.api_key_env) {
+                return Some(key);
+            }
+        }
+        stored_key(base_url)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url.trim_end_matches('/'), path)
+    }
+
+    fn request(
+        &self,
+        client: &reqwest::blocking::Client,
+        method: reqwest::Method,
+        url: &str,
+    ) -> reqwest::blocking::RequestBuilder {
+        let mut req = client.request(method, url);
+        match (self.kind, self.key()) {
+            (ProviderKind::Anthropic, key) => {
+                req = req.header("anthropic-version", ANTHROPIC_VERSION);
+                if let Some(key) = key {
+                    req = req.header("x-api-key", key);
+                }
+            }
+            (_, Some(key)) => req = req.bearer_auth(key),
+            (_, None) => {}
+        }
+        req
+    }
+
+    /// Sends and fails on a non-2xx status, with the server's error message if it gave one.
+    fn send_raw(
+        &self,
+        req: reqwest::blocking::RequestBuilder,
+        url: &str,
+    ) -> Result<reqwest::blocking::Response> {
+        let fail = |message: String| PromptError::Fetch {
+            url: url.to_string(),
+            message,
+        };
+        let resp = req.send().map_err(|e| fail(e.to_string()))?;
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(resp);
+        }
+        let body: Value = resp.json().unwrap_or(Value::Null);
+        let detail = body
+            .pointer("/error/message")
+            .or_else(|| body.get("error"))
+            .map(|v| {
+                v.as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| v.to_string())
+            })
+            .unwrap_or_default();
+        Err(fail(format!("{} {}", status, detail)))
+    }
+
+    fn send(&self, req: reqwest::blocking::RequestBuilder, url: &str) -> Result<Value> {
+        self.send_raw(req, url)?
+            .json()
+            .map_err(|e| PromptError::Fetch {
+                url: url.to_string(),
+                message: e.to_string(),
+            })
+    }
+
+    /// Model ids advertised by the server, sorted.
+    pub fn fetch_models(&self) -> Result<Vec<String>> {
+        let (path, list, field) = match self.kind {
+            ProviderKind::OpenAi | ProviderKind::LlamaCpp => ("models", "data", "id"),
+            ProviderKind::Anthropic => ("v1/models", "data", "id"),
+            ProviderKind::Ollama => ("api/tags", "models", "name"),
+        };
+        let url = self.url(path);
+        let client = client()?;
+        let body = self.send(self.request(&client, reqwest::Method::GET, &url), &url)?;
+        let mut models: Vec<String> = body
+            .get(list)
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|m| m.get(field).and_then(Value::as_str).map(str::to_string))
+            .collect();
+        models.sort();
+        Ok(models)
+    }
+
+    /// Context window the server reports for the selected model. Only local servers
+    /// expose it; hosted APIs return None.
+    pub fn probe_context(&self) -> Result<Option<usize>> {
+        let client = client()?;
+        let info = match self.kind {
+            ProviderKind::Ollama => {
+                let url = self.url("api/show");
+                let req = self
+                    .request(&client, reqwest::Method::POST, &url)
+                    .json(&json!({ "model": self.model }));
+                let body = self.send(req, &url)?;
+                // Keyed by architecture, e.g. "qwen2.context_length"
+                body.get("model_info")
+                    .and_then(Value::as_object)
+                    .and_then(|info| {
+                        info.iter()
+                            .find(|(k, _)| k.ends_with(".context_length"))
+                            .and_then(|(_, v)| v.as_u64())
+                    })
+            }
+            ProviderKind::LlamaCpp => {
+                let root = self.base_url.trim_end_matches('/').trim_end_matches("/v1");
+                let url = format!("{}/props", root);
+                let body = self.send(self.request(&client, reqwest::Method::GET, &url), &url)?;
+                body.pointer("/default_generation_settings/n_ctx")
+                    .or_else(|| body.get("n_ctx"))
+                    .and_then(Value::as_u64)
+            }
+            ProviderKind::OpenAi | ProviderKind::Anthropic => None,
+        };
+        Ok(info.map(|n| n as usize))
+    }
+
+    /// Sends `prompt` as a single user message, streaming the reply. `on_chunk` sees each
+    /// piece as it arrives; the full text is returned at the end. `breakpoints` (byte
+    /// offsets ending cacheable prefixes) become cache_control marks for Anthropic.
+    /// Setting `cancel` drops the connection at the next line the server sends.
+    pub fn stream(
+        &self,
+        prompt: &str,
+        breakpoints: &[usize],
+        cancel: &AtomicBool,
+        mut on_chunk: impl FnMut(&str),
+    ) -> Result<String> {
+        let messages = match self.kind {
+            ProviderKind::Anthropic if !breakpoints.is_empty() => {
+                json!([{ "role": "user", "content": cached_blocks(prompt, breakpoints) }])
+            }
+            _ => json!([{ "role": "user", "content": prompt }]),
+        };
+        let (path, mut body) = match self.kind {
+            ProviderKind::OpenAi | ProviderKind::LlamaCpp => (
+                "chat/completions",
+                json!({ "model": self.model, "messages": messages, "stream": true }),
+            ),
+            // max_tokens is mandatory for the messages API; options may override it
+            ProviderKind::Anthropic => (
+                "v1/messages",
+                json!({
+                    "model": self.model,
+                    "messages": messages,
+                    "max_tokens": 8192,
+                    "stream": true,
+                }),
+            ),
+            ProviderKind::Ollama => (
+                "api/chat",
+                json!({ "model": self.model, "messages": messages, "stream": true }),
+            ),
+        };
+        if let Some(obj) = body.as_object_mut() {
+            for (k, v) in &self.options {
+                obj.insert(k.clone(), v.clone());
+            }
+            // Ollama silently truncates to its small default num_ctx unless told otherwise
+            if let (ProviderKind::Ollama, Some(ctx)) = (self.kind, self.context_length) {
+                let opts = obj.entry("options").or_insert_with(|| json!({}));
+                if let Some(opts) = opts.as_object_mut() {
+                    opts.entry("num_ctx").or_insert(json!(ctx));
+                }
+            }
+        }
+        let url = self.url(path);
+        let client = client()?;
+        let resp = self.send_raw(
+            self.request(&client, reqwest::Method::POST, &url)
+                .json(&body),
+            &url,
+        )?;
+
+        let mut text = String::new();
+        for line in std::io::BufRead::lines(std::io::BufReader::new(resp)) {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(PromptError::Cancelled);
+            }
+            let line = line.map_err(|e| PromptError::Fetch {
+                url: url.clone(),
+                message: e.to_string(),
+            })?;
+            // Ollama sends bare JSON lines; the others use server-sent events
+            let data = match self.kind {
+                ProviderKind::Ollama => line.as_str(),
+                _ => match line.strip_prefix("data:") {
+                    Some(data) => data.trim_start(),
+                    None => continue,
+                },
+            };
+            if data.is_empty() || data == "[DONE]" {
+                continue;
+            }
+            let Ok(event) = serde_json::from_str::<Value>(data) else {
+                continue;
+            };
+            if let Some(err) = event.get("error") {
+                return Err(PromptError::Fetch {
+                    url,
+                    message: err.to_string(),
+                });
+            }
+            let piece = match self.kind {
+                ProviderKind::OpenAi | ProviderKind::LlamaCpp => {
+                    event.pointer("/choices/0/delta/content")
+                }
+                ProviderKind::Anthropic => event.pointer("/delta/text"),
+                ProviderKind::Ollama => event.pointer("/message/content"),
+            };
+            if let Some(piece) = piece.and_then(Value::as_str) {
+                text.push_str(piece);
+                on_chunk(piece);
+            }
+        }
+        if text.is_empty() {
+            return Err(PromptError::Fetch {
+                url,
+                message: "response had no message content".into(),
+            });
+        }
+        Ok(text)
+    }
+}
+
+/// Anthropic text blocks split at `breakpoints`, each block ending at one marked for
+/// caching. The API allows four marks; the furthest prefixes are kept.
+fn cached_blocks(prompt: &str, breakpoints: &[usize]) -> Value {
+    let mut marks: Vec<usize> = breakpoints
+        .iter()
+        .copied()
+        .filter(|&at| at > 0 && at <= prompt.len() && prompt.is_char_boundary(at))
+        .collect();
+    marks.sort_unstable();
+    marks.dedup();
+    let marks = &marks[marks.len().saturating_sub(MAX_CACHE_BREAKPOINTS)..];
+    let mut blocks = Vec::new();
+    let mut start = 0;
+    for &at in marks {
+        blocks.push(json!({
+            "type": "text",
+            "text": &prompt[start..at],
+            "cache_control": { "type": "ephemeral" },
+        }));
+        start = at;
+    }
+    if start < prompt.len() {
+        blocks.push(json!({ "type": "text", "text": &prompt[start..] }));
+    }
+    Value::Array(blocks)
+}
+
+fn client() -> Result<reqwest::blocking::Client> {
+    reqwest::blocking::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| PromptError::Fetch {
+            url: String::new(),
+            message: e.to_string(),
+        })
+}
+
+pub enum ProviderUpdate {
+    Models { index: usize, models: Vec<String> },
+    Context { index: usize, tokens: Option<usize> },
+    Chunk { text: String },
+    Completion { text: String },
+    Failed(PromptError),
+}
+
+pub struct Providers {
+    pub open: bool,
+    pub new_kind: ProviderKind,
+    /// Model id typed into the price table's add row.
+    pub new_price_model: String,
+    /// The Send to LLM request in flight, if any.
+    pub completion: Option<JobId>,
+}
+
+impl Default for Providers {
+    fn default() -> Self {
+        Self {
+            open: false,
+            new_kind: ProviderKind::OpenAi,
+            new_price_model: String::new(),
+            completion: None,
+        }
+    }
+}
+
+pub fn spawn_fetch_models(index: usize, provider: Provider, jobs: &mut Jobs) {
+    let label = format!("{}: list models", provider.name);
+    jobs.spawn(JobKind::Model, label, move |_| {
+        JobOutput::Provider(match provider.fetch_models() {
+            Ok(models) => ProviderUpdate::Models { index, models },
+            Err(e) => ProviderUpdate::Failed(e),
+        })
+    });
+}
+
+/// Streams chunks back as they arrive; the final output carries the whole reply.
+pub fn spawn_complete(
+    provider: Provider,
+    prompt: String,
+    breakpoints: Vec<usize>,
+    jobs: &mut Jobs,
+) -> JobId {
+    let label = format!("{} ({})", provider.name, provider.model);
+    jobs.spawn(JobKind::Model, label, move |ctx| {
+        let mut received = 0;
+        let streamed = provider.stream(&prompt, &breakpoints, ctx.cancel_flag(), |piece| {
+            received += piece.len();
+            ctx.progress(format!("{} bytes", received));
+            ctx.send(JobOutput::Provider(ProviderUpdate::Chunk {
+                text: piece.to_string(),
+            }));
+        });
+        JobOutput::Provider(match streamed {
+            Ok(text) => ProviderUpdate::Completion { text },
+            Err(e) => ProviderUpdate::Failed(e),
+        })
+    })
+}
+
+/// Runs `command` in `dir` with the prompt on stdin, streaming its stdout back like a
+/// provider reply.
+pub fn spawn_send_command(command: String, dir: PathBuf, prompt: String, jobs: &mut Jobs) -> JobId {
+    let label = command.clone();
+    jobs.spawn(JobKind::Model, label, move |ctx| {
+        let mut received = 0;
+        let result = run_send_command(&command, &dir, prompt, ctx.cancel_flag(), |piece| {
+            received += piece.len();
+            ctx.progress(format!("{} bytes", received));
+            ctx.send(JobOutput::Provider(ProviderUpdate::Chunk {
+                text: piece.to_string(),
+            }));
+        });
+        JobOutput::Provider(match result {
+            Ok(text) => ProviderUpdate::Completion { text },
+            Err(e) => ProviderUpdate::Failed(e),
+        })
+    })
+}
+
+fn run_send_command(
+    command: &str,
+    dir: &std::path::Path,
+    prompt: String,
+    cancel: &AtomicBool,
+    mut on_chunk: impl FnMut(&str),
+) -> Result<String> {
+    let words =
+        shell_words::split(command).map_err(|e| PromptError::CommandParse(e.to_string()))?;
+    let Some((program, args)) = words.split_first() else {
+        return Err(PromptError::CommandParse(
+            "the send command is empty".into(),
+        ));
+    };
+    let command_err = |source| PromptError::Command {
+        command: program.clone(),
+        source,
+    };
+    let mut child = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(command_err)?;
+    if let Some(mut stdin) = child.stdin.take() {
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(prompt.as_bytes());
+        });
+    }
+    let stderr = child.stderr.take();
+    let stderr = std::thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut stderr) = stderr {
+            let _ = stderr.read_to_end(&mut bytes);
+        }
+        String::from_utf8_lossy(&bytes).into_owned()
+    });
+
+    let mut reply = Vec::new();
+    let mut shown = 0;
+    let stdout = child.stdout.take();
+    let child_id = child.id();
+    let finished = AtomicBool::new(false);
+    let read = std::thread::scope(|s| {
+        // The read blocks until the command writes something; killing it on cancel closes
+        // its stdout and ends the read
+        s.spawn(|| {
+            while !finished.load(Ordering::Relaxed) {
+                if cancel.load(Ordering::Relaxed) {
+                    crate::command_runner::kill(child_id);
+                    return;
+                }
+                std::thread::sleep(crate::command_runner::CANCEL_POLL_INTERVAL);
+            }
+        });
+        let read = (|| {
+            let Some(mut stdout) = stdout else {
+                return Ok(());
+            };
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = stdout.read(&mut buf)?;
+                if n == 0 || cancel.load(Ordering::Relaxed) {
+                    return Ok(());
+                }
+                reply.extend_from_slice(&buf[..n]);
+                // Hold back a UTF-8 sequence split across reads; bytes that can't start or
+                // continue one are shown as U+FFFD, as the final reply has them
+                loop {
+                    let (valid, invalid) = match std::str::from_utf8(&reply[shown..]) {
+                        Ok(s) => (s.len(), None),
+                        Err(e) => (e.valid_up_to(), e.error_len()),
+                    };
+                    if valid > 0 {
+                        on_chunk(
+                            std::str::from_utf8(&reply[shown..shown + valid]).unwrap_or_default(),
+                        );
+                        shown += valid;
+                    }
+                    let Some(invalid) = invalid else {
+                        break;
+                    };
+                    on_chunk("\u{FFFD}");
+                    shown += invalid;
+                }
+            }
+        })();
+        finished.store(true, Ordering::Relaxed);
+        read
+    });
+    if cancel.load(Ordering::Relaxed) {
+        crate::command_runner::kill(child_id);
+        let _ = child.wait();
+        return Err(PromptError::Cancelled);
+    }
+    read.map_err(command_err)?;
+    let status = child.wait().map_err(command_err)?;
+    let stderr = stderr.join().unwrap_or_default();
+    if !status.success() {
+        return Err(command_err(std::io::Error::other(format!(
+            "{}: {}",
+            status,
+            stderr.trim()
+        ))));
+    }
+    Ok(String::from_utf8_lossy(&reply).into_owned())
+}
+
+pub fn spawn_probe_context(index: usize, provider: Provider, jobs: &mut Jobs) {
+    let label = format!("{}: probe context", provider.name);
+    jobs.spawn(JobKind::Model, label, move |_| {
+        JobOutput::Provider(match provider.probe_context() {
+            Ok(tokens) => ProviderUpdate::Context { index, tokens },
+            Err(e) => ProviderUpdate::Failed(e),
+        })
+    });
+}
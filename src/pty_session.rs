@@ -0,0 +1,285 @@
+// Interactive shell: the user's shell in a pseudo-terminal (portable-pty), for tools the
+// one-shot runner can't drive — REPLs, `cargo watch`, password prompts. Output is kept as
+// plain scrollback (escape sequences dropped, carriage returns overwrite the line) and can
+// be snapshotted into the prompt as an output tab.
+
+use crate::error::{PromptError, Result};
+use eframe::egui;
+use portable_pty::{Child, CommandBuilder, MasterPty, PtySize};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Label of snapshot runs in the output tabs and the prompt.
+pub const SNAPSHOT_COMMAND: &str = "interactive shell";
+/// Older scrollback is dropped, a line at a time, past this many bytes.
+const MAX_SCROLLBACK: usize = 512 * 1024;
+const ROWS: u16 = 40;
+const MIN_COLS: u16 = 40;
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+enum Escape {
+    #[default]
+    None,
+    /// After ESC.
+    Start,
+    /// `ESC [ ...` up to a final byte.
+    Csi,
+    /// `ESC ] ...` up to BEL or `ESC \`.
+    Osc,
+    OscEsc,
+}
+
+/// Terminal output reduced to text.
+#[derive(Default)]
+struct Screen {
+    text: String,
+    /// Bytes of a UTF-8 sequence split across reads.
+    partial: Vec<u8>,
+    escape: Escape,
+    /// A `\r` not yet known to start `\r\n`.
+    pending_cr: bool,
+}
+
+impl Screen {
+    fn feed(&mut self, bytes: &[u8]) {
+        self.partial.extend_from_slice(bytes);
+        let data = std::mem::take(&mut self.partial);
+        let mut rest = &data[..];
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(s) => {
+                    self.feed_str(s);
+                    break;
+                }
+                Err(e) => {
+                    let (valid, after) = rest.split_at(e.valid_up_to());
+                    self.feed_str(std::str::from_utf8(valid).unwrap_or_default());
+                    match e.error_len() {
+                        Some(len) => {
+                            self.feed_str("\u{FFFD}");
+                            rest = &after[len..];
+                        }
+                        None => {
+                            self.partial = after.to_vec();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        if self.text.len() > MAX_SCROLLBACK {
+            let cut = self.text.len() - MAX_SCROLLBACK;
+            let cut = self.text[cut..].find('\n').map_or(cut, |i| cut + i + 1);
+            let cut = (cut..=self.text.len())
+                .find(|&i| self.text.is_char_boundary(i))
+                .unwrap_or(self.text.len());
+            self.text.drain(..cut);
+        }
+    }
+
+    fn feed_str(&mut self, s: &str) {
+        for c in s.chars() {
+            match self.escape {
+                Escape::Start => {
+                    self.escape = match c {
+                        '[' => Escape::Csi,
+                        ']' => Escape::Osc,
+                        _ => Escape::None,
+                    };
+                    continue;
+                }
+                Escape::Csi => {
+                    if ('\u{40}'..='\u{7e}').contains(&c) {
+                        self.escape = Escape::None;
+                    }
+                    continue;
+                }
+                Escape::Osc => {
+                    match c {
+                        '\u{7}' => self.escape = Escape::None,
+                        '\u{1b}' => self.escape = Escape::OscEsc,
+                        _ => {}
+                    }
+                    continue;
+                }
+                Escape::OscEsc => {
+                    self.escape = if c == '\\' { Escape::None } else { Escape::Osc };
+                    continue;
+                }
+                Escape::None => {}
+            }
+            if std::mem::take(&mut self.pending_cr) && c != '\n' {
+                // A bare carriage return: the line is being redrawn (progress bars, prompts)
+                let start = self.text.rfind('\n').map_or(0, |i| i + 1);
+                self.text.truncate(start);
+            }
+            match c {
+                '\u{1b}' => self.escape = Escape::Start,
+                '\r' => self.pending_cr = true,
+                '\u{8}' => {
+                    if !self.text.ends_with('\n') {
+                        self.text.pop();
+                    }
+                }
+                '\n' | '\t' => self.text.push(c),
+                c if c.is_control() => {}
+                c => self.text.push(c),
+            }
+        }
+    }
+}
+
+/// A running shell.
+pub struct PtySession {
+    screen: Arc<Mutex<Screen>>,
+    master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    child: Box<dyn Child + Send + Sync>,
+    /// As typed in the terminal's Working dir field.
+    pub working_dir: String,
+    /// Preset values masked in what's shown and snapshotted.
+    secrets: Vec<String>,
+    cols: u16,
+    exit: Option<String>,
+}
+
+fn pty_error(e: impl std::fmt::Display) -> PromptError {
+    PromptError::Pty(e.to_string())
+}
+
+impl PtySession {
+    /// Starts `$SHELL` (the default shell on Windows) in `dir` with `env` added; output is
+    /// read on a thread that repaints `ctx` as it arrives.
+    pub fn start(
+        dir: &Path,
+        working_dir: String,
+        env: &[(String, String)],
+        secrets: Vec<String>,
+        ctx: &egui::Context,
+    ) -> Result<Self> {
+        let size = PtySize {
+            rows: ROWS,
+            cols: 120,
+            ..Default::default()
+        };
+        let pair = portable_pty::native_pty_system()
+            .openpty(size)
+            .map_err(pty_error)?;
+        let mut command = CommandBuilder::new_default_prog();
+        command.cwd(dir);
+        // Fewer colours and cursor tricks to strip
+        command.env("TERM", "dumb");
+        for (key, value) in env {
+            command.env(key, value);
+        }
+        let child = pair.slave.spawn_command(command).map_err(pty_error)?;
+        drop(pair.slave);
+        let mut reader = pair.master.try_clone_reader().map_err(pty_error)?;
+        let writer = pair.master.take_writer().map_err(pty_error)?;
+
+        let screen = Arc::new(Mutex::new(Screen::default()));
+        let shared = Arc::clone(&screen);
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            while let Ok(n) = reader.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                if let Ok(mut screen) = shared.lock() {
+                    screen.feed(&buf[..n]);
+                }
+                ctx.request_repaint();
+            }
+            ctx.request_repaint();
+        });
+
+        Ok(Self {
+            screen,
+            master: pair.master,
+            writer,
+            child,
+            working_dir,
+            secrets,
+            cols: size.cols,
+            exit: None,
+        })
+    }
+
+    pub fn send(&mut self, bytes: &[u8]) -> Result<()> {
+        self.writer
+            .write_all(bytes)
+            .and_then(|_| self.writer.flush())
+            .map_err(pty_error)
+    }
+
+    /// `line` followed by Enter.
+    pub fn send_line(&mut self, line: &str) -> Result<()> {
+        self.send(format!("{}\r", line).as_bytes())
+    }
+
+    pub fn interrupt(&mut self) -> Result<()> {
+        self.send(b"\x03")
+    }
+
+    /// Tells the shell how many columns fit in the pane.
+    pub fn fit_width(&mut self, cols: u16) {
+        let cols = cols.max(MIN_COLS);
+        if cols != self.cols {
+            let size = PtySize {
+                rows: ROWS,
+                cols,
+                ..Default::default()
+            };
+            if self.master.resize(size).is_ok() {
+                self.cols = cols;
+            }
+        }
+    }
+
+    /// The scrollback, secrets masked.
+    pub fn text(&self) -> String {
+        let text = self
+            .screen
+            .lock()
+            .map(|s| s.text.clone())
+            .unwrap_or_default();
+        crate::env_presets::mask(text, &self.secrets)
+    }
+
+    pub fn clear(&mut self) {
+        if let Ok(mut screen) = self.screen.lock() {
+            screen.text.clear();
+        }
+    }
+
+    /// "exit 0" etc. once the shell has ended.
+    pub fn exit_status(&mut self) -> Option<&str> {
+        if self.exit.is_none() {
+            if let Ok(Some(status)) = self.child.try_wait() {
+                self.exit = Some(match status.signal() {
+                    Some(signal) => format!("ended by {}", signal),
+                    None => format!("exit {}", status.exit_code()),
+                });
+            }
+        }
+        self.exit.as_deref()
+    }
+}
+
+impl Drop for PtySession {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+/// The interactive shell window's state.
+#[derive(Default)]
+pub struct Shell {
+    pub open: bool,
+    pub session: Option<PtySession>,
+    pub input: String,
+    /// Masks the input line, for password prompts.
+    pub hide_input: bool,
+}
@@ -0,0 +1,75 @@
+// Project redaction rules in `.prompt/redactions.toml`: regexes mapped to replacement
+// tokens, applied to file contents, snippets, terminal output and fetched remote text
+// whenever a prompt is built, so internal names never leave the machine.
+//
+//     [[rule]]
+//     pattern = '[a-z0-9-]+\.corp\.example\.com'
+//     replacement = "internal-host"
+//
+// Rules run in file order; replacements may use `$1`-style capture references.
+
+use crate::error::{PromptError, Result};
+use regex::Regex;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize)]
+struct RuleSpec {
+    pattern: String,
+    replacement: String,
+}
+
+#[derive(Deserialize)]
+struct RedactionFile {
+    #[serde(default)]
+    rule: Vec<RuleSpec>,
+}
+
+#[derive(Default, Clone)]
+pub struct Redactions {
+    rules: Vec<(Regex, String)>,
+}
+
+impl Redactions {
+    pub fn path(base: &Path) -> PathBuf {
+        base.join(".prompt").join("redactions.toml")
+    }
+
+    /// A missing file means no redaction; a malformed file or pattern is an error.
+    pub fn load(base: &Path) -> Result<Self> {
+        let path = Self::path(base);
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(PromptError::io("read", path, e)),
+        };
+        let file: RedactionFile = toml::from_str(&text).map_err(|e| PromptError::Config {
+            path: path.clone(),
+            message: e.to_string(),
+        })?;
+        let mut rules = Vec::with_capacity(file.rule.len());
+        for spec in file.rule {
+            let re = Regex::new(&spec.pattern).map_err(|e| PromptError::Config {
+                path: path.clone(),
+                message: format!("pattern {:?}: {}", spec.pattern, e),
+            })?;
+            rules.push((re, spec.replacement));
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut out = Cow::Borrowed(text);
+        for (re, replacement) in &self.rules {
+            let replaced = match re.replace_all(&out, replacement.as_str()) {
+                Cow::Owned(replaced) => Some(replaced),
+                Cow::Borrowed(_) => None,
+            };
+            if let Some(replaced) = replaced {
+                out = Cow::Owned(replaced);
+            }
+        }
+        out
+    }
+}
@@ -1,31 +1,543 @@
-use std::sync::mpsc;
+// Extra context pulled in from outside the selected files. Each kind of source implements
+// `Source` and is listed in `SOURCE_TYPES`; the panel, fetching, include toggles and token
+// counts only ever see the trait, so a new kind is one impl plus one registry entry.
+
+use crate::error::{PromptError, Result};
+use crate::jobs::{JobId, JobKind, JobOutput, Jobs};
+use crate::prompt_builder::{extract_pdf_text, extract_text, is_pdf, PAGE_BREAK};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Lines kept by a file tail when the input doesn't say (`path:lines`).
+const DEFAULT_TAIL_LINES: usize = 200;
+/// Output lines kept from each end of a command source.
+const COMMAND_HEAD_LINES: usize = 500;
+const COMMAND_TAIL_LINES: usize = 500;
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub trait Source: Send + Sync {
+    /// What the panel shows for this entry.
+    fn label(&self) -> String;
+    /// Runs as a job; returns plain text for the prompt. Long-running sources should
+    /// give up once `cancel` is set.
+    fn fetch(&self, cancel: &AtomicBool) -> Result<String>;
+}
+
+/// A kind of source the panel can create from one line of user input.
+pub struct SourceType {
+    pub name: &'static str,
+    pub hint: &'static str,
+    /// Parses the input; `base` is the open project folder.
+    pub build: fn(&str, &Path) -> Result<Arc<dyn Source>>,
+}
+
+pub const SOURCE_TYPES: &[SourceType] = &[
+    SourceType {
+        name: "URL",
+        hint: "https://docs.rs/… or org/repo@main:src/lib.rs",
+        build: |input, _| match GitFileSource::parse(input) {
+            Some(file) => Ok(Arc::new(file)),
+            None => Ok(Arc::new(UrlSource {
+                url: input.to_string(),
+            })),
+        },
+    },
+    SourceType {
+        name: "GitHub issue",
+        hint: "owner/repo#123 or issue URL",
+        build: |input, _| Ok(Arc::new(GithubIssueSource::parse(input)?)),
+    },
+    SourceType {
+        name: "File tail",
+        hint: "logs/app.log:200",
+        build: |input, base| Ok(Arc::new(FileTailSource::parse(input, base))),
+    },
+    SourceType {
+        name: "Command",
+        hint: "git log --oneline -20",
+        build: |input, base| {
+            Ok(Arc::new(CommandSource {
+                command: input.to_string(),
+                dir: base.to_path_buf(),
+            }))
+        },
+    },
+];
+
+pub struct UrlSource {
+    pub url: String,
+}
+
+impl Source for UrlSource {
+    fn label(&self) -> String {
+        self.url.clone()
+    }
+
+    fn fetch(&self, _cancel: &AtomicBool) -> Result<String> {
+        let url = &self.url;
+        let fetch_error = |err: reqwest::Error| PromptError::Fetch {
+            url: url.clone(),
+            message: err.to_string(),
+        };
+        let resp = reqwest::blocking::get(url)
+            .and_then(|resp| resp.error_for_status())
+            .map_err(fetch_error)?;
+        let content_type = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let bytes = resp.bytes().map_err(fetch_error)?;
+        if is_pdf(&content_type, &bytes) {
+            return extract_pdf_text(url, &bytes);
+        }
+        extract_text(url, &String::from_utf8_lossy(&bytes))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GitHost {
+    GitHub,
+    GitLab,
+    Bitbucket,
+}
+
+/// One file at a branch, tag or commit on a git host, fetched raw rather than as the HTML
+/// page around it. Accepts blob URLs (`github.com/org/repo/blob/main/src/lib.rs`, GitLab's
+/// `/-/blob/`, Bitbucket's `/src/`) and the shorthand `org/repo@main:src/lib.rs`, which
+/// means GitHub unless prefixed with `gitlab:` or `bitbucket:`.
+pub struct GitFileSource {
+    pub host: GitHost,
+    /// `owner/repo`; GitLab groups may nest deeper.
+    pub repo: String,
+    pub rev: String,
+    pub path: String,
+}
+
+impl GitFileSource {
+    pub fn parse(input: &str) -> Option<Self> {
+        let input = input.trim();
+        let bare = input
+            .strip_prefix("https://")
+            .or_else(|| input.strip_prefix("http://"))
+            .unwrap_or(input);
+        let bare = bare.strip_prefix("www.").unwrap_or(bare);
+        // `#L10-L20` anchors and query strings aren't part of the path
+        let bare = bare.split(['#', '?']).next().unwrap_or(bare);
+        let file = |host, repo: String, rev: &str, path: &str| {
+            (!repo.is_empty() && !rev.is_empty() && !path.is_empty()).then(|| Self {
+                host,
+                repo,
+                rev: rev.to_string(),
+                path: path.trim_start_matches('/').to_string(),
+            })
+        };
+        if let Some(rest) = bare.strip_prefix("github.com/") {
+            let parts: Vec<&str> = rest.splitn(5, '/').collect();
+            match parts.as_slice() {
+                [owner, repo, "blob" | "raw", rev, path] => {
+                    file(GitHost::GitHub, format!("{}/{}", owner, repo), rev, path)
+                }
+                _ => None,
+            }
+        } else if let Some(rest) = bare.strip_prefix("gitlab.com/") {
+            let (repo, tail) = rest.split_once("/-/")?;
+            let tail = tail
+                .strip_prefix("blob/")
+                .or_else(|| tail.strip_prefix("raw/"))?;
+            let (rev, path) = tail.split_once('/')?;
+            file(GitHost::GitLab, repo.to_string(), rev, path)
+        } else if let Some(rest) = bare.strip_prefix("bitbucket.org/") {
+            let parts: Vec<&str> = rest.splitn(5, '/').collect();
+            match parts.as_slice() {
+                [owner, repo, "src" | "raw", rev, path] => {
+                    file(GitHost::Bitbucket, format!("{}/{}", owner, repo), rev, path)
+                }
+                _ => None,
+            }
+        } else {
+            let (host, rest) = match input.split_once(':') {
+                Some(("github", rest)) => (GitHost::GitHub, rest),
+                Some(("gitlab", rest)) => (GitHost::GitLab, rest),
+                Some(("bitbucket", rest)) => (GitHost::Bitbucket, rest),
+                _ => (GitHost::GitHub, input),
+            };
+            let (repo, rest) = rest.split_once('@')?;
+            let (rev, path) = rest.split_once(':')?;
+            if !repo.contains('/') || repo.contains(char::is_whitespace) || repo.contains("//") {
+                return None;
+            }
+            file(host, repo.to_string(), rev, path)
+        }
+    }
+
+    pub fn raw_url(&self) -> String {
+        match self.host {
+            GitHost::GitHub => format!(
+                "https://raw.githubusercontent.com/{}/{}/{}",
+                self.repo, self.rev, self.path
+            ),
+            GitHost::GitLab => format!(
+                "https://gitlab.com/{}/-/raw/{}/{}",
+                self.repo, self.rev, self.path
+            ),
+            GitHost::Bitbucket => format!(
+                "https://bitbucket.org/{}/raw/{}/{}",
+                self.repo, self.rev, self.path
+            ),
+        }
+    }
+}
+
+impl Source for GitFileSource {
+    fn label(&self) -> String {
+        let prefix = match self.host {
+            GitHost::GitHub => "",
+            GitHost::GitLab => "gitlab:",
+            GitHost::Bitbucket => "bitbucket:",
+        };
+        format!("{}{}@{}:{}", prefix, self.repo, self.rev, self.path)
+    }
+
+    fn fetch(&self, _cancel: &AtomicBool) -> Result<String> {
+        let url = self.raw_url();
+        let mut request = reqwest::blocking::Client::new()
+            .get(&url)
+            .header("User-Agent", "prompt");
+        if self.host == GitHost::GitHub {
+            if let Ok(token) = std::env::var("GITHUB_TOKEN") {
+                request = request.bearer_auth(token);
+            }
+        }
+        request
+            .send()
+            .and_then(|resp| resp.error_for_status())
+            .and_then(|resp| resp.text())
+            .map_err(|err| PromptError::Fetch {
+                url,
+                message: err.to_string(),
+            })
+    }
+}
+
+/// An issue or pull request with its comments, via the GitHub REST API. Set
+/// `GITHUB_TOKEN` for private repositories or higher rate limits.
+pub struct GithubIssueSource {
+    pub owner: String,
+    pub repo: String,
+    pub number: u64,
+}
+
+impl GithubIssueSource {
+    fn parse(input: &str) -> Result<Self> {
+        let input = input.trim();
+        let invalid = || PromptError::Fetch {
+            url: input.to_string(),
+            message: "expected owner/repo#123 or a GitHub issue/PR URL".into(),
+        };
+        let (owner, repo, number) = match input.strip_prefix("https://github.com/") {
+            Some(path) => {
+                let parts: Vec<&str> = path.split('/').collect();
+                match parts.as_slice() {
+                    [owner, repo, "issues" | "pull", number, ..] => (*owner, *repo, *number),
+                    _ => return Err(invalid()),
+                }
+            }
+            None => {
+                let (repo_path, number) = input.split_once('#').ok_or_else(invalid)?;
+                let (owner, repo) = repo_path.split_once('/').ok_or_else(invalid)?;
+                (owner, repo, number)
+            }
+        };
+        Ok(Self {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            number: number.parse().map_err(|_| invalid())?,
+        })
+    }
+
+    fn get(&self, url: &str) -> Result<serde_json::Value> {
+        let mut request = reqwest::blocking::Client::new()
+            .get(url)
+            .header("User-Agent", "prompt")
+            .header("Accept", "application/vnd.github+json");
+        if let Ok(token) = std::env::var("GITHUB_TOKEN") {
+            request = request.bearer_auth(token);
+        }
+        request
+            .send()
+            .and_then(|resp| resp.error_for_status())
+            .and_then(|resp| resp.json())
+            .map_err(|err| PromptError::Fetch {
+                url: url.to_string(),
+                message: err.to_string(),
+            })
+    }
+}
+
+impl Source for GithubIssueSource {
+    fn label(&self) -> String {
+        format!("{}/{}#{}", self.owner, self.repo, self.number)
+    }
+
+    fn fetch(&self, _cancel: &AtomicBool) -> Result<String> {
+        let api = format!(
+            "https://api.github.com/repos/{}/{}/issues/{}",
+            self.owner, self.repo, self.number
+        );
+        let issue = self.get(&api)?;
+        let comments = self.get(&format!("{}/comments?per_page=100", api))?;
+        let str_of = |v: &serde_json::Value, key: &str| {
+            v[key].as_str().unwrap_or_default().trim().to_string()
+        };
+        let mut text = format!(
+            "# {} ({}, {})\nOpened by {}\n\n{}\n",
+            str_of(&issue, "title"),
+            self.label(),
+            str_of(&issue, "state"),
+            str_of(&issue["user"], "login"),
+            str_of(&issue, "body")
+        );
+        for comment in comments.as_array().into_iter().flatten() {
+            text.push_str(&format!(
+                "\n--- {} ---\n{}\n",
+                str_of(&comment["user"], "login"),
+                str_of(comment, "body")
+            ));
+        }
+        Ok(text)
+    }
+}
+
+/// The last lines of a (log) file, relative to the project folder.
+pub struct FileTailSource {
+    pub path: PathBuf,
+    pub lines: usize,
+}
+
+impl FileTailSource {
+    fn parse(input: &str, base: &Path) -> Self {
+        let input = input.trim();
+        let (path, lines) = match input.rsplit_once(':') {
+            Some((path, n)) if n.parse::<usize>().is_ok() => (path, n.parse().unwrap()),
+            _ => (input, DEFAULT_TAIL_LINES),
+        };
+        Self {
+            path: base.join(path),
+            lines,
+        }
+    }
+}
+
+impl Source for FileTailSource {
+    fn label(&self) -> String {
+        format!("tail -n {} {}", self.lines, self.path.display())
+    }
+
+    fn fetch(&self, _cancel: &AtomicBool) -> Result<String> {
+        let bytes =
+            std::fs::read(&self.path).map_err(|e| PromptError::io("read", &self.path, e))?;
+        let text = String::from_utf8_lossy(&bytes);
+        let lines: Vec<&str> = text.lines().collect();
+        let start = lines.len().saturating_sub(self.lines);
+        Ok(lines[start..].join("\n"))
+    }
+}
+
+/// Output of a command run in the project folder (head and tail kept when long).
+pub struct CommandSource {
+    pub command: String,
+    pub dir: PathBuf,
+}
+
+impl Source for CommandSource {
+    fn label(&self) -> String {
+        format!("$ {}", self.command)
+    }
+
+    fn fetch(&self, cancel: &AtomicBool) -> Result<String> {
+        let tokens = shell_words::split(&self.command)
+            .map_err(|e| PromptError::CommandParse(e.to_string()))?;
+        let Some((cmd, args)) = tokens.split_first() else {
+            return Err(PromptError::CommandParse("empty command".into()));
+        };
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        crate::command_runner::run_command(
+            &self.dir,
+            cmd,
+            &args,
+            COMMAND_HEAD_LINES,
+            COMMAND_TAIL_LINES,
+            true,
+            COMMAND_TIMEOUT,
+            &[],
+            "[... output truncated ...]",
+            None,
+            Some(cancel),
+        )
+        .map(|output| output.text)
+    }
+}
 
 pub enum RemoteUpdate {
     Fetched { index: usize, content: String },
+    Failed { index: usize, error: PromptError },
 }
 
-#[derive(Clone)]
-pub struct RemoteUrl {
-    pub url: String,
+/// The part of a source's text that goes into the prompt; doc pages are mostly navigation
+/// and boilerplate around the few paragraphs that matter.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Trim {
+    /// Keep the first / last this many lines; 0 on both = everything.
+    pub head_lines: usize,
+    pub tail_lines: usize,
+    /// Char range picked in the preview; takes precedence over head/tail.
+    pub selection: Option<(usize, usize)>,
+    /// First and last page (1-based) of a PDF, applied before head/tail.
+    pub pages: Option<(usize, usize)>,
+}
+
+impl Trim {
+    pub fn is_active(&self) -> bool {
+        self.selection.is_some()
+            || self.pages.is_some()
+            || self.head_lines > 0
+            || self.tail_lines > 0
+    }
+
+    /// `content` cut down; `marker` stands where lines between head and tail were dropped.
+    pub fn apply(&self, content: &str, marker: &str) -> String {
+        if let Some((start, end)) = self.selection {
+            return content.chars().skip(start).take(end - start).collect();
+        }
+        let paged;
+        let content = match self.pages {
+            Some((first, last)) => {
+                paged = content
+                    .split(PAGE_BREAK)
+                    .skip(first.saturating_sub(1))
+                    .take((last + 1).saturating_sub(first.max(1)))
+                    .collect::<Vec<_>>()
+                    .join(&PAGE_BREAK.to_string());
+                &paged
+            }
+            None => content,
+        };
+        let lines: Vec<&str> = content.lines().collect();
+        let (head, tail) = (self.head_lines, self.tail_lines);
+        if (head == 0 && tail == 0) || head + tail >= lines.len() {
+            return content.to_string();
+        }
+        let mut kept: Vec<&str> = lines[..head].to_vec();
+        if head > 0 && tail > 0 {
+            kept.push(marker);
+        }
+        kept.extend_from_slice(&lines[lines.len() - tail..]);
+        kept.join("\n")
+    }
+}
+
+pub struct RemoteSource {
+    pub source: Arc<dyn Source>,
     pub content: Option<String>,
     pub include: bool,
+    pub error: Option<String>,
+    /// Tokens of the trimmed text, recounted when the content or trim changes.
+    pub tokens: usize,
+    /// Tokens of the whole fetched text.
+    pub full_tokens: usize,
+    pub trim: Trim,
+    pub preview_open: bool,
+    /// The preview's last non-empty text selection, for "Keep selection".
+    pub marked: Option<(usize, usize)>,
+    /// The fetch in flight, if any.
+    pub job: Option<JobId>,
 }
 
+impl RemoteSource {
+    /// Pages in the fetched text; 1 for anything that isn't a PDF.
+    pub fn page_count(&self) -> usize {
+        self.content
+            .as_deref()
+            .map_or(1, |c| c.matches(PAGE_BREAK).count() + 1)
+    }
+
+    /// What goes into the prompt, once fetched.
+    pub fn prompt_text(&self, marker: &str) -> Option<String> {
+        self.content.as_deref().map(|c| self.trim.apply(c, marker))
+    }
+
+    /// Stores newly fetched text; a preview selection is dropped when the text changed,
+    /// since its offsets no longer point at the same words.
+    pub fn set_content(&mut self, content: String, marker: &str) {
+        if self.content.as_ref() != Some(&content) {
+            self.trim.selection = None;
+            self.marked = None;
+        }
+        self.full_tokens = crate::token_count::count_tokens(&content);
+        self.content = Some(content);
+        self.recount(marker);
+    }
+
+    pub fn recount(&mut self, marker: &str) {
+        self.tokens = match self.prompt_text(marker) {
+            Some(text) if self.trim.is_active() => crate::token_count::count_tokens(&text),
+            Some(_) => self.full_tokens,
+            None => 0,
+        };
+    }
+}
+
+#[derive(Default)]
 pub struct Remote {
-    pub remote_urls: Vec<RemoteUrl>,
-    pub new_url: String,
-    pub remote_update_rx: mpsc::Receiver<RemoteUpdate>,
-    pub remote_update_tx: mpsc::Sender<RemoteUpdate>,
+    pub sources: Vec<RemoteSource>,
+    /// Index into `SOURCE_TYPES` for the next entry.
+    pub new_kind: usize,
+    pub new_input: String,
 }
 
-impl Default for Remote {
-    fn default() -> Self {
-        let (remote_tx, remote_rx) = mpsc::channel();
-        Self {
-            remote_urls: Vec::new(),
-            new_url: String::new(),
-            remote_update_rx: remote_rx,
-            remote_update_tx: remote_tx,
-        }
+impl Remote {
+    /// Starts fetching entry `index` as a job.
+    pub fn refresh(&mut self, index: usize, jobs: &mut Jobs) {
+        let Some(entry) = self.sources.get_mut(index) else {
+            return;
+        };
+        entry.error = None;
+        let source = entry.source.clone();
+        entry.job = Some(jobs.spawn(JobKind::Fetch, source.label(), move |ctx| {
+            JobOutput::Remote(match source.fetch(ctx.cancel_flag()) {
+                Ok(content) => RemoteUpdate::Fetched { index, content },
+                Err(error) => RemoteUpdate::Failed { index, error },
+            })
+        }));
+    }
+
+    /// Adds a source and starts its first fetch.
+    pub fn add(&mut self, source: Arc<dyn Source>, jobs: &mut Jobs) {
+        self.sources.push(RemoteSource {
+            source,
+            content: None,
+            include: false,
+            error: None,
+            tokens: 0,
+            full_tokens: 0,
+            trim: Trim::default(),
+            preview_open: false,
+            marked: None,
+            job: None,
+        });
+        self.refresh(self.sources.len() - 1, jobs);
+    }
+
+    /// Tokens of the included sources that have content.
+    pub fn included_tokens(&self) -> usize {
+        self.sources
+            .iter()
+            .filter(|s| s.include)
+            .map(|s| s.tokens)
+            .sum()
     }
 }
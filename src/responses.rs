@@ -0,0 +1,250 @@
+// Model replies imported from the clipboard (or pasted): split into fenced code blocks with
+// a best-guess target path so each can be saved or applied on its own.
+
+use crate::conversation::content_hash;
+use crate::error::{PromptError, Result};
+use crate::escaping::Escaping;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+pub const POLL_INTERVAL: Duration = Duration::from_millis(1000);
+
+// "`src/main.rs`:", "File: src/main.rs", "// src/main.rs", "### src/main.rs"
+static PATH_HINT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?:#+\s*|//\s*|File:\s*|\*\*)?`?([\w./-]+\.[A-Za-z0-9]+)`?(?:\*\*)?:?\s*$")
+        .unwrap()
+});
+
+#[derive(Clone)]
+pub struct CodeBlock {
+    pub lang: String,
+    pub path_hint: Option<String>,
+    pub body: String,
+}
+
+impl CodeBlock {
+    pub fn is_diff(&self) -> bool {
+        matches!(self.lang.as_str(), "diff" | "patch")
+            || self.body.starts_with("diff --git")
+            || self.body.starts_with("--- ")
+    }
+}
+
+static FILE_TAG: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"^<file\s+[^>]*path\s*=\s*"([^"]+)"[^>]*>(.*)$"#).unwrap());
+
+const PATH_KEYS: [&str; 4] = ["title", "path", "file", "filename"];
+
+/// `rust title=src/lib.rs`, `rust src/lib.rs`, `src/lib.rs` -> (lang, path)
+fn parse_info(info: &str) -> (String, Option<String>) {
+    let mut lang = String::new();
+    let mut path = None;
+    for (i, token) in info.split_whitespace().enumerate() {
+        if let Some((key, value)) = token.split_once('=') {
+            if PATH_KEYS.contains(&key) {
+                path = Some(value.trim_matches(|c| c == '"' || c == '\'').to_string());
+            }
+        } else if i == 0 && !token.contains('/') && !token.contains('.') {
+            lang = token.to_string();
+        } else if path.is_none() {
+            path = Some(token.to_string());
+        }
+    }
+    (lang, path)
+}
+
+/// Undoes the builder's CDATA or sentinel wrapping when a model echoes `<file>` elements back.
+fn unwrap_content(body: String) -> String {
+    let trimmed = body.trim();
+    let inner = Escaping::Cdata
+        .unwrap(trimmed)
+        .or_else(|| Escaping::Sentinels.unwrap(trimmed));
+    match inner {
+        Some(inner) => {
+            let mut inner = inner.trim_start_matches('\n').to_string();
+            if !inner.ends_with('\n') {
+                inner.push('\n');
+            }
+            inner
+        }
+        None => body,
+    }
+}
+
+enum Open {
+    Fence(CodeBlock),
+    FileTag(CodeBlock),
+}
+
+/// Code blocks in order: ``` fences and `<file path="...">` elements. A fence's info string
+/// may carry a path (```rust title=src/lib.rs); otherwise the line just above is checked.
+pub fn parse_blocks(text: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut current: Option<Open> = None;
+    let mut prev_line = "";
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        match current.take() {
+            None if trimmed.starts_with("```") => {
+                let (lang, path) = parse_info(trimmed.trim_start_matches('`'));
+                let path_hint = path.or_else(|| {
+                    PATH_HINT
+                        .captures(prev_line.trim())
+                        .map(|c| c[1].to_string())
+                });
+                current = Some(Open::Fence(CodeBlock {
+                    lang,
+                    path_hint,
+                    body: String::new(),
+                }));
+            }
+            None => {
+                if let Some(c) = FILE_TAG.captures(trimmed) {
+                    let mut body = String::new();
+                    let rest = c[2].trim_end();
+                    if let Some(inline) = rest.strip_suffix("</file>") {
+                        blocks.push(CodeBlock {
+                            lang: String::new(),
+                            path_hint: Some(c[1].to_string()),
+                            body: unwrap_content(format!("{}\n", inline)),
+                        });
+                    } else {
+                        if !rest.is_empty() {
+                            body.push_str(rest);
+                            body.push('\n');
+                        }
+                        current = Some(Open::FileTag(CodeBlock {
+                            lang: String::new(),
+                            path_hint: Some(c[1].to_string()),
+                            body,
+                        }));
+                    }
+                }
+            }
+            Some(Open::Fence(block)) if trimmed.starts_with("```") => blocks.push(block),
+            // The builder's own format closes on the content's last line: `...]]></file>`
+            Some(Open::FileTag(mut block)) if line.trim_end().ends_with("</file>") => {
+                let last = line.trim_end().trim_end_matches("</file>");
+                if !last.trim().is_empty() {
+                    block.body.push_str(last);
+                    block.body.push('\n');
+                }
+                block.body = unwrap_content(block.body);
+                blocks.push(block);
+            }
+            Some(Open::Fence(mut block)) => {
+                block.body.push_str(line);
+                block.body.push('\n');
+                current = Some(Open::Fence(block));
+            }
+            Some(Open::FileTag(mut block)) => {
+                block.body.push_str(line);
+                block.body.push('\n');
+                current = Some(Open::FileTag(block));
+            }
+        }
+        prev_line = line;
+    }
+    // An unterminated final block still counts; replies often get cut off
+    match current {
+        Some(Open::Fence(block)) => blocks.push(block),
+        Some(Open::FileTag(mut block)) => {
+            block.body = unwrap_content(block.body);
+            blocks.push(block);
+        }
+        None => {}
+    }
+    blocks
+}
+
+/// `rel` joined onto `base`, or None if it is absolute or climbs out with `..`.
+pub fn project_path(base: &Path, rel: &str) -> Option<PathBuf> {
+    let rel = Path::new(rel.trim());
+    let escapes = rel.is_absolute()
+        || rel
+            .components()
+            .any(|c| matches!(c, std::path::Component::ParentDir));
+    (!escapes && !rel.as_os_str().is_empty()).then(|| base.join(rel))
+}
+
+/// Writes `body` to `path`, creating missing parent folders.
+pub fn write_file(path: &Path, body: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| PromptError::io("create", dir, e))?;
+    }
+    std::fs::write(path, body).map_err(|e| PromptError::io("write", path, e))
+}
+
+pub fn looks_like_reply(text: &str) -> bool {
+    let t = text.trim_start();
+    text.contains("```")
+        || text.contains("<file path=")
+        || t.starts_with("diff --git")
+        || t.starts_with("--- a/")
+}
+
+pub struct ResponseBlock {
+    pub block: CodeBlock,
+    /// Editable save/apply target, seeded from the block's path hint.
+    pub target: String,
+}
+
+pub struct ResponsePane {
+    pub open: bool,
+    pub text: String,
+    pub blocks: Vec<ResponseBlock>,
+    pub watch_clipboard: bool,
+    /// A provider reply is arriving; `text` grows and blocks are parsed at the end.
+    pub streaming: bool,
+    last_poll: Instant,
+    last_hash: u64,
+}
+
+impl Default for ResponsePane {
+    fn default() -> Self {
+        Self {
+            open: false,
+            text: String::new(),
+            blocks: Vec::new(),
+            watch_clipboard: false,
+            streaming: false,
+            last_poll: Instant::now(),
+            last_hash: 0,
+        }
+    }
+}
+
+impl ResponsePane {
+    pub fn load(&mut self, text: String) {
+        self.last_hash = content_hash(&text);
+        self.blocks = parse_blocks(&text)
+            .into_iter()
+            .map(|block| ResponseBlock {
+                target: block.path_hint.clone().unwrap_or_default(),
+                block,
+            })
+            .collect();
+        self.text = text;
+    }
+
+    /// Rate-limits clipboard reads to POLL_INTERVAL.
+    pub fn poll_due(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        true
+    }
+
+    /// True for clipboard text not seen before (and not already loaded).
+    pub fn is_new(&mut self, text: &str) -> bool {
+        let hash = content_hash(text);
+        if hash == self.last_hash {
+            return false;
+        }
+        self.last_hash = hash;
+        true
+    }
+}
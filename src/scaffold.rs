@@ -0,0 +1,157 @@
+// One-click `.prompt` setup for a freshly opened folder: ignore rules, system prompt addon,
+// config.toml and starter instruction snippets, tuned to the detected project type.
+
+use crate::config::ProjectConfig;
+use crate::error::{PromptError, Result};
+use crate::instructions::InstructionLibrary;
+use crate::language_packs::LanguagePack;
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ProjectKind {
+    Rust,
+    Node,
+    Python,
+    Go,
+    Generic,
+}
+
+impl ProjectKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            ProjectKind::Rust => "Rust",
+            ProjectKind::Node => "Node",
+            ProjectKind::Python => "Python",
+            ProjectKind::Go => "Go",
+            ProjectKind::Generic => "generic",
+        }
+    }
+
+    fn test_command(self) -> &'static str {
+        match self {
+            ProjectKind::Rust => "cargo test",
+            ProjectKind::Node => "npm test",
+            ProjectKind::Python => "pytest",
+            ProjectKind::Go => "go test ./...",
+            ProjectKind::Generic => "",
+        }
+    }
+}
+
+/// By marker file in the project root; the first match wins.
+pub fn detect(base: &Path) -> ProjectKind {
+    if base.join("Cargo.toml").is_file() {
+        ProjectKind::Rust
+    } else if base.join("package.json").is_file() {
+        ProjectKind::Node
+    } else if ["pyproject.toml", "setup.py", "requirements.txt"]
+        .iter()
+        .any(|f| base.join(f).is_file())
+    {
+        ProjectKind::Python
+    } else if base.join("go.mod").is_file() {
+        ProjectKind::Go
+    } else {
+        ProjectKind::Generic
+    }
+}
+
+/// Offered only for folders that have never been set up.
+pub fn needs_scaffold(base: &Path) -> bool {
+    !base.join(".prompt").exists()
+}
+
+pub fn promptignore(packs: &[&LanguagePack]) -> String {
+    format!(
+        r"# .promptignore
+# Lines starting with '#' are comments.
+# Globs match paths relative to the project root.
+
+# Common large or generated directories
+**/target/**
+**/.git/**
+**/node_modules/**
+**/out/**
+*.lock
+*.DS_Store
+
+# Temporary files
+*.tmp
+
+{}
+# Add your own patterns below
+",
+        crate::language_packs::ignore_text(packs)
+    )
+}
+
+pub fn addon(kind: ProjectKind) -> String {
+    let mut text = String::from(
+        r"# Project System Prompt Addon
+
+Use this file to add project-specific guidance. It is appended after the base system prompt.
+
+- Context: Briefly explain the project domain and any unusual conventions.
+- Commands: Typical run/test commands or env you want the assistant to be aware of.
+- Constraints: Any do/don't rules unique to this repo.
+- Terminology: Domain terms, file extensions, or technologies to use correctly.
+",
+    );
+    match kind {
+        ProjectKind::Generic => text.push_str(
+            r"
+Example notes:
+- Prefer `cargo run --bin <name>` for executables here.
+- Keep shader filenames and extensions consistent (e.g., .slang, .glsl, .wgsl as appropriate).
+- Large files should be summarized; avoid inlining binaries.
+",
+        ),
+        _ => text.push_str(&format!(
+            "\nThis is a {} project; tests run with `{}`.\n",
+            kind.label(),
+            kind.test_command()
+        )),
+    }
+    text
+}
+
+pub fn config(kind: ProjectKind) -> ProjectConfig {
+    ProjectConfig {
+        include_metadata: kind != ProjectKind::Generic,
+        verify_command: kind.test_command().to_string(),
+        ..ProjectConfig::default()
+    }
+}
+
+/// Writes whatever of the scaffold is missing and returns the paths created
+/// (relative to `.prompt/`). Existing files are never overwritten.
+pub fn create(base: &Path, kind: ProjectKind) -> Result<Vec<String>> {
+    let dir = base.join(".prompt");
+    std::fs::create_dir_all(&dir).map_err(|e| PromptError::io("create", &dir, e))?;
+    let mut created = Vec::new();
+    for (name, text) in [
+        (
+            ".promptignore",
+            promptignore(&crate::language_packs::detect(base)),
+        ),
+        ("system_prompt_addon.txt", addon(kind)),
+    ] {
+        let path = dir.join(name);
+        if !path.exists() {
+            std::fs::write(&path, text).map_err(|e| PromptError::io("write", &path, e))?;
+            created.push(name.to_string());
+        }
+    }
+    if !ProjectConfig::path(base).exists() {
+        config(kind).save(base)?;
+        created.push("config.toml".to_string());
+    }
+    let mut library = InstructionLibrary::default();
+    library.load(base)?;
+    let before = library.snippets.len();
+    library.create_starters(base)?;
+    if library.snippets.len() > before {
+        created.push(format!("snippets/ ({})", library.snippets.len() - before));
+    }
+    Ok(created)
+}
@@ -0,0 +1,107 @@
+// Order, repetition and inclusion of the full prompt's top-level sections. The default
+// repeats the instruction before and after the context; some models do better with a
+// single instruction at the end, or with code before the tree. Persisted in config.toml:
+//
+// ```toml
+// sections = ["system_prompt", "file_tree", "code", "instruction"]
+// ```
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Section {
+    SystemPrompt,
+    Instruction,
+    FileTree,
+    /// Still subject to the "Include project metadata" toggle.
+    Metadata,
+    /// Still subject to the "Include project notes" toggle.
+    ProjectNotes,
+    Code,
+    /// Files attached from outside the project folder.
+    Attachments,
+    /// Picked symbols; emitted only when any were chosen.
+    Snippets,
+    /// Included remote sources, as trimmed in their preview.
+    Remote,
+    TerminalCommand,
+    TerminalOutput,
+}
+
+impl Section {
+    pub const ALL: [Section; 11] = [
+        Section::SystemPrompt,
+        Section::Instruction,
+        Section::FileTree,
+        Section::Metadata,
+        Section::ProjectNotes,
+        Section::Code,
+        Section::Attachments,
+        Section::Snippets,
+        Section::Remote,
+        Section::TerminalCommand,
+        Section::TerminalOutput,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Section::SystemPrompt => "System prompt",
+            Section::Instruction => "Instruction",
+            Section::FileTree => "File tree",
+            Section::Metadata => "Project metadata",
+            Section::ProjectNotes => "Project notes",
+            Section::Code => "Code",
+            Section::Attachments => "Attachments",
+            Section::Snippets => "Symbol snippets",
+            Section::Remote => "Remote sources",
+            Section::TerminalCommand => "Terminal command",
+            Section::TerminalOutput => "Terminal output",
+        }
+    }
+
+    /// Content that usually stays the same from one prompt to the next while iterating,
+    /// and so can be served from a provider's prompt cache.
+    pub fn is_stable(self) -> bool {
+        matches!(
+            self,
+            Section::SystemPrompt
+                | Section::FileTree
+                | Section::Metadata
+                | Section::ProjectNotes
+                | Section::Code
+                | Section::Attachments
+        )
+    }
+}
+
+/// `order` rearranged for prompt caching: the stable sections first, as ordered, then the
+/// rest. A repeated volatile section (the default's second instruction) is kept only in
+/// its last position, so the instruction still closes the prompt.
+pub fn cache_order(order: &[Section]) -> Vec<Section> {
+    let mut out: Vec<Section> = order.iter().copied().filter(|s| s.is_stable()).collect();
+    for (i, &section) in order.iter().enumerate() {
+        if !section.is_stable() && !order[i + 1..].contains(&section) {
+            out.push(section);
+        }
+    }
+    out
+}
+
+/// The long-standing layout: instruction both first and last.
+pub fn default_order() -> Vec<Section> {
+    vec![
+        Section::SystemPrompt,
+        Section::Instruction,
+        Section::FileTree,
+        Section::Metadata,
+        Section::ProjectNotes,
+        Section::Code,
+        Section::Attachments,
+        Section::Snippets,
+        Section::Remote,
+        Section::TerminalCommand,
+        Section::TerminalOutput,
+        Section::Instruction,
+    ]
+}
@@ -0,0 +1,169 @@
+// Several independent prompt-building sessions in one window, one per tab: each tab is a
+// whole `MyApp` with its own folder, selection, instruction, terminal and token meter, so
+// different tasks (or different selections of the same folder) don't trample each other.
+// Only the active tab is drawn; the others keep applying their finished jobs. The tab
+// bar also holds the view settings, which apply to every tab.
+
+use crate::app::MyApp;
+use crate::ui_settings::UiSettings;
+use eframe::egui;
+
+pub struct Sessions {
+    tabs: Vec<MyApp>,
+    active: usize,
+    ui: UiSettings,
+    show_ui_settings: bool,
+}
+
+enum TabAction {
+    Switch(usize),
+    New,
+    Duplicate,
+    Close(usize),
+}
+
+impl Sessions {
+    pub fn new(first: MyApp, ui: UiSettings) -> Self {
+        Self {
+            tabs: vec![first],
+            active: 0,
+            ui,
+            show_ui_settings: false,
+        }
+    }
+
+    fn tab_bar(&mut self, ctx: &egui::Context) {
+        let mut action = None;
+        egui::TopBottomPanel::top("session_tabs").show(ctx, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for (i, tab) in self.tabs.iter().enumerate() {
+                    let busy = if tab.jobs.running_count() > 0 {
+                        " ⏳"
+                    } else {
+                        ""
+                    };
+                    let label = format!("{}{}", tab.tab_title(), busy);
+                    let response = ui.selectable_label(i == self.active, label);
+                    let response = match &tab.current_folder {
+                        Some(folder) => response.on_hover_text(folder.display().to_string()),
+                        None => response,
+                    };
+                    if response.clicked() {
+                        action = Some(TabAction::Switch(i));
+                    }
+                    if self.tabs.len() > 1
+                        && ui
+                            .small_button("×")
+                            .on_hover_text("Close this tab")
+                            .clicked()
+                    {
+                        action = Some(TabAction::Close(i));
+                    }
+                    ui.separator();
+                }
+                if ui
+                    .small_button("+")
+                    .on_hover_text("New tab on the current folder")
+                    .clicked()
+                {
+                    action = Some(TabAction::New);
+                }
+                if ui
+                    .small_button("⧉")
+                    .on_hover_text("New tab with this tab's folder, selection and instruction")
+                    .clicked()
+                {
+                    action = Some(TabAction::Duplicate);
+                }
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui
+                        .small_button("👁 View")
+                        .on_hover_text("Theme, accent colour, UI scale and window size")
+                        .clicked()
+                    {
+                        self.show_ui_settings = !self.show_ui_settings;
+                    }
+                });
+            });
+        });
+        match action {
+            Some(TabAction::Switch(i)) => self.active = i,
+            Some(TabAction::New) => {
+                let tab = MyApp::for_folder(self.active_folder());
+                self.open(tab);
+            }
+            Some(TabAction::Duplicate) => {
+                let tab = self.tabs[self.active].fork(self.active_folder());
+                self.open(tab);
+            }
+            Some(TabAction::Close(i)) => self.close(i),
+            None => {}
+        }
+    }
+
+    fn ui_settings_window(&mut self, ctx: &egui::Context) {
+        // Ctrl +/- zooms too; keep the slider and the saved scale in step with it
+        let zoom = ctx.zoom_factor();
+        let mut changed = (zoom - self.ui.scale).abs() > 0.001;
+        if changed {
+            self.ui.scale = zoom;
+        }
+        let mut open = self.show_ui_settings;
+        egui::Window::new("View settings")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                changed |= self.ui.show(ui);
+            });
+        self.show_ui_settings = open;
+        if changed {
+            self.ui.apply(ctx);
+            if let Err(e) = self.ui.save() {
+                self.tabs[self.active].report_error(e);
+            }
+        }
+    }
+
+    fn active_folder(&self) -> std::path::PathBuf {
+        self.tabs[self.active]
+            .current_folder
+            .clone()
+            .unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| ".".into()))
+    }
+
+    fn open(&mut self, tab: MyApp) {
+        self.tabs.insert(self.active + 1, tab);
+        self.active += 1;
+    }
+
+    /// Closes tab `i`; the MCP and HTTP servers, which belong to one tab, move to another.
+    fn close(&mut self, i: usize) {
+        if self.tabs.len() < 2 {
+            return;
+        }
+        let mut closed = self.tabs.remove(i);
+        if i < self.active || self.active == self.tabs.len() {
+            self.active -= 1;
+        }
+        if let Some(mcp) = closed.mcp.take() {
+            self.tabs[0].mcp = Some(mcp);
+        }
+        if let Some(server) = closed.http_api.take() {
+            self.tabs[0].http_api = Some(server);
+        }
+        closed.jobs.cancel_all();
+    }
+}
+
+impl eframe::App for Sessions {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        self.tab_bar(ctx);
+        for (i, tab) in self.tabs.iter_mut().enumerate() {
+            if i != self.active {
+                tab.background(ctx);
+            }
+        }
+        self.tabs[self.active].update(ctx, frame);
+        self.ui_settings_window(ctx);
+    }
+}
@@ -0,0 +1,42 @@
+// Compact "look at these files" strings: base64 JSON of the selected rel_paths plus a
+// hash of the instruction, pasted into a teammate's app to reproduce the selection on
+// their own checkout without sending the prompt itself.
+
+use crate::code_state::StableHasher;
+use crate::error::{PromptError, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+
+const PREFIX: &str = "prompt-selection:";
+
+#[derive(Serialize, Deserialize)]
+pub struct SelectionShare {
+    pub paths: Vec<String>,
+    /// Lets the receiver tell whether their instruction matches without seeing it.
+    pub instruction_hash: String,
+}
+
+pub fn instruction_hash(instruction: &str) -> String {
+    let mut hasher = StableHasher::default();
+    hasher.write(instruction.trim().as_bytes());
+    hasher.finish_hex()
+}
+
+pub fn encode(paths: Vec<String>, instruction: &str) -> String {
+    let share = SelectionShare {
+        paths,
+        instruction_hash: instruction_hash(instruction),
+    };
+    let json = serde_json::to_string(&share).unwrap_or_default();
+    format!("{}{}", PREFIX, URL_SAFE_NO_PAD.encode(json))
+}
+
+/// Accepts the string with or without its prefix and surrounding whitespace.
+pub fn decode(text: &str) -> Result<SelectionShare> {
+    let text = text.trim();
+    let body = text.strip_prefix(PREFIX).unwrap_or(text);
+    let json = URL_SAFE_NO_PAD
+        .decode(body.trim_end_matches('='))
+        .map_err(|e| PromptError::Share(e.to_string()))?;
+    serde_json::from_slice(&json).map_err(|e| PromptError::Share(e.to_string()))
+}
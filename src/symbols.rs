@@ -0,0 +1,463 @@
+// Project symbol index for the symbol picker.
+//
+// Same regex-per-language approach as deps.rs: good enough to find top-level and nested
+// definitions and cut out their bodies, without pulling in a parser per language.
+
+use crate::deps::{lang_of, Lang};
+use crate::error::{PromptError, Result};
+use crate::file_item::FileItem;
+use crate::intern::RelPath;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    Struct,
+    Enum,
+    Trait,
+    Type,
+    Class,
+    Macro,
+}
+
+impl SymbolKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            SymbolKind::Function => "fn",
+            SymbolKind::Struct => "struct",
+            SymbolKind::Enum => "enum",
+            SymbolKind::Trait => "trait",
+            SymbolKind::Type => "type",
+            SymbolKind::Class => "class",
+            SymbolKind::Macro => "macro",
+        }
+    }
+
+    fn from_keyword(kw: &str) -> SymbolKind {
+        match kw {
+            "struct" | "union" => SymbolKind::Struct,
+            "enum" => SymbolKind::Enum,
+            "trait" | "interface" => SymbolKind::Trait,
+            "type" => SymbolKind::Type,
+            "class" => SymbolKind::Class,
+            "macro_rules!" => SymbolKind::Macro,
+            _ => SymbolKind::Function,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub rel_path: RelPath,
+    /// 1-based line of the definition keyword
+    pub line: usize,
+}
+
+/// A symbol chosen for inclusion. Stored by name so it is re-located at build time.
+pub struct PickedSymbol {
+    pub rel_path: RelPath,
+    pub name: String,
+    pub kind: SymbolKind,
+    pub include_doc: bool,
+    pub include_callers: bool,
+    /// Follow the source with its `git blame`, condensed per commit; see blame.rs.
+    pub include_blame: bool,
+}
+
+#[derive(Default)]
+pub struct SymbolPicker {
+    pub open: bool,
+    pub query: String,
+    pub index: Vec<Symbol>,
+    pub picked: Vec<PickedSymbol>,
+    /// The project `picked` belongs to.
+    folder: Option<PathBuf>,
+}
+
+impl SymbolPicker {
+    /// Drops the index, rebuilt from the file list when next needed, and the picks when
+    /// they were made in another folder.
+    pub fn reset_for(&mut self, folder: &Path) {
+        self.index.clear();
+        if self.folder.as_deref() != Some(folder) {
+            self.picked.clear();
+            self.folder = Some(folder.to_path_buf());
+        }
+    }
+
+    pub fn is_picked(&self, symbol: &Symbol) -> bool {
+        self.picked.iter().any(|p| {
+            p.rel_path == symbol.rel_path && p.name == symbol.name && p.kind == symbol.kind
+        })
+    }
+
+    pub fn pick(&mut self, symbol: &Symbol) {
+        if !self.is_picked(symbol) {
+            self.picked.push(PickedSymbol {
+                rel_path: symbol.rel_path.clone(),
+                name: symbol.name.clone(),
+                kind: symbol.kind,
+                include_doc: true,
+                include_callers: false,
+                include_blame: false,
+            });
+        }
+    }
+}
+
+static RUST_DEF: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:(?:default|async|const|unsafe|extern\s+"[^"]*")\s+)*(fn|struct|enum|union|trait|type|macro_rules!)\s*([A-Za-z_][A-Za-z0-9_]*)"#).unwrap()
+});
+static SCRIPT_DEF: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s*(?:export\s+)?(?:default\s+)?(?:declare\s+)?(?:abstract\s+)?(?:async\s+)?(function\*?|class|interface|type|enum)\s+([A-Za-z_$][\w$]*)").unwrap()
+});
+static SCRIPT_ARROW: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^\s*(?:export\s+)?const\s+([A-Za-z_$][\w$]*)\s*(?::[^=]+)?=\s*(?:async\s*)?(?:\([^)]*\)|[A-Za-z_$][\w$]*)\s*(?::[^=]+)?=>").unwrap()
+});
+static PY_DEF: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\s*(?:async\s+)?(def|class)\s+([A-Za-z_]\w*)").unwrap());
+
+pub fn definition_on(lang: Lang, line: &str) -> Option<(SymbolKind, &str)> {
+    let (kw, name) = match lang {
+        Lang::Rust => {
+            let c = RUST_DEF.captures(line)?;
+            (c.get(1)?.as_str(), c.get(2)?.as_str())
+        }
+        Lang::Python => {
+            let c = PY_DEF.captures(line)?;
+            (c.get(1)?.as_str(), c.get(2)?.as_str())
+        }
+        Lang::Script => match SCRIPT_DEF.captures(line) {
+            Some(c) => (c.get(1)?.as_str(), c.get(2)?.as_str()),
+            None => ("function", SCRIPT_ARROW.captures(line)?.get(1)?.as_str()),
+        },
+    };
+    Some((SymbolKind::from_keyword(kw.trim_end_matches('*')), name))
+}
+
+pub fn symbols_in(rel_path: &RelPath, text: &str) -> Vec<Symbol> {
+    let Some(lang) = lang_of(rel_path) else {
+        return Vec::new();
+    };
+    text.lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let (kind, name) = definition_on(lang, line)?;
+            Some(Symbol {
+                name: name.to_string(),
+                kind,
+                rel_path: rel_path.clone(),
+                line: i + 1,
+            })
+        })
+        .collect()
+}
+
+/// Scans every supported, non-tree-only file. Unreadable files are skipped.
+pub fn index_project(files: &[FileItem]) -> Vec<Symbol> {
+    use rayon::prelude::*;
+
+    let mut all: Vec<Symbol> = (0..files.len())
+        .into_par_iter()
+        .filter(|&i| !files[i].tree_only && lang_of(&files[i].rel_path).is_some())
+        .flat_map_iter(|i| match files[i].read_text() {
+            Ok(text) => symbols_in(&files[i].rel_path, &text),
+            Err(_) => Vec::new(),
+        })
+        .collect();
+    all.sort_by(|a, b| a.name.cmp(&b.name).then(a.rel_path.cmp(&b.rel_path)));
+    all
+}
+
+/// Innermost definition whose block contains 1-based `line`.
+pub fn enclosing(rel_path: &RelPath, text: &str, line: usize) -> Option<Symbol> {
+    let lang = lang_of(rel_path)?;
+    let lines: Vec<&str> = text.lines().collect();
+    symbols_in(rel_path, text)
+        .into_iter()
+        .filter(|s| s.line <= line && block_end(lang, &lines, s.line - 1) + 1 >= line)
+        .max_by_key(|s| s.line)
+}
+
+/// Line range (1-based, inclusive) of a definition found by name and kind.
+pub struct Span {
+    pub doc_start: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Re-locates a symbol in the file's current text, so picks survive edits that shift lines.
+pub fn locate(rel_path: &str, text: &str, name: &str, kind: SymbolKind) -> Option<Span> {
+    let lang = lang_of(rel_path)?;
+    let lines: Vec<&str> = text.lines().collect();
+    let start = lines
+        .iter()
+        .position(|l| definition_on(lang, l) == Some((kind, name)))?;
+    let end = block_end(lang, &lines, start);
+    Some(Span {
+        doc_start: doc_start(lang, &lines, start) + 1,
+        start: start + 1,
+        end: end + 1,
+    })
+}
+
+/// Last line (0-based) of the block whose header is on `start`.
+pub fn block_end(lang: Lang, lines: &[&str], start: usize) -> usize {
+    match lang {
+        Lang::Python => python_block_end(lines, start),
+        Lang::Rust | Lang::Script => brace_block_end(lines, start),
+    }
+}
+
+/// Walks forward until braces opened on/after `start` are balanced, or a `;` ends a
+/// body-less item. String and comment contents are not special-cased.
+fn brace_block_end(lines: &[&str], start: usize) -> usize {
+    let mut depth = 0i32;
+    let mut parens = 0i32; // `;` inside `[u8; 4]` or argument lists doesn't end an item
+    let mut opened = false;
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        for c in line.chars() {
+            match c {
+                '{' => {
+                    depth += 1;
+                    opened = true;
+                }
+                '}' => depth -= 1,
+                '(' | '[' => parens += 1,
+                ')' | ']' => parens -= 1,
+                ';' if !opened && depth == 0 && parens <= 0 => return i,
+                _ => {}
+            }
+        }
+        if opened && depth <= 0 {
+            return i;
+        }
+    }
+    lines.len().saturating_sub(1)
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// The block is the lines indented past its header, plus whatever an open bracket or
+/// triple-quoted string carries onto less indented lines (a signature's closing `):`,
+/// a docstring's flush-left text). Comments at or left of the header don't end it.
+fn python_block_end(lines: &[&str], start: usize) -> usize {
+    let base = indent_of(lines[start]);
+    let mut end = start;
+    let mut depth = 0i32;
+    let mut triple = None;
+    python_scan(lines[start], &mut depth, &mut triple);
+    for (i, line) in lines.iter().enumerate().skip(start + 1) {
+        let continued = depth > 0 || triple.is_some();
+        let trimmed = line.trim();
+        if !continued {
+            if trimmed.is_empty() || (trimmed.starts_with('#') && indent_of(line) <= base) {
+                continue;
+            }
+            if indent_of(line) <= base {
+                break;
+            }
+        }
+        end = i;
+        python_scan(line, &mut depth, &mut triple);
+    }
+    end
+}
+
+/// Tracks bracket depth and an open triple-quoted string across one line; comments and
+/// single-line strings are skipped.
+fn python_scan(line: &str, depth: &mut i32, triple: &mut Option<&'static [u8]>) {
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+        if let Some(quote) = *triple {
+            if rest.starts_with(quote) {
+                *triple = None;
+                i += 3;
+            } else {
+                i += 1;
+            }
+            continue;
+        }
+        match bytes[i] {
+            b'#' => return,
+            b'"' | b'\'' => {
+                let quote: &[u8] = if bytes[i] == b'"' { b"\"\"\"" } else { b"'''" };
+                if rest.starts_with(quote) {
+                    *triple = Some(quote);
+                    i += 3;
+                    continue;
+                }
+                // To the closing quote, past escaped ones
+                let mut j = i + 1;
+                while j < bytes.len() && bytes[j] != bytes[i] {
+                    j += if bytes[j] == b'\\' { 2 } else { 1 };
+                }
+                i = j;
+            }
+            b'(' | b'[' | b'{' => *depth += 1,
+            b')' | b']' | b'}' => *depth -= 1,
+            _ => {}
+        }
+        i += 1;
+    }
+}
+
+/// First line of the doc comments / attributes / decorators directly above `start`.
+fn doc_start(lang: Lang, lines: &[&str], start: usize) -> usize {
+    let mut first = start;
+    while first > 0 {
+        let prev = lines[first - 1].trim_start();
+        let attached = match lang {
+            Lang::Rust => prev.starts_with("///") || prev.starts_with("#["),
+            Lang::Python => prev.starts_with('@'),
+            Lang::Script => {
+                prev.starts_with("/**")
+                    || prev.starts_with('*')
+                    || prev.starts_with("//")
+                    || prev.starts_with('@')
+            }
+        };
+        if !attached {
+            break;
+        }
+        first -= 1;
+    }
+    first
+}
+
+/// Call-like uses of `name` (`name(`, `name!{`, `Name {`), or with `any_use` every
+/// whole-word mention, as for a type used in signatures and paths.
+fn use_pattern(name: &str, any_use: bool) -> Option<Regex> {
+    let name = regex::escape(name);
+    let pattern = if any_use {
+        format!(r"\b{}\b", name)
+    } else {
+        format!(r"\b{}\s*(?:!\s*)?[(\[{{]", name)
+    };
+    Regex::new(&pattern).ok()
+}
+
+/// 0-based indices and text of the lines in `text` matching `pattern`, skipping the
+/// definitions of `name` themselves.
+fn uses_in<'a>(
+    lang: Lang,
+    text: &'a str,
+    pattern: &'a Regex,
+    name: &'a str,
+) -> impl Iterator<Item = (usize, &'a str)> + 'a {
+    text.lines().enumerate().filter(move |(_, line)| {
+        pattern.is_match(line) && definition_on(lang, line).is_none_or(|(_, d)| d != name)
+    })
+}
+
+/// Call-site looking references to `name` across the project, as `path:line: text`.
+pub fn find_callers(files: &[FileItem], name: &str, limit: usize) -> Vec<String> {
+    let Some(pattern) = use_pattern(name, false) else {
+        return Vec::new();
+    };
+    let mut out = Vec::new();
+    for file in files {
+        if out.len() >= limit {
+            break;
+        }
+        let Some(lang) = lang_of(&file.rel_path) else {
+            continue;
+        };
+        let Ok(text) = file.read_text() else {
+            continue;
+        };
+        for (n, line) in uses_in(lang, &text, &pattern, name) {
+            out.push(format!("{}:{}: {}", file.rel_path, n + 1, line.trim()));
+            if out.len() >= limit {
+                break;
+            }
+        }
+    }
+    out
+}
+
+/// Supported, non-tree-only files that use `symbol`: call sites for functions and
+/// macros, any mention for types. Unreadable files are returned as errors.
+pub fn files_using(files: &[FileItem], symbol: &Symbol) -> (Vec<usize>, Vec<PromptError>) {
+    use rayon::prelude::*;
+
+    let any_use = !matches!(symbol.kind, SymbolKind::Function | SymbolKind::Macro);
+    let Some(pattern) = use_pattern(&symbol.name, any_use) else {
+        return (Vec::new(), Vec::new());
+    };
+    let results: Vec<(usize, Result<bool>)> = (0..files.len())
+        .into_par_iter()
+        .filter(|&i| !files[i].tree_only)
+        .filter_map(|i| {
+            let lang = lang_of(&files[i].rel_path)?;
+            let uses = files[i].read_text().map(|text| {
+                uses_in(lang, &text, &pattern, &symbol.name)
+                    .next()
+                    .is_some()
+            });
+            Some((i, uses))
+        })
+        .collect();
+    let mut found = Vec::new();
+    let mut errors = Vec::new();
+    for (i, uses) in results {
+        match uses {
+            Ok(true) => found.push(i),
+            Ok(false) => {}
+            Err(e) => errors.push(e),
+        }
+    }
+    (found, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn python_end(source: &str) -> usize {
+        let lines: Vec<&str> = source.lines().collect();
+        block_end(Lang::Python, &lines, 0) + 1
+    }
+
+    #[test]
+    fn python_block_stops_before_the_next_definition() {
+        let source = "def first():\n    return 1\n\n\ndef second():\n    pass\n";
+        assert_eq!(python_end(source), 2);
+    }
+
+    #[test]
+    fn python_block_spans_a_wrapped_signature() {
+        let source = "def handler(\n    request,\n    *args,\n):\n    return request\n\nx = 1\n";
+        assert_eq!(python_end(source), 5);
+    }
+
+    #[test]
+    fn python_block_spans_a_flush_left_docstring() {
+        let source =
+            "def usage():\n    \"\"\"Usage:\n\nrun it (twice\n\"\"\"\n    print('#')\nprint()\n";
+        assert_eq!(python_end(source), 6);
+    }
+
+    #[test]
+    fn python_block_keeps_going_past_a_flush_left_comment() {
+        let source =
+            "class Shape:\n    def area(self):\n# disabled: cache\n        return 0\n\n# footer\n";
+        assert_eq!(python_end(source), 4);
+    }
+
+    #[test]
+    fn python_method_ends_with_its_class_body() {
+        let source =
+            "class A:\n    def m(self):\n        return 'é'\n    def n(self):\n        pass\n";
+        let lines: Vec<&str> = source.lines().collect();
+        assert_eq!(block_end(Lang::Python, &lines, 1), 2);
+    }
+}
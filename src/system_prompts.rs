@@ -0,0 +1,174 @@
+// Named system prompts for different kinds of task ("code review", "debugging", ...),
+// one text file each in `~/.config/prompt/system_prompts/`. A project can add to them in
+// `.prompt/system_prompts/`: a file named like a global profile is appended to it, any
+// other name is a profile of its own. The chosen profile replaces the usual system prompt
+// lookup and is remembered in config.toml.
+//
+// A profile may start with a `snippets: a, b` line naming the instruction snippets to
+// switch on when it is chosen; the line is not part of the prompt.
+
+use crate::error::{PromptError, Result};
+use std::path::{Path, PathBuf};
+
+const SNIPPETS_PREFIX: &str = "snippets:";
+
+const STARTERS: &[(&str, &str)] = &[
+    (
+        "code-review",
+        "snippets: explain-tradeoffs\n\
+         You are a senior engineer reviewing a change. Point out bugs, missed edge cases, \
+         unclear naming and missing tests, most important first. Quote the lines you mean. \
+         Don't rewrite code that is fine.",
+    ),
+    (
+        "debugging",
+        "snippets: write-tests\n\
+         You are helping track down a bug. Work from the evidence given (errors, logs, code): \
+         state the most likely cause, what would confirm it, and the smallest fix. Say so \
+         when the information is not enough to tell.",
+    ),
+    (
+        "architecture",
+        "snippets: explain-tradeoffs\n\
+         You are advising on the structure of this codebase. Describe how the parts fit \
+         together, where the proposed change belongs, and the trade-offs of the options. \
+         Prefer the project's existing patterns over new abstractions.",
+    ),
+    (
+        "docs-writing",
+        "snippets:\n\
+         You are writing documentation for this code. Be accurate to the code shown, lead \
+         with what a reader needs to use it, and keep examples short and runnable. Match the \
+         tone and format of the existing docs.",
+    ),
+];
+
+pub struct Profile {
+    /// File stem; also the key stored in config.
+    pub name: String,
+    pub text: String,
+    /// Snippets from the `snippets:` line; None when the profile has no such line.
+    pub snippets: Option<Vec<String>>,
+    /// Only defined by the project, not in the global folder.
+    pub project_only: bool,
+}
+
+#[derive(Default)]
+pub struct SystemProfiles {
+    pub profiles: Vec<Profile>,
+}
+
+/// `$XDG_CONFIG_HOME/prompt/system_prompts`, else `~/.config/prompt/system_prompts`.
+pub fn global_dir() -> Option<PathBuf> {
+    Some(crate::config::global_dir()?.join("system_prompts"))
+}
+
+pub fn project_dir(base: &Path) -> PathBuf {
+    base.join(".prompt").join("system_prompts")
+}
+
+/// `(name, text)` of the `.txt` and `.md` files in `dir`; a missing folder has none.
+fn read_dir(dir: &Path) -> Result<Vec<(String, String)>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(PromptError::io("read", dir, e)),
+    };
+    let mut found = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_text = matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("txt") | Some("md")
+        );
+        let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        if !is_text {
+            continue;
+        }
+        let text = std::fs::read_to_string(&path).map_err(|e| PromptError::io("read", &path, e))?;
+        found.push((name.to_string(), text));
+    }
+    Ok(found)
+}
+
+/// Splits off a leading `snippets:` line.
+fn parse(text: &str) -> (Option<Vec<String>>, String) {
+    let text = text.trim_start();
+    let (first, rest) = text.split_once('\n').unwrap_or((text, ""));
+    match first.trim().strip_prefix(SNIPPETS_PREFIX) {
+        Some(list) => {
+            let snippets = list
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect();
+            (Some(snippets), rest.trim().to_string())
+        }
+        None => (None, text.trim().to_string()),
+    }
+}
+
+impl SystemProfiles {
+    /// Global profiles merged with the project's (`base`), sorted by name.
+    pub fn load(&mut self, base: Option<&Path>) -> Result<()> {
+        self.profiles.clear();
+        if let Some(dir) = global_dir() {
+            for (name, text) in read_dir(&dir)? {
+                let (snippets, text) = parse(&text);
+                self.profiles.push(Profile {
+                    name,
+                    text,
+                    snippets,
+                    project_only: false,
+                });
+            }
+        }
+        if let Some(base) = base {
+            for (name, text) in read_dir(&project_dir(base))? {
+                let (snippets, text) = parse(&text);
+                match self.profiles.iter_mut().find(|p| p.name == name) {
+                    Some(profile) => {
+                        profile.text.push_str("\n\n");
+                        profile.text.push_str(&text);
+                        if snippets.is_some() {
+                            profile.snippets = snippets;
+                        }
+                    }
+                    None => self.profiles.push(Profile {
+                        name,
+                        text,
+                        snippets,
+                        project_only: true,
+                    }),
+                }
+            }
+        }
+        self.profiles.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+
+    /// Writes the starter profiles that don't exist yet to the global folder.
+    pub fn create_starters(&mut self, base: Option<&Path>) -> Result<()> {
+        let Some(dir) = global_dir() else {
+            return Err(PromptError::Config {
+                path: PathBuf::from("~/.config/prompt"),
+                message: "no home directory to put system prompts in".into(),
+            });
+        };
+        std::fs::create_dir_all(&dir).map_err(|e| PromptError::io("create", &dir, e))?;
+        for (name, text) in STARTERS {
+            let path = dir.join(format!("{}.txt", name));
+            if !path.exists() {
+                std::fs::write(&path, text).map_err(|e| PromptError::io("write", &path, e))?;
+            }
+        }
+        self.load(base)
+    }
+}
@@ -0,0 +1,224 @@
+// Data files (.csv, .tsv, .parquet) sent as a summary instead of their raw contents: the
+// row count, each column's name and type, and the first and last few rows. A large CSV
+// otherwise blows the token budget, or is truncated blindly in the middle of a row.
+// Summarizing is chosen per file from its context menu (`ProjectConfig::summarize_tables`).
+
+use crate::error::{PromptError, Result};
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::path::Path;
+
+pub fn is_table(rel_path: &str) -> bool {
+    let lower = rel_path.to_ascii_lowercase();
+    [".csv", ".tsv", ".parquet"]
+        .iter()
+        .any(|ext| lower.ends_with(ext))
+}
+
+fn table_error(path: &Path, message: impl ToString) -> PromptError {
+    PromptError::Table {
+        path: path.to_path_buf(),
+        message: message.to_string(),
+    }
+}
+
+/// The first and last `sample` rows, and how many there were in all.
+struct Sample<T> {
+    first: Vec<T>,
+    last: VecDeque<T>,
+    count: usize,
+}
+
+impl<T> Sample<T> {
+    fn new() -> Self {
+        Self {
+            first: Vec::new(),
+            last: VecDeque::new(),
+            count: 0,
+        }
+    }
+
+    fn push(&mut self, row: T, sample: usize) {
+        self.count += 1;
+        if self.first.len() < sample {
+            self.first.push(row);
+            return;
+        }
+        if self.last.len() == sample {
+            self.last.pop_front();
+        }
+        if sample > 0 {
+            self.last.push_back(row);
+        }
+    }
+}
+
+/// Column type inferred from the values seen; widens as values disagree.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Empty,
+    Boolean,
+    Integer,
+    Float,
+    Text,
+}
+
+impl Kind {
+    fn of(value: &str) -> Kind {
+        if value.parse::<i64>().is_ok() {
+            Kind::Integer
+        } else if value.parse::<f64>().is_ok() {
+            Kind::Float
+        } else if value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("false") {
+            Kind::Boolean
+        } else {
+            Kind::Text
+        }
+    }
+
+    fn widen(self, other: Kind) -> Kind {
+        match (self, other) {
+            (Kind::Empty, k) | (k, Kind::Empty) => k,
+            (a, b) if a == b => a,
+            (Kind::Integer, Kind::Float) | (Kind::Float, Kind::Integer) => Kind::Float,
+            _ => Kind::Text,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Kind::Empty => "empty",
+            Kind::Boolean => "boolean",
+            Kind::Integer => "integer",
+            Kind::Float => "float",
+            Kind::Text => "text",
+        }
+    }
+}
+
+/// Summary of the table at `path`, showing `sample` rows at each end.
+pub fn summarize(path: &Path, sample: usize) -> Result<String> {
+    let lower = path.to_string_lossy().to_ascii_lowercase();
+    if lower.ends_with(".parquet") {
+        summarize_parquet(path, sample)
+    } else {
+        let delimiter = if lower.ends_with(".tsv") { b'\t' } else { b',' };
+        summarize_delimited(path, delimiter, sample)
+    }
+}
+
+fn summarize_delimited(path: &Path, delimiter: u8, sample: usize) -> Result<String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_path(path)
+        .map_err(|e| table_error(path, e))?;
+    let header = reader.headers().map_err(|e| table_error(path, e))?.clone();
+    let mut kinds = vec![Kind::Empty; header.len()];
+    let mut empty = vec![0usize; header.len()];
+    let mut rows = Sample::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| table_error(path, e))?;
+        for (n, value) in record.iter().enumerate().take(header.len()) {
+            let value = value.trim();
+            if value.is_empty() {
+                empty[n] += 1;
+            } else {
+                kinds[n] = kinds[n].widen(Kind::of(value));
+            }
+        }
+        rows.push(record, sample);
+    }
+
+    let columns = header
+        .iter()
+        .zip(kinds.iter().zip(&empty))
+        .map(|(name, (kind, &empty))| {
+            let mut column = format!("{}: {}", name, kind.label());
+            if empty > 0 && *kind != Kind::Empty {
+                let _ = write!(column, " ({} empty)", empty);
+            }
+            column
+        })
+        .collect();
+    let format = if delimiter == b'\t' { "TSV" } else { "CSV" };
+    let mut out = header_lines(format, rows.count, columns);
+    let write_rows = |out: &mut String, title: &str, records: Vec<&csv::StringRecord>| {
+        if records.is_empty() {
+            return;
+        }
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(delimiter)
+            .flexible(true)
+            .from_writer(Vec::new());
+        let _ = writer.write_record(&header);
+        for record in records {
+            let _ = writer.write_record(record);
+        }
+        let bytes = writer.into_inner().unwrap_or_default();
+        let _ = writeln!(out, "{}:", title);
+        out.push_str(&String::from_utf8_lossy(&bytes));
+    };
+    write_rows(&mut out, "first rows", rows.first.iter().collect());
+    write_rows(&mut out, "last rows", rows.last.iter().collect());
+    Ok(out)
+}
+
+fn summarize_parquet(path: &Path, sample: usize) -> Result<String> {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    let file = std::fs::File::open(path).map_err(|e| PromptError::io("read", path, e))?;
+    let reader = SerializedFileReader::new(file).map_err(|e| table_error(path, e))?;
+    let metadata = reader.metadata().file_metadata();
+    let columns = metadata
+        .schema_descr()
+        .columns()
+        .iter()
+        .map(|column| {
+            let kind = match column.converted_type() {
+                parquet::basic::ConvertedType::NONE => column.physical_type().to_string(),
+                converted => converted.to_string(),
+            };
+            let nullable = if column.max_def_level() > 0 {
+                " (nullable)"
+            } else {
+                ""
+            };
+            format!("{}: {}{}", column.path().string(), kind, nullable)
+        })
+        .collect();
+    let mut out = header_lines("Parquet", metadata.num_rows().max(0) as usize, columns);
+
+    let mut rows = Sample::new();
+    for row in reader
+        .get_row_iter(None)
+        .map_err(|e| table_error(path, e))?
+    {
+        rows.push(row.map_err(|e| table_error(path, e))?, sample);
+    }
+    let write_rows = |out: &mut String, title: &str, records: Vec<&parquet::record::Row>| {
+        if records.is_empty() {
+            return;
+        }
+        let _ = writeln!(out, "{}:", title);
+        for row in records {
+            let _ = writeln!(out, "{}", row);
+        }
+    };
+    write_rows(&mut out, "first rows", rows.first.iter().collect());
+    write_rows(&mut out, "last rows", rows.last.iter().collect());
+    Ok(out)
+}
+
+fn header_lines(format: &str, rows: usize, columns: Vec<String>) -> String {
+    let mut out = format!(
+        "[table summary: {} rows, {} columns ({})]\ncolumns:\n",
+        rows,
+        columns.len(),
+        format
+    );
+    for column in columns {
+        let _ = writeln!(out, "  {}", column);
+    }
+    out
+}
@@ -0,0 +1,68 @@
+// `{{name}}` placeholders in the instruction, expanded when the prompt is built so saved
+// snippets can stay generic across projects. Unknown names are left as written.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::path::Path;
+
+static PLACEHOLDER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\{\{\s*([a-z_]+)\s*\}\}").expect("valid regex"));
+
+/// Name and description, for the hover help next to the instruction box.
+pub const VARIABLES: &[(&str, &str)] = &[
+    ("branch", "current git branch"),
+    ("date", "today's date (YYYY-MM-DD)"),
+    ("project", "project folder name"),
+    ("selected_file_count", "number of files included as content"),
+    (
+        "last_command_output",
+        "terminal output (capped like the prompt section)",
+    ),
+];
+
+pub struct Vars<'a> {
+    pub base: Option<&'a Path>,
+    pub selected_file_count: usize,
+    pub last_command_output: &'a str,
+}
+
+pub fn help() -> String {
+    VARIABLES
+        .iter()
+        .map(|(name, what)| format!("{{{{{}}}}}  {}", name, what))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn git_branch(base: &Path) -> Option<String> {
+    let output = std::process::Command::new("git")
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .current_dir(base)
+        .output()
+        .ok()?;
+    let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !branch.is_empty()).then_some(branch)
+}
+
+/// Git is only asked for the branch when the text actually uses `{{branch}}`.
+pub fn expand(text: &str, vars: &Vars) -> String {
+    if !text.contains("{{") {
+        return text.to_string();
+    }
+    PLACEHOLDER
+        .replace_all(text, |caps: &regex::Captures| {
+            let value = match &caps[1] {
+                "branch" => vars.base.and_then(git_branch),
+                "date" => Some(chrono::Local::now().format("%Y-%m-%d").to_string()),
+                "project" => vars
+                    .base
+                    .and_then(|b| b.file_name())
+                    .map(|n| n.to_string_lossy().into_owned()),
+                "selected_file_count" => Some(vars.selected_file_count.to_string()),
+                "last_command_output" => Some(vars.last_command_output.to_string()),
+                _ => None,
+            };
+            value.unwrap_or_else(|| caps[0].to_string())
+        })
+        .into_owned()
+}
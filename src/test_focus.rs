@@ -0,0 +1,145 @@
+// Test-failure focus: after a red test run, narrow the prompt to the failing test
+// functions, the definitions they call (found through the symbol index), and the run's
+// output, instead of whole files.
+
+use crate::build_errors::{Diagnostic, Level};
+use crate::deps::lang_of;
+use crate::file_item::FileItem;
+use crate::symbols::{Symbol, SymbolKind};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::path::Path;
+
+/// Used when the project has no verify command configured.
+pub const DEFAULT_TEST_COMMAND: &str = "cargo test";
+
+/// Names defined in more places than this (`new`, `from`, ...) are too ambiguous to pick.
+const MAX_DEFINITIONS_PER_NAME: usize = 3;
+const MAX_SYMBOLS_PER_TEST: usize = 12;
+
+/// `name(` calls and `Type::` paths.
+static REFERENCE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b([A-Za-z_]\w*)\s*(?:\(|::)").unwrap());
+
+const IGNORED_NAMES: &[&str] = &[
+    "if", "while", "for", "match", "return", "fn", "def", "function", "Some", "None", "Ok", "Err",
+    "Self", "self", "super", "crate", "std", "Box", "Vec", "String", "Option", "Result", "print",
+    "len", "str", "int", "expect", "describe", "it", "test",
+];
+
+#[derive(Default)]
+pub struct Plan {
+    pub failures: usize,
+    pub tests: Vec<Symbol>,
+    pub under_test: Vec<Symbol>,
+    /// Failing tests whose function wasn't found in the index.
+    pub missing: Vec<String>,
+}
+
+/// Panicking tests reported in `output`.
+pub fn failures(output: &str) -> Vec<Diagnostic> {
+    crate::build_errors::parse(output)
+        .into_iter()
+        .filter(|d| d.level == Level::TestFailure)
+        .collect()
+}
+
+fn references(body: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for c in REFERENCE.captures_iter(body) {
+        let name = &c[1];
+        if !IGNORED_NAMES.contains(&name) && !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// Source lines of `symbol`'s definition, read from disk.
+fn body_of(files: &[FileItem], symbol: &Symbol) -> Option<String> {
+    let file = files.iter().find(|f| f.rel_path == symbol.rel_path)?;
+    let text = file.read_text().ok()?;
+    let span = crate::symbols::locate(&symbol.rel_path, &text, &symbol.name, symbol.kind)?;
+    Some(
+        text.lines()
+            .skip(span.start - 1)
+            .take(span.end + 1 - span.start)
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )
+}
+
+/// For each failure: the test function (by the last segment of the test name, preferring
+/// the file the panic points at), the definition around the panic when that isn't the test
+/// itself, and the definitions the test body calls.
+pub fn plan(files: &[FileItem], index: &[Symbol], base: &Path, failures: &[Diagnostic]) -> Plan {
+    let mut plan = Plan {
+        failures: failures.len(),
+        ..Default::default()
+    };
+    let same =
+        |a: &Symbol, b: &Symbol| a.rel_path == b.rel_path && a.name == b.name && a.kind == b.kind;
+    for failure in failures {
+        let test_name = failure.code.clone().unwrap_or_default();
+        let short = test_name.rsplit("::").next().unwrap_or(&test_name);
+        let panic_file = failure
+            .file
+            .as_deref()
+            .and_then(|p| crate::error_refs::resolve(files, base, p));
+        let candidates: Vec<&Symbol> = index
+            .iter()
+            .filter(|s| s.kind == SymbolKind::Function && s.name == short)
+            .collect();
+        let test = candidates
+            .iter()
+            .find(|s| panic_file.is_some_and(|i| files[i].rel_path == s.rel_path))
+            .or_else(|| candidates.first())
+            .map(|s| (*s).clone());
+        let Some(test) = test else {
+            plan.missing.push(test_name);
+            continue;
+        };
+
+        let mut under_test: Vec<Symbol> = Vec::new();
+        if let (Some(i), Some(line)) = (panic_file, failure.line) {
+            let at_panic = files[i]
+                .read_text()
+                .ok()
+                .and_then(|text| crate::symbols::enclosing(&files[i].rel_path, &text, line));
+            if let Some(symbol) = at_panic.filter(|s| !same(s, &test)) {
+                under_test.push(symbol);
+            }
+        }
+        for name in references(&body_of(files, &test).unwrap_or_default()) {
+            if under_test.len() >= MAX_SYMBOLS_PER_TEST {
+                break;
+            }
+            let defs: Vec<&Symbol> = index
+                .iter()
+                .filter(|s| s.name == name && lang_of(&s.rel_path) == lang_of(&test.rel_path))
+                .collect();
+            if defs.len() > MAX_DEFINITIONS_PER_NAME {
+                continue;
+            }
+            for def in defs {
+                if !same(def, &test) && !under_test.iter().any(|s| same(s, def)) {
+                    under_test.push(def.clone());
+                }
+            }
+        }
+
+        if !plan.tests.iter().any(|s| same(s, &test)) {
+            plan.tests.push(test);
+        }
+        for symbol in under_test {
+            let known = plan
+                .tests
+                .iter()
+                .chain(&plan.under_test)
+                .any(|s| same(s, &symbol));
+            if !known {
+                plan.under_test.push(symbol);
+            }
+        }
+    }
+    plan
+}
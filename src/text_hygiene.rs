@@ -0,0 +1,214 @@
+use encoding_rs::Encoding;
+
+/// Byte-order mark found at the start of a file.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Bom {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Bom {
+    pub fn detect(bytes: &[u8]) -> Option<Bom> {
+        if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+            Some(Bom::Utf8)
+        } else if bytes.starts_with(&[0xFF, 0xFE]) {
+            Some(Bom::Utf16Le)
+        } else if bytes.starts_with(&[0xFE, 0xFF]) {
+            Some(Bom::Utf16Be)
+        } else {
+            None
+        }
+    }
+
+    pub fn len(self) -> usize {
+        match self {
+            Bom::Utf8 => 3,
+            Bom::Utf16Le | Bom::Utf16Be => 2,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Bom::Utf8 => "UTF-8 BOM",
+            Bom::Utf16Le => "UTF-16 LE BOM",
+            Bom::Utf16Be => "UTF-16 BE BOM",
+        }
+    }
+}
+
+/// Encoding quirks found while reading a file for the prompt. A stray BOM or a mix of
+/// line endings makes models echo them back, and the resulting patches don't apply.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct TextIssues {
+    pub bom: Option<Bom>,
+    pub crlf: usize,
+    pub lf: usize,
+    pub lone_cr: usize,
+    /// The file was not UTF-8 and was transcoded from this encoding.
+    pub encoding: Option<&'static Encoding>,
+}
+
+impl TextIssues {
+    pub fn scan(text: &str, bom: Option<Bom>, encoding: Option<&'static Encoding>) -> Self {
+        let bytes = text.as_bytes();
+        let mut issues = TextIssues {
+            bom,
+            encoding,
+            ..Default::default()
+        };
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'\r' if bytes.get(i + 1) == Some(&b'\n') => {
+                    issues.crlf += 1;
+                    i += 1;
+                }
+                b'\r' => issues.lone_cr += 1,
+                b'\n' => issues.lf += 1,
+                _ => {}
+            }
+            i += 1;
+        }
+        issues
+    }
+
+    pub fn mixed_line_endings(&self) -> bool {
+        [self.crlf, self.lf, self.lone_cr]
+            .iter()
+            .filter(|&&n| n > 0)
+            .count()
+            > 1
+    }
+
+    /// Worth warning about. Consistent CRLF alone is not flagged (only normalized).
+    pub fn is_flagged(&self) -> bool {
+        self.bom.is_some()
+            || self.encoding.is_some()
+            || self.mixed_line_endings()
+            || self.lone_cr > 0
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(bom) = self.bom {
+            parts.push(bom.label().to_string());
+        } else if let Some(encoding) = self.encoding {
+            parts.push(format!("decoded from {}", encoding.name()));
+        }
+        if self.mixed_line_endings() {
+            parts.push(format!(
+                "mixed line endings ({} CRLF, {} LF, {} CR)",
+                self.crlf, self.lf, self.lone_cr
+            ));
+        } else if self.lone_cr > 0 {
+            parts.push("CR-only line endings".to_string());
+        } else if self.crlf > 0 {
+            parts.push("CRLF line endings".to_string());
+        }
+        parts.join(", ")
+    }
+}
+
+/// Drops a leading BOM character and rewrites CRLF / lone CR as LF.
+pub fn normalize(text: String) -> String {
+    let text = match text.strip_prefix('\u{feff}') {
+        Some(rest) => rest.to_string(),
+        None => text,
+    };
+    if !text.contains('\r') {
+        return text;
+    }
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Encoding of a file from its first bytes: the BOM's if there is one, UTF-8 when the
+/// bytes are valid UTF-8 (a sequence cut off at the end of `head` still counts) or mostly
+/// so (more valid multi-byte sequences than invalid bytes, as in a UTF-8 file with a stray
+/// byte), and otherwise chardetng's guess (Windows-1252 for Latin-1 sources, Shift_JIS,
+/// GBK, ...).
+pub fn detect_encoding(head: &[u8], bom: Option<Bom>) -> &'static Encoding {
+    match bom {
+        Some(Bom::Utf16Le) => return encoding_rs::UTF_16LE,
+        Some(Bom::Utf16Be) => return encoding_rs::UTF_16BE,
+        Some(Bom::Utf8) => return encoding_rs::UTF_8,
+        None => {}
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => encoding_rs::UTF_8,
+        Err(e) if e.error_len().is_none() => encoding_rs::UTF_8,
+        Err(_) if mostly_utf8(head) => encoding_rs::UTF_8,
+        Err(_) => {
+            let mut detector = chardetng::EncodingDetector::new();
+            detector.feed(head, true);
+            detector.guess(None, true)
+        }
+    }
+}
+
+/// Whether valid multi-byte UTF-8 sequences outnumber the bytes that aren't UTF-8. Legacy
+/// 8-bit text almost never forms one, so a few of them settle it.
+fn mostly_utf8(bytes: &[u8]) -> bool {
+    let (mut multibyte, mut invalid) = (0, 0);
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let (valid, skip) = match std::str::from_utf8(rest) {
+            Ok(s) => (s, rest.len()),
+            Err(e) => {
+                invalid += e.error_len().unwrap_or(0);
+                let valid = e.valid_up_to();
+                // A sequence cut off at the end is neither
+                let skip = valid + e.error_len().unwrap_or(rest.len() - valid);
+                (
+                    std::str::from_utf8(&rest[..valid]).unwrap_or_default(),
+                    skip,
+                )
+            }
+        };
+        multibyte += valid.chars().filter(|c| c.len_utf8() > 1).count();
+        rest = &rest[skip..];
+    }
+    multibyte > invalid
+}
+
+/// Decodes `bytes` (any BOM already stripped) lossily.
+pub fn decode(bytes: &[u8], encoding: &'static Encoding) -> String {
+    encoding.decode_without_bom_handling(bytes).0.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf8_with_a_stray_byte_stays_utf8() {
+        let mut head = "// naïve café — résumé\nfn main() {}\n".as_bytes().to_vec();
+        head.insert(10, 0xff);
+        assert_eq!(detect_encoding(&head, None), encoding_rs::UTF_8);
+        assert!(decode(&head, encoding_rs::UTF_8).contains('\u{FFFD}'));
+    }
+
+    #[test]
+    fn utf8_cut_off_mid_sequence_is_utf8() {
+        let text = "emoji: 🚀".as_bytes();
+        assert_eq!(
+            detect_encoding(&text[..text.len() - 2], None),
+            encoding_rs::UTF_8
+        );
+    }
+
+    #[test]
+    fn latin1_text_is_not_taken_for_utf8() {
+        let (head, _, _) =
+            encoding_rs::WINDOWS_1252.encode("Le café et la crème brûlée, déjà vu.\n");
+        assert_eq!(detect_encoding(&head, None), encoding_rs::WINDOWS_1252);
+    }
+
+    #[test]
+    fn bom_wins() {
+        assert_eq!(
+            detect_encoding(b"abc", Some(Bom::Utf16Le)),
+            encoding_rs::UTF_16LE
+        );
+    }
+}
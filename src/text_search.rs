@@ -0,0 +1,219 @@
+// Find-with-highlight for the read-only viewers (file preview, prompt viewer): plain or
+// regex matching, a match counter and next/previous navigation that scrolls to the hit.
+
+use crate::conversation::content_hash;
+use eframe::egui;
+use egui::text::{LayoutJob, TextFormat};
+use std::ops::Range;
+
+/// Enough to show "10000+" without stalling a frame on a pathological pattern.
+const MAX_MATCHES: usize = 10_000;
+
+#[derive(Default)]
+pub struct TextSearch {
+    pub query: String,
+    pub regex: bool,
+    pub match_case: bool,
+    /// Byte ranges into the searched text.
+    pub matches: Vec<Range<usize>>,
+    pub current: usize,
+    pub error: Option<String>,
+    /// Set by navigation; the viewer scrolls to the current match and clears it.
+    pub scroll_pending: bool,
+    searched: Option<(u64, String, bool, bool)>,
+}
+
+impl TextSearch {
+    /// Recomputes matches when the text or the query changed since the last frame.
+    pub fn update(&mut self, text: &str) {
+        let key = (
+            content_hash(text),
+            self.query.clone(),
+            self.regex,
+            self.match_case,
+        );
+        if self.searched.as_ref() == Some(&key) {
+            return;
+        }
+        self.searched = Some(key);
+        self.matches.clear();
+        self.error = None;
+        self.current = 0;
+        if self.query.is_empty() {
+            return;
+        }
+        let pattern = if self.regex {
+            self.query.clone()
+        } else {
+            regex::escape(&self.query)
+        };
+        match regex::RegexBuilder::new(&pattern)
+            .case_insensitive(!self.match_case)
+            .build()
+        {
+            Ok(re) => {
+                self.matches = re
+                    .find_iter(text)
+                    .filter(|m| !m.is_empty())
+                    .take(MAX_MATCHES)
+                    .map(|m| m.range())
+                    .collect();
+                self.scroll_pending = !self.matches.is_empty();
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+
+    pub fn next(&mut self) {
+        if !self.matches.is_empty() {
+            self.current = (self.current + 1) % self.matches.len();
+            self.scroll_pending = true;
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if !self.matches.is_empty() {
+            self.current = (self.current + self.matches.len() - 1) % self.matches.len();
+            self.scroll_pending = true;
+        }
+    }
+
+    /// Byte offset of the current match, for viewers that scroll by line.
+    pub fn current_offset(&self) -> Option<usize> {
+        self.matches.get(self.current).map(|m| m.start)
+    }
+
+    /// Query field, options, counter and navigation. Enter jumps to the next match.
+    pub fn bar(&mut self, ui: &mut egui::Ui, text: &str) {
+        ui.horizontal(|ui| {
+            ui.label("Find:");
+            let field = ui.add(
+                egui::TextEdit::singleline(&mut self.query)
+                    .desired_width(220.0)
+                    .hint_text(if self.regex { "regex" } else { "text" }),
+            );
+            ui.checkbox(&mut self.regex, ".*")
+                .on_hover_text("Regular expression");
+            ui.checkbox(&mut self.match_case, "Aa")
+                .on_hover_text("Match case");
+            self.update(text);
+            if field.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                if ui.input(|i| i.modifiers.shift) {
+                    self.previous();
+                } else {
+                    self.next();
+                }
+                field.request_focus();
+            }
+            if ui.small_button("◀").on_hover_text("Previous").clicked() {
+                self.previous();
+            }
+            if ui.small_button("▶").on_hover_text("Next").clicked() {
+                self.next();
+            }
+            if let Some(err) = &self.error {
+                ui.colored_label(egui::Color32::LIGHT_RED, "invalid regex")
+                    .on_hover_text(err);
+            } else if !self.query.is_empty() {
+                let total = if self.matches.len() >= MAX_MATCHES {
+                    format!("{}+", MAX_MATCHES)
+                } else {
+                    self.matches.len().to_string()
+                };
+                let at = if self.matches.is_empty() {
+                    0
+                } else {
+                    self.current + 1
+                };
+                ui.label(format!("{}/{}", at, total));
+            }
+        });
+    }
+
+    /// `line` (without its newline) starting at byte `line_start` of the searched text,
+    /// after a plain `prefix` such as a line number, with matches highlighted. `colors`
+    /// (byte ranges of the line) tint the text outside matches, for syntax highlighting.
+    pub fn line_job(
+        &self,
+        ui: &egui::Ui,
+        prefix: &str,
+        line: &str,
+        line_start: usize,
+        colors: &[(egui::Color32, Range<usize>)],
+    ) -> LayoutJob {
+        let font = egui::TextStyle::Monospace.resolve(ui.style());
+        let plain = TextFormat {
+            font_id: font.clone(),
+            color: ui.visuals().text_color(),
+            ..Default::default()
+        };
+        let hit = |current: bool| TextFormat {
+            background: if current {
+                egui::Color32::from_rgb(200, 120, 0)
+            } else {
+                egui::Color32::from_rgb(90, 80, 20)
+            },
+            color: egui::Color32::WHITE,
+            ..plain.clone()
+        };
+        let tinted = |start: usize| match colors
+            .iter()
+            .find(|(_, r)| r.start <= start && start < r.end)
+        {
+            Some(&(color, _)) => TextFormat {
+                color,
+                ..plain.clone()
+            },
+            None => plain.clone(),
+        };
+        // Plain stretches are cut again wherever the syntax colour changes
+        let append_plain = |job: &mut LayoutJob, start: usize, end: usize| {
+            let mut pos = start;
+            for (_, r) in colors
+                .iter()
+                .filter(|(_, r)| r.end > start && r.start < end)
+            {
+                let cut = r.end.min(end);
+                if cut > pos && line.is_char_boundary(cut) {
+                    job.append(&line[pos..cut], 0.0, tinted(pos));
+                    pos = cut;
+                }
+            }
+            if pos < end {
+                job.append(&line[pos..end], 0.0, tinted(pos));
+            }
+        };
+        let mut job = LayoutJob::default();
+        job.append(prefix, 0.0, plain.clone());
+        let line_end = line_start + line.len();
+        let first = self.matches.partition_point(|m| m.end <= line_start);
+        let mut pos = 0;
+        for (i, m) in self.matches.iter().enumerate().skip(first) {
+            if m.start >= line_end {
+                break;
+            }
+            let start = m.start.max(line_start) - line_start;
+            let end = m.end.min(line_end) - line_start;
+            if !line.is_char_boundary(start) || !line.is_char_boundary(end) {
+                continue;
+            }
+            append_plain(&mut job, pos, start);
+            job.append(&line[start..end], 0.0, hit(i == self.current));
+            pos = end;
+        }
+        append_plain(&mut job, pos, line.len());
+        job
+    }
+}
+
+/// Byte offset where each line starts; index with the line number minus one.
+pub fn line_starts(text: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect()
+}
+
+/// Zero-based line containing byte `offset`.
+pub fn line_of(starts: &[usize], offset: usize) -> usize {
+    starts.partition_point(|&s| s <= offset).saturating_sub(1)
+}
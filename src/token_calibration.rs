@@ -0,0 +1,98 @@
+// Bytes-per-token ratios learned per file extension. Files that haven't been read are
+// estimated from their size; every exact count (the per-file counts taken on copy, and
+// ↻ recounts) adds that file's bytes and tokens to its extension's tally, so the live
+// total converges on what the tokenizer says for this project's kinds of file. Kept in
+// `.prompt/token_calibration.json` as `{ "rs": { "bytes": .., "tokens": .. } }`.
+
+use crate::error::{PromptError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Used until an extension has enough samples of its own.
+const DEFAULT_BYTES_PER_TOKEN: f64 = 4.0;
+/// Bytes an extension needs to have counted before its own ratio is trusted.
+const MIN_SAMPLE_BYTES: u64 = 4 * 1024;
+/// Tallies are halved past this, so recent counts keep moving the ratio.
+const MAX_SAMPLE_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Tally {
+    pub bytes: u64,
+    pub tokens: u64,
+}
+
+impl Tally {
+    fn bytes_per_token(&self) -> Option<f64> {
+        (self.bytes >= MIN_SAMPLE_BYTES && self.tokens > 0)
+            .then(|| self.bytes as f64 / self.tokens as f64)
+    }
+}
+
+#[derive(Default)]
+pub struct TokenCalibration {
+    pub by_ext: BTreeMap<String, Tally>,
+}
+
+/// Tally key: the lowercased extension, or the file name for extensionless files
+/// (`Makefile`, `.bashrc`).
+fn key(rel_path: &str) -> String {
+    let name = crate::languages::file_name(rel_path);
+    crate::languages::extension(name).unwrap_or_else(|| name.to_string())
+}
+
+impl TokenCalibration {
+    pub fn path(base: &Path) -> PathBuf {
+        base.join(".prompt").join("token_calibration.json")
+    }
+
+    /// A missing file means nothing learned yet; a malformed one is an error.
+    pub fn load(base: &Path) -> Result<Self> {
+        let path = Self::path(base);
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(PromptError::io("read", path, e)),
+        };
+        let by_ext = serde_json::from_str(&text).map_err(|e| PromptError::Config {
+            path,
+            message: e.to_string(),
+        })?;
+        Ok(Self { by_ext })
+    }
+
+    pub fn save(&self, base: &Path) -> Result<()> {
+        let path = Self::path(base);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| PromptError::io("create", dir, e))?;
+        }
+        let json = serde_json::to_string_pretty(&self.by_ext).unwrap();
+        std::fs::write(&path, json).map_err(|e| PromptError::io("write", &path, e))
+    }
+
+    /// Adds an exact count of `bytes` of the file at `rel_path`.
+    pub fn record(&mut self, rel_path: &str, bytes: usize, tokens: usize) {
+        if bytes == 0 {
+            return;
+        }
+        let tally = self.by_ext.entry(key(rel_path)).or_default();
+        tally.bytes += bytes as u64;
+        tally.tokens += tokens as u64;
+        if tally.bytes > MAX_SAMPLE_BYTES {
+            tally.bytes /= 2;
+            tally.tokens /= 2;
+        }
+    }
+
+    pub fn bytes_per_token(&self, rel_path: &str) -> f64 {
+        self.by_ext
+            .get(&key(rel_path))
+            .and_then(Tally::bytes_per_token)
+            .unwrap_or(DEFAULT_BYTES_PER_TOKEN)
+    }
+
+    /// Token estimate for a file of `size` bytes, without reading it.
+    pub fn estimate(&self, rel_path: &str, size: u64) -> usize {
+        (size as f64 / self.bytes_per_token(rel_path)).ceil() as usize
+    }
+}
@@ -0,0 +1,167 @@
+// Token contribution of the current selection per top-level directory, so it's obvious
+// where to cut when the prompt is over budget. The treemap view shows the same for any
+// directory level, of the selection or of the whole project, to see where the context
+// mass lives before selecting.
+
+use crate::file_item::FileItem;
+use egui::{pos2, vec2, Rect};
+use std::collections::HashMap;
+
+pub const ROOT_FILES: &str = "(root files)";
+
+pub struct Bar {
+    pub dir: String,
+    pub tokens: usize,
+    pub files: usize,
+}
+
+/// Selected files grouped by first path component, largest first.
+pub fn by_top_dir(files: &[FileItem]) -> Vec<Bar> {
+    let mut groups: HashMap<&str, (usize, usize)> = HashMap::new();
+    for f in files.iter().filter(|f| f.selected && !f.tree_only) {
+        let dir = match f.rel_path.split_once(['/', '\\']) {
+            Some((top, _)) => top,
+            None => ROOT_FILES,
+        };
+        let entry = groups.entry(dir).or_default();
+        entry.0 += f.token_count;
+        entry.1 += 1;
+    }
+    let mut bars: Vec<Bar> = groups
+        .into_iter()
+        .map(|(dir, (tokens, files))| Bar {
+            dir: dir.to_string(),
+            tokens,
+            files,
+        })
+        .collect();
+    bars.sort_by(|a, b| b.tokens.cmp(&a.tokens).then_with(|| a.dir.cmp(&b.dir)));
+    bars
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum HistogramView {
+    #[default]
+    Bars,
+    Treemap,
+}
+
+/// One rectangle of the treemap: a subdirectory or a file directly in the shown one.
+pub struct Cell {
+    pub name: String,
+    /// '/'-separated path from the project root; for directories, what to drill into.
+    pub path: String,
+    pub tokens: usize,
+    pub files: usize,
+    /// Index into the file list when the cell is a single file.
+    pub file: Option<usize>,
+}
+
+/// The children of directory `dir` ("" = project root) with their token totals, largest
+/// first. Counts every file with `whole_project`, else only the selected ones.
+pub fn cells(files: &[FileItem], dir: &str, whole_project: bool) -> Vec<Cell> {
+    let prefix = if dir.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", dir)
+    };
+    let mut dirs: HashMap<&str, (usize, usize)> = HashMap::new();
+    let mut cells = Vec::new();
+    for (i, f) in files.iter().enumerate() {
+        if f.tree_only || !(whole_project || f.selected) || f.token_count == 0 {
+            continue;
+        }
+        let Some(rest) = f.rel_path.strip_prefix(prefix.as_str()) else {
+            continue;
+        };
+        match rest.split_once('/') {
+            Some((child, _)) => {
+                let entry = dirs.entry(child).or_default();
+                entry.0 += f.token_count;
+                entry.1 += 1;
+            }
+            None => cells.push(Cell {
+                name: rest.to_string(),
+                path: f.rel_path.to_string(),
+                tokens: f.token_count,
+                files: 1,
+                file: Some(i),
+            }),
+        }
+    }
+    cells.extend(dirs.into_iter().map(|(child, (tokens, files))| Cell {
+        name: format!("{}/", child),
+        path: format!("{}{}", prefix, child),
+        tokens,
+        files,
+        file: None,
+    }));
+    cells.sort_by(|a, b| b.tokens.cmp(&a.tokens).then_with(|| a.name.cmp(&b.name)));
+    cells
+}
+
+/// Worst aspect ratio of a row of `areas` laid along a side of length `side`.
+fn worst_ratio(areas: &[f32], side: f32) -> f32 {
+    let sum: f32 = areas.iter().sum();
+    let max = areas.iter().cloned().fold(0.0, f32::max);
+    let min = areas.iter().cloned().fold(f32::INFINITY, f32::min);
+    let side2 = side * side;
+    (side2 * max / (sum * sum)).max(sum * sum / (side2 * min))
+}
+
+/// Squarified treemap: splits `rect` in proportion to `sizes` (largest first, all
+/// positive), filling rows along the shorter side while that keeps cells near square.
+pub fn squarify(sizes: &[usize], rect: Rect) -> Vec<Rect> {
+    let total: usize = sizes.iter().sum();
+    if total == 0 {
+        return Vec::new();
+    }
+    let scale = rect.area() / total as f32;
+    let areas: Vec<f32> = sizes.iter().map(|&s| s as f32 * scale).collect();
+    let mut out = Vec::with_capacity(areas.len());
+    let mut rest = rect;
+    let mut start = 0;
+    while start < areas.len() {
+        let side = rest.width().min(rest.height());
+        let mut end = start + 1;
+        let mut best = worst_ratio(&areas[start..end], side);
+        while end < areas.len() {
+            let ratio = worst_ratio(&areas[start..=end], side);
+            if ratio > best {
+                break;
+            }
+            best = ratio;
+            end += 1;
+        }
+        let row: f32 = areas[start..end].iter().sum();
+        if rest.width() >= rest.height() {
+            // A column at the left
+            let width = row / rest.height();
+            let mut y = rest.min.y;
+            for area in &areas[start..end] {
+                let height = area / width;
+                out.push(Rect::from_min_size(
+                    pos2(rest.min.x, y),
+                    vec2(width, height),
+                ));
+                y += height;
+            }
+            rest.min.x += width;
+        } else {
+            // A row along the top
+            let height = row / rest.width();
+            let mut x = rest.min.x;
+            for area in &areas[start..end] {
+                let width = area / height;
+                out.push(Rect::from_min_size(
+                    pos2(x, rest.min.y),
+                    vec2(width, height),
+                ));
+                x += width;
+            }
+            rest.min.y += height;
+        }
+        start = end;
+    }
+    out
+}
@@ -0,0 +1,99 @@
+// The bottom panel's token meter: the count against the context limit, with numbers in
+// the configured locale, turning yellow and then red as it passes the warning and
+// critical shares of the limit. The project sets the defaults (`ProjectConfig::meter`);
+// a provider may carry its own for its model.
+
+use eframe::egui::{self, Color32};
+use num_format::{Locale, ToFormattedString};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeterSettings {
+    /// num-format locale name for counts: "en", "de", "fr", "en-IN", ...
+    pub locale: String,
+    /// Share of the context limit at which the count shows as a warning.
+    pub warn_percent: u8,
+    /// Share at which it shows as critical.
+    pub critical_percent: u8,
+}
+
+impl Default for MeterSettings {
+    fn default() -> Self {
+        Self {
+            locale: "en".to_string(),
+            warn_percent: 80,
+            critical_percent: 95,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Normal,
+    Warning,
+    Critical,
+}
+
+impl Level {
+    /// Label colour; None keeps the theme's text colour.
+    pub fn color(self, visuals: &egui::Visuals) -> Option<Color32> {
+        match self {
+            Level::Normal => None,
+            Level::Warning => Some(visuals.warn_fg_color),
+            Level::Critical => Some(visuals.error_fg_color),
+        }
+    }
+}
+
+impl MeterSettings {
+    /// An unknown locale name falls back to English.
+    pub fn locale(&self) -> Locale {
+        Locale::from_name(self.locale.trim()).unwrap_or(Locale::en)
+    }
+
+    pub fn format(&self, n: usize) -> String {
+        n.to_formatted_string(&self.locale())
+    }
+
+    pub fn level(&self, tokens: usize, limit: usize) -> Level {
+        let percent = tokens as f64 * 100.0 / limit.max(1) as f64;
+        if percent >= self.critical_percent as f64 {
+            Level::Critical
+        } else if percent >= self.warn_percent as f64 {
+            Level::Warning
+        } else {
+            Level::Normal
+        }
+    }
+
+    /// Locale and threshold fields; true when one changed and was committed.
+    pub fn edit(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Locale");
+            let locale = ui
+                .add(egui::TextEdit::singleline(&mut self.locale).desired_width(60.0))
+                .on_hover_text("Number format: en, de, fr, en-IN, ...");
+            changed |= locale.lost_focus();
+            if Locale::from_name(self.locale.trim()).is_err() {
+                ui.colored_label(ui.visuals().warn_fg_color, "unknown; using en");
+            }
+            ui.label("Warn at");
+            let warn = ui.add(
+                egui::DragValue::new(&mut self.warn_percent)
+                    .range(1..=100)
+                    .suffix("%"),
+            );
+            ui.label("critical at");
+            let critical = ui.add(
+                egui::DragValue::new(&mut self.critical_percent)
+                    .range(1..=100)
+                    .suffix("%"),
+            );
+            changed |= warn.drag_stopped() || warn.lost_focus();
+            changed |= critical.drag_stopped() || critical.lost_focus();
+        });
+        changed
+    }
+}
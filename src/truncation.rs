@@ -0,0 +1,186 @@
+// Structure-aware truncation for files over the per-file cap. The file is cut into
+// blocks at blank lines, and the budget goes first to the header, imports and type
+// definitions, then to blocks mentioning words from the instruction, then to whatever
+// sits nearest the top. Kept blocks stay in file order with a marker at each gap.
+
+use std::collections::HashSet;
+
+/// Longer paragraphs are split so one huge block can't take the whole budget.
+const MAX_BLOCK_LINES: usize = 40;
+
+const STOPWORDS: &[&str] = &[
+    "about", "add", "also", "and", "any", "are", "but", "can", "code", "does", "each", "file",
+    "files", "fix", "for", "from", "have", "how", "into", "make", "more", "not", "only", "please",
+    "should", "some", "that", "the", "them", "then", "there", "this", "use", "using", "what",
+    "when", "where", "which", "while", "with", "without", "would", "you", "your",
+];
+
+const IMPORT_PREFIXES: &[&str] = &[
+    "use ",
+    "pub use ",
+    "mod ",
+    "pub mod ",
+    "extern crate ",
+    "import ",
+    "from ",
+    "#include",
+    "require ",
+    "package ",
+];
+
+const TYPE_MARKERS: &[&str] = &[
+    "struct ",
+    "enum ",
+    "trait ",
+    "type ",
+    "union ",
+    "interface ",
+    "class ",
+    "typedef ",
+    "impl ",
+];
+
+/// Lowercased identifiers (3+ chars) from the instruction, minus common English words.
+pub fn keywords(instruction: &str) -> Vec<String> {
+    let stop: HashSet<&str> = STOPWORDS.iter().copied().collect();
+    let mut seen = HashSet::new();
+    instruction
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|w| w.len() >= 3 && !w.chars().all(|c| c.is_ascii_digit()))
+        .map(str::to_lowercase)
+        .filter(|w| !stop.contains(w.as_str()))
+        .filter(|w| seen.insert(w.clone()))
+        .collect()
+}
+
+struct Block {
+    /// Line range into the file.
+    start: usize,
+    end: usize,
+    bytes: usize,
+    score: f64,
+}
+
+fn is_blank(line: &str) -> bool {
+    crate::line_numbers::strip(line).trim().is_empty()
+}
+
+fn split_blocks(lines: &[&str]) -> Vec<(usize, usize)> {
+    let mut blocks = Vec::new();
+    let mut start = 0;
+    for i in 1..=lines.len() {
+        let boundary = i == lines.len()
+            || i - start >= MAX_BLOCK_LINES
+            || (is_blank(lines[i - 1]) && !is_blank(lines[i]));
+        if boundary {
+            blocks.push((start, i));
+            start = i;
+        }
+    }
+    blocks
+}
+
+fn score(lines: &[&str], index: usize, count: usize, keywords: &[String]) -> f64 {
+    let mut score = 0.0;
+    if index == 0 {
+        score += 100.0; // module docs, license, leading imports
+    }
+    if lines.iter().any(|l| {
+        let t = crate::line_numbers::strip(l).trim_start();
+        IMPORT_PREFIXES.iter().any(|p| t.starts_with(p)) || t.contains("require(")
+    }) {
+        score += 50.0;
+    }
+    if lines.iter().any(|l| {
+        let t = crate::line_numbers::strip(l)
+            .trim_start()
+            .trim_start_matches("pub ")
+            .trim_start_matches("export ");
+        TYPE_MARKERS.iter().any(|m| t.starts_with(m))
+    }) {
+        score += 20.0;
+    }
+    if !keywords.is_empty() {
+        let text = lines.join("\n").to_lowercase();
+        for k in keywords {
+            let hits = text.matches(k.as_str()).count();
+            if hits > 0 {
+                score += 30.0 + 2.0 * (hits.min(10) - 1) as f64;
+            }
+        }
+    }
+    if index + 1 == count {
+        score += 10.0; // the tail often holds exports, main() or tests
+    }
+    // Mild bias toward the top as a tie-breaker
+    score + 5.0 * (1.0 - index as f64 / count.max(1) as f64)
+}
+
+/// Keeps at most about `max_bytes` of `text`, cutting only at line boundaries.
+pub fn truncate(text: &str, max_bytes: usize, keywords: &[String], marker: &str) -> String {
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+    let lines: Vec<&str> = text.lines().collect();
+    let ranges = split_blocks(&lines);
+    let count = ranges.len();
+    let blocks: Vec<Block> = ranges
+        .into_iter()
+        .enumerate()
+        .map(|(i, (start, end))| Block {
+            start,
+            end,
+            bytes: lines[start..end].iter().map(|l| l.len() + 1).sum(),
+            score: score(&lines[start..end], i, count, keywords),
+        })
+        .collect();
+
+    // Reserve room for a marker at (almost) every gap
+    let budget = max_bytes.saturating_sub(max_bytes / 50);
+    let mut order: Vec<usize> = (0..blocks.len()).collect();
+    order.sort_by(|&a, &b| blocks[b].score.total_cmp(&blocks[a].score));
+    let mut keep = vec![false; blocks.len()];
+    let mut used = 0;
+    for i in order {
+        if used + blocks[i].bytes <= budget {
+            used += blocks[i].bytes;
+            keep[i] = true;
+        }
+    }
+    if used == 0 {
+        // No block fits (e.g. minified one-liners): plain byte head and tail
+        return head_tail(text, max_bytes, marker);
+    }
+
+    let mut out = String::with_capacity(used + 256);
+    let mut last_end = 0;
+    for (block, _) in blocks.iter().zip(&keep).filter(|(_, &kept)| kept) {
+        if block.start > last_end {
+            out.push_str(marker);
+            out.push('\n');
+        }
+        for line in &lines[block.start..block.end] {
+            out.push_str(line);
+            out.push('\n');
+        }
+        last_end = block.end;
+    }
+    if last_end < lines.len() {
+        out.push_str(marker);
+        out.push('\n');
+    }
+    out
+}
+
+fn head_tail(text: &str, max_bytes: usize, marker: &str) -> String {
+    let half = max_bytes / 2;
+    let mut head_end = half.min(text.len());
+    while !text.is_char_boundary(head_end) {
+        head_end -= 1;
+    }
+    let mut tail_start = text.len().saturating_sub(half).max(head_end);
+    while !text.is_char_boundary(tail_start) {
+        tail_start += 1;
+    }
+    format!("{}\n{}\n{}", &text[..head_end], marker, &text[tail_start..])
+}
@@ -0,0 +1,187 @@
+// How the window looks, the same for every project and tab: theme (follow the system,
+// dark or light), an optional accent colour, the UI scale, and the startup window size.
+// Kept in `~/.config/prompt/ui.toml` (under $XDG_CONFIG_HOME when set) and edited in the
+// View settings window.
+
+use crate::error::{PromptError, Result};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    System,
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::System, Theme::Dark, Theme::Light];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Theme::System => "Follow system",
+            Theme::Dark => "Dark",
+            Theme::Light => "Light",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiSettings {
+    pub theme: Theme,
+    /// `#rrggbb` for selections and links; empty = the theme's own.
+    pub accent: String,
+    /// Zoom on top of the display's own scale.
+    pub scale: f32,
+    /// Startup window size in points; None starts maximized.
+    pub window_size: Option<[f32; 2]>,
+}
+
+impl Default for UiSettings {
+    fn default() -> Self {
+        Self {
+            theme: Theme::System,
+            accent: String::new(),
+            scale: 1.0,
+            window_size: None,
+        }
+    }
+}
+
+pub const MIN_SCALE: f32 = 0.5;
+pub const MAX_SCALE: f32 = 3.0;
+
+impl UiSettings {
+    pub fn path() -> Option<PathBuf> {
+        Some(crate::config::global_dir()?.join("ui.toml"))
+    }
+
+    /// A missing file yields the defaults; an unreadable or malformed one is an error.
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(PromptError::io("read", path, e)),
+        };
+        toml::from_str(&text).map_err(|e| PromptError::Config {
+            path,
+            message: e.to_string(),
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = Self::path() else {
+            return Err(PromptError::Config {
+                path: PathBuf::from("~/.config/prompt"),
+                message: "no home directory to save view settings in".into(),
+            });
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| PromptError::io("create", dir, e))?;
+        }
+        let text = toml::to_string_pretty(self).map_err(|e| PromptError::Config {
+            path: path.clone(),
+            message: e.to_string(),
+        })?;
+        std::fs::write(&path, text).map_err(|e| PromptError::io("write", &path, e))
+    }
+
+    pub fn accent_color(&self) -> Option<egui::Color32> {
+        let hex = self.accent.trim();
+        if hex.is_empty() {
+            return None;
+        }
+        egui::Color32::from_hex(&format!("#{}", hex.trim_start_matches('#'))).ok()
+    }
+
+    /// Applies theme, accent and scale to the whole UI.
+    pub fn apply(&self, ctx: &egui::Context) {
+        ctx.set_theme(match self.theme {
+            Theme::System => egui::ThemePreference::System,
+            Theme::Dark => egui::ThemePreference::Dark,
+            Theme::Light => egui::ThemePreference::Light,
+        });
+        for (theme, base) in [
+            (egui::Theme::Dark, egui::Visuals::dark()),
+            (egui::Theme::Light, egui::Visuals::light()),
+        ] {
+            let mut visuals = base;
+            if let Some(accent) = self.accent_color() {
+                visuals.selection.bg_fill = accent;
+                visuals.hyperlink_color = accent;
+                visuals.widgets.hovered.bg_stroke.color = accent;
+            }
+            ctx.set_visuals_of(theme, visuals);
+        }
+        ctx.set_zoom_factor(self.scale.clamp(MIN_SCALE, MAX_SCALE));
+    }
+
+    /// The settings form; true when something changed.
+    pub fn show(&mut self, ui: &mut egui::Ui) -> bool {
+        let before = self.clone();
+        egui::Grid::new("ui_settings_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Theme");
+                ui.horizontal(|ui| {
+                    for theme in Theme::ALL {
+                        ui.radio_value(&mut self.theme, theme, theme.label());
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Accent");
+                ui.horizontal(|ui| {
+                    let mut color = self
+                        .accent_color()
+                        .unwrap_or(ui.visuals().selection.bg_fill);
+                    if ui.color_edit_button_srgba(&mut color).changed() {
+                        self.accent = color.to_hex()[..7].to_string();
+                    }
+                    ui.add(egui::TextEdit::singleline(&mut self.accent).desired_width(80.0))
+                        .on_hover_text("#rrggbb; empty = the theme's own");
+                    if !self.accent.is_empty() && ui.small_button("Reset").clicked() {
+                        self.accent.clear();
+                    }
+                });
+                ui.end_row();
+
+                ui.label("UI scale");
+                ui.add(
+                    egui::Slider::new(&mut self.scale, MIN_SCALE..=MAX_SCALE)
+                        .step_by(0.05)
+                        .suffix("×"),
+                )
+                .on_hover_text("Also Ctrl + / Ctrl − / Ctrl 0");
+                ui.end_row();
+
+                ui.label("Startup size");
+                ui.horizontal(|ui| {
+                    match self.window_size {
+                        Some([w, h]) => ui.label(format!("{:.0} × {:.0}", w, h)),
+                        None => ui.label("maximized"),
+                    };
+                    if ui
+                        .small_button("Use current")
+                        .on_hover_text("Open at the window's current size from now on")
+                        .clicked()
+                    {
+                        let size = ui.ctx().screen_rect().size() * ui.ctx().zoom_factor();
+                        self.window_size = Some([size.x.round(), size.y.round()]);
+                    }
+                    if self.window_size.is_some() && ui.small_button("Maximized").clicked() {
+                        self.window_size = None;
+                    }
+                });
+                ui.end_row();
+            });
+        *self != before
+    }
+}
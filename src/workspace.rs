@@ -0,0 +1,172 @@
+use crate::error::{PromptError, Result};
+use crate::file_item::FileItem;
+use globset::Glob;
+use std::path::Path;
+
+/// A crate of the Cargo workspace rooted at the project folder.
+#[derive(Clone)]
+pub struct WorkspaceCrate {
+    pub name: String,
+    /// Relative, '/'-separated directory; empty for a root package.
+    pub dir: String,
+}
+
+impl WorkspaceCrate {
+    pub fn contains(&self, rel_path: &str) -> bool {
+        self.dir.is_empty()
+            || (rel_path.starts_with(&self.dir) && rel_path[self.dir.len()..].starts_with('/'))
+    }
+
+    pub fn manifest(&self) -> String {
+        self.join("Cargo.toml")
+    }
+
+    pub fn src_dir(&self) -> String {
+        self.join("src")
+    }
+
+    fn join(&self, rest: &str) -> String {
+        if self.dir.is_empty() {
+            rest.to_string()
+        } else {
+            format!("{}/{}", self.dir, rest)
+        }
+    }
+}
+
+fn read_manifest(path: &Path) -> Result<toml::Value> {
+    let text = std::fs::read_to_string(path).map_err(|e| PromptError::io("read", path, e))?;
+    toml::from_str(&text).map_err(|e| PromptError::Config {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    })
+}
+
+fn package_name(manifest: &toml::Value) -> Option<String> {
+    manifest
+        .get("package")?
+        .get("name")?
+        .as_str()
+        .map(str::to_string)
+}
+
+fn string_list(table: &toml::Value, key: &str) -> Vec<String> {
+    table
+        .get(key)
+        .and_then(|v| v.as_array())
+        .map(|a| {
+            a.iter()
+                .filter_map(|v| v.as_str())
+                .map(|s| s.trim_start_matches("./").trim_end_matches('/').to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Expands a `members` entry: plain paths as-is, globs by walking down from their literal
+/// prefix and matching directories at the same depth.
+fn expand_member(base: &Path, pattern: &str) -> Vec<String> {
+    if !pattern.contains(['*', '?', '[']) {
+        return vec![pattern.to_string()];
+    }
+    let Ok(glob) = Glob::new(pattern) else {
+        eprintln!(
+            "[prompt] WARN: invalid workspace member pattern {:?}",
+            pattern
+        );
+        return Vec::new();
+    };
+    let matcher = glob.compile_matcher();
+    let segments: Vec<&str> = pattern.split('/').collect();
+    let literal: Vec<&str> = segments
+        .iter()
+        .take_while(|s| !s.contains(['*', '?', '[']))
+        .copied()
+        .collect();
+    let mut current = vec![literal.join("/")];
+    for _ in literal.len()..segments.len() {
+        let mut next = Vec::new();
+        for dir in &current {
+            let Ok(entries) = std::fs::read_dir(base.join(dir)) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    next.push(if dir.is_empty() {
+                        name
+                    } else {
+                        format!("{}/{}", dir, name)
+                    });
+                }
+            }
+        }
+        current = next;
+    }
+    current.retain(|d| matcher.is_match(d));
+    current.sort();
+    current
+}
+
+/// Crates of the workspace at `base`. Returns an empty list when the root manifest has no
+/// `[workspace]` table (plain single crates get nothing extra).
+pub fn detect(base: &Path) -> Result<Vec<WorkspaceCrate>> {
+    let root_manifest = base.join("Cargo.toml");
+    if !root_manifest.is_file() {
+        return Ok(Vec::new());
+    }
+    let root = read_manifest(&root_manifest)?;
+    let Some(workspace) = root.get("workspace") else {
+        return Ok(Vec::new());
+    };
+    let excluded = string_list(workspace, "exclude");
+
+    let mut crates = Vec::new();
+    if let Some(name) = package_name(&root) {
+        crates.push(WorkspaceCrate {
+            name,
+            dir: String::new(),
+        });
+    }
+    for member in string_list(workspace, "members") {
+        for dir in expand_member(base, &member) {
+            if excluded.contains(&dir) {
+                continue;
+            }
+            let manifest = base.join(&dir).join("Cargo.toml");
+            if !manifest.is_file() {
+                continue;
+            }
+            let name = match read_manifest(&manifest) {
+                Ok(m) => package_name(&m).unwrap_or_else(|| dir.clone()),
+                Err(e) => {
+                    eprintln!("[prompt] WARN: {}", e);
+                    dir.clone()
+                }
+            };
+            crates.push(WorkspaceCrate { name, dir });
+        }
+    }
+    Ok(crates)
+}
+
+/// Index of the innermost crate holding `rel_path` (a root package only wins if no member does).
+pub fn crate_index(crates: &[WorkspaceCrate], rel_path: &str) -> Option<usize> {
+    crates
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.contains(rel_path))
+        .max_by_key(|(_, c)| c.dir.len())
+        .map(|(i, _)| i)
+}
+
+/// Per-crate token totals, in the same order as `crates`.
+pub fn crate_tokens(crates: &[WorkspaceCrate], files: &[FileItem]) -> Vec<usize> {
+    let mut totals = vec![0; crates.len()];
+    for f in files {
+        if let Some(i) = crate_index(crates, &f.rel_path) {
+            totals[i] += f.token_count;
+        }
+    }
+    totals
+}